@@ -188,4 +188,22 @@ pub mod tests {
             "Dropped connection should return None"
         );
     }
+
+    /// Test that maps are transported over connection
+    #[tokio::test]
+    async fn send_map() {
+        let (mut local, mut remote) = Connection::pair().unwrap();
+        let contents = Form::from_expr("{:a 1 \"b\" (2.5 {3 true})}").unwrap();
+
+        local
+            .send_req(Request {
+                id: 0,
+                contents: contents.clone(),
+            })
+            .await
+            .unwrap();
+
+        let req = remote.recv_req().await.unwrap().unwrap();
+        assert_eq!(req.contents, contents);
+    }
 }
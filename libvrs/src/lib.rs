@@ -13,7 +13,8 @@ pub use rt::program::{
     NativeFnOp, Pattern, Program, Val,
 };
 pub use rt::{
-    Error, Process, ProcessExit, ProcessHandle, ProcessResult, ProcessSet, Result, Runtime,
+    Config, Error, Isolation, MailboxConfig, Overflow, PortHandle, Process, ProcessExit,
+    ProcessHandle, ProcessResult, ProcessSet, Result, Runtime,
 }; // TODO: Should rt reexport from lib?
 
 /// The path to runtime socket
@@ -32,3 +33,28 @@ pub fn runtime_socket_name() -> &'static str {
         "vrsd.socket"
     }
 }
+
+/// The path to vrs configuration directory, under `$XDG_CONFIG_HOME` or `~/.config`.
+/// Returns None if neither config nor home directory is known.
+pub fn config_dir() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| dirs::home_dir().map(|h| h.join(".config")))?;
+    Some(config.join("vrs"))
+}
+
+/// The path to default init file loaded by runtime
+pub fn init_file() -> Option<PathBuf> {
+    config_dir().map(|d| d.join("init.ll"))
+}
+
+/// The path where runtime persists scheduled jobs
+pub fn jobs_file() -> Option<PathBuf> {
+    config_dir().map(|d| d.join("jobs.json"))
+}
+
+/// The path of SQLite database for runtime store
+pub fn store_file() -> Option<PathBuf> {
+    config_dir().map(|d| d.join("store.db"))
+}
@@ -1,12 +1,21 @@
 //! File System bindings for VRS Processes
 
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::UNIX_EPOCH;
+
 use crate::{Fiber, NativeAsyncFn, Val};
-use lyric::{parse, Error, Form, Result};
+use lyric::{
+    compile_spanned, parse, parse_all, Error, Form, KeywordId, Map, MapKey, Result, Span, SymbolId,
+};
 use tokio::{
-    fs::{File, OpenOptions},
+    fs::{self, File, OpenOptions},
     io::{AsyncReadExt, AsyncWriteExt},
 };
 
+/// Id of next temporary file used for atomic writes
+static NEXT_TMP_ID: AtomicUsize = AtomicUsize::new(0);
+
 pub(crate) fn fread_fn() -> NativeAsyncFn {
     NativeAsyncFn {
         doc: "(fread PATH) - Read the symbolic expression from file at PATH".to_string(),
@@ -16,66 +25,940 @@ pub(crate) fn fread_fn() -> NativeAsyncFn {
 
 async fn fread_impl(_fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
     let path = match &args[..] {
-        [Val::String(s)] => shellexpand::tilde(s).to_string(),
-        e => {
-            return Err(Error::UnexpectedArguments(format!(
-                "fread expects first argument to be string. Got {:?}",
-                e
-            )))
+        [path] => path_arg("fread", path)?,
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "fread expects single path argument".to_string(),
+            ))
         }
     };
-
-    let mut file = File::open(path)
-        .await
-        .map_err(|e| Error::Runtime(format!("Failed to open file - {e}")))?;
-
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)
-        .await
-        .map_err(|e| Error::Runtime(format!("Failed to read file - {e}")))?;
-
+    let contents = read_string(&path).await?;
     let val = Val::from(parse(&contents)?);
-
     Ok(val)
 }
 
 pub(crate) fn fdump_fn() -> NativeAsyncFn {
     NativeAsyncFn {
-        doc: "(fdump PATH FORM) - Dump the symbolic expression FORM to file at PATH".to_string(),
+        doc: "(fdump PATH FORM) - Dump the symbolic expression FORM to file at PATH. \
+              FORM is written to a temporary file that replaces PATH, so PATH is never partially written."
+            .to_string(),
         func: |f, args| Box::new(fdump_impl(f, args)),
     }
 }
 
 async fn fdump_impl(_fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
     let (path, val) = match &args[..] {
-        [Val::String(s), val] => (shellexpand::tilde(s).to_string(), val),
-        a => {
-            return Err(Error::UnexpectedArguments(format!(
-                "fread expects first argument to be string. Got {:?}",
-                a
-            )))
+        [path, val] => (path_arg("fdump", path)?, val),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "fdump expects path and form arguments".to_string(),
+            ))
+        }
+    };
+    let val_str = Form::try_from(val.clone())?.to_string();
+    write_atomic(&path, val_str.as_bytes()).await?;
+    Ok(Val::keyword("ok"))
+}
+
+/// Binding for fread_str
+pub(crate) fn fread_str_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(fread_str PATH) - Read contents of file at PATH as a string".to_string(),
+        func: |f, args| Box::new(fread_str_impl(f, args)),
+    }
+}
+
+/// Implementation for (fread_str PATH)
+async fn fread_str_impl(_fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let path = match &args[..] {
+        [path] => path_arg("fread_str", path)?,
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "fread_str expects single path argument".to_string(),
+            ))
+        }
+    };
+    Ok(Val::String(read_string(&path).await?))
+}
+
+/// Binding for fwrite_str
+pub(crate) fn fwrite_str_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(fwrite_str PATH STRING) - Replace contents of file at PATH with STRING. \
+              Like fdump, STRING is written to a temporary file that replaces PATH."
+            .to_string(),
+        func: |f, args| Box::new(fwrite_str_impl(f, args)),
+    }
+}
+
+/// Implementation for (fwrite_str PATH STRING)
+async fn fwrite_str_impl(_fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (path, contents) = match &args[..] {
+        [path, Val::String(contents)] => (path_arg("fwrite_str", path)?, contents),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "fwrite_str expects path and string arguments".to_string(),
+            ))
         }
     };
+    write_atomic(&path, contents.as_bytes()).await?;
+    Ok(Val::keyword("ok"))
+}
+
+/// Binding for fappend
+pub(crate) fn fappend_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(fappend PATH STRING) - Append STRING to file at PATH, creating file if it does not exist"
+            .to_string(),
+        func: |f, args| Box::new(fappend_impl(f, args)),
+    }
+}
 
+/// Implementation for (fappend PATH STRING)
+async fn fappend_impl(_fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (path, contents) = match &args[..] {
+        [path, Val::String(contents)] => (path_arg("fappend", path)?, contents),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "fappend expects path and string arguments".to_string(),
+            ))
+        }
+    };
     let mut file = OpenOptions::new()
-        .write(true)
+        .append(true)
         .create(true)
-        .truncate(true)
-        .open(path)
+        .open(&path)
         .await
-        .map_err(|e| Error::Runtime(format!("Failed to open file - {e}")))?;
+        .map_err(|e| io_error(&path, e))?;
+    file.write_all(contents.as_bytes())
+        .await
+        .map_err(|e| io_error(&path, e))?;
+    file.flush().await.map_err(|e| io_error(&path, e))?;
+    Ok(Val::keyword("ok"))
+}
 
-    let val_str = Form::try_from(val.clone())?.to_string();
-    file.write_all(val_str.as_bytes())
+/// Binding for ls_dir
+pub(crate) fn ls_dir_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(ls_dir PATH) - Returns sorted list of names of entries in directory at PATH"
+            .to_string(),
+        func: |f, args| Box::new(ls_dir_impl(f, args)),
+    }
+}
+
+/// Implementation for (ls_dir PATH)
+async fn ls_dir_impl(_fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let path = match &args[..] {
+        [path] => path_arg("ls_dir", path)?,
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "ls_dir expects single path argument".to_string(),
+            ))
+        }
+    };
+    let mut entries = fs::read_dir(&path).await.map_err(|e| io_error(&path, e))?;
+    let mut names = vec![];
+    while let Some(entry) = entries.next_entry().await.map_err(|e| io_error(&path, e))? {
+        names.push(entry.file_name().to_string_lossy().to_string());
+    }
+    names.sort();
+    Ok(Val::List(names.into_iter().map(Val::String).collect()))
+}
+
+/// Binding for fexists?
+pub(crate) fn fexists_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(fexists? PATH) - Returns true if a file or directory exists at PATH".to_string(),
+        func: |f, args| Box::new(fexists_impl(f, args)),
+    }
+}
+
+/// Implementation for (fexists? PATH)
+async fn fexists_impl(_fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let path = match &args[..] {
+        [path] => path_arg("fexists?", path)?,
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "fexists? expects single path argument".to_string(),
+            ))
+        }
+    };
+    let exists = fs::try_exists(&path)
         .await
-        .map_err(|e| Error::Runtime(format!("Failed to write to file - {e}")))?;
+        .map_err(|e| io_error(&path, e))?;
+    Ok(Val::Bool(exists))
+}
+
+/// Binding for fstat
+pub(crate) fn fstat_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(fstat PATH) - Returns map of :type (:file, :dir, or :symlink), :size in bytes, \
+              :modified time in unix seconds, and :readonly for entry at PATH. Symlinks are not followed."
+            .to_string(),
+        func: |f, args| Box::new(fstat_impl(f, args)),
+    }
+}
+
+/// Implementation for (fstat PATH)
+async fn fstat_impl(_fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let path = match &args[..] {
+        [path] => path_arg("fstat", path)?,
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "fstat expects single path argument".to_string(),
+            ))
+        }
+    };
+    let meta = fs::symlink_metadata(&path)
+        .await
+        .map_err(|e| io_error(&path, e))?;
+
+    let file_type = if meta.is_symlink() {
+        "symlink"
+    } else if meta.is_dir() {
+        "dir"
+    } else {
+        "file"
+    };
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| Val::Int(d.as_secs() as i64))
+        .unwrap_or(Val::Nil);
+
+    let key = |k: &str| MapKey::Keyword(KeywordId::from(k));
+    Ok(Val::Map(Map::from([
+        (key("type"), Val::keyword(file_type)),
+        (key("size"), Val::Int(meta.len() as i64)),
+        (key("modified"), modified),
+        (key("readonly"), Val::Bool(meta.permissions().readonly())),
+    ])))
+}
+
+/// Binding for mkdir
+pub(crate) fn mkdir_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(mkdir PATH) - Create directory at PATH, along with any missing parent directories"
+            .to_string(),
+        func: |f, args| Box::new(mkdir_impl(f, args)),
+    }
+}
+
+/// Implementation for (mkdir PATH)
+async fn mkdir_impl(_fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let path = match &args[..] {
+        [path] => path_arg("mkdir", path)?,
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "mkdir expects single path argument".to_string(),
+            ))
+        }
+    };
+    fs::create_dir_all(&path)
+        .await
+        .map_err(|e| io_error(&path, e))?;
+    Ok(Val::keyword("ok"))
+}
+
+/// Binding for rm
+pub(crate) fn rm_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(rm PATH) or (rm PATH :recursive true) - Remove file or empty directory at PATH. \
+              With :recursive, directories are removed along with their contents."
+            .to_string(),
+        func: |f, args| Box::new(rm_impl(f, args)),
+    }
+}
 
-    file.flush()
+/// Implementation for (rm PATH [:recursive BOOL])
+async fn rm_impl(_fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (path, recursive) = match &args[..] {
+        [path] => (path_arg("rm", path)?, false),
+        [path, Val::Keyword(k), Val::Bool(recursive)] if k.as_str() == "recursive" => {
+            (path_arg("rm", path)?, *recursive)
+        }
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "rm expects path, optionally followed by :recursive BOOL".to_string(),
+            ))
+        }
+    };
+    let meta = fs::symlink_metadata(&path)
         .await
-        .map_err(|e| Error::Runtime(format!("Failed to flush write to file - {e}")))?;
+        .map_err(|e| io_error(&path, e))?;
+    let res = if !meta.is_dir() {
+        fs::remove_file(&path).await
+    } else if recursive {
+        fs::remove_dir_all(&path).await
+    } else {
+        fs::remove_dir(&path).await
+    };
+    res.map_err(|e| io_error(&path, e))?;
+    Ok(Val::keyword("ok"))
+}
 
+/// Binding for rename
+pub(crate) fn rename_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(rename FROM TO) - Rename file or directory at FROM to TO, replacing file at TO if it exists"
+            .to_string(),
+        func: |f, args| Box::new(rename_impl(f, args)),
+    }
+}
+
+/// Implementation for (rename FROM TO)
+async fn rename_impl(_fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (from, to) = match &args[..] {
+        [from, to] => (path_arg("rename", from)?, path_arg("rename", to)?),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "rename expects from and to path arguments".to_string(),
+            ))
+        }
+    };
+    fs::rename(&from, &to)
+        .await
+        .map_err(|e| io_error(&from, e))?;
     Ok(Val::keyword("ok"))
 }
 
-// TODO: Test Cases for fread
-// TODO: Test Cases for fdump
+/// Path from string argument, with `~` expanded
+fn path_arg(name: &str, val: &Val) -> Result<PathBuf> {
+    match val {
+        Val::String(s) => Ok(PathBuf::from(shellexpand::tilde(s).to_string())),
+        v => Err(Error::UnexpectedArguments(format!(
+            "{name} expects path to be a string. Got {v}"
+        ))),
+    }
+}
+
+/// Read contents of file at path as string
+async fn read_string(path: &Path) -> Result<String> {
+    let mut contents = String::new();
+    File::open(path)
+        .await
+        .map_err(|e| io_error(path, e))?
+        .read_to_string(&mut contents)
+        .await
+        .map_err(|e| io_error(path, e))?;
+    Ok(contents)
+}
+
+/// Write contents to temporary file in same directory as path, then rename temporary file to path.
+/// Renames within a file system are atomic, so readers see either previous or new contents.
+/// Symlinks are resolved so the file they point to is replaced, keeping permissions of replaced file.
+pub(crate) async fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let target = match fs::canonicalize(path).await {
+        Ok(target) => target,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(e) => return Err(io_error(path, e)),
+    };
+    let file_name = target
+        .file_name()
+        .ok_or_else(|| Error::IO {
+            kind: KeywordId::from("invalid_input"),
+            path: path.display().to_string(),
+            message: "path does not name a file".to_string(),
+        })?
+        .to_string_lossy();
+    let tmp = target.with_file_name(format!(
+        ".{file_name}.{}.{}.tmp",
+        std::process::id(),
+        NEXT_TMP_ID.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let write = async {
+        let mut file = File::create(&tmp).await?;
+        file.write_all(contents).await?;
+        file.sync_all().await?;
+        if let Ok(meta) = fs::metadata(&target).await {
+            fs::set_permissions(&tmp, meta.permissions()).await?;
+        }
+        fs::rename(&tmp, &target).await
+    };
+    if let Err(e) = write.await {
+        let _ = fs::remove_file(&tmp).await;
+        return Err(io_error(path, e));
+    }
+    Ok(())
+}
+
+/// Structured error for IO error on path, with kind of error as keyword like `:not_found`
+fn io_error(path: &Path, e: std::io::Error) -> Error {
+    // Kinds are named in CamelCase, e.g. `NotFound` becomes `:not_found`
+    let mut kind = String::new();
+    for (i, c) in format!("{:?}", e.kind()).chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            kind.push('_');
+        }
+        kind.extend(c.to_lowercase());
+    }
+    Error::IO {
+        kind: KeywordId::from(kind.as_str()),
+        path: path.display().to_string(),
+        message: e.to_string(),
+    }
+}
+
+/// Binding for load
+pub(crate) fn load_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(load PATH) or (load PATH :keep_going true) - Evaluate each form in file at PATH in order, returning value of last form. \
+              Raises an error with file and line of first form that fails, with locations of enclosing calls. \
+              With :keep_going, forms after failing forms are evaluated, returning a list of the value or error of each form."
+            .to_string(),
+        func: |f, args| Box::new(load_impl(f, args)),
+    }
+}
+
+/// Implementation for (load PATH [:keep_going BOOL])
+async fn load_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (path, keep_going) = match &args[..] {
+        [Val::String(s)] => (s, false),
+        [Val::String(s), Val::Keyword(k), Val::Bool(keep_going)] if k.as_str() == "keep_going" => {
+            (s, *keep_going)
+        }
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "load expects string path, optionally followed by :keep_going BOOL".to_string(),
+            ))
+        }
+    };
+    let path = PathBuf::from(shellexpand::tilde(path).to_string());
+    let contents = read_string(&path).await?;
+    load_src(fiber, &contents, &path.display().to_string(), 1, keep_going).await
+}
+
+/// Binding for load_str
+pub(crate) fn load_str_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(load_str STRING NAME) or (load_str STRING NAME LINE) - Like `load`, but evaluates forms in STRING. \
+              Errors are located in file NAME, where STRING starts at LINE."
+            .to_string(),
+        func: |f, args| Box::new(load_str_impl(f, args)),
+    }
+}
+
+/// Implementation for (load_str STRING NAME [LINE])
+async fn load_str_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (src, name, line) = match &args[..] {
+        [Val::String(src), Val::String(name)] => (src, name, 1),
+        [Val::String(src), Val::String(name), Val::Int(line)] if *line > 0 => {
+            (src, name, *line as usize)
+        }
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "load_str expects a string, name, and optional positive line".to_string(),
+            ))
+        }
+    };
+    load_src(fiber, src, name, line, false).await
+}
+
+/// Binding for include
+pub(crate) fn include_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(include PATH) - Like `load`, but relative paths are resolved against directories in `load_path`."
+            .to_string(),
+        func: |f, args| Box::new(include_impl(f, args)),
+    }
+}
+
+/// Implementation for (include PATH)
+async fn include_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let path = match &args[..] {
+        [Val::String(s)] => s.clone(),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "include expects single string argument".to_string(),
+            ))
+        }
+    };
+
+    let load_path = fiber
+        .cur_env()
+        .lock()
+        .unwrap()
+        .get(&SymbolId::from("load_path"));
+    let load_path = match load_path {
+        Some(Val::List(dirs)) => dirs
+            .into_iter()
+            .map(|d| match d {
+                Val::String(d) => Ok(d),
+                v => Err(Error::Runtime(format!(
+                    "load_path should be a list of strings - found {v}"
+                ))),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        Some(v) => {
+            return Err(Error::Runtime(format!(
+                "load_path should be a list of strings - found {v}"
+            )))
+        }
+        None => vec![],
+    };
+
+    let path = resolve_include(&path, &load_path).await?;
+    let contents = read_string(&path).await?;
+    load_src(fiber, &contents, &path.display().to_string(), 1, false).await
+}
+
+/// Evaluate each form in source within caller's environment, locating forms in file where source
+/// starts at given line. Returns value of last form, or list of value or error of each form if
+/// evaluation keeps going after forms that fail.
+async fn load_src(
+    fiber: &mut Fiber,
+    src: &str,
+    file: &str,
+    line: usize,
+    keep_going: bool,
+) -> Result<Val> {
+    let forms = parse_all(src).map_err(|e| traced(e, file.to_string()))?;
+
+    let mut results = vec![];
+    for (form, mut span) in forms {
+        shift_lines(&mut span, line - 1);
+        let run = async {
+            let code = compile_spanned(&Val::from(form), &span, Some(file), Some(fiber))?;
+            lyric::run(&mut fiber.nested(code)).await
+        };
+        match run.await {
+            Ok(v) => results.push(v),
+            Err(e) => {
+                let e = traced(e, format!("{file}:{}", span.pos.line));
+                if !keep_going {
+                    return Err(e);
+                }
+                results.push(Val::Error(e));
+            }
+        }
+    }
+
+    Ok(match keep_going {
+        true => Val::List(results),
+        false => results.pop().unwrap_or(Val::Nil),
+    })
+}
+
+/// Shift positions of span and its children by given number of lines
+fn shift_lines(span: &mut Span, lines: usize) {
+    span.pos.line += lines;
+    span.children.iter_mut().for_each(|s| shift_lines(s, lines));
+}
+
+/// Attach location to error that is not raised with source locations
+fn traced(e: Error, loc: String) -> Error {
+    match e {
+        e @ Error::Traceback { .. } => e,
+        e => Error::Traceback {
+            error: Box::new(e),
+            trace: vec![loc],
+        },
+    }
+}
+
+/// Resolve path to include against load path
+async fn resolve_include(path: &str, load_path: &[String]) -> Result<PathBuf> {
+    let expanded = PathBuf::from(shellexpand::tilde(path).to_string());
+    if expanded.is_absolute() || path.starts_with("./") || path.starts_with("../") {
+        return Ok(expanded);
+    }
+
+    for dir in load_path {
+        let candidate = PathBuf::from(shellexpand::tilde(dir).to_string()).join(path);
+        if tokio::fs::try_exists(&candidate).await.unwrap_or(false) {
+            return Ok(candidate);
+        }
+    }
+
+    Err(Error::Runtime(format!("{path} is not found in load_path")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rt::{kernel, ProcessResult};
+    use crate::{Program, Val};
+    use assert_matches::assert_matches;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn write_atomic_keeps_symlink_and_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("vrs-write-atomic-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let target = dir.join("synced.ll");
+        let link = dir.join("todos.ll");
+        tokio::fs::write(&target, "(:old)").await.unwrap();
+        tokio::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o600))
+            .await
+            .unwrap();
+        tokio::fs::symlink(&target, &link).await.unwrap();
+
+        super::write_atomic(&link, b"(:new)").await.unwrap();
+
+        let link_meta = tokio::fs::symlink_metadata(&link).await.unwrap();
+        assert!(link_meta.file_type().is_symlink(), "symlink should be kept");
+        assert_eq!(tokio::fs::read_to_string(&target).await.unwrap(), "(:new)");
+        let mode = tokio::fs::metadata(&target)
+            .await
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600, "permissions should be kept");
+
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[tokio::test]
+    async fn fdump_fread() {
+        let dir = std::env::temp_dir().join(format!("vrs-fdump-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("todos.ll");
+        tokio::fs::write(&path, "(:old)").await.unwrap();
+
+        let k = kernel::start();
+        let prog = format!(
+            r#"(begin
+                (fdump "{0}" '(:todo "Buy milk"))
+                (list (fread "{0}") (ls_dir "{1}")))"#,
+            path.display(),
+            dir.display()
+        );
+        let hdl = k
+            .spawn_prog(Program::from_expr(&prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![
+                Val::List(vec![Val::keyword("todo"), Val::string("Buy milk")]),
+                Val::List(vec![Val::string("todos.ll")]),
+            ])),
+            "fdump should replace file without leaving temporary files"
+        );
+
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[tokio::test]
+    async fn text_io() {
+        let dir = std::env::temp_dir().join(format!("vrs-text-io-{}", std::process::id()));
+        let k = kernel::start();
+        let prog = format!(
+            r#"(begin
+                (mkdir "{0}/notes")
+                (fwrite_str "{0}/notes/a.txt" "hello")
+                (fappend "{0}/notes/a.txt" " world")
+                (fappend "{0}/notes/b.txt" "new")
+                (rename "{0}/notes/b.txt" "{0}/notes/c.txt")
+                (list
+                    (fread_str "{0}/notes/a.txt")
+                    (ls_dir "{0}/notes")
+                    (fexists? "{0}/notes/b.txt")
+                    (fexists? "{0}/notes/c.txt")))"#,
+            dir.display()
+        );
+        let hdl = k
+            .spawn_prog(Program::from_expr(&prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![
+                Val::string("hello world"),
+                Val::List(vec![Val::string("a.txt"), Val::string("c.txt")]),
+                Val::Bool(false),
+                Val::Bool(true),
+            ]))
+        );
+
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[tokio::test]
+    async fn fstat_rm() {
+        let dir = std::env::temp_dir().join(format!("vrs-fstat-{}", std::process::id()));
+        tokio::fs::create_dir_all(dir.join("sub")).await.unwrap();
+        tokio::fs::write(dir.join("sub/file"), "12345")
+            .await
+            .unwrap();
+
+        let k = kernel::start();
+        let prog = format!(
+            r#"(begin
+                (def stat (fstat "{0}/sub/file"))
+                (list
+                    (get stat :type)
+                    (get stat :size)
+                    (get (fstat "{0}/sub") :type)
+                    (err_kind (try (rm "{0}/sub")))
+                    (rm "{0}/sub" :recursive true)
+                    (fexists? "{0}/sub")))"#,
+            dir.display()
+        );
+        let hdl = k
+            .spawn_prog(Program::from_expr(&prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![
+                Val::keyword("file"),
+                Val::Int(5),
+                Val::keyword("dir"),
+                Val::keyword("directory_not_empty"),
+                Val::keyword("ok"),
+                Val::Bool(false),
+            ]))
+        );
+
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[tokio::test]
+    async fn io_errors() {
+        let path = std::env::temp_dir().join(format!("vrs-missing-{}", std::process::id()));
+        let k = kernel::start();
+        let prog = format!(r#"(try (fread_str "{}"))"#, path.display());
+        let hdl = k
+            .spawn_prog(Program::from_expr(&prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_matches!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::Error(lyric::Error::IO { kind, path: p, .. }))
+                if kind.as_str() == "not_found" && p == path.display().to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn include_from_load_path() {
+        let dir = std::env::temp_dir().join(format!("vrs-include-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        tokio::fs::write(
+            dir.join("lib.ll"),
+            "# Comment\n(def x 40)\n(defn add_x (y) (+ x y)) # trailing comment",
+        )
+        .await
+        .unwrap();
+
+        let k = kernel::start();
+        let prog = format!(
+            r#"(begin
+                (set load_path (list "{}"))
+                (include "lib.ll")
+                (add_x 2))"#,
+            dir.display()
+        );
+        let hdl = k
+            .spawn_prog(Program::from_expr(&prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(exit.status.unwrap(), ProcessResult::Done(Val::Int(42)));
+
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[tokio::test]
+    async fn load_reports_failing_line() {
+        let path = std::env::temp_dir().join(format!("vrs-load-{}.ll", std::process::id()));
+        tokio::fs::write(
+            &path,
+            "(def x 1)\n(defn fail ()\n  (undefined_fn))\n(set x (+ x 1))\n(begin\n  (fail))\n(set x 10)",
+        )
+        .await
+        .unwrap();
+
+        let k = kernel::start();
+        let prog = format!(
+            r#"(begin
+                (def res (try (load "{}")))
+                (list x res))"#,
+            path.display()
+        );
+        let hdl = k
+            .spawn_prog(Program::from_expr(&prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        let expected_err = lyric::Error::Traceback {
+            error: Box::new(lyric::Error::UndefinedSymbol(lyric::SymbolId::from(
+                "undefined_fn",
+            ))),
+            trace: vec![
+                format!("{}:3", path.display()),
+                format!("{}:6", path.display()),
+            ],
+        };
+        assert_matches!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(l)) if matches!(
+                &l[..],
+                [Val::Int(2), Val::Error(e)] if *e == expected_err
+            ),
+            "forms before failure should be evaluated in caller environment"
+        );
+
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+
+    #[tokio::test]
+    async fn load_reports_invalid_form() {
+        let path = std::env::temp_dir().join(format!("vrs-load-invalid-{}.ll", std::process::id()));
+        tokio::fs::write(&path, "(def x 1)\n\n(if)").await.unwrap();
+
+        let k = kernel::start();
+        let prog = format!(r#"(try (load "{}"))"#, path.display());
+        let hdl = k
+            .spawn_prog(Program::from_expr(&prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_matches!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::Error(lyric::Error::Traceback { error, trace }))
+                if matches!(*error, lyric::Error::InvalidExpression(_))
+                    && trace == vec![format!("{}:3", path.display())]
+        );
+
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+
+    #[tokio::test]
+    async fn load_reports_parse_error() {
+        let path = std::env::temp_dir().join(format!("vrs-load-parse-{}.ll", std::process::id()));
+        tokio::fs::write(&path, "(def x 1)\n(def y").await.unwrap();
+
+        let k = kernel::start();
+        let prog = format!(r#"(try (load "{}"))"#, path.display());
+        let hdl = k
+            .spawn_prog(Program::from_expr(&prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_matches!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::Error(lyric::Error::Traceback { error, trace }))
+                if matches!(*error, lyric::Error::IncompleteExpression(_))
+                    && trace == vec![path.display().to_string()]
+        );
+
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+
+    #[tokio::test]
+    async fn load_keep_going() {
+        let path = std::env::temp_dir().join(format!("vrs-load-keep-{}.ll", std::process::id()));
+        tokio::fs::write(&path, "(def x 1)\n(undefined_fn)\n(set x (+ x 1))")
+            .await
+            .unwrap();
+
+        let k = kernel::start();
+        let prog = format!(r#"(list (load "{}" :keep_going true) x)"#, path.display());
+        let hdl = k
+            .spawn_prog(Program::from_expr(&prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        let expected_err = lyric::Error::Traceback {
+            error: Box::new(lyric::Error::UndefinedSymbol(lyric::SymbolId::from(
+                "undefined_fn",
+            ))),
+            trace: vec![format!("{}:2", path.display())],
+        };
+        assert_matches!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(l)) if matches!(
+                &l[..],
+                [Val::List(results), Val::Int(2)] if matches!(
+                    &results[..],
+                    [Val::Int(1), Val::Error(e), Val::Int(2)] if *e == expected_err
+                )
+            ),
+            "forms after failing form should be evaluated"
+        );
+
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+
+    #[tokio::test]
+    async fn load_str_reports_line() {
+        let k = kernel::start();
+        let prog = r#"(begin
+            (def x (load_str "(def y 1)\n(+ y 1)" "script.ll"))
+            (list x (try (load_str "(begin\n  (undefined_fn))" "script.ll" 10))))"#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        let expected_err = lyric::Error::Traceback {
+            error: Box::new(lyric::Error::UndefinedSymbol(lyric::SymbolId::from(
+                "undefined_fn",
+            ))),
+            trace: vec!["script.ll:11".to_string()],
+        };
+        assert_matches!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(l)) if matches!(
+                &l[..],
+                [Val::Int(2), Val::Error(e)] if *e == expected_err
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn load_str_reports_line_after_defmacro() {
+        let k = kernel::start();
+        let prog = r#"(try (load_str "(begin\n  (defmacro m (x) x)\n  (m 1)\n  (undefined_fn))" "script.ll"))"#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        let expected_err = lyric::Error::Traceback {
+            error: Box::new(lyric::Error::UndefinedSymbol(lyric::SymbolId::from(
+                "undefined_fn",
+            ))),
+            trace: vec!["script.ll:4".to_string(), "script.ll:1".to_string()],
+        };
+        assert_matches!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::Error(e)) if e == expected_err,
+            "forms after defmacro should keep their locations"
+        );
+    }
+
+    #[tokio::test]
+    async fn include_missing() {
+        let k = kernel::start();
+        let hdl = k
+            .spawn_prog(
+                Program::from_expr(r#"(begin (set load_path '()) (include "missing.ll"))"#)
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_matches!(exit.status, Err(_));
+    }
+}
@@ -1,9 +1,11 @@
 //! Bindings for Process Mailbox
+use std::time::Duration;
+
 use crate::rt::{
-    mailbox::Message,
-    program::{Extern, Fiber, Lambda, NativeAsyncFn, Pattern, Val},
+    mailbox::{MailboxHandle, Message},
+    program::{Extern, Fiber, NativeAsyncFn, Pattern, Val},
 };
-use lyric::{compile, parse, Error, Result, SymbolId};
+use lyric::{Error, KeywordId, Map, MapKey, Ref, Result};
 
 pub(crate) fn send_fn() -> NativeAsyncFn {
     NativeAsyncFn {
@@ -22,33 +24,33 @@ pub(crate) fn recv_fn() -> NativeAsyncFn {
     }
 }
 
+/// Binding to recv messages with a timeout
+pub(crate) fn recv_timeout_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(recv_timeout MS [PATTERN]) - Poll mailbox for a message like recv, waiting at most MS milliseconds. \
+              If no message is received in time, returns an error of kind :timeout. MS may be nil to wait forever."
+            .to_string(),
+        func: |f, args| Box::new(recv_timeout_impl(f, args)),
+    }
+}
+
 /// Binding to list messages
 pub(crate) fn ls_msgs_fn() -> NativeAsyncFn {
     NativeAsyncFn {
-        doc: "(ls_msgs) - Returns contents of mailbox without consuming messages or blocking when mailbox is empty.".to_string(),
+        doc: "(ls_msgs [:stats]) - Returns contents of mailbox without consuming messages or blocking when mailbox is empty. \
+              With :stats, returns a map of number of messages in mailbox as :depth, pending polls as :pending, \
+              senders blocked on a full mailbox as :blocked, and mailbox :capacity.".to_string(),
         func: |f, args| Box::new(ls_msgs_impl(f, args)),
     }
 }
 
 /// Binding for call
-pub(crate) fn call_fn() -> Lambda {
-    Lambda {
-        doc: Some("(call PID MSG) - Send process PID a message MSG and block until receiving a response for the message".to_string()),
-        params: vec![SymbolId::from("pid"), SymbolId::from("msg")],
-        code: compile(
-            &parse(
-                r#"
-            (begin
-                (def r (ref))
-                (send pid (list r (self) msg))
-                (get (recv (list r 'any)) 1))
-        "#,
-            )
-            .unwrap()
-            .into(),
-        )
-        .unwrap(),
-        parent: None,
+pub(crate) fn call_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(call PID MSG [:timeout MS]) - Send process PID a message MSG and block until receiving a response for the message. \
+              If no response is received within MS milliseconds, returns an error of kind :timeout, and the response is dropped if it arrives later."
+            .to_string(),
+        func: |f, args| Box::new(call_impl(f, args)),
     }
 }
 
@@ -90,31 +92,130 @@ async fn send_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
 
 /// Implementation for (recv PAT)
 async fn recv_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
-    let pat = match &args[..] {
-        [pat] => Some(Pattern::from_val(pat.clone())),
-        [] => None,
-        _ => Some(Pattern::from_vals(&args[..])),
+    let msg = mailbox(fiber)
+        .poll(recv_pattern(&args))
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(msg.contents)
+}
+
+/// Implementation for (recv_timeout MS PAT)
+async fn recv_timeout_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (timeout, args) = match &args[..] {
+        [ms, args @ ..] => (timeout_ms(ms)?, args),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "Unexpected recv_timeout call - (recv_timeout MS [PATTERN])".to_string(),
+            ))
+        }
     };
-    let mailbox = fiber
+    let mailbox = mailbox(fiber);
+    let pat = recv_pattern(args);
+    let msg = match timeout {
+        Some(timeout) => mailbox.poll_timeout(pat, timeout).await,
+        None => mailbox.poll(pat).await.map(Some),
+    }
+    .map_err(|e| Error::Runtime(format!("{e}")))?;
+
+    Ok(match msg {
+        Some(msg) => msg.contents,
+        None => Val::Error(timeout_err(timeout)),
+    })
+}
+
+/// Implementation for (call PID MSG [:timeout MS])
+async fn call_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (pid, msg, timeout) = match &args[..] {
+        [pid, msg] => (pid, msg, None),
+        [pid, msg, Val::Keyword(k), ms] if k.as_str() == "timeout" => (pid, msg, timeout_ms(ms)?),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "Unexpected call - (call PID MSG [:timeout MS])".to_string(),
+            ))
+        }
+    };
+
+    // Request is tagged with unique ref, which response should include
+    let r = Val::Ref(Ref::new());
+    let req = Val::List(vec![
+        r.clone(),
+        Val::Extern(Extern::ProcessId(fiber.locals().pid)),
+        msg.clone(),
+    ]);
+    send_impl(fiber, vec![pid.clone(), req]).await?;
+
+    let mailbox = mailbox(fiber);
+    let pat = Pattern::from_val(Val::List(vec![r, Val::symbol("_")]));
+    let resp = match timeout {
+        Some(timeout) => mailbox.poll_timeout(Some(pat.clone()), timeout).await,
+        None => mailbox.poll(Some(pat.clone())).await.map(Some),
+    }
+    .map_err(|e| Error::Runtime(format!("{e}")))?;
+
+    match resp.map(|m| m.contents) {
+        Some(Val::List(l)) if l.len() == 2 => Ok(l[1].clone()),
+        Some(v) => Err(Error::Runtime(format!("Unexpected response to call - {v}"))),
+        None => {
+            // Response arriving after timeout is no longer waited for
+            mailbox
+                .discard(pat)
+                .await
+                .map_err(|e| Error::Runtime(format!("{e}")))?;
+            Ok(Val::Error(timeout_err(timeout)))
+        }
+    }
+}
+
+/// Mailbox of process running fiber
+fn mailbox(fiber: &Fiber) -> MailboxHandle {
+    fiber
         .locals()
         .self_handle
         .as_ref()
         .expect("process should have self handle")
-        .mailbox();
-    let msg = mailbox
-        .poll(pat)
-        .await
-        .map_err(|e| Error::Runtime(format!("{e}")))?;
-    Ok(msg.contents)
+        .mailbox()
+        .clone()
+}
+
+/// Pattern for recv from given pattern arguments, matching any message if there are none
+fn recv_pattern(args: &[Val]) -> Option<Pattern> {
+    match args {
+        [pat] => Some(Pattern::from_val(pat.clone())),
+        [] => None,
+        _ => Some(Pattern::from_vals(args)),
+    }
+}
+
+/// Parse timeout in milliseconds, where nil is no timeout
+fn timeout_ms(ms: &Val) -> Result<Option<Duration>> {
+    match ms {
+        Val::Nil => Ok(None),
+        Val::Int(ms) if *ms >= 0 => Ok(Some(Duration::from_millis(*ms as u64))),
+        _ => Err(Error::UnexpectedArguments(format!(
+            "timeout expects milliseconds as a non-negative integer - got {ms}"
+        ))),
+    }
+}
+
+/// Error for timing out while waiting for a message
+fn timeout_err(timeout: Option<Duration>) -> Error {
+    Error::Timeout(format!(
+        "No message after {}ms",
+        timeout.unwrap_or_default().as_millis()
+    ))
 }
 
 /// Implementation for (ls_msgs)
 async fn ls_msgs_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
-    if !args.is_empty() {
-        return Err(Error::UnexpectedArguments(
-            "Unexpected ls_msgs call - No arguments expected".to_string(),
-        ));
-    }
+    let stats = match &args[..] {
+        [] => false,
+        [Val::Keyword(k)] if k.as_str() == "stats" => true,
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "Unexpected ls_msgs call - (ls_msgs [:stats])".to_string(),
+            ))
+        }
+    };
 
     let mailbox = fiber
         .locals()
@@ -123,6 +224,32 @@ async fn ls_msgs_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
         .expect("process should have self handle")
         .mailbox();
 
+    if stats {
+        let stats = mailbox
+            .stats()
+            .await
+            .map_err(|e| Error::Runtime(format!("{e}")))?;
+        let count = |n: usize| Val::Int(n as i64);
+        return Ok(Val::Map(Map::from([
+            (
+                MapKey::Keyword(KeywordId::from("depth")),
+                count(stats.depth),
+            ),
+            (
+                MapKey::Keyword(KeywordId::from("pending")),
+                count(stats.pending),
+            ),
+            (
+                MapKey::Keyword(KeywordId::from("blocked")),
+                count(stats.blocked),
+            ),
+            (
+                MapKey::Keyword(KeywordId::from("capacity")),
+                stats.capacity.map(count).unwrap_or(Val::Nil),
+            ),
+        ])));
+    }
+
     let msgs = mailbox
         .all()
         .await
@@ -386,4 +513,109 @@ mod tests {
             )
         );
     }
+
+    #[tokio::test]
+    async fn recv_timeout() {
+        let k = kernel::start();
+
+        let prog = r#"(begin
+            (send (self) :late)
+            (list
+                (err_kind (recv_timeout 5 :never))
+                (recv_timeout 5 :late)
+                (err_kind (recv_timeout 0))
+                (send (self) '(:a :timeout 5))
+                (recv_timeout 5 '(:a :timeout 5))))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(
+                Val::from_expr("(:timeout :late :timeout (:a :timeout 5) (:a :timeout 5))")
+                    .unwrap()
+            ),
+            "timeout should not be confused with patterns or messages"
+        );
+    }
+
+    #[tokio::test]
+    async fn call_timeout() {
+        let k = kernel::start();
+
+        let prog = r#"(begin
+            (def silent (spawn (lambda () (recv :never))))
+            (def echo (spawn (lambda () (loop
+                (def (r src msg) (recv))
+                (send src (list r msg))))))
+            (list
+                (err_kind (call silent :hello :timeout 5))
+                (call echo :hello :timeout 1000)))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::from_expr("(:timeout :hello)").unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn call_timeout_drops_late_response() {
+        let k = kernel::start();
+
+        let prog = r#"(begin
+            (def slow (spawn (lambda () (loop
+                (def (r src msg) (recv))
+                (sleep_ms 50)
+                (send src (list r msg))))))
+            (def timed_out (err? (call slow :first :timeout 5)))
+            (list
+                timed_out
+                (call slow :second)
+                (ls_msgs)))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::from_expr("(true :second ())").unwrap()),
+            "response arriving after timeout should be dropped"
+        );
+    }
+
+    #[tokio::test]
+    async fn ls_msgs_stats() {
+        let k = kernel::start();
+
+        let prog = r#"(begin
+            (send (self) :one)
+            (send (self) :two)
+            (ls_msgs :stats))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(
+                Val::from_expr("{:depth 2 :pending 0 :blocked 0 :capacity nil}").unwrap()
+            )
+        );
+    }
 }
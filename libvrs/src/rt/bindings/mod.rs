@@ -1,11 +1,17 @@
 mod fs;
 mod mailbox;
 mod open;
+mod port;
 mod proc;
 mod pubsub;
+mod scheduler;
 mod service;
+mod store;
+mod supervisor;
 mod system;
 mod term;
+mod timer;
+mod watcher;
 
 pub(crate) use term::recv_req_fn;
 pub(crate) use term::send_resp_fn;
@@ -13,18 +19,26 @@ pub(crate) use term::send_resp_fn;
 pub(crate) use mailbox::call_fn;
 pub(crate) use mailbox::ls_msgs_fn;
 pub(crate) use mailbox::recv_fn;
+pub(crate) use mailbox::recv_timeout_fn;
 pub(crate) use mailbox::send_fn;
 
 pub(crate) use proc::kill_fn;
+pub(crate) use proc::link_fn;
+pub(crate) use proc::monitor_fn;
 pub(crate) use proc::pid_fn;
 pub(crate) use proc::ps_fn;
 pub(crate) use proc::self_fn;
-pub(crate) use proc::sleep_fn;
 pub(crate) use proc::spawn_fn;
+pub(crate) use proc::spawn_link_fn;
+pub(crate) use proc::trap_exit_fn;
 
 pub(crate) use system::exec_fn;
 pub(crate) use system::shell_expand_fn;
 
+pub(crate) use port::port_close_fn;
+pub(crate) use port::port_write_fn;
+pub(crate) use port::spawn_cmd_fn;
+
 pub(crate) use open::open_app_fn;
 pub(crate) use open::open_file_fn;
 pub(crate) use open::open_url_fn;
@@ -35,11 +49,48 @@ pub(crate) use service::find_srv_fn;
 pub(crate) use service::info_srv_fn;
 pub(crate) use service::ls_srv_fn;
 pub(crate) use service::register_fn;
-pub(crate) use service::spawn_srv_fn;
-pub(crate) use service::srv_fn;
+pub(crate) use service::spawn_srv_macro;
+pub(crate) use service::srv_macro;
+pub(crate) use service::srv_match_arms_fn;
+
+pub(crate) use supervisor::supervise_fn;
+
+pub(crate) use store::store_delete_fn;
+pub(crate) use store::store_get_fn;
+pub(crate) use store::store_put_fn;
+pub(crate) use store::store_query_fn;
+pub(crate) use store::store_tx_fn;
+
+pub(crate) use scheduler::ls_jobs_fn;
+pub(crate) use scheduler::schedule_job_fn;
+pub(crate) use scheduler::schedule_macro;
+pub(crate) use scheduler::unschedule_fn;
+
+pub(crate) use timer::cancel_timer_fn;
+pub(crate) use timer::duration_fn;
+pub(crate) use timer::interval_fn;
+pub(crate) use timer::send_after_fn;
+pub(crate) use timer::sleep_fn;
+pub(crate) use timer::sleep_ms_fn;
 
 pub(crate) use pubsub::publish_fn;
 pub(crate) use pubsub::subscribe_fn;
 
+pub(crate) use fs::fappend_fn;
 pub(crate) use fs::fdump_fn;
+pub(crate) use fs::fexists_fn;
 pub(crate) use fs::fread_fn;
+pub(crate) use fs::fread_str_fn;
+pub(crate) use fs::fstat_fn;
+pub(crate) use fs::fwrite_str_fn;
+pub(crate) use fs::include_fn;
+pub(crate) use fs::load_fn;
+pub(crate) use fs::load_str_fn;
+pub(crate) use fs::ls_dir_fn;
+pub(crate) use fs::mkdir_fn;
+pub(crate) use fs::rename_fn;
+pub(crate) use fs::rm_fn;
+pub(crate) use fs::write_atomic;
+
+pub(crate) use watcher::unwatch_path_fn;
+pub(crate) use watcher::watch_path_fn;
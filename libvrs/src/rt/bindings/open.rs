@@ -10,8 +10,12 @@ pub(crate) fn open_url_fn() -> Lambda {
     Lambda {
         doc: Some("(open_url URL) - Opens URL in browser".to_string()),
         params: vec![SymbolId::from("url")],
-        code: compile(&parse(r#"(exec "open" "-a" "Safari" url)"#).unwrap().into()).unwrap(),
+        code: compile(&parse(r#"(exec "open" "-a" "Safari" url)"#).unwrap().into())
+            .unwrap()
+            .into(),
         parent: None,
+        scope: None,
+        upvals: vec![],
     }
 }
 
@@ -22,8 +26,12 @@ pub(crate) fn open_app_fn() -> Lambda {
             "(open_app APP_NAME) - Opens the application APP_NAME on host machine".to_string(),
         ),
         params: vec![SymbolId::from("app")],
-        code: compile(&parse(r#"(exec "open" "-a" app)"#).unwrap().into()).unwrap(),
+        code: compile(&parse(r#"(exec "open" "-a" app)"#).unwrap().into())
+            .unwrap()
+            .into(),
         parent: None,
+        scope: None,
+        upvals: vec![],
     }
 }
 
@@ -37,7 +45,10 @@ pub(crate) fn open_file_fn() -> Lambda {
                 .unwrap()
                 .into(),
         )
-        .unwrap(),
+        .unwrap()
+        .into(),
         parent: None,
+        scope: None,
+        upvals: vec![],
     }
 }
@@ -0,0 +1,192 @@
+//! Port Bindings
+use crate::rt::port::PortHandle;
+use crate::rt::program::{Extern, Fiber, NativeAsyncFn, Val};
+use lyric::{Error, Result};
+
+/// Binding for spawn_cmd
+pub(crate) fn spawn_cmd_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(spawn_cmd PROG ARG1 ARG2 ... ARGN) - Start external executable PROG with command line arguments ARG1 to ARGN, \
+              returning a port PORT. Each line of output is sent to caller as (:stdout PORT LINE) or (:stderr PORT LINE), \
+              followed by (:exit PORT CODE) when executable exits. Executable is killed when caller exits."
+            .to_string(),
+        func: |f, args| Box::new(spawn_cmd_impl(f, args)),
+    }
+}
+
+/// Binding for port_write
+pub(crate) fn port_write_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(port_write PORT STR) - Write STR to standard input of executable running in PORT"
+            .to_string(),
+        func: |_, args| Box::new(port_write_impl(args)),
+    }
+}
+
+/// Binding for port_close
+pub(crate) fn port_close_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(port_close PORT) - Close standard input of executable running in PORT and kill it. \
+              Remaining output and (:exit PORT CODE) are still sent to owner of PORT."
+            .to_string(),
+        func: |_, args| Box::new(port_close_impl(args)),
+    }
+}
+
+/// Implementation for (spawn_cmd PROG ARGS...)
+async fn spawn_cmd_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let args = args
+        .iter()
+        .map(|a| match a {
+            Val::String(s) => Ok(s.clone()),
+            _ => Err(Error::UnexpectedArguments(
+                "spawn_cmd expects string arguments - (spawn_cmd PROG [ARGS...])".to_string(),
+            )),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let (prog, args) = args.split_first().ok_or(Error::UnexpectedArguments(
+        "spawn_cmd expects executable to run - (spawn_cmd PROG [ARGS...])".to_string(),
+    ))?;
+
+    let owner = fiber
+        .locals()
+        .self_handle
+        .clone()
+        .ok_or(Error::Runtime("Process is missing self handle".to_string()))?;
+    let port = PortHandle::spawn(prog, args, owner).map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::Extern(Extern::Port(port)))
+}
+
+/// Implementation for (port_write PORT STR)
+async fn port_write_impl(args: Vec<Val>) -> Result<Val> {
+    let (port, contents) = match &args[..] {
+        [Val::Extern(Extern::Port(port)), Val::String(s)] => (port, s),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "port_write expects a port and string - (port_write PORT STR)".to_string(),
+            ))
+        }
+    };
+    port.write(contents)
+        .await
+        .map_err(|e| Error::Runtime(format!("Failed to write to {port} - {e}")))?;
+    Ok(Val::keyword("ok"))
+}
+
+/// Implementation for (port_close PORT)
+async fn port_close_impl(args: Vec<Val>) -> Result<Val> {
+    let port = match &args[..] {
+        [Val::Extern(Extern::Port(port))] => port,
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "port_close expects single port argument".to_string(),
+            ))
+        }
+    };
+    port.close().await;
+    Ok(Val::keyword("ok"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rt::{kernel, ProcessResult};
+    use crate::{Program, Val};
+    use assert_matches::assert_matches;
+    use std::time::Duration;
+    use tokio::time;
+
+    #[tokio::test]
+    async fn spawn_cmd_streams_lines() {
+        let k = kernel::start();
+        let prog = r#"(begin
+            (def port (spawn_cmd "sh" "-c" "echo one; echo two; echo oops >&2; exit 2"))
+            (def (:stdout p1 a) (recv '(:stdout _ _)))
+            (def (:stdout p2 b) (recv '(:stdout _ _)))
+            (def (:stderr _ c) (recv '(:stderr _ _)))
+            (def (:exit _ code) (recv '(:exit _ _)))
+            (list (eq? p1 port) (eq? p2 port) a b c code))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = time::timeout(Duration::from_secs(1), hdl.join())
+            .await
+            .expect("port should deliver output and exit")
+            .unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![
+                Val::Bool(true),
+                Val::Bool(true),
+                Val::string("one"),
+                Val::string("two"),
+                Val::string("oops"),
+                Val::Int(2),
+            ]))
+        );
+    }
+
+    #[tokio::test]
+    async fn port_write_and_close() {
+        let k = kernel::start();
+        let prog = r#"(begin
+            (def port (spawn_cmd "cat"))
+            (port_write port "hello\n")
+            (def (:stdout _ line) (recv '(:stdout _ _)))
+            (port_close port)
+            (def (:exit _ code) (recv '(:exit _ _)))
+            (list line code (err? (try (port_write port "closed\n")))))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = time::timeout(Duration::from_secs(1), hdl.join())
+            .await
+            .expect("closed port should exit")
+            .unwrap();
+        assert_matches!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(res)) if matches!(
+                &res[..],
+                // cat may exit on closed stdin before it is killed
+                [Val::String(line), Val::Nil | Val::Int(0), Val::Bool(true)] if line == "hello"
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn port_killed_on_owner_exit() {
+        let k = kernel::start();
+        let prog = r#"(begin
+            (def parent (self))
+            (spawn (lambda () (begin
+                (def port (spawn_cmd "sh" "-c" "echo $$; exec sleep 10"))
+                (send parent (recv '(:stdout _ _))))))
+            (def (:stdout _ pid) (recv))
+            pid)
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+        let pid = match hdl.join().await.unwrap().status.unwrap() {
+            ProcessResult::Done(Val::String(pid)) => pid,
+            status => panic!("Unexpected result - {status:?}"),
+        };
+
+        let proc_path = std::path::PathBuf::from(format!("/proc/{pid}"));
+        time::timeout(Duration::from_secs(1), async {
+            while std::fs::read_to_string(proc_path.join("stat"))
+                .is_ok_and(|stat| !stat.contains(") Z "))
+            {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("child should be killed when owner exits");
+    }
+}
@@ -1,10 +1,8 @@
 //! Process Management Bindings
+use crate::rt::kernel::KernelHandle;
 use crate::rt::program::{Extern, Fiber, NativeAsyncFn, NativeFn, NativeFnOp, Program, Val};
 use crate::rt::ProcessId;
-use lyric::{Error, Result};
-use std::time::Duration;
-use tokio::time;
-use tracing::debug;
+use lyric::{Error, KeywordId, Map, MapKey, Result};
 
 /// binding to get current process's pid
 pub(crate) fn self_fn() -> NativeFn {
@@ -40,8 +38,10 @@ pub(crate) fn pid_fn() -> NativeFn {
 /// Binding to list processes
 pub(crate) fn ps_fn() -> NativeAsyncFn {
     NativeAsyncFn {
-        doc: "(ps) - Returns a list of running process by process id".to_string(),
-        func: |f, _| Box::new(ps_impl(f)),
+        doc: "(ps) - Returns a list of running process by process id. \
+              (ps :info) returns a list of maps with :pid and :reductions, the number of instructions run by each process"
+            .to_string(),
+        func: |f, args| Box::new(ps_impl(f, args)),
     }
 }
 
@@ -53,55 +53,95 @@ pub(crate) fn kill_fn() -> NativeAsyncFn {
     }
 }
 
-/// Binding for sleep
-pub(crate) fn sleep_fn() -> NativeAsyncFn {
+/// Binding for spawn
+pub(crate) fn spawn_fn() -> NativeAsyncFn {
     NativeAsyncFn {
-        doc: "(sleep SECS) - Sleep current process for SECS seconds, blocking execution."
+        doc: "(spawn LAMBDA) - Spawn a new child process that runs LAMBDA in new process space."
             .to_string(),
-        func: |_, args| {
-            Box::new({
-                async move {
-                    let secs = match args[..] {
-                        [Val::Int(secs)] => secs,
-                        _ => {
-                            return Err(Error::UnexpectedArguments(
-                                "sleep expects single integer argument".to_string(),
-                            ))
-                        }
-                    };
-                    debug!("sleep secs = {:?}", secs);
-                    time::sleep(Duration::from_secs(secs as u64)).await;
-                    Ok(Val::keyword("ok"))
-                }
-            })
-        },
+        func: |f, args| Box::new(spawn_impl(f, args)),
     }
 }
 
-/// Binding for spawn
-pub(crate) fn spawn_fn() -> NativeAsyncFn {
+/// Binding for spawn_link
+pub(crate) fn spawn_link_fn() -> NativeAsyncFn {
     NativeAsyncFn {
-        doc: "(spawn LAMBDA) - Spawn a new child process that runs LAMBDA in new process space."
+        doc: "(spawn_link LAMBDA) - Spawn a new child process that runs LAMBDA in new process space, \
+              linked to caller."
             .to_string(),
-        func: |f, args| Box::new(spawn_impl(f, args)),
+        func: |f, args| Box::new(spawn_link_impl(f, args)),
     }
 }
 
-/// Implementation for (ps)
-async fn ps_impl(fiber: &mut Fiber) -> Result<Val> {
-    let kernel = fiber
-        .locals()
-        .kernel
-        .as_ref()
-        .and_then(|k| k.upgrade())
-        .ok_or(Error::Runtime("Kernel is missing for process".into()))?;
-    let procs = kernel
+/// Binding for link
+pub(crate) fn link_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(link PID) - Link caller to process PID. When either process exits abnormally, \
+              the other is killed unless it traps exits."
+            .to_string(),
+        func: |f, args| Box::new(link_impl(f, args)),
+    }
+}
+
+/// Binding for monitor
+pub(crate) fn monitor_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(monitor PID) - Monitor process PID, returning a reference REF. \
+              The message (:down REF PID REASON) is sent to caller when PID exits."
+            .to_string(),
+        func: |f, args| Box::new(monitor_impl(f, args)),
+    }
+}
+
+/// Binding for trap_exit
+pub(crate) fn trap_exit_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(trap_exit BOOL) - When BOOL is true, exits of linked processes are received as \
+              (:exit PID REASON) messages instead of killing caller."
+            .to_string(),
+        func: |f, args| Box::new(trap_exit_impl(f, args)),
+    }
+}
+
+/// Implementation for (ps) and (ps :info)
+async fn ps_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let info = match &args[..] {
+        [] => false,
+        [Val::Keyword(k)] if k.as_str() == "info" => true,
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "ps expects no arguments or :info".to_string(),
+            ))
+        }
+    };
+    let kernel = kernel(fiber)?;
+    let pids = kernel
         .procs()
         .await
-        .map_err(|e| Error::Runtime(format!("{e}")))?
-        .into_iter()
-        .map(|pid| Val::Extern(Extern::ProcessId(pid)))
-        .collect::<Vec<_>>();
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    if !info {
+        return Ok(Val::List(
+            pids.into_iter()
+                .map(|pid| Val::Extern(Extern::ProcessId(pid)))
+                .collect(),
+        ));
+    }
+
+    let key = |k: &str| MapKey::Keyword(KeywordId::from(k));
+    let mut procs = vec![];
+    for pid in pids {
+        // Process may exit between listing and lookup
+        let Some(hdl) = kernel
+            .process(pid)
+            .await
+            .map_err(|e| Error::Runtime(format!("{e}")))?
+        else {
+            continue;
+        };
+        procs.push(Val::Map(Map::from([
+            (key("pid"), Val::Extern(Extern::ProcessId(pid))),
+            (key("reductions"), Val::Int(hdl.reductions() as i64)),
+        ])));
+    }
     Ok(Val::List(procs))
 }
 
@@ -116,12 +156,7 @@ async fn kill_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
             ))
         }
     };
-    let kernel = fiber
-        .locals()
-        .kernel
-        .as_ref()
-        .and_then(|k| k.upgrade())
-        .ok_or(Error::Runtime("Kernel is missing for process".to_string()))?;
+    let kernel = kernel(fiber)?;
     kernel
         .kill_proc(pid)
         .await
@@ -140,12 +175,7 @@ async fn spawn_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
         }
     };
     let prog = Program::from_lambda(lambda)?;
-    let kernel = fiber
-        .locals()
-        .kernel
-        .as_ref()
-        .and_then(|k| k.upgrade())
-        .ok_or(Error::Runtime("Kernel is missing for process".to_string()))?;
+    let kernel = kernel(fiber)?;
     let hdl = kernel
         .spawn_prog(prog)
         .await
@@ -153,11 +183,100 @@ async fn spawn_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
     Ok(Val::Extern(Extern::ProcessId(hdl.id())))
 }
 
+/// Implementation for (spawn_link PROG)
+async fn spawn_link_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let lambda = match args.as_slice() {
+        [Val::Lambda(l)] => l.clone(),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "spawn_link expects single lambda".to_string(),
+            ))
+        }
+    };
+    let prog = Program::from_lambda(lambda)?;
+    let pid = fiber.locals().pid;
+    let kernel = kernel(fiber)?;
+    let hdl = kernel
+        .spawn_prog_linked(prog, pid)
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::Extern(Extern::ProcessId(hdl.id())))
+}
+
+/// Implementation for (link PID)
+async fn link_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let other = match args[..] {
+        [Val::Extern(Extern::ProcessId(pid))] => pid,
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "link expects single pid argument".to_string(),
+            ))
+        }
+    };
+    let pid = fiber.locals().pid;
+    let kernel = kernel(fiber)?;
+    kernel
+        .link(pid, other)
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::keyword("ok"))
+}
+
+/// Implementation for (monitor PID)
+async fn monitor_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let target = match args[..] {
+        [Val::Extern(Extern::ProcessId(pid))] => pid,
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "monitor expects single pid argument".to_string(),
+            ))
+        }
+    };
+    let pid = fiber.locals().pid;
+    let kernel = kernel(fiber)?;
+    let r = kernel
+        .monitor(pid, target)
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::Ref(r))
+}
+
+/// Implementation for (trap_exit BOOL)
+async fn trap_exit_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let trap = match args[..] {
+        [Val::Bool(trap)] => trap,
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "trap_exit expects single boolean argument".to_string(),
+            ))
+        }
+    };
+    let pid = fiber.locals().pid;
+    let kernel = kernel(fiber)?;
+    kernel
+        .trap_exit(pid, trap)
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::keyword("ok"))
+}
+
+/// Kernel of process running fiber
+fn kernel(fiber: &Fiber) -> Result<KernelHandle> {
+    fiber
+        .locals()
+        .kernel
+        .as_ref()
+        .and_then(|k| k.upgrade())
+        .ok_or(Error::Runtime("Kernel is missing for process".to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::rt::{kernel, ProcessResult};
     use assert_matches::assert_matches;
+    use std::time::Duration;
+    use tokio::time;
 
     #[tokio::test]
     async fn binding_self() {
@@ -176,43 +295,82 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn sleep() {
+    async fn ps() {
         let k = kernel::start();
         let hdl = k
-            .spawn_prog(Program::from_expr("(sleep 0)").unwrap())
+            .spawn_prog(Program::from_expr("(ps)").unwrap())
             .await
-            .expect("Kernel should spawn new process");
-        assert_eq!(k.procs().await.unwrap(), vec![hdl.id()]);
+            .unwrap();
 
+        let pid = Val::Extern(Extern::ProcessId(hdl.id()));
         let exit = hdl.join().await.unwrap();
-
-        assert_eq!(
+        assert_matches!(
             exit.status.unwrap(),
-            ProcessResult::Done(Val::keyword("ok"))
+            ProcessResult::Done(Val::List(pids)) if
+                pids.contains(&pid)
         );
     }
 
     #[tokio::test]
-    async fn ps() {
+    async fn ps_info() {
         let k = kernel::start();
         let hdl = k
-            .spawn_prog(Program::from_expr("(ps)").unwrap())
+            .spawn_prog(
+                Program::from_expr(
+                    "(begin
+                        (defn count (n) (if (eq? n 0) :done (count (- n 1))))
+                        (count 100)
+                        (ps :info))",
+                )
+                .unwrap(),
+            )
             .await
             .unwrap();
 
         let pid = Val::Extern(Extern::ProcessId(hdl.id()));
         let exit = hdl.join().await.unwrap();
+        let ProcessResult::Done(Val::List(procs)) = exit.status.unwrap() else {
+            panic!("ps :info should return list");
+        };
+        let info = procs
+            .iter()
+            .find_map(|p| match p {
+                Val::Map(m) if m.get(&MapKey::Keyword("pid".into())) == Some(&pid) => Some(m),
+                _ => None,
+            })
+            .expect("ps :info should include caller");
         assert_matches!(
-            exit.status.unwrap(),
-            ProcessResult::Done(Val::List(pids)) if
-                pids.contains(&pid)
+            info.get(&MapKey::Keyword("reductions".into())),
+            Some(Val::Int(n)) if *n > 100
         );
     }
 
     #[tokio::test]
-    async fn kill() {
-        use tokio::time;
+    async fn busy_process_does_not_starve() {
+        let k = kernel::start();
+        let busy = k
+            .spawn_prog(Program::from_expr("(loop 5)").unwrap())
+            .await
+            .unwrap();
+        let hdl = k
+            .spawn_prog(Program::from_expr("(+ 1 2)").unwrap())
+            .await
+            .unwrap();
+
+        let exit = time::timeout(Duration::from_secs(5), hdl.join())
+            .await
+            .expect("process should run alongside busy process")
+            .unwrap();
+        assert_eq!(exit.status.unwrap(), ProcessResult::Done(Val::Int(3)));
 
+        assert!(busy.reductions() > 0);
+        busy.kill().await;
+        let exit = busy.join().await.unwrap();
+        assert_eq!(exit.status.unwrap(), ProcessResult::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn kill() {
         let k = kernel::start();
 
         let kill_target = k
@@ -272,4 +430,153 @@ mod tests {
             "Spawned pid should still be running"
         );
     }
+
+    #[tokio::test]
+    async fn spawn_link_kills_on_error() {
+        let k = kernel::start();
+
+        let prog = r#"(begin
+            (spawn_link (lambda () (undefined_fn)))
+            (loop (sleep 1)))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = time::timeout(Duration::from_secs(1), hdl.join())
+            .await
+            .expect("linked process should be killed")
+            .unwrap();
+        assert_eq!(exit.status.unwrap(), ProcessResult::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn spawn_link_normal_exit() {
+        let k = kernel::start();
+
+        let prog = r#"(begin
+            (def parent (self))
+            (spawn_link (lambda () (send parent :child_done)))
+            (recv :child_done)
+            (sleep 0)
+            :parent_done)
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::keyword("parent_done")),
+            "normal exits should not kill linked processes"
+        );
+    }
+
+    #[tokio::test]
+    async fn trap_exit_receives_exit_msg() {
+        let k = kernel::start();
+
+        let prog = r#"(begin
+            (trap_exit true)
+            (def child (spawn_link (lambda () (undefined_fn))))
+            (def (:exit pid reason) (recv '(:exit _ _)))
+            (list (eq? pid child) (err? reason)))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![Val::Bool(true), Val::Bool(true)]))
+        );
+    }
+
+    #[tokio::test]
+    async fn link_kills_both_ways() {
+        let k = kernel::start();
+
+        let target = k
+            .spawn_prog(Program::from_expr("(loop (sleep 1))").unwrap())
+            .await
+            .unwrap();
+
+        let linker = k
+            .spawn_prog(
+                Program::from_expr(&format!(
+                    "(begin (link (pid {})) (loop (sleep 1)))",
+                    target.id().inner()
+                ))
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // wait for link to be established
+        while k.procs().await.unwrap().len() != 2 {
+            tokio::task::yield_now().await;
+        }
+        time::sleep(Duration::from_millis(10)).await;
+
+        target.kill().await;
+
+        let exit = time::timeout(Duration::from_secs(1), linker.join())
+            .await
+            .expect("linked process should be killed")
+            .unwrap();
+        assert_eq!(exit.status.unwrap(), ProcessResult::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn monitor_receives_down_msg() {
+        let k = kernel::start();
+
+        let prog = r#"(begin
+            (def child (spawn (lambda () (recv :exit))))
+            (def mref (monitor child))
+            (send child :exit)
+            (def (:down r pid reason) (recv '(:down _ _ _)))
+            (list (eq? r mref) (eq? pid child) reason))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![
+                Val::Bool(true),
+                Val::Bool(true),
+                Val::keyword("normal")
+            ]))
+        );
+    }
+
+    #[tokio::test]
+    async fn monitor_dead_process() {
+        let k = kernel::start();
+
+        let prog = r#"(begin
+            (def mref (monitor (pid 9999)))
+            (def (:down r _ reason) (recv '(:down _ _ _)))
+            (list (eq? r mref) reason))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![Val::Bool(true), Val::keyword("noproc")]))
+        );
+    }
 }
@@ -0,0 +1,238 @@
+//! Scheduler Bindings
+use chrono::{DateTime, Local, TimeZone};
+
+use crate::rt::program::{Fiber, Lambda, NativeAsyncFn, Val};
+use crate::rt::scheduler::{Schedule, SchedulerHandle};
+use lyric::{compile, parse, Error, KeywordId, Map, MapKey, Result, SymbolId};
+
+/// Macro for schedule
+pub(crate) fn schedule_macro() -> Lambda {
+    Lambda {
+        doc: Some("(schedule :cron CRON LAMBDA) or (schedule :at TIMESTAMP LAMBDA) - Schedule a job that calls LAMBDA in a new process, \
+                   on each occurrence of CRON expression like \"0 9 * * 1-5\", or once at TIMESTAMP in unix seconds or RFC 3339. \
+                   Returns id of scheduled job. LAMBDA is saved as an expression, so it does not capture caller's environment.".to_string()),
+        params: vec![
+            SymbolId::from("kind"),
+            SymbolId::from("spec"),
+            SymbolId::from("lambda"),
+        ],
+        code: compile(
+            &parse(r#"`(schedule_job ,kind ,spec (quote ,lambda))"#)
+                .unwrap()
+                .into(),
+        )
+        .unwrap().into(),
+        parent: None,
+        scope: None,
+        upvals: vec![],
+    }
+}
+
+/// Binding for schedule_job
+pub(crate) fn schedule_job_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(schedule_job :cron CRON EXPR) or (schedule_job :at TIMESTAMP EXPR) - Schedule a job that calls lambda expression EXPR \
+              in a new process. Returns id of scheduled job."
+            .to_string(),
+        func: |f, args| Box::new(schedule_job_impl(f, args)),
+    }
+}
+
+/// Binding for ls_jobs
+pub(crate) fn ls_jobs_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(ls_jobs) - Returns list of scheduled jobs, as maps of :id, :cron or :at, :next run time, and lambda :code"
+            .to_string(),
+        func: |f, _| Box::new(ls_jobs_impl(f)),
+    }
+}
+
+/// Binding for unschedule
+pub(crate) fn unschedule_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc:
+            "(unschedule ID) - Remove scheduled job with ID. Returns false if there is no such job"
+                .to_string(),
+        func: |f, args| Box::new(unschedule_impl(f, args)),
+    }
+}
+
+/// Implementation for (schedule_job KIND SPEC EXPR)
+async fn schedule_job_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (schedule, code) = match &args[..] {
+        [Val::Keyword(k), Val::String(cron), code] if k.as_str() == "cron" => (
+            Schedule::Cron(cron.parse().map_err(|e| Error::Runtime(format!("{e}")))?),
+            code.clone(),
+        ),
+        [Val::Keyword(k), at, code] if k.as_str() == "at" => {
+            (Schedule::At(timestamp(at)?), code.clone())
+        }
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "schedule expects :cron CRON or :at TIMESTAMP, followed by lambda".to_string(),
+            ))
+        }
+    };
+    let id = scheduler(fiber)?
+        .add(schedule, code)
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::Int(id as i64))
+}
+
+/// Implementation for (ls_jobs)
+async fn ls_jobs_impl(fiber: &mut Fiber) -> Result<Val> {
+    let jobs = scheduler(fiber)?
+        .list()
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    let key = |k: &str| MapKey::Keyword(KeywordId::from(k));
+    Ok(Val::List(
+        jobs.into_iter()
+            .map(|job| {
+                let schedule = match job.schedule {
+                    Schedule::Cron(cron) => (key("cron"), Val::string(cron.as_str())),
+                    Schedule::At(at) => (key("at"), Val::string(&at.to_rfc3339())),
+                };
+                Val::Map(Map::from([
+                    (key("id"), Val::Int(job.id as i64)),
+                    schedule,
+                    (
+                        key("next"),
+                        job.next
+                            .map(|next| Val::string(&next.to_rfc3339()))
+                            .unwrap_or(Val::Nil),
+                    ),
+                    (key("code"), job.code),
+                ]))
+            })
+            .collect(),
+    ))
+}
+
+/// Implementation for (unschedule ID)
+async fn unschedule_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let id = match &args[..] {
+        [Val::Int(id)] if *id >= 0 => *id as u64,
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "unschedule expects single job id".to_string(),
+            ))
+        }
+    };
+    let removed = scheduler(fiber)?
+        .remove(id)
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::Bool(removed))
+}
+
+/// Parse timestamp in unix seconds or RFC 3339
+fn timestamp(val: &Val) -> Result<DateTime<Local>> {
+    match val {
+        Val::Int(secs) => Local
+            .timestamp_opt(*secs, 0)
+            .single()
+            .ok_or_else(|| Error::Runtime(format!("Invalid timestamp {secs}"))),
+        Val::String(s) => DateTime::parse_from_rfc3339(s)
+            .map(|dt| dt.with_timezone(&Local))
+            .map_err(|e| Error::Runtime(format!("Invalid timestamp {s} - {e}"))),
+        _ => Err(Error::UnexpectedArguments(format!(
+            "Expected timestamp in unix seconds or RFC 3339 - got {val}"
+        ))),
+    }
+}
+
+fn scheduler(fiber: &Fiber) -> Result<SchedulerHandle> {
+    fiber.locals().scheduler.clone().ok_or(Error::Runtime(
+        "Scheduler is missing for process".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rt::{kernel, ProcessResult};
+    use crate::Program;
+    use assert_matches::assert_matches;
+
+    #[tokio::test]
+    async fn schedule_ls_unschedule() {
+        let k = kernel::start();
+        let prog = r#"(begin
+            (def id (schedule :cron "0 9 * * 1-5" (lambda () (publish :standup :now))))
+            (def jobs (ls_jobs))
+            (list id jobs (unschedule id) (ls_jobs)))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        let res = match exit.status.unwrap() {
+            ProcessResult::Done(Val::List(res)) => res,
+            status => panic!("Unexpected result - {status:?}"),
+        };
+        assert_eq!(res[0], Val::Int(0));
+        assert_matches!(&res[1], Val::List(jobs) if jobs.len() == 1);
+        let Val::List(jobs) = &res[1] else {
+            unreachable!()
+        };
+        let Val::Map(job) = &jobs[0] else {
+            panic!("jobs should be maps");
+        };
+        let key = |k: &str| MapKey::Keyword(KeywordId::from(k));
+        assert_eq!(job.get(&key("id")), Some(&Val::Int(0)));
+        assert_eq!(job.get(&key("cron")), Some(&Val::string("0 9 * * 1-5")));
+        assert_matches!(job.get(&key("next")), Some(Val::String(_)));
+        assert_eq!(
+            job.get(&key("code")),
+            Some(&parse("(lambda () (publish :standup :now))").unwrap().into())
+        );
+        assert_eq!(res[2], Val::Bool(true));
+        assert_eq!(res[3], Val::List(vec![]));
+    }
+
+    #[tokio::test]
+    async fn schedule_at_spawns_process() {
+        let k = kernel::start();
+        let prog = r#"(begin
+            (register :schedule_test)
+            (schedule :at 0 (lambda () (send (find_srv :schedule_test) :fired)))
+            (recv :fired))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = tokio::time::timeout(std::time::Duration::from_secs(1), hdl.join())
+            .await
+            .expect("job should fire")
+            .unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::keyword("fired"))
+        );
+    }
+
+    #[tokio::test]
+    async fn schedule_invalid() {
+        let k = kernel::start();
+        let prog = r#"(list
+            (try (schedule :cron "* * *" (lambda () :never)))
+            (try (schedule :every 10 (lambda () :never))))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_matches!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(res)) if res.iter().all(|r| matches!(r, Val::Error(_)))
+        );
+    }
+}
@@ -2,6 +2,7 @@
 //! See also [super::registry]
 
 use lyric::builtin::cond::is_true;
+use lyric::params::Params;
 use lyric::{compile, kwargs, parse, Error, KeywordId, Result, SymbolId};
 
 use crate::rt::program::{Extern, Fiber, Lambda, NativeAsyncFn, NativeFn, NativeFnOp, Val};
@@ -48,7 +49,8 @@ async fn register_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
             })
             .collect::<Result<Vec<_>>>()?;
 
-        let env = fiber.cur_env().lock().unwrap();
+        let env = fiber.frame_env();
+        let env = env.lock().unwrap();
         let mut interface = vec![];
         for sym in symbols {
             let val = env.get(sym).ok_or(Error::InvalidExpression(format!(
@@ -151,8 +153,12 @@ pub(crate) fn find_srv_fn() -> Lambda {
                 .to_string(),
         ),
         params: vec![SymbolId::from("srv_name")],
-        code: compile(&parse("(info_srv srv_name :pid)").unwrap().into()).unwrap(),
+        code: compile(&parse("(info_srv srv_name :pid)").unwrap().into())
+            .unwrap()
+            .into(),
         parent: None,
+        scope: None,
+        upvals: vec![],
     }
 }
 
@@ -174,8 +180,10 @@ pub(crate) fn bind_srv_fn() -> Lambda {
             .unwrap()
             .into(),
         )
-        .unwrap(),
+        .unwrap().into(),
         parent: None,
+        scope: None,
+        upvals: vec![],
     }
 }
 
@@ -269,7 +277,7 @@ pub(crate) fn def_bind_interface() -> NativeFn {
                 .as_string()?
                 .clone();
 
-            let (msg_name, args) = interface.split_first().ok_or(Error::UnexpectedArguments(
+            let (msg_name, params) = interface.split_first().ok_or(Error::UnexpectedArguments(
                 "interface list must contain at least one item".to_string(),
             ))?;
 
@@ -281,7 +289,7 @@ pub(crate) fn def_bind_interface() -> NativeFn {
                 ))),
             }?;
 
-            let arg_syms = args
+            let params = params
                 .iter()
                 .cloned()
                 .map(|m| match m {
@@ -298,8 +306,8 @@ pub(crate) fn def_bind_interface() -> NativeFn {
             env.define(
                 sym,
                 Val::Lambda(lambda_stub_for_interface(
-                    svc_name, arg_syms, msg_name, args, doc
-                )),
+                    svc_name, params, msg_name, doc
+                )?),
             );
 
             Ok(NativeFnOp::Return(Val::List(interface.to_vec())))
@@ -307,83 +315,87 @@ pub(crate) fn def_bind_interface() -> NativeFn {
     }
 }
 
-// TODO: Define as lisp macro
-/// Implementation of spawn_srv
-pub(crate) fn spawn_srv_fn() -> NativeFn {
-    NativeFn {
-        doc: "(spawn_srv SVC_NAME [:interface INTERFACE]) - Spawn a separate process as service registered as SVC_NAME, \
-              optionally exporting interface INTERFACE.".to_string(),
-        func: spawn_srv_impl,
+/// Binding for spawn_srv
+pub(crate) fn spawn_srv_macro() -> Lambda {
+    Lambda {
+        doc: Some("(spawn_srv SVC_NAME [:interface INTERFACE]) - Spawn a separate process as service registered as SVC_NAME, \
+                   optionally exporting interface INTERFACE.".to_string()),
+        params: vec![
+            SymbolId::from("name"),
+            SymbolId::from("&key"),
+            SymbolId::from("interface"),
+        ],
+        code: compile(
+            &parse(
+                r#"
+            `(spawn (lambda () (begin
+                (try (kill (find_srv ,name)))
+                (srv ,name :interface ,interface))))
+        "#,
+            )
+            .unwrap()
+            .into(),
+        )
+        .unwrap()
+        .into(),
+        parent: None,
+        scope: None,
+        upvals: vec![],
     }
 }
 
-/// Implementation for (spawn_srv) that matches (srv)'s signature
-fn spawn_srv_impl(_f: &mut Fiber, args: &[Val]) -> Result<NativeFnOp> {
-    // Expand
-    //     (spawn_srv :SRV_NAME :interface '(sym_a sym_b))
-    // Into
-    //     (spawn (lambda () (begin
-    //            (try (kill (find_srv :SRV_NAME)))
-    //            (srv :SRV_NAME :interface '(sym_a sym_b)))))
-
-    let mut srv = vec![Val::symbol("srv")];
-    srv.push(args[0].clone());
-
-    if let Some(interfaces) = kwargs::get(args, &KeywordId::from("interface")) {
-        srv.push(Val::keyword("interface"));
-        srv.push(Val::List(vec![Val::symbol("quote"), interfaces.clone()]));
+/// Binding for `srv`
+pub(crate) fn srv_macro() -> Lambda {
+    Lambda {
+        doc: Some("(srv SVC_NAME [:interface INTERFACE]) - Register current process as SVC_NAME, \
+                   optionally exporting interface INTERFACE. This function blocks until service exits.".to_string()),
+        params: vec![
+            SymbolId::from("name"),
+            SymbolId::from("&key"),
+            SymbolId::from("interface"),
+        ],
+        // Handler matching messages is built when service starts, since exported lambdas may
+        // not be bound when form is expanded
+        code: compile(
+            &parse(
+                r#"
+            (begin
+                (def interface (or interface ''()))
+                `(begin
+                    (register ,name :overwrite :interface ,interface)
+                    (def srv_handler (eval `(fn (msg)
+                        (match msg
+                            ,@(srv_match_arms ,interface)
+                            (_ '(:err "Unrecognized message"))))))
+                    (loop
+                        (def (r src msg) (recv))
+                        (def resp (try (srv_handler msg)))
+                        (send src (list r resp)))))
+        "#,
+            )
+            .unwrap()
+            .into(),
+        )
+        .unwrap()
+        .into(),
+        parent: None,
+        scope: None,
+        upvals: vec![],
     }
-
-    let kill_srv = Val::from_expr(&format!("(try (kill (find_srv {})))", args[0].clone())).unwrap();
-
-    let ast = Val::List(vec![
-        Val::symbol("spawn"),
-        Val::List(vec![
-            Val::symbol("lambda"),
-            Val::List(vec![]),
-            Val::List(vec![Val::symbol("begin"), kill_srv, Val::List(srv)]),
-        ]),
-    ]);
-
-    let bc = compile(&ast)?;
-    Ok(NativeFnOp::Exec(bc))
 }
 
-/// Binding for `srv`
-pub(crate) fn srv_fn() -> NativeFn {
+/// Binding for srv_match_arms
+pub(crate) fn srv_match_arms_fn() -> NativeFn {
     NativeFn {
-        doc: "(srv SVC_NAME [:interface INTERFACE]) - Register current process as SVC_NAME, \
-              optionally exporting interface INTERFACE. This function blocks until service exits."
-            .to_string(),
-        func: srv_impl,
+        doc: "(srv_match_arms INTERFACE) - Runtime internal use only. Returns match arms calling lambdas in INTERFACE for srv".to_string(),
+        func: srv_match_arms_impl,
     }
 }
 
-// TODO: Define as lisp macro
-fn srv_impl(f: &mut Fiber, args: &[Val]) -> Result<NativeFnOp> {
-    // Expand
-    //     (srv :SRV_NAME :interface '(sym_a sym_b))
-    // to
-    //     (begin
-    //         (register :launcher :overwrite :interface '(sym_a sym_b))
-    //         (loop
-    //             (def (r src msg) (recv))
-    //             (def resp
-    //                 (try (match msg
-    //                     ((:sym_a arg1 arg2) (sym_a arg1 arg2))
-    //                     ((:sym_b) (sym_b))
-    //                     (_ '(:err "Unrecognized message")))))
-    //             (send src (list r resp))))
-
-    let name = args.first().ok_or(Error::UnexpectedArguments(
-        "First argument must be a value used to identify service".to_string(),
-    ))?;
-
-    let interface = kwargs::get(args, &KeywordId::from("interface")).ok_or(
-        Error::UnexpectedArguments("Missing :interface keyword argument".to_string()),
-    )?;
-    let symbols = match interface {
-        Val::List(ref symbols) => Ok(symbols),
+/// Implementation for (srv_match_arms INTERFACE)
+fn srv_match_arms_impl(f: &mut Fiber, args: &[Val]) -> Result<NativeFnOp> {
+    let symbols = match args {
+        [Val::List(symbols)] => Ok(symbols),
         _ => Err(Error::UnexpectedArguments(
             ":interface keyword argument must be a list".to_string(),
         )),
@@ -398,87 +410,29 @@ fn srv_impl(f: &mut Fiber, args: &[Val]) -> Result<NativeFnOp> {
         })
         .collect::<Result<Vec<_>>>()?;
 
-    let mut match_form = vec![Val::symbol("match"), Val::symbol("msg")];
-
-    {
-        let env = f.cur_env().lock().unwrap();
-
-        for sym in symbols {
-            let val = env.get(sym).ok_or(Error::InvalidExpression(format!(
-                "No symbol bound to {}",
-                sym
-            )))?;
-            let lambda = match val {
-                Val::Lambda(l) => Ok(l),
-                _ => Err(Error::UnexpectedArguments(format!(
-                    "{} is not a lambda - found {}",
-                    sym, val
-                ))),
-            }?;
-            let pattern = lambda_interface(sym, &lambda);
-            match_form.push(Val::List(vec![pattern, lambda_call(sym, &lambda)]));
-        }
+    let env = f.frame_env();
+    let env = env.lock().unwrap();
+
+    let mut arms = vec![];
+    for sym in symbols {
+        let val = env.get(sym).ok_or(Error::InvalidExpression(format!(
+            "No symbol bound to {}",
+            sym
+        )))?;
+        let lambda = match val {
+            Val::Lambda(l) => Ok(l),
+            _ => Err(Error::UnexpectedArguments(format!(
+                "{} is not a lambda - found {}",
+                sym, val
+            ))),
+        }?;
+        let pattern = lambda_pattern(sym, &lambda)?;
+        arms.push(Val::List(vec![pattern, lambda_call(sym, &lambda)?]));
     }
-    // catch-all
-    match_form.push(Val::List(vec![
-        Val::symbol("_"),
-        Val::List(vec![
-            Val::symbol("quote"),
-            Val::List(vec![
-                Val::keyword("err"),
-                Val::string("Unrecognized message"),
-            ]),
-        ]),
-    ]));
-
-    let register_form = Val::List(vec![
-        Val::symbol("register"),
-        name.clone(),
-        Val::keyword("overwrite"),
-        Val::keyword("interface"),
-        Val::List(vec![Val::symbol("quote"), interface]),
-    ]);
-
-    // TODO: Rust macros plz
-    let ast = Val::List(vec![
-        Val::symbol("begin"),
-        register_form,
-        Val::List(vec![
-            Val::symbol("loop"),
-            // (def (r src msg) (recv))
-            Val::List(vec![
-                Val::symbol("def"),
-                Val::List(vec![
-                    Val::symbol("r"),
-                    Val::symbol("src"),
-                    Val::symbol("msg"),
-                ]),
-                Val::List(vec![Val::symbol("recv")]),
-            ]),
-            // (def resp (try (match ...)))
-            Val::List(vec![
-                Val::symbol("def"),
-                Val::symbol("resp"),
-                Val::List(vec![Val::symbol("try"), Val::List(match_form)]),
-            ]),
-            // (send src (list r resp))
-            Val::List(vec![
-                Val::symbol("send"),
-                Val::symbol("src"),
-                Val::List(vec![
-                    Val::symbol("list"),
-                    Val::symbol("r"),
-                    Val::symbol("resp"),
-                ]),
-            ]),
-        ]),
-    ]);
-
-    let bc = compile(&ast)?;
-    Ok(NativeFnOp::Exec(bc))
+    Ok(NativeFnOp::Return(Val::List(arms)))
 }
 
-/// Generates interface for calling exported lambda
+/// Generates interface for calling exported lambda, including `&optional`, `&rest` and `&key` markers
 fn lambda_interface(symbol: &SymbolId, lambda: &Lambda) -> Val {
     Val::List(
         std::iter::once(Val::Keyword(symbol.clone().to_keyword()))
@@ -487,14 +441,54 @@ fn lambda_interface(symbol: &SymbolId, lambda: &Lambda) -> Val {
     )
 }
 
-/// Generates function call expression compatible with [lambda_interface]
-fn lambda_call(symbol: &SymbolId, lambda: &Lambda) -> Val {
-    //
-    Val::List(
+/// Generates message pattern for exported lambda, with one symbol per parameter
+fn lambda_pattern(symbol: &SymbolId, lambda: &Lambda) -> Result<Val> {
+    Ok(Val::List(
+        std::iter::once(Val::Keyword(symbol.clone().to_keyword()))
+            .chain(
+                Params::parse(&lambda.params)?
+                    .symbols()
+                    .map(|v| Val::Symbol(v.clone())),
+            )
+            .collect::<Vec<_>>(),
+    ))
+}
+
+/// Generates function call expression compatible with [lambda_pattern]
+fn lambda_call(symbol: &SymbolId, lambda: &Lambda) -> Result<Val> {
+    let params = Params::parse(&lambda.params)?;
+    let positional = params
+        .required
+        .iter()
+        .chain(params.optional)
+        .map(|v| Val::Symbol(v.clone()));
+
+    // Rest parameter already contains any keyword arguments, so splice it with `apply`
+    if let Some(rest) = params.rest {
+        let args = std::iter::once(Val::symbol("list"))
+            .chain(positional)
+            .collect::<Vec<_>>();
+        return Ok(Val::List(vec![
+            Val::symbol("apply"),
+            Val::Symbol(symbol.clone()),
+            Val::List(vec![
+                Val::symbol("concat"),
+                Val::List(args),
+                Val::Symbol(rest.clone()),
+            ]),
+        ]));
+    }
+
+    let keys = params
+        .key
+        .iter()
+        .flat_map(|k| [Val::Keyword(k.clone().to_keyword()), Val::Symbol(k.clone())]);
+    Ok(Val::List(
         std::iter::once(Val::Symbol(symbol.clone()))
-            .chain(lambda.params.iter().map(|v| Val::Symbol(v.clone())))
+            .chain(positional)
+            .chain(keys)
             .collect::<Vec<_>>(),
-    )
+    ))
 }
 
 /// Given a [lambda_interface] [Val], turns it into client-side =Lambda= definition
@@ -502,28 +496,30 @@ fn lambda_stub_for_interface(
     srv_name: &KeywordId,
     params: Vec<SymbolId>,
     msg_name: &KeywordId,
-    msg_args: &[Val],
     doc: String,
-) -> Lambda {
+) -> Result<Lambda> {
     // TODO: Need to do this hack since there's no splice in lists atm
     let msg = [Val::symbol("list"), Val::Keyword(msg_name.clone())]
         .into_iter()
-        .chain(msg_args.iter().cloned())
+        .chain(Params::parse(&params)?.symbols().cloned().map(Val::Symbol))
         .collect::<Vec<_>>();
     let ast =
         parse(format!(r#"(call (find_srv {}) {})"#, srv_name, Val::List(msg)).as_str()).unwrap();
     let code = compile(&ast.into()).unwrap();
-    Lambda {
+    Ok(Lambda {
         doc: Some(doc),
         params,
-        code,
+        code: code.into(),
         parent: None,
-    }
+        scope: None,
+        upvals: vec![],
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use lyric::Inst;
+    use std::sync::Arc;
 
     use crate::rt::bindings::service::lambda_stub_for_interface;
 
@@ -534,8 +530,10 @@ mod tests {
         let lambda = Lambda {
             doc: None,
             params: vec![],
-            code: vec![Inst::PushConst(Val::Nil)],
+            code: Arc::new(vec![Inst::PushConst(Val::Nil)].into()),
             parent: None,
+            scope: None,
+            upvals: vec![],
         };
 
         assert_eq!(
@@ -549,8 +547,10 @@ mod tests {
         let lambda = Lambda {
             doc: None,
             params: vec![SymbolId::from("arg1"), SymbolId::from("arg2")],
-            code: vec![Inst::PushConst(Val::Nil)],
+            code: Arc::new(vec![Inst::PushConst(Val::Nil)].into()),
             parent: None,
+            scope: None,
+            upvals: vec![],
         };
 
         assert_eq!(
@@ -564,11 +564,16 @@ mod tests {
         let lambda = Lambda {
             doc: None,
             params: vec![],
-            code: vec![Inst::PushConst(Val::Nil)],
+            code: Arc::new(vec![Inst::PushConst(Val::Nil)].into()),
             parent: None,
+            scope: None,
+            upvals: vec![],
         };
 
-        assert_eq!(lambda_call(&SymbolId::from("hello"), &lambda), v("(hello)"));
+        assert_eq!(
+            lambda_call(&SymbolId::from("hello"), &lambda).unwrap(),
+            v("(hello)")
+        );
     }
 
     #[test]
@@ -576,16 +581,59 @@ mod tests {
         let lambda = Lambda {
             doc: None,
             params: vec![SymbolId::from("arg1"), SymbolId::from("arg2")],
-            code: vec![Inst::PushConst(Val::Nil)],
+            code: Arc::new(vec![Inst::PushConst(Val::Nil)].into()),
             parent: None,
+            scope: None,
+            upvals: vec![],
         };
 
         assert_eq!(
-            lambda_call(&SymbolId::from("hello"), &lambda),
+            lambda_call(&SymbolId::from("hello"), &lambda).unwrap(),
             v("(hello arg1 arg2)")
         );
     }
 
+    #[test]
+    fn lambda_interface_optional_rest_key() {
+        let lambda = Lambda {
+            doc: None,
+            params: symbols("a &optional b &key k"),
+            code: Arc::new(vec![Inst::PushConst(Val::Nil)].into()),
+            parent: None,
+            scope: None,
+            upvals: vec![],
+        };
+        assert_eq!(
+            lambda_interface(&SymbolId::from("hello"), &lambda),
+            v("(:hello a &optional b &key k)")
+        );
+        assert_eq!(
+            lambda_pattern(&SymbolId::from("hello"), &lambda).unwrap(),
+            v("(:hello a b k)")
+        );
+        assert_eq!(
+            lambda_call(&SymbolId::from("hello"), &lambda).unwrap(),
+            v("(hello a b :k k)")
+        );
+
+        let lambda = Lambda {
+            doc: None,
+            params: symbols("a &rest r &key k"),
+            code: Arc::new(vec![Inst::PushConst(Val::Nil)].into()),
+            parent: None,
+            scope: None,
+            upvals: vec![],
+        };
+        assert_eq!(
+            lambda_pattern(&SymbolId::from("hello"), &lambda).unwrap(),
+            v("(:hello a r k)")
+        );
+        assert_eq!(
+            lambda_call(&SymbolId::from("hello"), &lambda).unwrap(),
+            v("(apply hello (concat (list a) r))")
+        );
+    }
+
     #[test]
     fn stub_for_interface() {
         {
@@ -594,9 +642,9 @@ mod tests {
                 &srv_name,
                 vec![],
                 &KeywordId::from("get_items"),
-                &[],
                 String::new(),
-            );
+            )
+            .unwrap();
             assert_eq!(
                 lambda,
                 Lambda {
@@ -605,8 +653,11 @@ mod tests {
                     code: compile(&v(r#"
                         (call (find_srv :launcher) (list :get_items))
                         "#))
-                    .unwrap(),
-                    parent: None
+                    .unwrap()
+                    .into(),
+                    parent: None,
+                    scope: None,
+                    upvals: vec![],
                 }
             )
         }
@@ -616,9 +667,9 @@ mod tests {
                 &srv_name,
                 vec![SymbolId::from("title"), SymbolId::from("cmd")],
                 &KeywordId::from("add_item"),
-                &[Val::symbol("title"), Val::symbol("cmd")],
                 String::new(),
-            );
+            )
+            .unwrap();
             assert_eq!(
                 lambda,
                 Lambda {
@@ -627,13 +678,20 @@ mod tests {
                     code: compile(&v(r#"
                         (call (find_srv :launcher) (list :add_item title cmd))
                         "#))
-                    .unwrap(),
+                    .unwrap()
+                    .into(),
                     parent: None,
+                    scope: None,
+                    upvals: vec![],
                 }
             )
         }
     }
 
+    fn symbols(s: &str) -> Vec<SymbolId> {
+        s.split_whitespace().map(SymbolId::from).collect()
+    }
+
     fn v(expr: &str) -> Val {
         lyric::parse(expr).unwrap().into()
     }
@@ -0,0 +1,297 @@
+//! Store Bindings
+use crate::rt::program::{Fiber, NativeAsyncFn, Val};
+use crate::rt::store::{Op, StoreHandle};
+use lyric::{Error, Form, Result};
+
+/// Binding for store_put
+pub(crate) fn store_put_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(store_put COLL ID FORM) - Store FORM under ID in collection COLL, replacing any existing form"
+            .to_string(),
+        func: |f, args| Box::new(store_put_impl(f, args)),
+    }
+}
+
+/// Binding for store_get
+pub(crate) fn store_get_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(store_get COLL ID) - Returns form stored under ID in collection COLL, or nil if missing"
+            .to_string(),
+        func: |f, args| Box::new(store_get_impl(f, args)),
+    }
+}
+
+/// Binding for store_query
+pub(crate) fn store_query_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(store_query COLL [:FIELD VAL ...]) - Returns forms in collection COLL in order they were first stored, \
+              keeping forms where each keyword FIELD has value VAL, e.g. (store_query :todos :done false)"
+            .to_string(),
+        func: |f, args| Box::new(store_query_impl(f, args)),
+    }
+}
+
+/// Binding for store_delete
+pub(crate) fn store_delete_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(store_delete COLL ID) - Delete form stored under ID in collection COLL. Returns false if there is no such form"
+            .to_string(),
+        func: |f, args| Box::new(store_delete_impl(f, args)),
+    }
+}
+
+/// Binding for store_tx
+pub(crate) fn store_tx_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(store_tx OPS) - Apply list of updates OPS to store, all at once or not at all. Each update is \
+              (:put COLL ID FORM), (:delete COLL ID), or (:expect COLL ID FORM), which fails the transaction \
+              unless form stored under ID is FORM, or is missing when FORM is nil."
+            .to_string(),
+        func: |f, args| Box::new(store_tx_impl(f, args)),
+    }
+}
+
+/// Implementation for (store_put COLL ID FORM)
+async fn store_put_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (collection, id, form) = match &args[..] {
+        [Val::Keyword(collection), id, form] => (collection.clone(), to_form(id)?, to_form(form)?),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "store_put expects collection keyword, id, and form".to_string(),
+            ))
+        }
+    };
+    store(fiber)?
+        .put(collection, id, form)
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::keyword("ok"))
+}
+
+/// Implementation for (store_get COLL ID)
+async fn store_get_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (collection, id) = match &args[..] {
+        [Val::Keyword(collection), id] => (collection.clone(), to_form(id)?),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "store_get expects collection keyword and id".to_string(),
+            ))
+        }
+    };
+    let form = store(fiber)?
+        .get(collection, id)
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(form.map(Val::from).unwrap_or(Val::Nil))
+}
+
+/// Implementation for (store_query COLL [:FIELD VAL ...])
+async fn store_query_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (collection, fields) = match &args[..] {
+        [Val::Keyword(collection), fields @ ..] if fields.len() % 2 == 0 => {
+            let fields = fields
+                .chunks(2)
+                .map(|f| match f {
+                    [Val::Keyword(k), v] => Ok((k.clone(), to_form(v)?)),
+                    _ => Err(Error::UnexpectedArguments(
+                        "store_query expects fields as keyword and value pairs".to_string(),
+                    )),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            (collection.clone(), fields)
+        }
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "store_query expects collection keyword, followed by keyword and value pairs"
+                    .to_string(),
+            ))
+        }
+    };
+    let forms = store(fiber)?
+        .query(collection, fields)
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::List(forms.into_iter().map(Val::from).collect()))
+}
+
+/// Implementation for (store_delete COLL ID)
+async fn store_delete_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (collection, id) = match &args[..] {
+        [Val::Keyword(collection), id] => (collection.clone(), to_form(id)?),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "store_delete expects collection keyword and id".to_string(),
+            ))
+        }
+    };
+    let deleted = store(fiber)?
+        .delete(collection, id)
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::Bool(deleted))
+}
+
+/// Implementation for (store_tx OPS)
+async fn store_tx_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let ops = match &args[..] {
+        [Val::List(ops)] => ops.iter().map(to_op).collect::<Result<Vec<_>>>()?,
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "store_tx expects list of updates".to_string(),
+            ))
+        }
+    };
+    store(fiber)?
+        .transact(ops)
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::keyword("ok"))
+}
+
+/// Parse update for store_tx
+fn to_op(val: &Val) -> Result<Op> {
+    let Val::List(op) = val else {
+        return Err(Error::UnexpectedArguments(format!(
+            "store_tx expects updates to be lists - got {val}"
+        )));
+    };
+    match &op[..] {
+        [Val::Keyword(k), Val::Keyword(c), id, form] if k.as_str() == "put" => Ok(Op::Put {
+            collection: c.clone(),
+            id: to_form(id)?,
+            form: to_form(form)?,
+        }),
+        [Val::Keyword(k), Val::Keyword(c), id] if k.as_str() == "delete" => Ok(Op::Delete {
+            collection: c.clone(),
+            id: to_form(id)?,
+        }),
+        [Val::Keyword(k), Val::Keyword(c), id, form] if k.as_str() == "expect" => Ok(Op::Expect {
+            collection: c.clone(),
+            id: to_form(id)?,
+            form: match form {
+                Val::Nil => None,
+                form => Some(to_form(form)?),
+            },
+        }),
+        _ => Err(Error::UnexpectedArguments(format!(
+            "store_tx expects (:put COLL ID FORM), (:delete COLL ID), or (:expect COLL ID FORM) - got {val}"
+        ))),
+    }
+}
+
+/// Convert value to form that can be stored. Values that do not round trip as forms, like lambdas, are rejected
+fn to_form(val: &Val) -> Result<Form> {
+    match val {
+        Val::Nil
+        | Val::Bool(_)
+        | Val::Int(_)
+        | Val::Float(_)
+        | Val::String(_)
+        | Val::Symbol(_)
+        | Val::Keyword(_) => Form::try_from(val.clone()),
+        Val::List(l) => Ok(Form::List(l.iter().map(to_form).collect::<Result<_>>()?)),
+        Val::Map(m) => Ok(Form::Map(
+            m.iter()
+                .map(|(k, v)| Ok((k.clone(), to_form(v)?)))
+                .collect::<Result<_>>()?,
+        )),
+        _ => Err(Error::UnexpectedType(format!(
+            "Only data can be stored - got {val}"
+        ))),
+    }
+}
+
+fn store(fiber: &Fiber) -> Result<StoreHandle> {
+    fiber
+        .locals()
+        .store
+        .clone()
+        .ok_or(Error::Runtime("Store is missing for process".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rt::{kernel, ProcessResult};
+    use crate::{Program, Val};
+    use assert_matches::assert_matches;
+
+    #[tokio::test]
+    async fn store_put_get_query_delete() {
+        let k = kernel::start();
+        let prog = r#"(begin
+            (store_put :todos 0 '(:todo :id 0 :title "Buy milk" :done false))
+            (store_put :todos 1 '(:todo :id 1 :title "Walk dog" :done true))
+            (list
+                (store_get :todos 0)
+                (store_get :todos 2)
+                (store_query :todos :done true)
+                (store_delete :todos 1)
+                (store_query :todos)))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        let milk: Val = lyric::parse(r#"(:todo :id 0 :title "Buy milk" :done false)"#)
+            .unwrap()
+            .into();
+        let dog: Val = lyric::parse(r#"(:todo :id 1 :title "Walk dog" :done true)"#)
+            .unwrap()
+            .into();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![
+                milk.clone(),
+                Val::Nil,
+                Val::List(vec![dog]),
+                Val::Bool(true),
+                Val::List(vec![milk]),
+            ]))
+        );
+    }
+
+    #[tokio::test]
+    async fn store_tx() {
+        let k = kernel::start();
+        let prog = r#"(begin
+            (store_put :counters :a 1)
+            (def conflict (try (store_tx '((:put :counters :a 2) (:expect :counters :a 0)))))
+            (def before (store_get :counters :a))
+            (store_tx '((:expect :counters :a 1) (:put :counters :a 2) (:delete :counters :b)))
+            (list (err? conflict) before (store_get :counters :a)))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![Val::Bool(true), Val::Int(1), Val::Int(2)]))
+        );
+    }
+
+    #[tokio::test]
+    async fn store_invalid() {
+        let k = kernel::start();
+        let prog = r#"(list
+            (try (store_put "todos" 0 :a))
+            (try (store_put :todos 0 (fn () nil)))
+            (try (store_query :todos :done))
+            (try (store_tx '((:update :todos 0)))))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_matches!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(res)) if res.iter().all(|r| matches!(r, Val::Error(_)))
+        );
+    }
+}
@@ -0,0 +1,358 @@
+//! Supervisor Bindings
+//! See also [super::supervisor]
+
+use std::time::Duration;
+
+use lyric::{kwargs, Error, KeywordId, Result};
+
+use crate::rt::program::{Fiber, NativeAsyncFn, Val};
+use crate::rt::supervisor::{ChildSpec, Strategy, Supervisor, DEFAULT_INTENSITY, DEFAULT_PERIOD};
+
+/// Binding for supervise
+pub(crate) fn supervise_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(supervise CHILDREN [:strategy STRATEGY] [:intensity N] [:period SECS]) - \
+              Start and supervise CHILDREN, restarting children that exit. \
+              Each child is a zero-arity lambda, or a child spec of form (:start LAMBDA [:name SVC_NAME] [:restart RESTART]), \
+              where RESTART is one of :permanent, :transient, or :temporary. \
+              STRATEGY is one of :one_for_one, :one_for_all, or :rest_for_one. \
+              Raises an error when more than N restarts occur within SECS seconds, \
+              or when a linked process that is not a child exits abnormally, terminating children first."
+            .to_string(),
+        func: |f, args| Box::new(supervise_impl(f, args)),
+    }
+}
+
+/// Implementation for (supervise CHILDREN [:strategy STRATEGY] [:intensity N] [:period SECS])
+async fn supervise_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let children = match args.first() {
+        Some(Val::List(children)) => children
+            .iter()
+            .map(|c| ChildSpec::try_from(c.clone()))
+            .collect::<Result<Vec<_>>>()?,
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "supervise expects list of child specs as first argument".to_string(),
+            ))
+        }
+    };
+
+    let strategy = match kwargs::get(&args[1..], &KeywordId::from("strategy")) {
+        Some(s) => Strategy::try_from(s)?,
+        None => Strategy::OneForOne,
+    };
+
+    let intensity = match kwargs::get(&args[1..], &KeywordId::from("intensity")) {
+        Some(Val::Int(n)) if n >= 0 => n as usize,
+        Some(_) => {
+            return Err(Error::UnexpectedArguments(
+                ":intensity should be a non-negative integer".to_string(),
+            ))
+        }
+        None => DEFAULT_INTENSITY,
+    };
+
+    let period = match kwargs::get(&args[1..], &KeywordId::from("period")) {
+        Some(Val::Int(n)) if n > 0 => Duration::from_secs(n as u64),
+        Some(_) => {
+            return Err(Error::UnexpectedArguments(
+                ":period should be a positive integer".to_string(),
+            ))
+        }
+        None => DEFAULT_PERIOD,
+    };
+
+    let locals = fiber.locals();
+    let kernel = locals
+        .kernel
+        .clone()
+        .ok_or(Error::Runtime("Kernel is missing for process".to_string()))?;
+    let mailbox = locals
+        .self_handle
+        .as_ref()
+        .ok_or(Error::Runtime(
+            "Self handle is missing for process".to_string(),
+        ))?
+        .mailbox()
+        .clone();
+    let registry = locals.registry.clone().ok_or(Error::Runtime(
+        "Registry is missing for process".to_string(),
+    ))?;
+
+    Supervisor::new(locals.pid, kernel, mailbox, registry, children)
+        .strategy(strategy)
+        .intensity(intensity, period)
+        .run()
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+
+    Ok(Val::keyword("ok"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rt::{kernel, ProcessResult};
+    use crate::{Extern, Program, Val};
+    use assert_matches::assert_matches;
+    use std::time::Duration;
+    use tokio::time;
+
+    #[tokio::test]
+    async fn supervise_restarts_permanent() {
+        let k = kernel::start();
+
+        let prog = r#"(begin
+            (def parent (self))
+            (spawn (lambda ()
+                (supervise (list (lambda () (begin
+                    (send parent (list :started (self)))
+                    (recv :crash)
+                    (undefined_fn)))))))
+            (def (:started first) (recv '(:started _)))
+            (send first :crash)
+            (def (:started second) (recv '(:started _)))
+            (list first second))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = time::timeout(Duration::from_secs(1), hdl.join())
+            .await
+            .expect("child should be restarted")
+            .unwrap();
+        assert_matches!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(pids)) if pids[0] != pids[1]
+        );
+    }
+
+    #[tokio::test]
+    async fn supervise_exits_with_linked_parent() {
+        let k = kernel::start();
+
+        let prog = r#"(begin
+            (def observer (self))
+            (def parent (spawn (lambda () (begin
+                (spawn_link (lambda ()
+                    (supervise (list (lambda () (begin
+                        (send observer (list :started (self)))
+                        (loop (sleep 1))))))))
+                (loop (sleep 1))))))
+            (def (:started child) (recv '(:started _)))
+            (kill parent)
+            (defn wait_exit ()
+                (when (contains? (ps) child)
+                    (sleep_ms 1)
+                    (wait_exit)))
+            (wait_exit)
+            (ls_msgs))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = time::timeout(Duration::from_secs(1), hdl.join())
+            .await
+            .expect("children should exit when parent of supervisor is killed")
+            .unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![])),
+            "children should not be restarted"
+        );
+    }
+
+    #[tokio::test]
+    async fn supervise_transient_normal_exit() {
+        let k = kernel::start();
+
+        let prog = r#"(begin
+            (def parent (self))
+            (spawn (lambda ()
+                (supervise (list
+                    (list :start (lambda () (send parent :started))
+                          :restart :transient)))))
+            (recv :started)
+            (sleep 0)
+            (ls_msgs))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![])),
+            "transient children should not restart on normal exit"
+        );
+    }
+
+    #[tokio::test]
+    async fn supervise_one_for_all() {
+        let k = kernel::start();
+
+        let prog = r#"(begin
+            (def parent (self))
+            (spawn (lambda ()
+                (supervise (list
+                        (lambda () (begin (send parent (list :a (self))) (recv :crash) (undefined_fn)))
+                        (lambda () (begin (send parent (list :b (self))) (loop (sleep 1)))))
+                    :strategy :one_for_all)))
+            (def (:a a1) (recv '(:a _)))
+            (def (:b b1) (recv '(:b _)))
+            (send a1 :crash)
+            (def (:a a2) (recv '(:a _)))
+            (def (:b b2) (recv '(:b _)))
+            (list (eq? a1 a2) (eq? b1 b2) (contains? (ps) b1)))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = time::timeout(Duration::from_secs(1), hdl.join())
+            .await
+            .expect("children should be restarted")
+            .unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![
+                Val::Bool(false),
+                Val::Bool(false),
+                Val::Bool(false)
+            ])),
+        );
+    }
+
+    #[tokio::test]
+    async fn supervise_one_for_all_sibling_exited() {
+        let k = kernel::start();
+
+        // sibling may already have exited when it is terminated for restart
+        let prog = r#"(begin
+            (def parent (self))
+            (spawn (lambda ()
+                (supervise (list
+                        (lambda () (begin (send parent (list :a (self))) (recv :crash) (undefined_fn)))
+                        (lambda () (begin (send parent (list :b (self))) (recv :crash) (undefined_fn))))
+                    :strategy :one_for_all)))
+            (def (:a a1) (recv '(:a _)))
+            (def (:b b1) (recv '(:b _)))
+            (send a1 :crash)
+            (send b1 :crash)
+            (def (:a a2) (recv '(:a _)))
+            (def (:b b2) (recv '(:b _)))
+            (list (eq? a1 a2) (eq? b1 b2)))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = time::timeout(Duration::from_secs(1), hdl.join())
+            .await
+            .expect("children should be restarted")
+            .unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![Val::Bool(false), Val::Bool(false)])),
+        );
+    }
+
+    #[tokio::test]
+    async fn supervise_rest_for_one() {
+        let k = kernel::start();
+
+        let prog = r#"(begin
+            (def parent (self))
+            (spawn (lambda ()
+                (supervise (list
+                        (lambda () (begin (send parent (list :a (self))) (loop (sleep 1))))
+                        (lambda () (begin (send parent (list :b (self))) (recv :crash) (undefined_fn)))
+                        (lambda () (begin (send parent (list :c (self))) (loop (sleep 1)))))
+                    :strategy :rest_for_one)))
+            (def (:a a1) (recv '(:a _)))
+            (def (:b b1) (recv '(:b _)))
+            (def (:c c1) (recv '(:c _)))
+            (send b1 :crash)
+            (def (:b b2) (recv '(:b _)))
+            (def (:c c2) (recv '(:c _)))
+            (list (contains? (ps) a1) (eq? b1 b2) (eq? c1 c2)))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = time::timeout(Duration::from_secs(1), hdl.join())
+            .await
+            .expect("children should be restarted")
+            .unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![
+                Val::Bool(true),
+                Val::Bool(false),
+                Val::Bool(false)
+            ])),
+        );
+    }
+
+    #[tokio::test]
+    async fn supervise_max_intensity() {
+        let k = kernel::start();
+
+        let prog = r#"(supervise (list (lambda () (undefined_fn)))
+                                 :intensity 2
+                                 :period 5)"#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = time::timeout(Duration::from_secs(1), hdl.join())
+            .await
+            .expect("supervisor should exit")
+            .unwrap();
+        assert_matches!(exit.status, Err(_));
+    }
+
+    #[tokio::test]
+    async fn supervise_named_restart() {
+        let k = kernel::start();
+
+        let prog = r#"(begin
+            (def parent (self))
+            (spawn (lambda ()
+                (supervise (list
+                    (list :name :counter
+                          :start (lambda () (begin
+                                    (register :counter)
+                                    (send parent :registered)
+                                    (recv :crash)
+                                    (undefined_fn))))))))
+            (recv :registered)
+            (send (find_srv :counter) :crash)
+            (recv :registered)
+            (find_srv :counter))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = time::timeout(Duration::from_secs(1), hdl.join())
+            .await
+            .expect("child should be restarted and re-registered")
+            .unwrap();
+        assert_matches!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::Extern(Extern::ProcessId(_)))
+        );
+    }
+}
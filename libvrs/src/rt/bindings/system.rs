@@ -1,16 +1,22 @@
 //! Host System Bindings
 
-use std::process::Stdio;
+use std::process::{Output, Stdio};
+use std::time::Duration;
 
 use crate::rt::program::{NativeAsyncFn, NativeFn, NativeFnOp, Val};
 use lyric::{Error, Result};
-use tokio::{io::AsyncReadExt, process::Command};
+use tokio::{io::AsyncWriteExt, process::Command, time};
 use tracing::{debug, error};
 
 /// Binding for exec
 pub(crate) fn exec_fn() -> NativeAsyncFn {
     NativeAsyncFn {
-        doc: "(exec PROG ARG1 ARG2 ... ARGN) - Execute external executable PROG passing optional command line arguments ARG1 to ARGN.".to_string(),
+        doc: "(exec PROG ARG1 ARG2 ... ARGN [:stdin STR] [:cwd DIR] [:env ALIST] [:timeout MS] [:check BOOL]) - \
+              Execute external executable PROG passing optional command line arguments ARG1 to ARGN. \
+              STR is written to standard input, DIR is working directory, and ALIST is a list of (NAME VALUE) environment variables. \
+              Returns (:ok :status N :stdout S :stderr S). Non-zero exits are errors, unless :check is false, \
+              where (:err :status N :stdout S :stderr S) is returned instead. \
+              Executable is killed if it does not exit within MS milliseconds.".to_string(),
         func: |_, args| Box::new(exec_impl(args)),
     }
 }
@@ -35,6 +41,16 @@ pub(crate) fn shell_expand_fn() -> NativeFn {
     }
 }
 
+/// Options for (exec ...)
+#[derive(Debug, Default)]
+struct ExecOpts {
+    stdin: Option<String>,
+    cwd: Option<String>,
+    env: Vec<(String, String)>,
+    timeout: Option<Duration>,
+    check: bool,
+}
+
 /// Implementation of (exec PROG ARGS...)
 async fn exec_impl(args: Vec<Val>) -> Result<Val> {
     let (prog, args) = args.split_first().ok_or(Error::UnexpectedArguments(
@@ -50,6 +66,7 @@ async fn exec_impl(args: Vec<Val>) -> Result<Val> {
         }
     };
 
+    let (args, opts) = exec_opts(args)?;
     let args = args
         .iter()
         .map(|a| match a {
@@ -60,35 +77,209 @@ async fn exec_impl(args: Vec<Val>) -> Result<Val> {
         })
         .collect::<Result<Vec<_>>>()?;
 
-    debug!("exec {:?} {:?}", &prog, &args);
+    debug!("exec {:?} {:?} {:?}", &prog, &args, &opts);
 
-    let mut cmd = Command::new(prog.clone())
-        .args(args.clone())
+    let mut cmd = Command::new(prog.clone());
+    cmd.args(args.clone())
+        .envs(opts.env.iter().map(|(k, v)| (k, v)))
+        .stdin(if opts.stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
         .stdout(Stdio::piped())
-        .spawn()
-        .map_err(|e| Error::Runtime(format!("{e}")))?;
-
-    let exit_status = cmd
-        .wait()
-        .await
-        .map_err(|e| Error::Runtime(format!("{e}")))?;
-
-    let mut output = cmd.stdout.take().expect("Expected stdout handle");
-    let mut output_str = String::new();
-    output
-        .read_to_string(&mut output_str)
-        .await
-        .map_err(|e| Error::Runtime(format!("{e}")))?;
-    let output_str = output_str.trim();
-
-    if exit_status.success() {
-        debug!("exec {:?} {:?} - {:?}", prog, args, exit_status);
-        Ok(Val::List(vec![Val::keyword("ok"), Val::string(output_str)]))
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(cwd) = &opts.cwd {
+        cmd.current_dir(shellexpand::tilde(cwd).as_ref());
+    }
+
+    let mut child = cmd.spawn().map_err(|e| Error::Runtime(format!("{e}")))?;
+
+    // Write stdin while output is read, so neither side blocks on full pipes
+    if let (Some(input), Some(mut stdin)) = (opts.stdin, child.stdin.take()) {
+        tokio::spawn(async move {
+            let _ = stdin.write_all(input.as_bytes()).await;
+        });
+    }
+
+    let output = child.wait_with_output();
+    let output = match opts.timeout {
+        Some(timeout) => time::timeout(timeout, output).await.map_err(|_| {
+            error!("exec {:?} {:?} - timed out", prog, args);
+            Error::Runtime(format!(
+                "Timed out after {}ms executing {prog}",
+                timeout.as_millis()
+            ))
+        })?,
+        None => output.await,
+    }
+    .map_err(|e| Error::Runtime(format!("{e}")))?;
+
+    if output.status.success() {
+        debug!("exec {:?} {:?} - {:?}", prog, args, output.status);
+        Ok(exec_result("ok", &output))
+    } else if !opts.check {
+        debug!("exec {:?} {:?} - {:?}", prog, args, output.status);
+        Ok(exec_result("err", &output))
     } else {
-        error!("exec {:?} {:?} - {:?}", prog, args, exit_status);
+        error!("exec {:?} {:?} - {:?}", prog, args, output.status);
         Err(Error::Runtime(format!(
-            "Failed to execute - {}",
-            exit_status
+            "Failed to execute - {} - {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
         )))
     }
 }
+
+/// Split trailing keyword options from arguments of (exec ...)
+fn exec_opts(args: &[Val]) -> Result<(&[Val], ExecOpts)> {
+    let mut args = args;
+    let mut opts = ExecOpts {
+        check: true,
+        ..Default::default()
+    };
+    while let [rest @ .., Val::Keyword(k), val] = args {
+        match (k.as_str(), val) {
+            ("stdin", Val::String(s)) => opts.stdin = Some(s.clone()),
+            ("cwd", Val::String(s)) => opts.cwd = Some(s.clone()),
+            ("env", Val::List(vars)) => {
+                opts.env = vars
+                    .iter()
+                    .map(|var| match var {
+                        Val::List(kv) => match &kv[..] {
+                            [k, v] => Ok((env_str(k)?, env_str(v)?)),
+                            _ => Err(Error::UnexpectedArguments(format!(
+                                ":env expects (NAME VALUE) pairs - got {var}"
+                            ))),
+                        },
+                        _ => Err(Error::UnexpectedArguments(format!(
+                            ":env expects (NAME VALUE) pairs - got {var}"
+                        ))),
+                    })
+                    .collect::<Result<_>>()?
+            }
+            ("timeout", Val::Nil) => opts.timeout = None,
+            ("timeout", Val::Int(ms)) if *ms >= 0 => {
+                opts.timeout = Some(Duration::from_millis(*ms as u64))
+            }
+            ("check", Val::Bool(check)) => opts.check = *check,
+            ("stdin" | "cwd" | "env" | "timeout" | "check", _) => {
+                return Err(Error::UnexpectedArguments(format!(
+                    "Unexpected value for :{k} in exec - got {val}"
+                )))
+            }
+            _ => break,
+        }
+        args = rest;
+    }
+    Ok((args, opts))
+}
+
+/// Environment variable name or value
+fn env_str(val: &Val) -> Result<String> {
+    match val {
+        Val::String(s) => Ok(s.clone()),
+        Val::Symbol(s) => Ok(s.to_string()),
+        Val::Keyword(k) => Ok(k.to_string()),
+        Val::Int(i) => Ok(i.to_string()),
+        _ => Err(Error::UnexpectedArguments(format!(
+            ":env expects strings for names and values - got {val}"
+        ))),
+    }
+}
+
+/// Result of executable as (TAG :status N :stdout S :stderr S)
+fn exec_result(tag: &str, output: &Output) -> Val {
+    Val::List(vec![
+        Val::keyword(tag),
+        Val::keyword("status"),
+        output
+            .status
+            .code()
+            .map(|c| Val::Int(c as i64))
+            .unwrap_or(Val::Nil),
+        Val::keyword("stdout"),
+        Val::string(String::from_utf8_lossy(&output.stdout).trim()),
+        Val::keyword("stderr"),
+        Val::string(String::from_utf8_lossy(&output.stderr).trim()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rt::{kernel, ProcessResult};
+    use crate::{Program, Val};
+    use assert_matches::assert_matches;
+
+    async fn eval(expr: &str) -> ProcessResult {
+        let k = kernel::start();
+        let hdl = k
+            .spawn_prog(Program::from_expr(expr).unwrap())
+            .await
+            .unwrap();
+        hdl.join().await.unwrap().status.unwrap()
+    }
+
+    fn result(tag: &str, status: i64, stdout: &str, stderr: &str) -> ProcessResult {
+        ProcessResult::Done(Val::List(vec![
+            Val::keyword(tag),
+            Val::keyword("status"),
+            Val::Int(status),
+            Val::keyword("stdout"),
+            Val::string(stdout),
+            Val::keyword("stderr"),
+            Val::string(stderr),
+        ]))
+    }
+
+    #[tokio::test]
+    async fn exec_stdout() {
+        assert_eq!(
+            eval(r#"(exec "echo" "hello" "world")"#).await,
+            result("ok", 0, "hello world", "")
+        );
+    }
+
+    #[tokio::test]
+    async fn exec_stdin_env_cwd() {
+        assert_eq!(
+            eval(r#"(exec "cat" :stdin "from stdin")"#).await,
+            result("ok", 0, "from stdin", "")
+        );
+        assert_eq!(
+            eval(r#"(exec "sh" "-c" "echo $GREETING" :env '(("GREETING" "hi")))"#).await,
+            result("ok", 0, "hi", "")
+        );
+        assert_eq!(
+            eval(r#"(exec "pwd" :cwd "/")"#).await,
+            result("ok", 0, "/", "")
+        );
+    }
+
+    #[tokio::test]
+    async fn exec_failure() {
+        assert_matches!(
+            eval(r#"(try (exec "sh" "-c" "echo oops >&2; exit 3"))"#).await,
+            ProcessResult::Done(Val::Error(e)) if e.to_string().contains("oops")
+        );
+        assert_eq!(
+            eval(r#"(exec "sh" "-c" "echo out; echo oops >&2; exit 3" :check false)"#).await,
+            result("err", 3, "out", "oops")
+        );
+    }
+
+    #[tokio::test]
+    async fn exec_large_output() {
+        let res = eval(r#"(get (exec "sh" "-c" "yes | head -n 100000") :stdout)"#).await;
+        assert_matches!(res, ProcessResult::Done(Val::String(s)) if s.len() == 200_000 - 1);
+    }
+
+    #[tokio::test]
+    async fn exec_timeout() {
+        assert_matches!(
+            eval(r#"(try (exec "sleep" "5" :timeout 50))"#).await,
+            ProcessResult::Done(Val::Error(e)) if e.to_string().contains("Timed out")
+        );
+    }
+}
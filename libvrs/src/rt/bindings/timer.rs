@@ -0,0 +1,399 @@
+//! Timer Bindings
+use std::time::Duration;
+
+use crate::rt::mailbox::Message;
+use crate::rt::program::{Extern, Fiber, NativeAsyncFn, NativeFn, NativeFnOp, Val};
+use crate::rt::timer::TimerHandle;
+use crate::rt::ProcessHandle;
+use lyric::{Error, Result};
+use tracing::debug;
+
+/// Milliseconds in each unit accepted by `duration`
+const UNITS: &[(&str, u64)] = &[
+    ("years", 365 * 24 * 60 * 60 * 1000),
+    ("weeks", 7 * 24 * 60 * 60 * 1000),
+    ("days", 24 * 60 * 60 * 1000),
+    ("hours", 60 * 60 * 1000),
+    ("mins", 60 * 1000),
+    ("secs", 1000),
+    ("ms", 1),
+];
+
+/// Binding for duration
+pub(crate) fn duration_fn() -> NativeFn {
+    NativeFn {
+        doc: "(duration [:years N] [:weeks N] [:days N] [:hours N] [:mins N] [:secs N] [:ms N]) - \
+              Creates a duration that is the sum of each given amount of time."
+            .to_string(),
+        func: |_, args| {
+            if !args.len().is_multiple_of(2) {
+                return Err(Error::UnexpectedArguments(
+                    "duration expects pairs of unit keyword and amount".to_string(),
+                ));
+            }
+            let mut total = Duration::ZERO;
+            for kv in args.chunks(2) {
+                let unit_ms = match &kv[0] {
+                    Val::Keyword(k) => UNITS.iter().find(|(u, _)| *u == k.as_str()),
+                    _ => None,
+                }
+                .map(|(_, ms)| *ms)
+                .ok_or_else(|| {
+                    Error::UnexpectedArguments(format!(
+                        "Unexpected duration unit {} - expected one of :years :weeks :days :hours :mins :secs :ms",
+                        kv[0]
+                    ))
+                })?;
+                total = total
+                    .checked_add(to_duration("duration", &kv[1], unit_ms)?)
+                    .ok_or_else(|| Error::Runtime("Duration overflow".to_string()))?;
+            }
+            Ok(NativeFnOp::Return(Val::Extern(Extern::Duration(total))))
+        },
+    }
+}
+
+/// Binding for sleep
+pub(crate) fn sleep_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(sleep SECS) - Sleep current process for SECS seconds or given duration, blocking execution."
+            .to_string(),
+        func: |f, args| Box::new(sleep_impl(f, args, "sleep", 1000)),
+    }
+}
+
+/// Binding for sleep_ms
+pub(crate) fn sleep_ms_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(sleep_ms MS) - Sleep current process for MS milliseconds, blocking execution."
+            .to_string(),
+        func: |f, args| Box::new(sleep_impl(f, args, "sleep_ms", 1)),
+    }
+}
+
+/// Binding for send_after
+pub(crate) fn send_after_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(send_after MS PID MSG) - Send process PID the message MSG after MS milliseconds or given duration. \
+              Returns a reference REF for cancelling timer with (cancel_timer REF)."
+            .to_string(),
+        func: |f, args| Box::new(send_after_impl(f, args)),
+    }
+}
+
+/// Binding for interval
+pub(crate) fn interval_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(interval MS MSG) - Send caller the message MSG every MS milliseconds or given duration, until caller exits. \
+              Returns a reference REF for cancelling timer with (cancel_timer REF)."
+            .to_string(),
+        func: |f, args| Box::new(interval_impl(f, args)),
+    }
+}
+
+/// Binding for cancel_timer
+pub(crate) fn cancel_timer_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(cancel_timer REF) - Cancel timer created by send_after or interval. \
+              Returns true if timer was cancelled, or false if timer already fired or was cancelled."
+            .to_string(),
+        func: |f, args| Box::new(cancel_timer_impl(f, args)),
+    }
+}
+
+/// Implementation for (sleep DURATION)
+async fn sleep_impl(fiber: &mut Fiber, args: Vec<Val>, name: &str, unit_ms: u64) -> Result<Val> {
+    let duration = match &args[..] {
+        [arg] => to_duration(name, arg, unit_ms)?,
+        _ => {
+            return Err(Error::UnexpectedArguments(format!(
+                "{name} expects single argument"
+            )))
+        }
+    };
+    debug!("{name} = {:?}", duration);
+    timers(fiber)?
+        .sleep(duration)
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::keyword("ok"))
+}
+
+/// Implementation for (send_after MS PID MSG)
+async fn send_after_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (after, dst, msg) = match &args[..] {
+        [after, Val::Extern(Extern::ProcessId(dst)), msg] => {
+            (to_duration("send_after", after, 1)?, *dst, msg.clone())
+        }
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "send_after expects milliseconds, pid, and message - (send_after MS PID MSG)"
+                    .to_string(),
+            ))
+        }
+    };
+
+    let src = fiber.locals().pid;
+    let (dst, msg) = if src == dst {
+        (self_handle(fiber)?, msg)
+    } else {
+        let kernel = fiber
+            .locals()
+            .kernel
+            .as_ref()
+            .and_then(|k| k.upgrade())
+            .ok_or(Error::Runtime("Kernel is missing for process".to_string()))?;
+        let msg = kernel
+            .isolate(msg)
+            .map_err(|e| Error::Runtime(format!("{e}")))?;
+        let dst = kernel
+            .process(dst)
+            .await
+            .map_err(|e| Error::Runtime(format!("{e}")))?
+            .ok_or_else(|| Error::Runtime(format!("No running process for {dst}")))?;
+        (dst, msg)
+    };
+
+    let r = timers(fiber)?
+        .send_after(after, dst, Message::new(src, msg))
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::Ref(r))
+}
+
+/// Implementation for (interval MS MSG)
+async fn interval_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (period, msg) = match &args[..] {
+        [period, msg] => (to_duration("interval", period, 1)?, msg.clone()),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "interval expects milliseconds and message - (interval MS MSG)".to_string(),
+            ))
+        }
+    };
+    if period.is_zero() {
+        return Err(Error::UnexpectedArguments(
+            "interval expects a non-zero period".to_string(),
+        ));
+    }
+
+    let src = fiber.locals().pid;
+    let r = timers(fiber)?
+        .interval(period, self_handle(fiber)?, Message::new(src, msg))
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::Ref(r))
+}
+
+/// Implementation for (cancel_timer REF)
+async fn cancel_timer_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let r = match &args[..] {
+        [Val::Ref(r)] => r.clone(),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "cancel_timer expects single reference argument".to_string(),
+            ))
+        }
+    };
+    let cancelled = timers(fiber)?
+        .cancel(r)
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::Bool(cancelled))
+}
+
+/// Convert an integer amount of given unit, or duration value, to duration
+fn to_duration(name: &str, val: &Val, unit_ms: u64) -> Result<Duration> {
+    match val {
+        Val::Extern(Extern::Duration(d)) => Ok(*d),
+        Val::Int(n) if *n >= 0 => (*n as u64)
+            .checked_mul(unit_ms)
+            .map(Duration::from_millis)
+            .ok_or_else(|| Error::Runtime("Duration overflow".to_string())),
+        Val::Float(n) if *n >= 0.0 => Duration::try_from_secs_f64(n * unit_ms as f64 / 1000.0)
+            .map_err(|e| Error::Runtime(format!("Invalid duration - {e}"))),
+        _ => Err(Error::UnexpectedArguments(format!(
+            "{name} expects a non-negative number or duration - got {val}"
+        ))),
+    }
+}
+
+fn timers(fiber: &Fiber) -> Result<TimerHandle> {
+    fiber
+        .locals()
+        .timers
+        .clone()
+        .ok_or(Error::Runtime("Timers are missing for process".to_string()))
+}
+
+fn self_handle(fiber: &Fiber) -> Result<ProcessHandle> {
+    fiber
+        .locals()
+        .self_handle
+        .clone()
+        .ok_or(Error::Runtime("Process is missing self handle".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rt::{kernel, ProcessResult};
+    use crate::Program;
+    use tokio::time;
+
+    #[tokio::test]
+    async fn sleep() {
+        let k = kernel::start();
+        let hdl = k
+            .spawn_prog(Program::from_expr("(sleep (duration :ms 50))").unwrap())
+            .await
+            .expect("Kernel should spawn new process");
+        assert_eq!(k.procs().await.unwrap(), vec![hdl.id()]);
+
+        let exit = hdl.join().await.unwrap();
+
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::keyword("ok"))
+        );
+    }
+
+    #[tokio::test]
+    async fn duration() {
+        let k = kernel::start();
+        let hdl = k
+            .spawn_prog(
+                Program::from_expr(
+                    "(list (duration :mins 1 :secs 30 :ms 5) (duration :years 1) (duration :secs 0.5))",
+                )
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![
+                Val::Extern(Extern::Duration(Duration::from_millis(90_005))),
+                Val::Extern(Extern::Duration(Duration::from_secs(365 * 24 * 60 * 60))),
+                Val::Extern(Extern::Duration(Duration::from_millis(500))),
+            ]))
+        );
+    }
+
+    #[tokio::test]
+    async fn sleep_ms() {
+        let k = kernel::start();
+        let hdl = k
+            .spawn_prog(Program::from_expr("(sleep_ms 10)").unwrap())
+            .await
+            .unwrap();
+
+        let exit = time::timeout(Duration::from_secs(1), hdl.join())
+            .await
+            .expect("sleep_ms should not sleep for seconds")
+            .unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::keyword("ok"))
+        );
+    }
+
+    #[tokio::test]
+    async fn send_after() {
+        let k = kernel::start();
+        let prog = r#"(begin
+            (def parent (self))
+            (def child (spawn (lambda () (begin
+                (def (sender msg) (recv))
+                (send sender (list :echo msg))))))
+            (send_after 10 child (list parent :delayed))
+            (send_after (duration :ms 5) (self) :first)
+            (list (recv) (recv)))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![
+                Val::keyword("first"),
+                Val::List(vec![Val::keyword("echo"), Val::keyword("delayed")]),
+            ]))
+        );
+    }
+
+    #[tokio::test]
+    async fn cancel_timer() {
+        let k = kernel::start();
+        let prog = r#"(begin
+            (def r (send_after 20 (self) :cancelled))
+            (def cancelled (cancel_timer r))
+            (send_after 40 (self) :done)
+            (list cancelled (cancel_timer r) (recv)))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![
+                Val::Bool(true),
+                Val::Bool(false),
+                Val::keyword("done")
+            ]))
+        );
+    }
+
+    #[tokio::test]
+    async fn interval() {
+        let k = kernel::start();
+        let prog = r#"(begin
+            (def r (interval 5 :tick))
+            (recv :tick)
+            (recv :tick)
+            (recv :tick)
+            (cancel_timer r))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = time::timeout(Duration::from_secs(1), hdl.join())
+            .await
+            .expect("interval should deliver repeatedly")
+            .unwrap();
+        assert_eq!(exit.status.unwrap(), ProcessResult::Done(Val::Bool(true)));
+    }
+
+    #[tokio::test]
+    async fn interval_stops_on_exit() {
+        let k = kernel::start();
+        let prog = r#"(begin
+            (def parent (self))
+            (spawn (lambda () (send parent (interval 5 :tick))))
+            (def r (recv))
+            (sleep_ms 20)
+            (cancel_timer r))
+        "#;
+        let hdl = k
+            .spawn_prog(Program::from_expr(prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::Bool(false)),
+            "interval should stop once process exits"
+        );
+    }
+}
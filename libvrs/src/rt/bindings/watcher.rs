@@ -0,0 +1,131 @@
+//! File System Watcher Bindings
+use std::path::PathBuf;
+
+use crate::rt::program::{Fiber, NativeAsyncFn, Val};
+use crate::rt::watcher::WatcherHandle;
+use lyric::{Error, Result};
+
+/// Binding for watch_path
+pub(crate) fn watch_path_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc: "(watch_path PATH) or (watch_path PATH :recursive) - Watch file or directory at PATH, \
+              delivering (:fs_event PATH KIND) messages to this process, where KIND is :created, :modified, or :removed. \
+              With :recursive, subdirectories are also watched. Returns a reference for unwatch_path. \
+              Watchers are removed when this process exits."
+            .to_string(),
+        func: |f, args| Box::new(watch_path_impl(f, args)),
+    }
+}
+
+/// Binding for unwatch_path
+pub(crate) fn unwatch_path_fn() -> NativeAsyncFn {
+    NativeAsyncFn {
+        doc:
+            "(unwatch_path REF) - Stop watcher with REF. Returns false if there is no such watcher"
+                .to_string(),
+        func: |f, args| Box::new(unwatch_path_impl(f, args)),
+    }
+}
+
+/// Implementation for (watch_path PATH [:recursive])
+async fn watch_path_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let (path, recursive) = match &args[..] {
+        [Val::String(path)] => (path, false),
+        [Val::String(path), Val::Keyword(k)] if k.as_str() == "recursive" => (path, true),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "watch_path expects path, optionally followed by :recursive".to_string(),
+            ))
+        }
+    };
+    let path = PathBuf::from(shellexpand::tilde(path).to_string());
+    let owner = fiber
+        .locals()
+        .self_handle
+        .clone()
+        .ok_or(Error::Runtime("Process is missing self handle".to_string()))?;
+
+    let r = watchers(fiber)?
+        .watch(path, recursive, owner)
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::Ref(r))
+}
+
+/// Implementation for (unwatch_path REF)
+async fn unwatch_path_impl(fiber: &mut Fiber, args: Vec<Val>) -> Result<Val> {
+    let r = match &args[..] {
+        [Val::Ref(r)] => r.clone(),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "unwatch_path expects single reference argument".to_string(),
+            ))
+        }
+    };
+    let removed = watchers(fiber)?
+        .unwatch(r)
+        .await
+        .map_err(|e| Error::Runtime(format!("{e}")))?;
+    Ok(Val::Bool(removed))
+}
+
+fn watchers(fiber: &Fiber) -> Result<WatcherHandle> {
+    fiber.locals().watchers.clone().ok_or(Error::Runtime(
+        "Watchers are missing for process".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rt::{kernel, ProcessResult};
+    use crate::{Program, Val};
+    use assert_matches::assert_matches;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn watch_path_events() {
+        let dir = std::env::temp_dir().join(format!("vrs-watch-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let file = dir.join("rlist.ll");
+
+        let k = kernel::start();
+        let prog = format!(
+            r#"(begin
+                (def w (watch_path "{0}"))
+                (fwrite_str "{1}" "(:a)")
+                (def created (recv '(:fs_event "{1}" :created)))
+                (rm "{1}")
+                (def removed (recv '(:fs_event "{1}" :removed)))
+                (list (unwatch_path w) (unwatch_path w)))"#,
+            dir.display(),
+            file.display()
+        );
+        let hdl = k
+            .spawn_prog(Program::from_expr(&prog).unwrap())
+            .await
+            .unwrap();
+
+        let exit = tokio::time::timeout(Duration::from_secs(5), hdl.join())
+            .await
+            .expect("events should be delivered")
+            .unwrap();
+        assert_eq!(
+            exit.status.unwrap(),
+            ProcessResult::Done(Val::List(vec![Val::Bool(true), Val::Bool(false)]))
+        );
+
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[tokio::test]
+    async fn watch_path_missing() {
+        let k = kernel::start();
+        let hdl = k
+            .spawn_prog(Program::from_expr(r#"(try (watch_path "/no/such/vrs/path"))"#).unwrap())
+            .await
+            .unwrap();
+
+        let exit = hdl.join().await.unwrap();
+        assert_matches!(exit.status.unwrap(), ProcessResult::Done(Val::Error(_)));
+    }
+}
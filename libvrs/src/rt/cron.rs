@@ -0,0 +1,223 @@
+//! Cron Expressions
+//! Expressions have five fields for minute, hour, day of month, month, and day of week.
+//! Each field is `*`, a value, a range `A-B`, or a comma-separated list of those, optionally
+//! followed by a step `/N`.
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone, Timelike};
+
+use crate::rt::{Error, Result};
+
+/// Days searched for next occurrence, which covers leap days within a few years
+const MAX_DAYS: i64 = 366 * 5;
+
+/// A parsed cron expression
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Cron {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether day of month and day of week fields are restricted, instead of `*`
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Cron {
+    /// The expression this cron was parsed from
+    pub(crate) fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// The next occurrence after the minute of given time, in local time
+    pub(crate) fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let after = after.naive_local();
+        let start =
+            after.date().and_hms_opt(after.hour(), after.minute(), 0)? + Duration::minutes(1);
+        let start_date = start.date();
+        for offset in 0..MAX_DAYS {
+            let date = start_date + Duration::days(offset);
+            if !self.matches_date(date) {
+                continue;
+            }
+            for hour in 0..24 {
+                if !is_set(self.hours, hour) {
+                    continue;
+                }
+                for minute in 0..60 {
+                    if !is_set(self.minutes, minute) {
+                        continue;
+                    }
+                    let Some(time) = date.and_hms_opt(hour, minute, 0) else {
+                        continue;
+                    };
+                    if time < start {
+                        continue;
+                    }
+                    // Skip times that do not exist in local time, i.e. on DST transitions
+                    if let Some(dt) = Local.from_local_datetime(&time).earliest() {
+                        return Some(dt);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Whether or not date matches day of month, month, and day of week fields.
+    /// Like cron, dates match either day field when both day of month and day of week are restricted.
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if !is_set(self.months, date.month()) {
+            return false;
+        }
+        let day = is_set(self.days, date.day());
+        let weekday = is_set(self.weekdays, date.weekday().num_days_from_sunday());
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+impl FromStr for Cron {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(Error::InvalidCron(format!(
+                "{s} - expected 5 fields for minute, hour, day of month, month, and day of week"
+            )));
+        };
+
+        let mut weekdays_bits = parse_field(weekdays, 0, 7)?;
+        if is_set(weekdays_bits, 7) {
+            // 7 is also Sunday
+            weekdays_bits |= 1;
+        }
+
+        Ok(Cron {
+            source: fields.join(" "),
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays: weekdays_bits,
+            days_restricted: !days.starts_with('*'),
+            weekdays_restricted: !weekdays.starts_with('*'),
+        })
+    }
+}
+
+impl std::fmt::Display for Cron {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// Parse field into bitset of values within `min..=max`
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let invalid = || Error::InvalidCron(format!("{field} - expected values between {min}-{max}"));
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (
+                    start.parse().map_err(|_| invalid())?,
+                    end.parse().map_err(|_| invalid())?,
+                ),
+                None => {
+                    let v = range.parse().map_err(|_| invalid())?;
+                    // `N/STEP` runs from N to end of range
+                    (v, if part.contains('/') { max } else { v })
+                }
+            },
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for v in (start..=end).step_by(step as usize) {
+            bits |= 1 << v;
+        }
+    }
+    Ok(bits)
+}
+
+fn is_set(bits: u64, v: u32) -> bool {
+    bits & (1 << v) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_matches!("* * * *".parse::<Cron>(), Err(Error::InvalidCron(_)));
+        assert_matches!("60 * * * *".parse::<Cron>(), Err(Error::InvalidCron(_)));
+        assert_matches!("* * 0 * *".parse::<Cron>(), Err(Error::InvalidCron(_)));
+        assert_matches!("*/0 * * * *".parse::<Cron>(), Err(Error::InvalidCron(_)));
+        assert_matches!("5-1 * * * *".parse::<Cron>(), Err(Error::InvalidCron(_)));
+        assert_matches!("a * * * *".parse::<Cron>(), Err(Error::InvalidCron(_)));
+    }
+
+    #[test]
+    fn next_weekday_morning() {
+        let cron = "0 9 * * 1-5".parse::<Cron>().unwrap();
+
+        // Friday 2024-03-01 08:30
+        assert_eq!(
+            cron.next_after(local(2024, 3, 1, 8, 30)),
+            Some(local(2024, 3, 1, 9, 0))
+        );
+        // Friday after 9am runs on Monday
+        assert_eq!(
+            cron.next_after(local(2024, 3, 1, 9, 0) + Duration::seconds(30)),
+            Some(local(2024, 3, 4, 9, 0))
+        );
+    }
+
+    #[test]
+    fn next_steps_and_lists() {
+        let cron = "*/15 * * * *".parse::<Cron>().unwrap();
+        assert_eq!(
+            cron.next_after(local(2024, 3, 1, 8, 50)),
+            Some(local(2024, 3, 1, 9, 0))
+        );
+
+        let cron = "30 8,20 * * *".parse::<Cron>().unwrap();
+        assert_eq!(
+            cron.next_after(local(2024, 3, 1, 9, 0)),
+            Some(local(2024, 3, 1, 20, 30))
+        );
+    }
+
+    #[test]
+    fn next_day_of_month_or_week() {
+        // 1st of month, or Sundays
+        let cron = "0 0 1 * 7".parse::<Cron>().unwrap();
+        assert_eq!(
+            cron.next_after(local(2024, 3, 1, 12, 0)),
+            Some(local(2024, 3, 3, 0, 0))
+        );
+
+        // Leap days
+        let cron = "0 0 29 2 *".parse::<Cron>().unwrap();
+        assert_eq!(
+            cron.next_after(local(2024, 3, 1, 0, 0)),
+            Some(local(2028, 2, 29, 0, 0))
+        );
+    }
+}
@@ -20,6 +20,15 @@ pub enum Error {
     #[error("Registry Error - {0}")]
     RegistryError(String),
 
+    #[error("Supervisor Error - {0}")]
+    SupervisorError(String),
+
+    #[error("Scheduler Error - {0}")]
+    SchedulerError(String),
+
+    #[error("Invalid cron expression - {0}")]
+    InvalidCron(String),
+
     #[error("Process Exec Error - {0}")]
     ProcessExecError(lyric::Error),
 
@@ -50,6 +59,27 @@ pub enum Error {
     #[error("No mailbox")]
     NoMailbox,
 
+    #[error("Mailbox is full")]
+    MailboxFull,
+
+    #[error("No timers")]
+    NoTimers,
+
+    #[error("No scheduler")]
+    NoScheduler,
+
+    #[error("No watchers")]
+    NoWatchers,
+
+    #[error("No store")]
+    NoStore,
+
+    #[error("Store Error - {0}")]
+    StoreError(String),
+
+    #[error("Store conflict - {0} was changed")]
+    StoreConflict(String),
+
     #[error("Dead pubsub")]
     DeadPubSub,
 
@@ -58,4 +88,7 @@ pub enum Error {
 
     #[error("Missing IO Source - {0}")]
     NoIOResource(String),
+
+    #[error("Message can not be sent between processes - {0}")]
+    NotTransferable(String),
 }
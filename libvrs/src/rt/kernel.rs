@@ -1,35 +1,74 @@
 //! Runtime Kernel Task
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 
-use super::mailbox::Message;
+use super::mailbox::{MailboxConfig, Message};
 use super::proc::{ProcessExit, ProcessHandle, ProcessSet};
 use super::program;
 use super::pubsub::{PubSub, PubSubHandle};
 use super::registry::Registry;
+use super::scheduler::{Scheduler, SchedulerHandle};
+use super::store::{Store, StoreHandle};
+use super::timer::{TimerHandle, Timers};
+use super::watcher::{WatcherHandle, Watchers};
 use crate::rt::term::Term;
 use crate::rt::{proc::Process, Error, ProcessId, Result};
 use crate::{Connection, Program};
+use lyric::Ref;
 use tokio::sync::{mpsc, oneshot};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 /// Handle to `Kernel`
 #[derive(Debug, Clone)]
 pub(crate) struct KernelHandle {
     ev_tx: mpsc::Sender<Event>,
+    isolation: Isolation,
+}
+
+/// Configuration for runtime kernel
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// Policy for messages sent between processes
+    pub isolation: Isolation,
+    /// Configuration for mailbox of each process
+    pub mailbox: MailboxConfig,
+    /// Path where scheduled jobs are persisted. Jobs are only kept in memory if missing.
+    pub jobs: Option<PathBuf>,
+    /// Path of SQLite database for store. Stored forms are only kept in memory if missing.
+    pub store: Option<PathBuf>,
 }
 
 /// Handle to `Kernel`
 #[derive(Debug, Clone)]
 pub(crate) struct WeakKernelHandle {
     ev_tx: mpsc::WeakSender<Event>,
+    isolation: Isolation,
+}
+
+/// Policy for messages containing lambdas that capture sender's environment
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Isolation {
+    /// Deep copy messages, so received lambdas close over a copy of sender's environment
+    #[default]
+    Copy,
+    /// Reject messages containing lambdas that capture an environment
+    Reject,
 }
 
 /// Starts the kernel task, which manages processes on runtime
 pub(crate) fn start() -> KernelHandle {
+    start_with_config(Config::default())
+}
+
+/// Starts the kernel task with given configuration
+pub(crate) fn start_with_config(config: Config) -> KernelHandle {
     let (ev_tx, mut ev_rx) = mpsc::channel(32);
 
-    let handle = KernelHandle { ev_tx };
-    let mut kernel = Kernel::new(handle.clone());
+    let handle = KernelHandle {
+        ev_tx,
+        isolation: config.isolation,
+    };
+    let mut kernel = Kernel::new(handle.clone(), config);
     tokio::spawn(async move {
         loop {
             tokio::select! {
@@ -62,6 +101,21 @@ impl KernelHandle {
             .map_err(Error::FailedToReceiveResponseFromKernelTask)
     }
 
+    /// Spawn a new program linked to given process
+    pub(crate) async fn spawn_prog_linked(
+        &self,
+        prog: Program,
+        link_to: ProcessId,
+    ) -> Result<ProcessHandle> {
+        let (tx, rx) = oneshot::channel();
+        self.ev_tx
+            .send(Event::SpawnLinkedProg(prog, link_to, tx))
+            .await
+            .map_err(|_| Error::NoMessageReceiver("spawn_linked failed".to_string()))?;
+        rx.await
+            .map_err(Error::FailedToReceiveResponseFromKernelTask)
+    }
+
     /// Spawn a new process
     pub(crate) async fn spawn_for_conn(&self, conn: Connection) -> Result<ProcessHandle> {
         let (tx, rx) = oneshot::channel();
@@ -86,30 +140,92 @@ impl KernelHandle {
 
     /// Kill specified process
     pub(crate) async fn kill_proc(&self, pid: ProcessId) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.ev_tx
+            .send(Event::KillProcess(pid, tx))
+            .await
+            .map_err(|_| Error::NoMessageReceiver("kill_procs failed".to_string()))?;
+        rx.await
+            .map_err(Error::FailedToReceiveResponseFromKernelTask)?
+    }
+
+    /// Create a bidirectional link between two processes
+    pub(crate) async fn link(&self, pid: ProcessId, other: ProcessId) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.ev_tx
+            .send(Event::LinkProcess(pid, other, tx))
+            .await
+            .map_err(|_| Error::NoMessageReceiver("link failed".to_string()))?;
+        rx.await
+            .map_err(Error::FailedToReceiveResponseFromKernelTask)?
+    }
+
+    /// Monitor `target` from `watcher`, returning reference for the monitor
+    pub(crate) async fn monitor(&self, watcher: ProcessId, target: ProcessId) -> Result<Ref> {
+        let (tx, rx) = oneshot::channel();
+        self.ev_tx
+            .send(Event::MonitorProcess(watcher, target, tx))
+            .await
+            .map_err(|_| Error::NoMessageReceiver("monitor failed".to_string()))?;
+        rx.await
+            .map_err(Error::FailedToReceiveResponseFromKernelTask)
+    }
+
+    /// Set whether or not exit signals from linked processes are trapped as messages
+    pub(crate) async fn trap_exit(&self, pid: ProcessId, trap: bool) -> Result<()> {
         self.ev_tx
-            .send(Event::KillProcess(pid))
+            .send(Event::TrapExit(pid, trap))
             .await
-            .map_err(|_| Error::NoMessageReceiver("kill_procs failed".to_string()))
+            .map_err(|_| Error::NoMessageReceiver("trap_exit failed".to_string()))
     }
 
     // TODO(sec): SRC IDs too flexible
-    /// Handle a message being sent from one process to another
+    /// Handle a message being sent from one process to another.
+    /// Message is copied or rejected according to [Isolation] policy, so processes do not share state.
     pub(crate) async fn send_message(
         &self,
         src: ProcessId,
         dst: ProcessId,
         val: program::Val,
     ) -> Result<()> {
+        let val = self.isolate(val)?;
+
+        // Push from sender's task, so senders blocked on full mailboxes do not block kernel
+        match self.process(dst).await? {
+            Some(hdl) => hdl.mailbox().push(Message::new(src, val)).await,
+            None => Ok(()), // messages to exited processes are dropped
+        }
+    }
+
+    /// Get handle of running process, if any
+    pub(crate) async fn process(&self, pid: ProcessId) -> Result<Option<ProcessHandle>> {
+        let (tx, rx) = oneshot::channel();
         self.ev_tx
-            .send(Event::ProcessSendMessage(src, dst, val))
+            .send(Event::GetProcess(pid, tx))
             .await
-            .map_err(|_| Error::NoMessageReceiver("send_message failed".to_string()))
+            .map_err(|_| Error::NoMessageReceiver("process failed".to_string()))?;
+        rx.await
+            .map_err(Error::FailedToReceiveResponseFromKernelTask)
+    }
+
+    /// Copy or reject value leaving a process, according to [Isolation] policy
+    pub(crate) fn isolate(&self, val: program::Val) -> Result<program::Val> {
+        match self.isolation {
+            Isolation::Copy => val
+                .fork()
+                .map_err(|e| Error::NotTransferable(format!("{e}"))),
+            Isolation::Reject if val.has_closure() => Err(Error::NotTransferable(format!(
+                "{val} contains lambdas capturing sender's environment"
+            ))),
+            Isolation::Reject => Ok(val),
+        }
     }
 
     /// Downgrade a strong kernel handle to weak handle
     pub(crate) fn downgrade(&self) -> WeakKernelHandle {
         WeakKernelHandle {
             ev_tx: self.ev_tx.downgrade(),
+            isolation: self.isolation,
         }
     }
 }
@@ -118,7 +234,10 @@ impl WeakKernelHandle {
     /// Update a weak process handle into strong ref
     pub(crate) fn upgrade(&self) -> Option<KernelHandle> {
         let ev_tx = self.ev_tx.upgrade()?;
-        Some(KernelHandle { ev_tx })
+        Some(KernelHandle {
+            ev_tx,
+            isolation: self.isolation,
+        })
     }
 }
 
@@ -132,11 +251,15 @@ impl std::cmp::PartialEq for WeakKernelHandle {
 #[derive(Debug)]
 pub enum Event {
     SpawnProg(Program, oneshot::Sender<ProcessHandle>),
+    SpawnLinkedProg(Program, ProcessId, oneshot::Sender<ProcessHandle>),
     SpawnTermProc(Connection, oneshot::Sender<ProcessHandle>),
     ProcessExit(ProcessExit),
     ListProcess(oneshot::Sender<Vec<ProcessId>>),
-    KillProcess(ProcessId),
-    ProcessSendMessage(ProcessId, ProcessId, program::Val),
+    KillProcess(ProcessId, oneshot::Sender<Result<()>>),
+    GetProcess(ProcessId, oneshot::Sender<Option<ProcessHandle>>),
+    LinkProcess(ProcessId, ProcessId, oneshot::Sender<Result<()>>),
+    MonitorProcess(ProcessId, ProcessId, oneshot::Sender<Ref>),
+    TrapExit(ProcessId, bool),
 }
 
 /// The runtime kernel task
@@ -147,10 +270,25 @@ struct Kernel {
     next_proc_id: usize,
     registry: Registry,
     pubsub: PubSubHandle,
+    timers: TimerHandle,
+    scheduler: SchedulerHandle,
+    watchers: WatcherHandle,
+    store: StoreHandle,
+    links: HashMap<ProcessId, HashSet<ProcessId>>,
+    monitors: HashMap<ProcessId, Vec<Monitor>>,
+    trap_exits: HashSet<ProcessId>,
+    mailbox: MailboxConfig,
+}
+
+/// A monitor on a process, owned by watching process
+#[derive(Debug)]
+struct Monitor {
+    id: Ref,
+    watcher: ProcessId,
 }
 
 impl Kernel {
-    pub fn new(handle: KernelHandle) -> Self {
+    pub fn new(handle: KernelHandle, config: Config) -> Self {
         Self {
             weak_hdl: handle.downgrade(),
             procs: ProcessSet::new(),
@@ -158,6 +296,14 @@ impl Kernel {
             next_proc_id: 0,
             registry: Registry::spawn(),
             pubsub: PubSub::spawn(),
+            timers: Timers::spawn(),
+            scheduler: Scheduler::spawn(handle.downgrade(), config.jobs),
+            watchers: Watchers::spawn(),
+            store: Store::spawn(config.store),
+            links: HashMap::new(),
+            monitors: HashMap::new(),
+            trap_exits: HashSet::new(),
+            mailbox: config.mailbox,
         }
     }
 
@@ -170,6 +316,13 @@ impl Kernel {
                 let _ = tx.send(hdl);
                 Ok(())
             }
+            Event::SpawnLinkedProg(prog, link_to, tx) => {
+                let proc = Process::from_prog(self.next_pid(), prog);
+                let hdl = self.spawn(proc)?;
+                self.link(hdl.id(), link_to);
+                let _ = tx.send(hdl);
+                Ok(())
+            }
             Event::SpawnTermProc(conn, tx) => {
                 let proc = Process::from_prog(self.next_pid(), program::term_prog())
                     .term(Term::spawn(conn, self.pubsub.clone()));
@@ -177,14 +330,43 @@ impl Kernel {
                 let _ = tx.send(hdl);
                 Ok(())
             }
-            Event::ProcessExit(exit) => self.handle_exit(exit),
+            Event::ProcessExit(exit) => self.handle_exit(exit).await,
             Event::ListProcess(tx) => {
                 let ids = self.proc_hdls.keys().copied().collect();
                 let _ = tx.send(ids);
                 Ok(())
             }
-            Event::KillProcess(pid) => self.kill_proc(pid).await,
-            Event::ProcessSendMessage(src, dst, msg) => self.dispatch_msg(src, dst, msg).await,
+            Event::KillProcess(pid, tx) => {
+                let _ = tx.send(self.kill_proc(pid).await);
+                Ok(())
+            }
+            Event::GetProcess(pid, tx) => {
+                let _ = tx.send(self.proc_hdls.get(&pid).cloned());
+                Ok(())
+            }
+            Event::LinkProcess(pid, other, tx) => {
+                let res = if self.proc_hdls.contains_key(&other) {
+                    self.link(pid, other);
+                    Ok(())
+                } else {
+                    Err(Error::UnknownProcess)
+                };
+                let _ = tx.send(res);
+                Ok(())
+            }
+            Event::MonitorProcess(watcher, target, tx) => {
+                let id = self.monitor(watcher, target).await;
+                let _ = tx.send(id);
+                Ok(())
+            }
+            Event::TrapExit(pid, trap) => {
+                if trap {
+                    self.trap_exits.insert(pid);
+                } else {
+                    self.trap_exits.remove(&pid);
+                }
+                Ok(())
+            }
         }
     }
 
@@ -194,16 +376,98 @@ impl Kernel {
             .kernel(self.weak_hdl.clone())
             .registry(self.registry.clone())
             .pubsub(self.pubsub.clone())
+            .timers(self.timers.clone())
+            .scheduler(self.scheduler.clone())
+            .watchers(self.watchers.clone())
+            .store(self.store.clone())
+            .mailbox(self.mailbox)
             .spawn(&mut self.procs)?;
         self.proc_hdls.insert(hdl.id(), hdl.clone());
         Ok(hdl)
     }
 
-    /// Cleanup process that terminated with given result
-    fn handle_exit(&mut self, exit: ProcessExit) -> Result<()> {
-        match self.proc_hdls.remove(&exit.id) {
-            Some(_) => Ok(()),
-            None => panic!("Kernel notified of unmanaged process"),
+    /// Cleanup process that terminated with given result, and propagate exit signals to linked
+    /// and monitoring processes
+    async fn handle_exit(&mut self, exit: ProcessExit) -> Result<()> {
+        if self.proc_hdls.remove(&exit.id).is_none() {
+            warn!("Kernel notified of unmanaged process {}", exit.id);
+            return Ok(());
+        }
+
+        let pid = exit.id;
+        let pid_val = program::Val::Extern(program::Extern::ProcessId(pid));
+        let reason = exit.reason();
+        self.trap_exits.remove(&pid);
+
+        // monitors held by exiting process
+        for monitors in self.monitors.values_mut() {
+            monitors.retain(|m| m.watcher != pid);
+        }
+
+        for m in self.monitors.remove(&pid).unwrap_or_default() {
+            let msg = program::Val::List(vec![
+                program::Val::keyword("down"),
+                program::Val::Ref(m.id),
+                pid_val.clone(),
+                reason.clone(),
+            ]);
+            self.notify(pid, m.watcher, msg).await;
+        }
+
+        for linked in self.links.remove(&pid).unwrap_or_default() {
+            if let Some(l) = self.links.get_mut(&linked) {
+                l.remove(&pid);
+            }
+
+            if self.trap_exits.contains(&linked) {
+                let msg = program::Val::List(vec![
+                    program::Val::keyword("exit"),
+                    pid_val.clone(),
+                    reason.clone(),
+                ]);
+                self.notify(pid, linked, msg).await;
+            } else if !exit.is_normal() {
+                debug!("killing {linked} linked to {pid} - {exit}");
+                let _ = self.kill_proc(linked).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Link two processes, such that abnormal exits in one terminates the other
+    fn link(&mut self, pid: ProcessId, other: ProcessId) {
+        if pid == other {
+            return;
+        }
+        self.links.entry(pid).or_default().insert(other);
+        self.links.entry(other).or_default().insert(pid);
+    }
+
+    /// Monitor target process from watcher. Watcher is notified immediately if target is not running
+    async fn monitor(&mut self, watcher: ProcessId, target: ProcessId) -> Ref {
+        let id = Ref::new();
+        if self.proc_hdls.contains_key(&target) {
+            self.monitors.entry(target).or_default().push(Monitor {
+                id: id.clone(),
+                watcher,
+            });
+        } else {
+            let msg = program::Val::List(vec![
+                program::Val::keyword("down"),
+                program::Val::Ref(id.clone()),
+                program::Val::Extern(program::Extern::ProcessId(target)),
+                program::Val::keyword("noproc"),
+            ]);
+            self.notify(target, watcher, msg).await;
+        }
+        id
+    }
+
+    /// Deliver a message to process's mailbox, if the process is running
+    async fn notify(&self, src: ProcessId, dst: ProcessId, msg: program::Val) {
+        if let Some(hdl) = self.proc_hdls.get(&dst) {
+            hdl.notify_message(Message::new(src, msg)).await;
         }
     }
 
@@ -218,13 +482,6 @@ impl Kernel {
         }
     }
 
-    /// Dispatc message from src to dst
-    async fn dispatch_msg(&self, src: ProcessId, dst: ProcessId, msg: program::Val) -> Result<()> {
-        let dst = self.proc_hdls.get(&dst).ok_or(Error::UnknownProcess)?;
-        dst.notify_message(Message::new(src, msg)).await;
-        Ok(())
-    }
-
     /// Get the next process id
     fn next_pid(&mut self) -> ProcessId {
         let id = ProcessId::from(self.next_proc_id);
@@ -362,6 +619,20 @@ mod tests {
         assert!(k.procs().await.unwrap().is_empty(),);
     }
 
+    #[tokio::test]
+    async fn kill_unknown_proc() {
+        let k = start();
+
+        assert_matches!(
+            k.kill_proc(ProcessId::from(999)).await,
+            Err(Error::UnknownProcess)
+        );
+        assert!(
+            k.procs().await.is_ok(),
+            "Kernel should keep running after killing unknown process"
+        );
+    }
+
     #[tokio::test]
     async fn kill_proc_from_proc() {
         use tokio::time;
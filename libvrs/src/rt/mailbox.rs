@@ -1,5 +1,6 @@
 //! A Process's Mailbox
 use std::collections::VecDeque;
+use std::time::Duration;
 
 use super::proc::ProcessId;
 use super::program::{Pattern, Val};
@@ -13,11 +14,36 @@ pub(crate) struct MailboxHandle {
     tx: mpsc::Sender<Cmd>,
 }
 
+/// Configuration for process mailboxes
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MailboxConfig {
+    /// Maximum number of messages held in mailbox, or unbounded if None
+    pub capacity: Option<usize>,
+    /// Policy when message is sent to a full mailbox
+    pub overflow: Overflow,
+}
+
+/// Policy for messages sent to a full mailbox
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Overflow {
+    /// Drop oldest message in mailbox to make room for new message
+    #[default]
+    DropOldest,
+    /// Reject new message, returning an error to sender
+    Reject,
+    /// Block sender until there is room in mailbox
+    Block,
+}
+
 /// Mailbox for given process
 #[derive(Debug, Default)]
 pub(crate) struct Mailbox {
+    config: MailboxConfig,
     messages: VecDeque<Message>,
-    pending: Option<PendingPoll>,
+    pending: VecDeque<PendingPoll>,
+    blocked: VecDeque<BlockedPush>,
+    /// Patterns of messages that are dropped when received, e.g. responses no longer waited for
+    discards: Vec<Pattern>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -25,12 +51,28 @@ pub(crate) struct Message {
     pub(crate) contents: Val,
 }
 
+/// Snapshot of mailbox queue sizes
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MailboxStats {
+    /// Number of messages in mailbox
+    pub(crate) depth: usize,
+    /// Number of pending polls waiting for messages
+    pub(crate) pending: usize,
+    /// Number of senders blocked on full mailbox
+    pub(crate) blocked: usize,
+    /// Maximum number of messages in mailbox, if bounded
+    pub(crate) capacity: Option<usize>,
+}
+
 /// Commands between mailbox handle and async task
 #[derive(Debug)]
 enum Cmd {
-    Push(Message),
+    Push(Message, oneshot::Sender<Result<()>>),
+    Notify(Message),
     GetAll(oneshot::Sender<Vec<Message>>),
     Poll(Option<Pattern>, oneshot::Sender<Message>),
+    Discard(Pattern),
+    Stats(oneshot::Sender<MailboxStats>),
 }
 
 /// A pending handle for polling mailbox
@@ -40,14 +82,31 @@ struct PendingPoll {
     tx: oneshot::Sender<Message>,
 }
 
+/// A message from sender waiting for room in full mailbox
+#[derive(Debug)]
+struct BlockedPush {
+    msg: Message,
+    ack: oneshot::Sender<Result<()>>,
+}
+
 impl MailboxHandle {
-    /// Push a new message to mailbox
+    /// Push a new message to mailbox, following mailbox's [Overflow] policy if mailbox is full
     pub(crate) async fn push(&self, msg: Message) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
         self.tx
-            .send(Cmd::Push(msg))
+            .send(Cmd::Push(msg, tx))
             .await
             .map_err(|_| Error::NoMailbox)?;
-        Ok(())
+        rx.await?
+    }
+
+    /// Push a new message to mailbox regardless of mailbox capacity.
+    /// Used for messages from runtime, which should not be dropped or block
+    pub(crate) async fn notify(&self, msg: Message) -> Result<()> {
+        self.tx
+            .send(Cmd::Notify(msg))
+            .await
+            .map_err(|_| Error::NoMailbox)
     }
 
     /// Get all messages from mailbox
@@ -60,6 +119,16 @@ impl MailboxHandle {
         Ok(rx.await?)
     }
 
+    /// Get sizes of mailbox queues
+    pub(crate) async fn stats(&self) -> Result<MailboxStats> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(Cmd::Stats(tx))
+            .await
+            .map_err(|_| Error::NoMailbox)?;
+        Ok(rx.await?)
+    }
+
     /// Poll mailbox for matching message.
     /// Blocks calling task until message is receive
     pub(crate) async fn poll(&self, pat: Option<Pattern>) -> Result<Message> {
@@ -70,23 +139,71 @@ impl MailboxHandle {
             .map_err(|_| Error::NoMailbox)?;
         Ok(rx.await?)
     }
+
+    /// Drop a message matching pattern, whether it is already in mailbox or received later
+    pub(crate) async fn discard(&self, pat: Pattern) -> Result<()> {
+        self.tx
+            .send(Cmd::Discard(pat))
+            .await
+            .map_err(|_| Error::NoMailbox)
+    }
+
+    /// Poll mailbox for matching message, waiting at most `timeout` for message to be received.
+    /// Returns None if no matching message was received in time.
+    pub(crate) async fn poll_timeout(
+        &self,
+        pat: Option<Pattern>,
+        timeout: Duration,
+    ) -> Result<Option<Message>> {
+        let (tx, mut rx) = oneshot::channel();
+        self.tx
+            .send(Cmd::Poll(pat, tx))
+            .await
+            .map_err(|_| Error::NoMailbox)?;
+        if let Ok(msg) = tokio::time::timeout(timeout, &mut rx).await {
+            return Ok(Some(msg?));
+        }
+
+        // Closing cancels the pending poll - messages sent before closing are still in `rx`,
+        // and mailbox keeps messages it fails to send after closing
+        rx.close();
+        Ok(rx.try_recv().ok())
+    }
 }
 
 impl Mailbox {
     /// Spawn a new mailbox task
-    pub(crate) fn spawn(id: ProcessId) -> MailboxHandle {
+    pub(crate) fn spawn(id: ProcessId, config: MailboxConfig) -> MailboxHandle {
         let (tx, mut rx) = mpsc::channel(32);
         tokio::spawn(async move {
-            let mut mailbox = Mailbox::default();
+            let mut mailbox = Mailbox {
+                config,
+                ..Default::default()
+            };
             while let Some(cmd) = rx.recv().await {
                 debug!("mailbox {}: {:?}", id, cmd);
                 match cmd {
-                    Cmd::Push(msg) => mailbox.push(msg),
+                    Cmd::Push(msg, ack) => mailbox.push(msg, ack),
+                    Cmd::Notify(msg) => {
+                        if let Some(msg) = mailbox.deliver(msg) {
+                            mailbox.messages.push_back(msg);
+                        }
+                    }
                     Cmd::GetAll(tx) => {
                         let msgs = mailbox.messages.iter().cloned().collect();
                         let _ = tx.send(msgs);
                     }
                     Cmd::Poll(pat, tx) => mailbox.handle_poll(pat, tx),
+                    Cmd::Discard(pat) => mailbox.handle_discard(pat),
+                    Cmd::Stats(tx) => {
+                        mailbox.pending.retain(|p| !p.tx.is_closed());
+                        let _ = tx.send(MailboxStats {
+                            depth: mailbox.messages.len(),
+                            pending: mailbox.pending.len(),
+                            blocked: mailbox.blocked.len(),
+                            capacity: mailbox.config.capacity,
+                        });
+                    }
                 }
             }
         });
@@ -94,50 +211,134 @@ impl Mailbox {
     }
 
     /// Push a new message into mailbox. This may resolve pending requests
-    fn push(&mut self, msg: Message) {
-        let fufills_pending = match &self.pending {
-            Some(pending) => match &pending.pattern {
-                Some(pat) => pat.is_match(&msg.contents),
-                None => true,
-            },
-            None => false,
+    fn push(&mut self, msg: Message, ack: oneshot::Sender<Result<()>>) {
+        let msg = match self.deliver(msg) {
+            Some(msg) => msg,
+            None => {
+                let _ = ack.send(Ok(()));
+                return;
+            }
         };
 
-        if fufills_pending {
-            let pending = self.pending.take().unwrap();
-            let _ = pending.tx.send(msg);
-        } else {
+        if !self.is_full() {
             self.messages.push_back(msg);
+            let _ = ack.send(Ok(()));
+            return;
         }
+
+        match self.config.overflow {
+            Overflow::DropOldest => {
+                self.messages.pop_front();
+                self.messages.push_back(msg);
+                let _ = ack.send(Ok(()));
+            }
+            Overflow::Reject => {
+                let _ = ack.send(Err(Error::MailboxFull));
+            }
+            Overflow::Block => self.blocked.push_back(BlockedPush { msg, ack }),
+        }
+    }
+
+    /// Deliver message to earliest pending poll matching message.
+    /// Returns the message if no pending poll accepts it
+    fn deliver(&mut self, mut msg: Message) -> Option<Message> {
+        if let Some(idx) = self.discards.iter().position(|p| p.is_match(&msg.contents)) {
+            self.discards.remove(idx);
+            return None;
+        }
+        self.pending.retain(|p| !p.tx.is_closed());
+        while let Some(idx) = self.pending.iter().position(|p| p.is_match(&msg)) {
+            let pending = self.pending.remove(idx).unwrap();
+            match pending.tx.send(msg) {
+                Ok(()) => return None,
+                Err(m) => msg = m,
+            }
+        }
+        Some(msg)
     }
 
     /// Handle a poll for message matching given [Pattern] that will be sent to `tx`
     fn handle_poll(&mut self, pattern: Option<Pattern>, tx: oneshot::Sender<Message>) {
-        match self.pop_match(&pattern) {
-            Some(msg) => {
-                let _ = tx.send(msg);
+        let pending = PendingPoll { pattern, tx };
+
+        if let Some(idx) = self.messages.iter().position(|m| pending.is_match(m)) {
+            let msg = self.messages.remove(idx).unwrap();
+            if let Err(msg) = pending.tx.send(msg) {
+                self.messages.insert(idx, msg);
             }
-            None => {
-                if self.pending.is_some() {
-                    panic!("Unexpected poll on mailbox - mailbox should only be polled from process task");
+            self.unblock();
+            return;
+        }
+
+        // Blocked senders may hold the only matching message
+        if let Some(idx) = self.blocked.iter().position(|b| pending.is_match(&b.msg)) {
+            let blocked = self.blocked.remove(idx).unwrap();
+            match pending.tx.send(blocked.msg) {
+                Ok(()) => {
+                    let _ = blocked.ack.send(Ok(()));
                 }
-                self.pending = Some(PendingPoll { pattern, tx });
+                Err(msg) => self.blocked.insert(
+                    idx,
+                    BlockedPush {
+                        msg,
+                        ack: blocked.ack,
+                    },
+                ),
             }
+            return;
         }
+
+        self.pending.push_back(pending);
     }
 
-    /// Find the first matching message for given pattern, if any
-    fn pop_match(&mut self, pat: &Option<Pattern>) -> Option<Message> {
-        match pat {
-            None => self.messages.pop_front(),
-            Some(pat) => {
-                for i in 0..self.messages.len() {
-                    if pat.is_match(&self.messages[i].contents) {
-                        return self.messages.remove(i);
-                    }
-                }
-                None
+    /// Handle request to drop a single message matching given [Pattern]
+    fn handle_discard(&mut self, pattern: Pattern) {
+        if let Some(idx) = self
+            .messages
+            .iter()
+            .position(|m| pattern.is_match(&m.contents))
+        {
+            self.messages.remove(idx);
+            self.unblock();
+        } else if let Some(idx) = self
+            .blocked
+            .iter()
+            .position(|b| pattern.is_match(&b.msg.contents))
+        {
+            let blocked = self.blocked.remove(idx).unwrap();
+            let _ = blocked.ack.send(Ok(()));
+        } else {
+            self.discards.push(pattern);
+        }
+    }
+
+    /// Move messages from blocked senders into mailbox while there is room
+    fn unblock(&mut self) {
+        while !self.is_full() {
+            let Some(BlockedPush { msg, ack }) = self.blocked.pop_front() else {
+                break;
+            };
+            if let Some(msg) = self.deliver(msg) {
+                self.messages.push_back(msg);
             }
+            let _ = ack.send(Ok(()));
+        }
+    }
+
+    /// Whether or not mailbox is at capacity
+    fn is_full(&self) -> bool {
+        self.config
+            .capacity
+            .is_some_and(|cap| self.messages.len() >= cap)
+    }
+}
+
+impl PendingPoll {
+    /// Whether or not message satisfies poll
+    fn is_match(&self, msg: &Message) -> bool {
+        match &self.pattern {
+            Some(pat) => pat.is_match(&msg.contents),
+            None => true,
         }
     }
 }
@@ -151,6 +352,7 @@ impl Message {
 #[cfg(test)]
 mod tests {
 
+    use assert_matches::assert_matches;
     use lyric::parse;
     use tokio::task::yield_now;
 
@@ -158,13 +360,13 @@ mod tests {
 
     #[tokio::test]
     async fn messages() {
-        let mb = Mailbox::spawn(ProcessId::from(0));
+        let mb = Mailbox::spawn(ProcessId::from(0), MailboxConfig::default());
         assert_eq!(mb.all().await.unwrap(), vec![]);
     }
 
     #[tokio::test]
     async fn all() {
-        let mb = Mailbox::spawn(0.into());
+        let mb = Mailbox::spawn(0.into(), MailboxConfig::default());
 
         mb.push(Message::new(1.into(), Val::symbol("one")))
             .await
@@ -189,7 +391,7 @@ mod tests {
 
     #[tokio::test]
     async fn poll_after_push() {
-        let mb = Mailbox::spawn(0.into());
+        let mb = Mailbox::spawn(0.into(), MailboxConfig::default());
 
         mb.push(Message::new(
             1.into(),
@@ -207,7 +409,7 @@ mod tests {
 
     #[tokio::test]
     async fn poll_before_push() {
-        let mb = Mailbox::spawn(0.into());
+        let mb = Mailbox::spawn(0.into(), MailboxConfig::default());
 
         let mb_clone = mb.clone();
         let hdl = tokio::spawn(async move { mb_clone.poll(None).await });
@@ -228,7 +430,7 @@ mod tests {
 
     #[tokio::test]
     async fn poll_after_push_pattern() {
-        let mb = Mailbox::spawn(0.into());
+        let mb = Mailbox::spawn(0.into(), MailboxConfig::default());
 
         let msg1 = Message::new(1.into(), Val::from_expr("(:one 1)").unwrap());
         let msg2 = Message::new(2.into(), Val::from_expr("(:two 2)").unwrap());
@@ -270,7 +472,7 @@ mod tests {
 
     #[tokio::test]
     async fn poll_before_push_pattern() {
-        let mb = Mailbox::spawn(0.into());
+        let mb = Mailbox::spawn(0.into(), MailboxConfig::default());
 
         let mbc = mb.clone();
         let hdl = tokio::spawn(async move {
@@ -305,4 +507,182 @@ mod tests {
         assert_eq!(hdl.await.unwrap(), vec![msg4, msg1, msg2]);
         assert_eq!(mb.all().await.unwrap(), vec![msg3, msg5]);
     }
+
+    #[tokio::test]
+    async fn poll_timeout() {
+        let mb = Mailbox::spawn(0.into(), MailboxConfig::default());
+        let pat = Pattern::from_expr("(:one _)").unwrap();
+
+        assert_eq!(
+            mb.poll_timeout(Some(pat.clone()), Duration::from_millis(1))
+                .await
+                .unwrap(),
+            None,
+            "Poll should time out without matching message"
+        );
+
+        let msg = Message::new(1.into(), Val::from_expr("(:one 1)").unwrap());
+        mb.push(msg.clone()).await.unwrap();
+        assert_eq!(
+            mb.all().await.unwrap(),
+            vec![msg.clone()],
+            "Message after timeout should not be lost"
+        );
+
+        assert_eq!(
+            mb.poll_timeout(Some(pat), Duration::from_millis(1))
+                .await
+                .unwrap(),
+            Some(msg),
+            "Poll should return matching message before timeout"
+        );
+    }
+
+    #[tokio::test]
+    async fn poll_timeout_before_push() {
+        let mb = Mailbox::spawn(0.into(), MailboxConfig::default());
+
+        let mbc = mb.clone();
+        let hdl = tokio::spawn(async move { mbc.poll_timeout(None, Duration::from_secs(5)).await });
+
+        yield_now().await;
+        let msg = Message::new(1.into(), Val::symbol("hi"));
+        mb.push(msg.clone()).await.unwrap();
+
+        assert_eq!(hdl.await.unwrap().unwrap(), Some(msg));
+        assert_eq!(mb.all().await.unwrap(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn concurrent_polls() {
+        let mb = Mailbox::spawn(0.into(), MailboxConfig::default());
+
+        let poll = |pat: &str| {
+            let mb = mb.clone();
+            let pat = Pattern::from_expr(pat).unwrap();
+            tokio::spawn(async move { mb.poll(Some(pat)).await.unwrap() })
+        };
+        let first = poll("(:one _)");
+        let second = poll("(:two _)");
+        let third = poll("(_ _)");
+        yield_now().await;
+
+        assert_eq!(mb.stats().await.unwrap().pending, 3);
+
+        let msg1 = Message::new(1.into(), Val::from_expr("(:one 1)").unwrap());
+        let msg2 = Message::new(2.into(), Val::from_expr("(:two 2)").unwrap());
+        let msg3 = Message::new(3.into(), Val::from_expr("(:three 3)").unwrap());
+        mb.push(msg2.clone()).await.unwrap();
+        mb.push(msg3.clone()).await.unwrap();
+        mb.push(msg1.clone()).await.unwrap();
+
+        assert_eq!(first.await.unwrap(), msg1);
+        assert_eq!(second.await.unwrap(), msg2);
+        assert_eq!(third.await.unwrap(), msg3);
+        assert_eq!(mb.all().await.unwrap(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn discard() {
+        let mb = Mailbox::spawn(0.into(), MailboxConfig::default());
+        let pat = |expr| Pattern::from_val(Val::from_expr(expr).unwrap());
+
+        mb.push(Message::new(
+            1.into(),
+            Val::from_expr("(:early 1)").unwrap(),
+        ))
+        .await
+        .unwrap();
+        mb.discard(pat("(:early _)")).await.unwrap();
+        mb.discard(pat("(:late _)")).await.unwrap();
+
+        let msg = Message::new(1.into(), Val::from_expr("(:late 2)").unwrap());
+        mb.push(msg.clone()).await.unwrap();
+        mb.push(msg.clone()).await.unwrap();
+        assert_eq!(
+            mb.all().await.unwrap(),
+            vec![msg],
+            "Only a single message should be dropped for each discard"
+        );
+    }
+
+    #[tokio::test]
+    async fn overflow_drop_oldest() {
+        let mb = Mailbox::spawn(
+            0.into(),
+            MailboxConfig {
+                capacity: Some(2),
+                overflow: Overflow::DropOldest,
+            },
+        );
+        for i in 0..3 {
+            mb.push(Message::new(1.into(), Val::Int(i))).await.unwrap();
+        }
+        assert_eq!(
+            mb.all().await.unwrap(),
+            vec![
+                Message::new(1.into(), Val::Int(1)),
+                Message::new(1.into(), Val::Int(2)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn overflow_reject() {
+        let mb = Mailbox::spawn(
+            0.into(),
+            MailboxConfig {
+                capacity: Some(1),
+                overflow: Overflow::Reject,
+            },
+        );
+        mb.push(Message::new(1.into(), Val::Int(0))).await.unwrap();
+        assert_matches!(
+            mb.push(Message::new(1.into(), Val::Int(1))).await,
+            Err(Error::MailboxFull)
+        );
+        mb.notify(Message::new(1.into(), Val::Int(2)))
+            .await
+            .unwrap();
+        assert_eq!(
+            mb.stats().await.unwrap(),
+            MailboxStats {
+                depth: 2,
+                pending: 0,
+                blocked: 0,
+                capacity: Some(1),
+            },
+            "Notifications should ignore capacity"
+        );
+    }
+
+    #[tokio::test]
+    async fn overflow_block() {
+        let mb = Mailbox::spawn(
+            0.into(),
+            MailboxConfig {
+                capacity: Some(1),
+                overflow: Overflow::Block,
+            },
+        );
+        mb.push(Message::new(1.into(), Val::Int(0))).await.unwrap();
+
+        let mbc = mb.clone();
+        let sender =
+            tokio::spawn(async move { mbc.push(Message::new(1.into(), Val::Int(1))).await });
+        yield_now().await;
+        assert!(!sender.is_finished(), "Sender should block on full mailbox");
+        assert_eq!(mb.stats().await.unwrap().blocked, 1);
+
+        assert_eq!(
+            mb.poll(None).await.unwrap(),
+            Message::new(1.into(), Val::Int(0))
+        );
+        sender.await.unwrap().unwrap();
+        assert_eq!(
+            mb.all().await.unwrap(),
+            vec![Message::new(1.into(), Val::Int(1))],
+            "Blocked message should be in mailbox after room is made"
+        );
+    }
 }
@@ -1,16 +1,26 @@
 mod bindings;
+mod cron;
 mod error;
 mod kernel;
+mod port;
 pub mod program;
 mod pubsub;
 mod registry;
 mod runtime;
+mod scheduler;
+mod store;
+mod supervisor;
 mod term;
+mod timer;
+mod watcher;
 
 mod mailbox;
 mod proc;
 
 pub use error::Error;
+pub use kernel::{Config, Isolation};
+pub use mailbox::{MailboxConfig, Overflow};
+pub use port::PortHandle;
 pub type Result<T> = std::result::Result<T, Error>;
 pub use proc::{Process, ProcessExit, ProcessHandle, ProcessId, ProcessResult, ProcessSet};
 pub use runtime::Runtime;
@@ -0,0 +1,167 @@
+//! Ports to OS Child Processes
+//! A port streams lines of child's stdout and stderr to the mailbox of owning process, as
+//! `(:stdout PORT LINE)` and `(:stderr PORT LINE)` messages, followed by `(:exit PORT CODE)`.
+//! The child is killed when owning process exits.
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::{ChildStdin, Command};
+use tokio::sync::{mpsc, Mutex};
+use tracing::{debug, info};
+
+use super::mailbox::Message;
+use super::proc::ProcessHandle;
+use super::program::{Extern, Val};
+use crate::rt::{Error, Result};
+
+/// Id of next port
+static NEXT_PORT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Handle to a running port
+#[derive(Debug, Clone)]
+pub struct PortHandle {
+    id: usize,
+    stdin: Arc<Mutex<Option<ChildStdin>>>,
+    tx: mpsc::Sender<Cmd>,
+}
+
+/// Commands between port handle and async task
+#[derive(Debug)]
+enum Cmd {
+    Kill,
+}
+
+impl PortHandle {
+    /// Start child process running `prog` with `args`, delivering its output to `owner`
+    pub(crate) fn spawn(prog: &str, args: &[String], owner: ProcessHandle) -> Result<Self> {
+        let mut child = Command::new(prog)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| Error::IOError(format!("{e}")))?;
+
+        let (tx, mut rx) = mpsc::channel(1);
+        let hdl = PortHandle {
+            id: NEXT_PORT_ID.fetch_add(1, Ordering::Relaxed),
+            stdin: Arc::new(Mutex::new(child.stdin.take())),
+            tx,
+        };
+        info!("port spawn - {hdl} - {prog} {args:?}");
+
+        let port = hdl.clone();
+        let stdout = child.stdout.take().expect("child has stdout");
+        let stderr = child.stderr.take().expect("child has stderr");
+        tokio::spawn(async move {
+            // Lines are read on separate tasks, since reading lines is not cancel-safe
+            let readers = futures::future::join(
+                tokio::spawn(forward_lines(stdout, "stdout", port.clone(), owner.clone())),
+                tokio::spawn(forward_lines(stderr, "stderr", port.clone(), owner.clone())),
+            );
+            tokio::pin!(readers);
+            let exit = owner.clone().join();
+            tokio::pin!(exit);
+
+            let mut readers_done = false;
+            loop {
+                tokio::select! {
+                    _ = &mut readers, if !readers_done => readers_done = true,
+                    Some(Cmd::Kill) = rx.recv() => {
+                        debug!("port kill - {port}");
+                        let _ = child.start_kill();
+                    }
+                    _ = &mut exit => {
+                        debug!("port owner exited - {port}");
+                        let _ = child.kill().await;
+                        break;
+                    }
+                    status = child.wait(), if readers_done => {
+                        let code = match status {
+                            Ok(status) => status.code().map(|c| Val::Int(c as i64)).unwrap_or(Val::Nil),
+                            Err(e) => Val::Error(lyric::Error::Runtime(format!("{e}"))),
+                        };
+                        info!("port exit - {port} - {code}");
+                        notify(&owner, "exit", &port, code).await;
+                        break;
+                    }
+                }
+            }
+            port.stdin.lock().await.take();
+        });
+
+        Ok(hdl)
+    }
+
+    /// Write string to standard input of child
+    pub(crate) async fn write(&self, contents: &str) -> Result<()> {
+        let mut stdin = self.stdin.lock().await;
+        let stdin = stdin.as_mut().ok_or(Error::ConnectionClosed)?;
+        stdin
+            .write_all(contents.as_bytes())
+            .await
+            .map_err(|e| Error::IOError(format!("{e}")))?;
+        stdin
+            .flush()
+            .await
+            .map_err(|e| Error::IOError(format!("{e}")))
+    }
+
+    /// Close standard input of child, then kill child
+    pub(crate) async fn close(&self) {
+        self.stdin.lock().await.take();
+        let _ = self.tx.send(Cmd::Kill).await;
+    }
+}
+
+/// Deliver each line of stream to owner as `(TAG PORT LINE)`, without line endings
+async fn forward_lines(
+    stream: impl AsyncRead + Unpin,
+    tag: &'static str,
+    port: PortHandle,
+    owner: ProcessHandle,
+) {
+    let mut reader = BufReader::new(stream);
+    let mut buf = vec![];
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                if buf.ends_with(b"\n") {
+                    buf.pop();
+                    if buf.ends_with(b"\r") {
+                        buf.pop();
+                    }
+                }
+                let line = Val::String(String::from_utf8_lossy(&buf).to_string());
+                notify(&owner, tag, &port, line).await;
+            }
+        }
+    }
+}
+
+/// Deliver `(TAG PORT VAL)` to owner
+async fn notify(owner: &ProcessHandle, tag: &str, port: &PortHandle, val: Val) {
+    let msg = Val::List(vec![
+        Val::keyword(tag),
+        Val::Extern(Extern::Port(port.clone())),
+        val,
+    ]);
+    owner.notify_message(Message::new(owner.id(), msg)).await;
+}
+
+impl std::cmp::PartialEq for PortHandle {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl std::fmt::Display for PortHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<port {}>", self.id)
+    }
+}
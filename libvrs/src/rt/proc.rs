@@ -3,15 +3,24 @@ use super::mailbox::Message;
 use super::program::{Extern, Locals, Val};
 use super::pubsub::PubSubHandle;
 use super::registry::Registry;
+use super::scheduler::SchedulerHandle;
+use super::store::StoreHandle;
 use super::term::TermHandle;
-use crate::rt::mailbox::{Mailbox, MailboxHandle};
+use super::timer::TimerHandle;
+use super::watcher::WatcherHandle;
+use crate::rt::mailbox::{Mailbox, MailboxConfig, MailboxHandle};
 use crate::rt::{Error, Result};
 use crate::Program;
 use futures::future::{FutureExt, Shared};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot};
 use tokio::task::JoinSet;
 use tracing::info;
 
+/// Number of instructions a process runs before yielding to other processes
+const REDUCTIONS_PER_SLICE: u64 = 2000;
+
 /// Set of running processes
 pub type ProcessSet = JoinSet<ProcessExit>;
 
@@ -24,6 +33,7 @@ pub struct Process {
     id: ProcessId,
     prog: Program,
     locals: Locals,
+    mailbox: MailboxConfig,
 }
 
 /// A handle to [Process]
@@ -33,6 +43,7 @@ pub struct ProcessHandle {
     hdl_tx: mpsc::Sender<Event>,
     mailbox: MailboxHandle,
     exit_rx: Shared<oneshot::Receiver<ProcessExit>>,
+    reductions: Arc<AtomicU64>,
 }
 
 /// The result of process
@@ -58,9 +69,16 @@ impl Process {
             id,
             prog,
             locals: Locals::new(id),
+            mailbox: MailboxConfig::default(),
         }
     }
 
+    /// Set mailbox configuration for process
+    pub(crate) fn mailbox(mut self, config: MailboxConfig) -> Self {
+        self.mailbox = config;
+        self
+    }
+
     /// Set kernel handle for process
     pub(crate) fn kernel(mut self, k: WeakKernelHandle) -> Self {
         self.locals.kernel(k);
@@ -85,23 +103,49 @@ impl Process {
         self
     }
 
+    /// Set timers handle for process
+    pub(crate) fn timers(mut self, timers: TimerHandle) -> Self {
+        self.locals.timers(timers);
+        self
+    }
+
+    /// Set scheduler handle for process
+    pub(crate) fn scheduler(mut self, scheduler: SchedulerHandle) -> Self {
+        self.locals.scheduler(scheduler);
+        self
+    }
+
+    /// Set file system watchers handle for process
+    pub(crate) fn watchers(mut self, watchers: WatcherHandle) -> Self {
+        self.locals.watchers(watchers);
+        self
+    }
+
+    /// Set store handle for process
+    pub(crate) fn store(mut self, store: StoreHandle) -> Self {
+        self.locals.store(store);
+        self
+    }
+
     /// Spawn a process
-    pub(crate) fn spawn(mut self, procs: &mut ProcessSet) -> Result<ProcessHandle> {
+    pub(crate) fn spawn(self, procs: &mut ProcessSet) -> Result<ProcessHandle> {
         info!("proc spawn - {}", self.id);
 
         let (exit_tx, exit_rx) = oneshot::channel();
         let (msg_tx, mut msg_rx) = mpsc::channel(32);
 
-        let mailbox: MailboxHandle = Mailbox::spawn(self.id);
+        let mut fiber = self.prog.into_fiber(self.locals);
+        fiber.set_budget(Some(REDUCTIONS_PER_SLICE));
+
+        let mailbox: MailboxHandle = Mailbox::spawn(self.id, self.mailbox);
         let proc_hdl = ProcessHandle {
             id: self.id,
             hdl_tx: msg_tx,
             exit_rx: exit_rx.shared(),
             mailbox,
+            reductions: fiber.reduction_counter(),
         };
-        self.locals.handle(proc_hdl.clone());
-
-        let mut fiber = self.prog.into_fiber(self.locals);
+        fiber.locals_mut().handle(proc_hdl.clone());
 
         procs.spawn(async move {
             // TODO: Implement ProcessResult::Disconnected when Error::ConnectionClosed is returned
@@ -147,14 +191,24 @@ impl ProcessHandle {
         let _ = self.hdl_tx.send(Event::Kill).await;
     }
 
+    /// Number of instructions run by process so far
+    pub fn reductions(&self) -> u64 {
+        self.reductions.load(Ordering::Relaxed)
+    }
+
+    /// Whether or not process is still running
+    pub(crate) fn is_running(&self) -> bool {
+        !self.hdl_tx.is_closed()
+    }
+
     /// Wait for process to end
     pub async fn join(self) -> Result<ProcessExit> {
         Ok(self.exit_rx.await?)
     }
 
-    /// Send a new message to process's mailbox
+    /// Send a new message to process's mailbox, regardless of mailbox capacity
     pub(crate) async fn notify_message(&self, msg: Message) {
-        let _ = self.mailbox.push(msg).await;
+        let _ = self.mailbox.notify(msg).await;
     }
 
     /// Get reference to process mailbox
@@ -197,6 +251,8 @@ impl std::fmt::Display for Extern {
         match self {
             Extern::ProcessId(pid) => write!(f, "{}", pid),
             Extern::RequestId(id) => write!(f, "<request_id {}>", id),
+            Extern::Duration(d) => write!(f, "<duration {:?}>", d),
+            Extern::Port(port) => write!(f, "{}", port),
         }
     }
 }
@@ -211,6 +267,23 @@ impl std::fmt::Display for ProcessExit {
     }
 }
 
+impl ProcessExit {
+    /// Whether or not process exited normally, i.e. ran to completion
+    pub fn is_normal(&self) -> bool {
+        matches!(self.status, Ok(ProcessResult::Done(_)))
+    }
+
+    /// The reason for exit, delivered to linked and monitoring processes
+    pub fn reason(&self) -> Val {
+        match &self.status {
+            Ok(ProcessResult::Done(_)) => Val::keyword("normal"),
+            Ok(ProcessResult::Cancelled) => Val::keyword("killed"),
+            Err(Error::EvaluationError(e)) => Val::Error(e.clone()),
+            Err(e) => Val::Error(lyric::Error::Runtime(e.to_string())),
+        }
+    }
+}
+
 impl ProcessResult {
     pub fn unwrap(self) -> Val {
         match self {
@@ -284,7 +357,6 @@ mod tests {
         );
     }
 
-    // TODO: Implement + test preemption
     // #[tokio::test]
     // async fn drop_handle_ends_process() {
     //     let mut procs = ProcessSet::new();
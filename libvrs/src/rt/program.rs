@@ -7,10 +7,15 @@ use crate::ProcessHandle;
 
 use super::bindings;
 use super::kernel::WeakKernelHandle;
+use super::port::PortHandle;
 use super::proc::ProcessId;
 use super::pubsub::PubSubHandle;
 use super::registry::Registry;
+use super::scheduler::SchedulerHandle;
+use super::store::StoreHandle;
 use super::term::TermHandle;
+use super::timer::TimerHandle;
+use super::watcher::WatcherHandle;
 
 /// Program used to spawn new processes
 #[derive(Debug, Clone)]
@@ -57,6 +62,8 @@ pub type Bytecode = lyric::Bytecode<Extern, Locals>;
 pub enum Extern {
     ProcessId(ProcessId),
     RequestId(u32), // TODO: Type request id as RequestId
+    Duration(std::time::Duration),
+    Port(PortHandle),
 }
 
 /// Locals for Program Fiber
@@ -74,6 +81,14 @@ pub struct Locals {
     pub(crate) self_handle: Option<ProcessHandle>,
     /// Handle to controlling terminal, if any
     pub(crate) term: Option<TermHandle>,
+    /// Handle to runtime timers
+    pub(crate) timers: Option<TimerHandle>,
+    /// Handle to job scheduler
+    pub(crate) scheduler: Option<SchedulerHandle>,
+    /// Handle to file system watchers
+    pub(crate) watchers: Option<WatcherHandle>,
+    /// Handle to embedded store
+    pub(crate) store: Option<StoreHandle>,
 }
 
 impl Program {
@@ -85,8 +100,9 @@ impl Program {
     }
 
     pub fn from_val(val: Val) -> Result<Self> {
-        let code = lyric::compile(&val)?;
-        Ok(Self::from_bytecode(code))
+        // Compiled when program starts, so macros bound in program's environment are expanded
+        let code = vec![lyric::Inst::PushConst(val), lyric::Inst::Eval(false)];
+        Ok(Self::from_bytecode(code.into()))
     }
 
     pub fn from_expr(expr: &str) -> Result<Self> {
@@ -101,14 +117,20 @@ impl Program {
             ));
         }
 
-        let code = lambda.code;
-        let env = lambda.parent.as_ref();
-        let prog = Self::from_bytecode(code).env(match env {
-            Some(env) => env.lock().unwrap().fork(),
+        // Lambda runs within copy of captured bindings, so spawned process does not share state with caller.
+        // Copy extends a new process environment, so symbols only known at runtime resolve to bindings for process.
+        let lambda = Val::Lambda(lambda).fork_with_root(proc_env())?;
+        let env = match &lambda {
+            Val::Lambda(l) => l.parent.as_ref(),
+            _ => None,
+        };
+        let env = match env {
+            Some(env) => Env::extend(env),
             None => proc_env(),
-        });
+        };
+        let code = vec![lyric::Inst::PushConst(lambda), lyric::Inst::CallFunc(0)];
 
-        Ok(prog)
+        Ok(Self::from_bytecode(code.into()).env(env))
     }
 
     pub fn env(mut self, env: Env) -> Self {
@@ -143,6 +165,10 @@ impl Locals {
             pubsub: None,
             self_handle: None,
             term: None,
+            timers: None,
+            scheduler: None,
+            watchers: None,
+            store: None,
         }
     }
 
@@ -170,6 +196,26 @@ impl Locals {
         self.term = Some(term);
         self
     }
+
+    pub(crate) fn timers(&mut self, timers: TimerHandle) -> &mut Self {
+        self.timers = Some(timers);
+        self
+    }
+
+    pub(crate) fn scheduler(&mut self, scheduler: SchedulerHandle) -> &mut Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    pub(crate) fn watchers(&mut self, watchers: WatcherHandle) -> &mut Self {
+        self.watchers = Some(watchers);
+        self
+    }
+
+    pub(crate) fn store(&mut self, store: StoreHandle) -> &mut Self {
+        self.store = Some(store);
+        self
+    }
 }
 
 impl PartialEq for Program {
@@ -184,20 +230,25 @@ pub fn proc_env() -> Env {
 
     {
         e.bind_native_async(SymbolId::from("recv"), bindings::recv_fn())
+            .bind_native_async(SymbolId::from("recv_timeout"), bindings::recv_timeout_fn())
             .bind_native_async(SymbolId::from("ls_msgs"), bindings::ls_msgs_fn())
             .bind_native_async(SymbolId::from("send"), bindings::send_fn())
-            .bind_lambda(SymbolId::from("call"), bindings::call_fn());
+            .bind_native_async(SymbolId::from("call"), bindings::call_fn());
     }
 
     {
-        e.bind_native(SymbolId::from("srv"), bindings::srv_fn())
+        e.bind_macro(SymbolId::from("srv"), bindings::srv_macro())
+            .bind_native(
+                SymbolId::from("srv_match_arms"),
+                bindings::srv_match_arms_fn(),
+            )
             .bind_lambda(SymbolId::from("bind_srv"), bindings::bind_srv_fn())
             .bind_native(
                 SymbolId::from("def_bind_interface"),
                 bindings::def_bind_interface(),
             )
             .bind_native_async(SymbolId::from("info_srv"), bindings::info_srv_fn())
-            .bind_native(SymbolId::from("spawn_srv"), bindings::spawn_srv_fn());
+            .bind_macro(SymbolId::from("spawn_srv"), bindings::spawn_srv_macro());
     }
 
     {
@@ -205,18 +256,75 @@ pub fn proc_env() -> Env {
             .bind_native(SymbolId::from("pid"), bindings::pid_fn())
             .bind_native_async(SymbolId::from("ps"), bindings::ps_fn())
             .bind_native(SymbolId::from("self"), bindings::self_fn())
+            .bind_native_async(SymbolId::from("spawn"), bindings::spawn_fn())
+            .bind_native_async(SymbolId::from("spawn_link"), bindings::spawn_link_fn())
+            .bind_native_async(SymbolId::from("link"), bindings::link_fn())
+            .bind_native_async(SymbolId::from("monitor"), bindings::monitor_fn())
+            .bind_native_async(SymbolId::from("trap_exit"), bindings::trap_exit_fn());
+    }
+
+    {
+        e.bind_native(SymbolId::from("duration"), bindings::duration_fn())
             .bind_native_async(SymbolId::from("sleep"), bindings::sleep_fn())
-            .bind_native_async(SymbolId::from("spawn"), bindings::spawn_fn());
+            .bind_native_async(SymbolId::from("sleep_ms"), bindings::sleep_ms_fn())
+            .bind_native_async(SymbolId::from("send_after"), bindings::send_after_fn())
+            .bind_native_async(SymbolId::from("interval"), bindings::interval_fn())
+            .bind_native_async(SymbolId::from("cancel_timer"), bindings::cancel_timer_fn());
+    }
+
+    {
+        e.bind_macro(SymbolId::from("schedule"), bindings::schedule_macro())
+            .bind_native_async(SymbolId::from("schedule_job"), bindings::schedule_job_fn())
+            .bind_native_async(SymbolId::from("ls_jobs"), bindings::ls_jobs_fn())
+            .bind_native_async(SymbolId::from("unschedule"), bindings::unschedule_fn());
+    }
+
+    {
+        e.bind_native_async(SymbolId::from("supervise"), bindings::supervise_fn());
     }
 
     {
         e.bind_native_async(SymbolId::from("fread"), bindings::fread_fn())
-            .bind_native_async(SymbolId::from("fdump"), bindings::fdump_fn());
+            .bind_native_async(SymbolId::from("fdump"), bindings::fdump_fn())
+            .bind_native_async(SymbolId::from("fread_str"), bindings::fread_str_fn())
+            .bind_native_async(SymbolId::from("fwrite_str"), bindings::fwrite_str_fn())
+            .bind_native_async(SymbolId::from("fappend"), bindings::fappend_fn())
+            .bind_native_async(SymbolId::from("ls_dir"), bindings::ls_dir_fn())
+            .bind_native_async(SymbolId::from("fexists?"), bindings::fexists_fn())
+            .bind_native_async(SymbolId::from("fstat"), bindings::fstat_fn())
+            .bind_native_async(SymbolId::from("mkdir"), bindings::mkdir_fn())
+            .bind_native_async(SymbolId::from("rm"), bindings::rm_fn())
+            .bind_native_async(SymbolId::from("rename"), bindings::rename_fn())
+            .bind_native_async(SymbolId::from("watch_path"), bindings::watch_path_fn())
+            .bind_native_async(SymbolId::from("unwatch_path"), bindings::unwatch_path_fn())
+            .bind_native_async(SymbolId::from("load"), bindings::load_fn())
+            .bind_native_async(SymbolId::from("load_str"), bindings::load_str_fn())
+            .bind_native_async(SymbolId::from("include"), bindings::include_fn());
+        e.define(
+            SymbolId::from("load_path"),
+            Val::List(
+                crate::config_dir()
+                    .map(|d| Val::String(d.to_string_lossy().to_string()))
+                    .into_iter()
+                    .collect(),
+            ),
+        );
+    }
+
+    {
+        e.bind_native_async(SymbolId::from("store_put"), bindings::store_put_fn())
+            .bind_native_async(SymbolId::from("store_get"), bindings::store_get_fn())
+            .bind_native_async(SymbolId::from("store_query"), bindings::store_query_fn())
+            .bind_native_async(SymbolId::from("store_delete"), bindings::store_delete_fn())
+            .bind_native_async(SymbolId::from("store_tx"), bindings::store_tx_fn());
     }
 
     {
         e.bind_native_async(SymbolId::from("exec"), bindings::exec_fn())
-            .bind_native(SymbolId::from("shell_expand"), bindings::shell_expand_fn());
+            .bind_native(SymbolId::from("shell_expand"), bindings::shell_expand_fn())
+            .bind_native_async(SymbolId::from("spawn_cmd"), bindings::spawn_cmd_fn())
+            .bind_native_async(SymbolId::from("port_write"), bindings::port_write_fn())
+            .bind_native_async(SymbolId::from("port_close"), bindings::port_close_fn());
     }
 
     {
@@ -22,6 +22,8 @@ pub struct Registry {
 pub struct RegistryTask {
     weak_tx: mpsc::WeakSender<Cmd>,
     entries: HashMap<KeywordId, Entry>,
+    /// Processes waiting for keyword to be released by process
+    releases: HashMap<KeywordId, Vec<(ProcessId, oneshot::Sender<()>)>>,
 }
 
 /// Identifier for Entries
@@ -79,6 +81,16 @@ impl Registry {
         Ok(resp_rx.await?)
     }
 
+    /// Wait until given process no longer holds registration for name
+    pub async fn released(&self, keyword: KeywordId, pid: ProcessId) -> Result<()> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.tx
+            .send(Cmd::Released(keyword, pid, resp_tx))
+            .await
+            .map_err(|_| Error::NoMessageReceiver("registry task is dead".to_string()))?;
+        Ok(resp_rx.await?)
+    }
+
     /// Get all entries
     pub async fn all(&self) -> Result<Vec<Entry>> {
         let (resp_tx, resp_rx) = oneshot::channel();
@@ -101,6 +113,7 @@ impl RegistryTask {
         Self {
             weak_tx,
             entries: HashMap::new(),
+            releases: HashMap::new(),
         }
     }
 
@@ -112,6 +125,17 @@ impl RegistryTask {
             Cmd::Lookup(keyword, resp_tx) => {
                 let _ = resp_tx.send(self.entries.get(&keyword).cloned());
             }
+            Cmd::Released(keyword, pid, resp_tx) => match self.entries.get(&keyword) {
+                Some(e) if e.pid() == pid => {
+                    self.releases
+                        .entry(keyword)
+                        .or_default()
+                        .push((pid, resp_tx));
+                }
+                _ => {
+                    let _ = resp_tx.send(());
+                }
+            },
             Cmd::NotifyExit(keyword, id, exit) => {
                 self.handle_exit(keyword, id, exit);
             }
@@ -148,6 +172,7 @@ impl RegistryTask {
         });
 
         self.entries.insert(keyword.clone(), entry);
+        self.notify_released(keyword);
 
         Ok(())
     }
@@ -156,6 +181,7 @@ impl RegistryTask {
         match self.entries.get(&keyword) {
             Some(e) if e.id == id => {
                 self.entries.remove(&keyword);
+                self.notify_released(&keyword);
             }
             _ => {
                 error!(
@@ -165,6 +191,24 @@ impl RegistryTask {
             }
         };
     }
+
+    /// Notify processes waiting for keyword to be released, if it is no longer held by process they wait on
+    fn notify_released(&mut self, keyword: &KeywordId) {
+        let waiting = match self.releases.remove(keyword) {
+            Some(waiting) => waiting,
+            None => return,
+        };
+        let holder = self.entries.get(keyword).map(|e| e.pid());
+        let (held, released): (Vec<_>, Vec<_>) = waiting
+            .into_iter()
+            .partition(|(pid, _)| Some(*pid) == holder);
+        for (_, tx) in released {
+            let _ = tx.send(());
+        }
+        if !held.is_empty() {
+            self.releases.insert(keyword.clone(), held);
+        }
+    }
 }
 
 impl Entry {
@@ -247,6 +291,7 @@ impl From<String> for EntryId {
 enum Cmd {
     Register(Registration, ProcessHandle, oneshot::Sender<Result<()>>),
     Lookup(KeywordId, oneshot::Sender<Option<Entry>>),
+    Released(KeywordId, ProcessId, oneshot::Sender<()>),
     NotifyExit(KeywordId, EntryId, Result<ProcessExit>),
     GetAll(oneshot::Sender<Vec<Entry>>),
 }
@@ -365,6 +410,38 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn released_on_proc_exit() {
+        let r = Registry::spawn();
+        let k = kernel::start();
+
+        let prog = Program::from_expr("(recv)").unwrap();
+        let hdl = k.spawn_prog(prog.clone()).await.unwrap();
+
+        r.register(Registration::new(KeywordId::from("A")), hdl.clone())
+            .await
+            .expect("registration should succeed");
+
+        let released = {
+            let r = r.clone();
+            let pid = hdl.id();
+            tokio::spawn(async move { r.released(KeywordId::from("A"), pid).await })
+        };
+        tokio::task::yield_now().await;
+        assert!(!released.is_finished(), "name is held by running process");
+
+        hdl.kill().await;
+        released
+            .await
+            .unwrap()
+            .expect("name should be released after process exits");
+        assert_matches!(r.lookup(KeywordId::from("A")).await.unwrap(), None);
+
+        r.released(KeywordId::from("B"), hdl.id())
+            .await
+            .expect("name that is not registered is released");
+    }
+
     #[tokio::test]
     async fn get_all() {
         let r = Registry::spawn();
@@ -391,7 +468,7 @@ mod tests {
             .await
             .expect("Should be able to retrieve entries")
             .into_iter()
-            .map(|e| (e.registration))
+            .map(|e| e.registration)
             .collect();
         assert!(entries.contains(
             Registration::new(KeywordId::from("A")).interface(vec![Val::keyword("interface_a")])
@@ -408,7 +485,7 @@ mod tests {
             .await
             .expect("Should be able to retrieve entries")
             .into_iter()
-            .map(|e| (e.registration.keyword))
+            .map(|e| e.registration.keyword)
             .collect();
         assert!(
             !entries.contains(&KeywordId::from("A")),
@@ -1,5 +1,5 @@
 //! Runtime
-use super::kernel::{self, KernelHandle};
+use super::kernel::{self, Config, KernelHandle};
 use crate::rt::{ProcessHandle, Result};
 use crate::{Connection, Program};
 
@@ -15,6 +15,12 @@ impl Runtime {
         Self { kernel_task }
     }
 
+    /// Create new runtime instance with given configuration
+    pub fn with_config(config: Config) -> Self {
+        let kernel_task = kernel::start_with_config(config);
+        Self { kernel_task }
+    }
+
     /// Notify the runtime of new connection to handle
     pub async fn handle_conn(&self, conn: Connection) -> Result<ProcessHandle> {
         self.kernel_task.spawn_for_conn(conn).await
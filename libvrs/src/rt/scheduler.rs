@@ -0,0 +1,430 @@
+//! Scheduled Jobs
+//! Scheduler spawns a new process for each job when its schedule fires. Job definitions are
+//! persisted as JSON when scheduler has a store, so jobs survive runtime restarts.
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time;
+use tracing::{debug, error, info};
+
+use super::bindings;
+use super::cron::Cron;
+use super::kernel::WeakKernelHandle;
+use super::program::Val;
+use crate::rt::{Error, Result};
+use crate::Program;
+
+/// Longest time scheduler sleeps before checking wall clock again, in case clock changes or
+/// machine is suspended
+const MAX_SLEEP: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Handle to [Scheduler] task
+#[derive(Debug, Clone)]
+pub(crate) struct SchedulerHandle {
+    tx: mpsc::Sender<Cmd>,
+}
+
+/// Schedule for a job
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Schedule {
+    /// Run on each occurrence of cron expression
+    Cron(Cron),
+    /// Run once at given time
+    At(DateTime<Local>),
+}
+
+/// Information on a scheduled job
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct JobInfo {
+    pub(crate) id: u64,
+    pub(crate) schedule: Schedule,
+    pub(crate) code: Val,
+    pub(crate) next: Option<DateTime<Local>>,
+}
+
+/// Task that spawns processes for scheduled jobs
+pub(crate) struct Scheduler {
+    kernel: WeakKernelHandle,
+    store: Option<PathBuf>,
+    jobs: BTreeMap<u64, Job>,
+    next_id: u64,
+}
+
+/// A scheduled job
+#[derive(Debug)]
+struct Job {
+    schedule: Schedule,
+    /// Expression for lambda that is called in spawned process
+    code: Val,
+    /// Next time job fires, if any
+    next: Option<DateTime<Local>>,
+}
+
+/// Persisted definition of a job
+#[derive(Debug, Serialize, Deserialize)]
+struct JobDef {
+    id: u64,
+    #[serde(flatten)]
+    schedule: ScheduleDef,
+    code: String,
+}
+
+/// Persisted schedule of a job
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ScheduleDef {
+    Cron(String),
+    At(i64),
+}
+
+/// Commands between scheduler handle and async task
+#[derive(Debug)]
+enum Cmd {
+    Add(Schedule, Val, oneshot::Sender<Result<u64>>),
+    Remove(u64, oneshot::Sender<bool>),
+    List(oneshot::Sender<Vec<JobInfo>>),
+}
+
+impl SchedulerHandle {
+    /// Schedule a job that calls lambda expression `code` in a new process
+    pub(crate) async fn add(&self, schedule: Schedule, code: Val) -> Result<u64> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(Cmd::Add(schedule, code, tx))
+            .await
+            .map_err(|_| Error::NoScheduler)?;
+        rx.await.map_err(|_| Error::NoScheduler)?
+    }
+
+    /// Remove job with given id. Returns false if there is no such job
+    pub(crate) async fn remove(&self, id: u64) -> Result<bool> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(Cmd::Remove(id, tx))
+            .await
+            .map_err(|_| Error::NoScheduler)?;
+        rx.await.map_err(|_| Error::NoScheduler)
+    }
+
+    /// List scheduled jobs, ordered by id
+    pub(crate) async fn list(&self) -> Result<Vec<JobInfo>> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(Cmd::List(tx))
+            .await
+            .map_err(|_| Error::NoScheduler)?;
+        rx.await.map_err(|_| Error::NoScheduler)
+    }
+}
+
+impl Scheduler {
+    /// Spawn a new scheduler task, loading persisted jobs from store if any
+    pub(crate) fn spawn(kernel: WeakKernelHandle, store: Option<PathBuf>) -> SchedulerHandle {
+        let (tx, mut rx) = mpsc::channel(32);
+        tokio::spawn(async move {
+            let mut scheduler = Scheduler {
+                kernel,
+                store,
+                jobs: BTreeMap::new(),
+                next_id: 0,
+            };
+            scheduler.load().await;
+            loop {
+                let sleep = scheduler
+                    .next_due()
+                    .map(|next| (next - Local::now()).to_std().unwrap_or_default())
+                    .unwrap_or(MAX_SLEEP)
+                    .min(MAX_SLEEP);
+                tokio::select! {
+                    cmd = rx.recv() => match cmd {
+                        Some(cmd) => scheduler.handle_cmd(cmd).await,
+                        None => break,
+                    },
+                    _ = time::sleep(sleep) => scheduler.fire(Local::now()).await,
+                }
+            }
+        });
+        SchedulerHandle { tx }
+    }
+
+    async fn handle_cmd(&mut self, cmd: Cmd) {
+        debug!("scheduler: {:?}", cmd);
+        match cmd {
+            Cmd::Add(schedule, code, tx) => {
+                let id = self.next_id;
+                let res = self.insert(id, schedule, code, Local::now());
+                if res.is_ok() {
+                    self.save().await;
+                }
+                let _ = tx.send(res.map(|_| id));
+            }
+            Cmd::Remove(id, tx) => {
+                let removed = self.jobs.remove(&id).is_some();
+                if removed {
+                    self.save().await;
+                }
+                let _ = tx.send(removed);
+            }
+            Cmd::List(tx) => {
+                let jobs = self
+                    .jobs
+                    .iter()
+                    .map(|(id, job)| JobInfo {
+                        id: *id,
+                        schedule: job.schedule.clone(),
+                        code: job.code.clone(),
+                        next: job.next,
+                    })
+                    .collect();
+                let _ = tx.send(jobs);
+            }
+        }
+    }
+
+    /// Insert job, computing next time it fires after `now`
+    fn insert(
+        &mut self,
+        id: u64,
+        schedule: Schedule,
+        code: Val,
+        now: DateTime<Local>,
+    ) -> Result<()> {
+        let next =
+            match &schedule {
+                Schedule::Cron(cron) => Some(cron.next_after(now).ok_or_else(|| {
+                    Error::InvalidCron(format!("{cron} - expression never occurs"))
+                })?),
+                Schedule::At(at) => Some(*at),
+            };
+        self.jobs.insert(
+            id,
+            Job {
+                schedule,
+                code,
+                next,
+            },
+        );
+        self.next_id = self.next_id.max(id + 1);
+        Ok(())
+    }
+
+    /// Earliest time any job fires
+    fn next_due(&self) -> Option<DateTime<Local>> {
+        self.jobs.values().filter_map(|j| j.next).min()
+    }
+
+    /// Spawn processes for all jobs that are due at `now`
+    async fn fire(&mut self, now: DateTime<Local>) {
+        let mut finished = vec![];
+        for (id, job) in self.jobs.iter_mut() {
+            if job.next.is_none_or(|next| next > now) {
+                continue;
+            }
+            info!("scheduler: running job {id}");
+            if let Err(e) = spawn_job(&self.kernel, &job.code).await {
+                error!("scheduler: failed to spawn job {id} - {e}");
+            }
+            job.next = match &job.schedule {
+                Schedule::Cron(cron) => cron.next_after(now),
+                Schedule::At(_) => None,
+            };
+            if job.next.is_none() {
+                finished.push(*id);
+            }
+        }
+
+        if !finished.is_empty() {
+            for id in finished {
+                self.jobs.remove(&id);
+            }
+            self.save().await;
+        }
+    }
+
+    /// Load jobs from store. Jobs at times that passed while runtime was stopped fire immediately.
+    async fn load(&mut self) {
+        let Some(path) = &self.store else {
+            return;
+        };
+        let contents = match tokio::fs::read_to_string(path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                error!("scheduler: failed to read {} - {e}", path.display());
+                self.store = None;
+                return;
+            }
+        };
+        let defs: Vec<JobDef> = match serde_json::from_str(&contents) {
+            Ok(defs) => defs,
+            Err(e) => {
+                // Do not overwrite jobs that could not be loaded
+                error!("scheduler: failed to parse {} - {e}", path.display());
+                self.store = None;
+                return;
+            }
+        };
+
+        let now = Local::now();
+        for def in defs {
+            let id = def.id;
+            if let Err(e) = def
+                .try_into()
+                .and_then(|(schedule, code)| self.insert(id, schedule, code, now))
+            {
+                error!("scheduler: skipping job {id} - {e}");
+            }
+        }
+        info!("scheduler: loaded {} jobs", self.jobs.len());
+    }
+
+    /// Save job definitions to store, if any
+    async fn save(&self) {
+        let Some(path) = &self.store else {
+            return;
+        };
+        let defs = self
+            .jobs
+            .iter()
+            .map(|(id, job)| JobDef::new(*id, job))
+            .collect::<Vec<_>>();
+        let contents = match serde_json::to_string_pretty(&defs) {
+            Ok(contents) => contents,
+            Err(e) => {
+                error!("scheduler: failed to serialize jobs - {e}");
+                return;
+            }
+        };
+        if let Some(dir) = path.parent() {
+            let _ = tokio::fs::create_dir_all(dir).await;
+        }
+        // Replace store atomically, so crash while writing does not lose persisted jobs
+        if let Err(e) = bindings::write_atomic(path, contents.as_bytes()).await {
+            error!("scheduler: failed to write {} - {e}", path.display());
+        }
+    }
+}
+
+/// Spawn a process calling job's lambda expression
+async fn spawn_job(kernel: &WeakKernelHandle, code: &Val) -> Result<()> {
+    let kernel = kernel.upgrade().ok_or(Error::NoKernel)?;
+    let prog = Program::from_val(Val::List(vec![code.clone()]))?;
+    kernel.spawn_prog(prog).await?;
+    Ok(())
+}
+
+impl JobDef {
+    fn new(id: u64, job: &Job) -> Self {
+        let schedule = match &job.schedule {
+            Schedule::Cron(cron) => ScheduleDef::Cron(cron.to_string()),
+            Schedule::At(at) => ScheduleDef::At(at.timestamp()),
+        };
+        Self {
+            id,
+            schedule,
+            code: job.code.to_string(),
+        }
+    }
+}
+
+impl TryFrom<JobDef> for (Schedule, Val) {
+    type Error = Error;
+
+    fn try_from(def: JobDef) -> Result<Self> {
+        let schedule = match def.schedule {
+            ScheduleDef::Cron(cron) => Schedule::Cron(cron.parse()?),
+            ScheduleDef::At(secs) => Schedule::At(
+                Local
+                    .timestamp_opt(secs, 0)
+                    .single()
+                    .ok_or_else(|| Error::SchedulerError(format!("Invalid timestamp {secs}")))?,
+            ),
+        };
+        let code = lyric::parse(&def.code)?.into();
+        Ok((schedule, code))
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Schedule::Cron(cron) => write!(f, "cron {cron}"),
+            Schedule::At(at) => write!(f, "at {}", at.to_rfc3339()),
+        }
+    }
+}
+
+impl std::cmp::PartialEq for SchedulerHandle {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(&self.tx, &other.tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rt::kernel;
+
+    #[tokio::test]
+    async fn persist_jobs() {
+        let dir = std::env::temp_dir().join(format!("vrs-scheduler-{}", nanoid::nanoid!()));
+        let store = dir.join("jobs.json");
+        let k = kernel::start();
+
+        let scheduler = Scheduler::spawn(k.downgrade(), Some(store.clone()));
+        let code: Val = lyric::parse("(lambda () :hello)").unwrap().into();
+        let cron = scheduler
+            .add(Schedule::Cron("0 9 * * 1-5".parse().unwrap()), code.clone())
+            .await
+            .unwrap();
+        let at = Local::now() + chrono::Duration::days(1);
+        scheduler.add(Schedule::At(at), code.clone()).await.unwrap();
+        assert!(scheduler.remove(cron).await.unwrap());
+        assert!(!scheduler.remove(cron).await.unwrap());
+
+        // Restarted scheduler loads remaining job
+        let scheduler = Scheduler::spawn(k.downgrade(), Some(store.clone()));
+        let jobs = scheduler.list().await.unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, 1);
+        assert_eq!(jobs[0].code, code);
+        assert_eq!(
+            jobs[0].schedule,
+            Schedule::At(Local.timestamp_opt(at.timestamp(), 0).unwrap())
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn fire_at() {
+        let k = kernel::start();
+        let scheduler = Scheduler::spawn(k.downgrade(), None);
+
+        let code: Val = lyric::parse("(lambda () (loop (sleep 1)))").unwrap().into();
+        scheduler
+            .add(Schedule::At(Local::now()), code)
+            .await
+            .unwrap();
+
+        let procs = time::timeout(std::time::Duration::from_secs(1), async {
+            loop {
+                let procs = k.procs().await.unwrap();
+                if !procs.is_empty() {
+                    break procs;
+                }
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("job should spawn a process");
+        assert_eq!(procs.len(), 1);
+        assert!(
+            scheduler.list().await.unwrap().is_empty(),
+            "jobs at a time should be removed after firing"
+        );
+    }
+}
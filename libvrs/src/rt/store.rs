@@ -0,0 +1,443 @@
+//! Embedded Store
+//! Forms are stored in SQLite, keyed by collection and id. Queries match forms on values of
+//! keyword fields, in property lists like `(:title "Title" :done false)` or maps.
+//! SQLite calls block, so store runs on its own thread.
+use std::path::PathBuf;
+
+use lyric::{parse, Form, KeywordId, MapKey};
+use rusqlite::{params, Connection, OptionalExtension};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, error};
+
+use crate::rt::{Error, Result};
+
+/// Handle to [Store] task
+#[derive(Debug, Clone)]
+pub(crate) struct StoreHandle {
+    tx: mpsc::Sender<Cmd>,
+}
+
+/// Task owning connection to SQLite database
+pub(crate) struct Store {
+    conn: Result<Connection>,
+}
+
+/// An update to store, applied within a transaction
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Op {
+    /// Insert or replace form with id in collection
+    Put {
+        collection: KeywordId,
+        id: Form,
+        form: Form,
+    },
+    /// Delete form with id in collection
+    Delete { collection: KeywordId, id: Form },
+    /// Fail transaction unless form with id in collection is given form, or is missing if none
+    Expect {
+        collection: KeywordId,
+        id: Form,
+        form: Option<Form>,
+    },
+}
+
+/// Commands between store handle and task
+#[derive(Debug)]
+enum Cmd {
+    Get {
+        collection: KeywordId,
+        id: Form,
+        resp_tx: oneshot::Sender<Result<Option<Form>>>,
+    },
+    Query {
+        collection: KeywordId,
+        fields: Vec<(KeywordId, Form)>,
+        resp_tx: oneshot::Sender<Result<Vec<Form>>>,
+    },
+    Transact(Vec<Op>, oneshot::Sender<Result<Vec<bool>>>),
+}
+
+impl StoreHandle {
+    /// Get form with id in collection, if any
+    pub(crate) async fn get(&self, collection: KeywordId, id: Form) -> Result<Option<Form>> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.send(Cmd::Get {
+            collection,
+            id,
+            resp_tx,
+        })
+        .await?;
+        resp_rx.await.map_err(|_| Error::NoStore)?
+    }
+
+    /// Forms in collection with given values for each field, in order of insertion
+    pub(crate) async fn query(
+        &self,
+        collection: KeywordId,
+        fields: Vec<(KeywordId, Form)>,
+    ) -> Result<Vec<Form>> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.send(Cmd::Query {
+            collection,
+            fields,
+            resp_tx,
+        })
+        .await?;
+        resp_rx.await.map_err(|_| Error::NoStore)?
+    }
+
+    /// Insert or replace form with id in collection
+    pub(crate) async fn put(&self, collection: KeywordId, id: Form, form: Form) -> Result<()> {
+        self.transact(vec![Op::Put {
+            collection,
+            id,
+            form,
+        }])
+        .await?;
+        Ok(())
+    }
+
+    /// Delete form with id in collection. Returns false if there is no such form
+    pub(crate) async fn delete(&self, collection: KeywordId, id: Form) -> Result<bool> {
+        let res = self.transact(vec![Op::Delete { collection, id }]).await?;
+        Ok(res.first().copied().unwrap_or_default())
+    }
+
+    /// Apply all updates, or none if any update fails. Returns whether each update changed store
+    pub(crate) async fn transact(&self, ops: Vec<Op>) -> Result<Vec<bool>> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.send(Cmd::Transact(ops, resp_tx)).await?;
+        resp_rx.await.map_err(|_| Error::NoStore)?
+    }
+
+    async fn send(&self, cmd: Cmd) -> Result<()> {
+        self.tx.send(cmd).await.map_err(|_| Error::NoStore)
+    }
+}
+
+impl Store {
+    /// Spawn a new store task, backed by SQLite database at path, or in memory if missing
+    pub(crate) fn spawn(path: Option<PathBuf>) -> StoreHandle {
+        let (tx, mut rx) = mpsc::channel(32);
+        std::thread::Builder::new()
+            .name("vrs-store".to_string())
+            .spawn(move || {
+                let mut store = Store {
+                    conn: Self::open(path),
+                };
+                if let Err(e) = &store.conn {
+                    error!("store: failed to open - {e}");
+                }
+                while let Some(cmd) = rx.blocking_recv() {
+                    store.handle_cmd(cmd);
+                }
+            })
+            .expect("failed to spawn store thread");
+        StoreHandle { tx }
+    }
+
+    fn open(path: Option<PathBuf>) -> Result<Connection> {
+        let conn = match path {
+            Some(path) => {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir).map_err(|e| Error::IOError(format!("{e}")))?;
+                }
+                Connection::open(path)
+            }
+            None => Connection::open_in_memory(),
+        }
+        .map_err(store_err)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS entities (
+                collection TEXT NOT NULL,
+                id TEXT NOT NULL,
+                form TEXT NOT NULL,
+                PRIMARY KEY (collection, id)
+            )",
+            (),
+        )
+        .map_err(store_err)?;
+        Ok(conn)
+    }
+
+    fn handle_cmd(&mut self, cmd: Cmd) {
+        debug!("store: {:?}", cmd);
+        match cmd {
+            Cmd::Get {
+                collection,
+                id,
+                resp_tx,
+            } => {
+                let _ = resp_tx.send(self.get(&collection, &id));
+            }
+            Cmd::Query {
+                collection,
+                fields,
+                resp_tx,
+            } => {
+                let _ = resp_tx.send(self.query(&collection, &fields));
+            }
+            Cmd::Transact(ops, resp_tx) => {
+                let _ = resp_tx.send(self.transact(&ops));
+            }
+        }
+    }
+
+    fn get(&mut self, collection: &KeywordId, id: &Form) -> Result<Option<Form>> {
+        let form = self
+            .conn()?
+            .query_row(
+                "SELECT form FROM entities WHERE collection = ?1 AND id = ?2",
+                params![collection.as_str(), id.to_string()],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .map_err(store_err)?;
+        form.map(|f| parse_form(&f)).transpose()
+    }
+
+    fn query(&mut self, collection: &KeywordId, fields: &[(KeywordId, Form)]) -> Result<Vec<Form>> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT form FROM entities WHERE collection = ?1 ORDER BY rowid")
+            .map_err(store_err)?;
+        let rows = stmt
+            .query_map(params![collection.as_str()], |row| row.get::<_, String>(0))
+            .map_err(store_err)?;
+
+        let mut forms = vec![];
+        for row in rows {
+            let form = parse_form(&row.map_err(store_err)?)?;
+            if fields
+                .iter()
+                .all(|(key, val)| field(&form, key) == Some(val))
+            {
+                forms.push(form);
+            }
+        }
+        Ok(forms)
+    }
+
+    fn transact(&mut self, ops: &[Op]) -> Result<Vec<bool>> {
+        let conn = self.conn()?;
+        let tx = conn.transaction().map_err(store_err)?;
+        let mut res = vec![];
+        for op in ops {
+            let changed = match op {
+                Op::Put {
+                    collection,
+                    id,
+                    form,
+                } => tx.execute(
+                    "INSERT INTO entities (collection, id, form) VALUES (?1, ?2, ?3)
+                     ON CONFLICT (collection, id) DO UPDATE SET form = excluded.form",
+                    params![collection.as_str(), id.to_string(), form.to_string()],
+                ),
+                Op::Delete { collection, id } => tx.execute(
+                    "DELETE FROM entities WHERE collection = ?1 AND id = ?2",
+                    params![collection.as_str(), id.to_string()],
+                ),
+                Op::Expect {
+                    collection,
+                    id,
+                    form,
+                } => {
+                    let cur = tx
+                        .query_row(
+                            "SELECT form FROM entities WHERE collection = ?1 AND id = ?2",
+                            params![collection.as_str(), id.to_string()],
+                            |row| row.get::<_, String>(0),
+                        )
+                        .optional()
+                        .map_err(store_err)?
+                        .map(|f| parse_form(&f))
+                        .transpose()?;
+                    if cur != *form {
+                        // Dropping transaction rolls back earlier updates
+                        return Err(Error::StoreConflict(format!("{collection} {id}")));
+                    }
+                    Ok(0)
+                }
+            }
+            .map_err(store_err)?;
+            res.push(changed > 0);
+        }
+        tx.commit().map_err(store_err)?;
+        Ok(res)
+    }
+
+    fn conn(&mut self) -> Result<&mut Connection> {
+        self.conn.as_mut().map_err(|e| e.clone())
+    }
+}
+
+/// Value of keyword field in property list or map
+fn field<'a>(form: &'a Form, key: &KeywordId) -> Option<&'a Form> {
+    match form {
+        Form::List(items) => items
+            .windows(2)
+            .find(|w| matches!(&w[0], Form::Keyword(k) if k == key))
+            .map(|w| &w[1]),
+        Form::Map(entries) => entries
+            .iter()
+            .find(|(k, _)| matches!(k, MapKey::Keyword(k) if k == key))
+            .map(|(_, v)| v),
+        _ => None,
+    }
+}
+
+fn parse_form(s: &str) -> Result<Form> {
+    parse(s).map_err(|e| Error::StoreError(format!("Failed to parse stored form - {e}")))
+}
+
+fn store_err(e: rusqlite::Error) -> Error {
+    Error::StoreError(format!("{e}"))
+}
+
+impl std::cmp::PartialEq for StoreHandle {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(&self.tx, &other.tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn todo(id: i64, title: &str, done: bool) -> Form {
+        Form::List(vec![
+            Form::Keyword(KeywordId::from("id")),
+            Form::Int(id),
+            Form::Keyword(KeywordId::from("title")),
+            Form::string(title),
+            Form::Keyword(KeywordId::from("done")),
+            Form::Bool(done),
+        ])
+    }
+
+    #[tokio::test]
+    async fn put_get_delete() {
+        let store = Store::spawn(None);
+        let todos = KeywordId::from("todos");
+
+        store
+            .put(todos.clone(), Form::Int(0), todo(0, "Buy milk", false))
+            .await
+            .unwrap();
+        store
+            .put(todos.clone(), Form::Int(0), todo(0, "Buy milk", true))
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get(todos.clone(), Form::Int(0)).await.unwrap(),
+            Some(todo(0, "Buy milk", true))
+        );
+        assert_eq!(
+            store
+                .get(KeywordId::from("rlist"), Form::Int(0))
+                .await
+                .unwrap(),
+            None,
+            "collections should not share ids"
+        );
+
+        assert!(store.delete(todos.clone(), Form::Int(0)).await.unwrap());
+        assert!(!store.delete(todos.clone(), Form::Int(0)).await.unwrap());
+        assert_eq!(store.get(todos, Form::Int(0)).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn query_fields() {
+        let store = Store::spawn(None);
+        let todos = KeywordId::from("todos");
+        for (id, title, done) in [(2, "b", false), (1, "a", true), (3, "c", false)] {
+            store
+                .put(todos.clone(), Form::Int(id), todo(id, title, done))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            store
+                .query(
+                    todos.clone(),
+                    vec![(KeywordId::from("done"), Form::Bool(false))]
+                )
+                .await
+                .unwrap(),
+            vec![todo(2, "b", false), todo(3, "c", false)],
+            "forms should be in insertion order"
+        );
+        assert_eq!(store.query(todos, vec![]).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn transaction_conflict() {
+        let store = Store::spawn(None);
+        let todos = KeywordId::from("todos");
+        store
+            .put(todos.clone(), Form::Int(0), todo(0, "a", false))
+            .await
+            .unwrap();
+
+        let res = store
+            .transact(vec![
+                Op::Delete {
+                    collection: todos.clone(),
+                    id: Form::Int(0),
+                },
+                Op::Expect {
+                    collection: todos.clone(),
+                    id: Form::Int(0),
+                    form: Some(todo(0, "a", false)),
+                },
+            ])
+            .await;
+        assert_matches!(res, Err(Error::StoreConflict(_)));
+        assert_eq!(
+            store.get(todos.clone(), Form::Int(0)).await.unwrap(),
+            Some(todo(0, "a", false)),
+            "updates before failed expectation should be rolled back"
+        );
+
+        let res = store
+            .transact(vec![
+                Op::Expect {
+                    collection: todos.clone(),
+                    id: Form::Int(0),
+                    form: Some(todo(0, "a", false)),
+                },
+                Op::Put {
+                    collection: todos.clone(),
+                    id: Form::Int(0),
+                    form: todo(0, "a", true),
+                },
+            ])
+            .await
+            .unwrap();
+        assert_eq!(res, vec![false, true]);
+    }
+
+    #[tokio::test]
+    async fn persist() {
+        let path = std::env::temp_dir().join(format!("vrs-store-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let todos = KeywordId::from("todos");
+
+        let store = Store::spawn(Some(path.clone()));
+        store
+            .put(todos.clone(), Form::string("a"), todo(0, "a", false))
+            .await
+            .unwrap();
+        drop(store);
+
+        let store = Store::spawn(Some(path.clone()));
+        assert_eq!(
+            store.get(todos, Form::string("a")).await.unwrap(),
+            Some(todo(0, "a", false))
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,348 @@
+//! Process Supervisor
+//! Supervisors own a list of child specs, and restart children according to a restart strategy
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use lyric::{kwargs, KeywordId};
+use tracing::{debug, info};
+
+use super::kernel::{KernelHandle, WeakKernelHandle};
+use super::mailbox::MailboxHandle;
+use super::program::{Extern, Lambda, Pattern, Val};
+use super::registry::Registry;
+use super::{Error, ProcessId, Result};
+use crate::Program;
+
+/// Default maximum number of restarts within [DEFAULT_PERIOD]
+pub(crate) const DEFAULT_INTENSITY: usize = 3;
+
+/// Default period for restart intensity
+pub(crate) const DEFAULT_PERIOD: Duration = Duration::from_secs(5);
+
+/// Specification for child processes of a supervisor
+#[derive(Debug, Clone)]
+pub(crate) struct ChildSpec {
+    /// Zero-arity lambda run by child process
+    start: Lambda,
+    /// Service name of child, if any
+    name: Option<KeywordId>,
+    /// When the child should be restarted
+    restart: Restart,
+}
+
+/// Restart policy for a child
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Restart {
+    /// Child is always restarted
+    Permanent,
+    /// Child is restarted only if it exits abnormally
+    Transient,
+    /// Child is never restarted
+    Temporary,
+}
+
+/// Which children are restarted when a child is restarted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Strategy {
+    /// Only the exited child is restarted
+    OneForOne,
+    /// All children are restarted
+    OneForAll,
+    /// The exited child, and children started after it are restarted
+    RestForOne,
+}
+
+/// Supervisor state
+#[derive(Debug)]
+pub(crate) struct Supervisor {
+    /// Own process id
+    pid: ProcessId,
+    /// Kernel used to spawn and terminate children
+    kernel: WeakKernelHandle,
+    /// Own mailbox, where exit signals of children are received
+    mailbox: MailboxHandle,
+    /// Registry used to wait for service names of exited children to be released
+    registry: Registry,
+    /// Child specs and pids for running children, in start order
+    children: Vec<(ChildSpec, Option<ProcessId>)>,
+    strategy: Strategy,
+    intensity: usize,
+    period: Duration,
+    /// Time of restarts within period
+    restarts: VecDeque<Instant>,
+}
+
+impl ChildSpec {
+    pub(crate) fn new(start: Lambda) -> Self {
+        Self {
+            start,
+            name: None,
+            restart: Restart::Permanent,
+        }
+    }
+
+    pub(crate) fn name(mut self, name: KeywordId) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    pub(crate) fn restart(mut self, restart: Restart) -> Self {
+        self.restart = restart;
+        self
+    }
+
+    /// Whether or not child should be restarted after exiting with given reason
+    fn should_restart(&self, reason: &Val) -> bool {
+        match self.restart {
+            Restart::Permanent => true,
+            Restart::Transient => *reason != Val::keyword("normal"),
+            Restart::Temporary => false,
+        }
+    }
+}
+
+impl TryFrom<Val> for ChildSpec {
+    type Error = lyric::Error;
+
+    /// Child specs are either zero-arity lambdas, or keyword lists of the form
+    /// `(:start LAMBDA [:name SVC_NAME] [:restart RESTART])`
+    fn try_from(value: Val) -> lyric::Result<Self> {
+        let spec = match value {
+            Val::Lambda(start) => return Ok(ChildSpec::new(start)),
+            Val::List(spec) => spec,
+            _ => {
+                return Err(lyric::Error::UnexpectedArguments(format!(
+                    "Child spec should be a lambda or keyword list - found {value}"
+                )))
+            }
+        };
+
+        let mut child = match kwargs::get(&spec, &KeywordId::from("start")) {
+            Some(Val::Lambda(start)) => ChildSpec::new(start),
+            _ => {
+                return Err(lyric::Error::UnexpectedArguments(
+                    "Child spec requires :start lambda".to_string(),
+                ))
+            }
+        };
+
+        match kwargs::get(&spec, &KeywordId::from("name")) {
+            Some(Val::Keyword(name)) => child = child.name(name),
+            Some(v) => {
+                return Err(lyric::Error::UnexpectedArguments(format!(
+                    ":name should be a keyword - found {v}"
+                )))
+            }
+            None => (),
+        }
+
+        if let Some(restart) = kwargs::get(&spec, &KeywordId::from("restart")) {
+            child = child.restart(Restart::try_from(restart)?);
+        }
+
+        Ok(child)
+    }
+}
+
+impl TryFrom<Val> for Restart {
+    type Error = lyric::Error;
+
+    fn try_from(value: Val) -> lyric::Result<Self> {
+        match value {
+            Val::Keyword(k) if k.as_str() == "permanent" => Ok(Restart::Permanent),
+            Val::Keyword(k) if k.as_str() == "transient" => Ok(Restart::Transient),
+            Val::Keyword(k) if k.as_str() == "temporary" => Ok(Restart::Temporary),
+            _ => Err(lyric::Error::UnexpectedArguments(format!(
+                "Restart should be one of :permanent, :transient, or :temporary - found {value}"
+            ))),
+        }
+    }
+}
+
+impl TryFrom<Val> for Strategy {
+    type Error = lyric::Error;
+
+    fn try_from(value: Val) -> lyric::Result<Self> {
+        match value {
+            Val::Keyword(k) if k.as_str() == "one_for_one" => Ok(Strategy::OneForOne),
+            Val::Keyword(k) if k.as_str() == "one_for_all" => Ok(Strategy::OneForAll),
+            Val::Keyword(k) if k.as_str() == "rest_for_one" => Ok(Strategy::RestForOne),
+            _ => Err(lyric::Error::UnexpectedArguments(format!(
+                "Strategy should be one of :one_for_one, :one_for_all, or :rest_for_one - found {value}"
+            ))),
+        }
+    }
+}
+
+impl Supervisor {
+    pub(crate) fn new(
+        pid: ProcessId,
+        kernel: WeakKernelHandle,
+        mailbox: MailboxHandle,
+        registry: Registry,
+        children: Vec<ChildSpec>,
+    ) -> Self {
+        Self {
+            pid,
+            kernel,
+            mailbox,
+            registry,
+            children: children.into_iter().map(|c| (c, None)).collect(),
+            strategy: Strategy::OneForOne,
+            intensity: DEFAULT_INTENSITY,
+            period: DEFAULT_PERIOD,
+            restarts: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Allow at most `intensity` restarts within `period`
+    pub(crate) fn intensity(mut self, intensity: usize, period: Duration) -> Self {
+        self.intensity = intensity;
+        self.period = period;
+        self
+    }
+
+    /// Start all children, then supervise them.
+    /// Returns error when maximum restart intensity is reached, which terminates linked children.
+    pub(crate) async fn run(mut self) -> Result<()> {
+        self.kernel()?.trap_exit(self.pid, true).await?;
+
+        for idx in 0..self.children.len() {
+            self.start_child(idx).await?;
+        }
+
+        loop {
+            let (pid, reason) = self.recv_exit(None).await?;
+            let idx = match self.children.iter().position(|(_, p)| *p == Some(pid)) {
+                Some(idx) => idx,
+                None if reason == Val::keyword("normal") => continue,
+                None => {
+                    // linked process that is not a child, e.g. parent of supervisor, exited abnormally
+                    info!(
+                        "supervisor {} - linked process {pid} exited - {reason}",
+                        self.pid
+                    );
+                    for i in (0..self.children.len()).rev() {
+                        self.terminate_child(i).await?;
+                    }
+                    return Err(Error::SupervisorError(format!(
+                        "Linked process {pid} exited - {reason}"
+                    )));
+                }
+            };
+            self.children[idx].1 = None;
+            self.release_name(idx, pid).await?;
+
+            let (spec, _) = &self.children[idx];
+            debug!("supervisor {} - child {pid} exited - {reason}", self.pid);
+            if !spec.should_restart(&reason) {
+                continue;
+            }
+
+            if !self.record_restart() {
+                info!(
+                    "supervisor {} - reached max restart intensity of {} in {:?}",
+                    self.pid, self.intensity, self.period
+                );
+                return Err(Error::SupervisorError(format!(
+                    "Reached max restart intensity of {} restarts in {:?}",
+                    self.intensity, self.period
+                )));
+            }
+
+            let restart = match self.strategy {
+                Strategy::OneForOne => idx..idx + 1,
+                Strategy::OneForAll => 0..self.children.len(),
+                Strategy::RestForOne => idx..self.children.len(),
+            };
+
+            for i in restart.clone().rev() {
+                self.terminate_child(i).await?;
+            }
+            for i in restart {
+                if i == idx || self.children[i].0.restart != Restart::Temporary {
+                    self.start_child(i).await?;
+                }
+            }
+        }
+    }
+
+    /// Record a restart, returning false if restart exceeds max restart intensity
+    fn record_restart(&mut self) -> bool {
+        let now = Instant::now();
+        self.restarts.push_back(now);
+        while let Some(t) = self.restarts.front() {
+            if now.duration_since(*t) > self.period {
+                self.restarts.pop_front();
+            } else {
+                break;
+            }
+        }
+        self.restarts.len() <= self.intensity
+    }
+
+    fn kernel(&self) -> Result<KernelHandle> {
+        self.kernel.upgrade().ok_or(Error::NoKernel)
+    }
+
+    /// Start child at index
+    async fn start_child(&mut self, idx: usize) -> Result<()> {
+        let (spec, _) = &self.children[idx];
+        let prog = Program::from_lambda(spec.start.clone())?;
+        let hdl = self.kernel()?.spawn_prog_linked(prog, self.pid).await?;
+        debug!("supervisor {} - started child {}", self.pid, hdl.id());
+        self.children[idx].1 = Some(hdl.id());
+        Ok(())
+    }
+
+    /// Terminate child at index if running, waiting for its service name to be released
+    async fn terminate_child(&mut self, idx: usize) -> Result<()> {
+        if let Some(pid) = self.children[idx].1.take() {
+            match self.kernel()?.kill_proc(pid).await {
+                // child exited before it was killed, but its exit signal is still delivered
+                Ok(()) | Err(Error::UnknownProcess) => (),
+                Err(e) => return Err(e),
+            }
+            self.recv_exit(Some(pid)).await?;
+            self.release_name(idx, pid).await?;
+        }
+        Ok(())
+    }
+
+    /// Receive the next exit signal, optionally for specific process
+    async fn recv_exit(&mut self, pid: Option<ProcessId>) -> Result<(ProcessId, Val)> {
+        let pid_pat = match pid {
+            Some(pid) => Val::Extern(Extern::ProcessId(pid)),
+            None => Val::symbol("_"),
+        };
+        let pat = Pattern::from_val(Val::List(vec![
+            Val::keyword("exit"),
+            pid_pat,
+            Val::symbol("_"),
+        ]));
+        let msg = self.mailbox.poll(Some(pat)).await?;
+        let (pid, reason) = match msg.contents {
+            Val::List(l) => match &l[..] {
+                [_, Val::Extern(Extern::ProcessId(pid)), reason] => (*pid, reason.clone()),
+                _ => return Err(Error::UnexpectedProcessResult),
+            },
+            _ => return Err(Error::UnexpectedProcessResult),
+        };
+
+        Ok((pid, reason))
+    }
+
+    /// Wait until service name of child is released from registry by exited process, so the
+    /// restarted child can register under same name
+    async fn release_name(&self, idx: usize, pid: ProcessId) -> Result<()> {
+        match &self.children[idx].0.name {
+            Some(name) => self.registry.released(name.clone(), pid).await,
+            None => Ok(()),
+        }
+    }
+}
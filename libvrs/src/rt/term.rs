@@ -303,7 +303,7 @@ mod tests {
                 client
                     .send_req(Request {
                         id: i,
-                        contents: Form::Int(i.try_into().unwrap()),
+                        contents: Form::Int(i.into()),
                     })
                     .await
                     .unwrap();
@@ -326,7 +326,7 @@ mod tests {
         let expected = (0..5)
             .map(|i| Request {
                 id: i,
-                contents: Form::Int(i.try_into().unwrap()),
+                contents: Form::Int(i.into()),
             })
             .collect::<Vec<_>>();
         assert_eq!(reqs, expected, "Requests are returned in order");
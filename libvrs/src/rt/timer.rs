@@ -0,0 +1,268 @@
+//! Runtime Timers
+//! A single task tracks deadlines of all timers in runtime, instead of a task per sleeping process
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use lyric::Ref;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{self, Instant};
+use tracing::debug;
+
+use super::mailbox::Message;
+use super::proc::ProcessHandle;
+use crate::rt::{Error, Result};
+
+/// Handle to [Timers] task
+#[derive(Debug, Clone)]
+pub(crate) struct TimerHandle {
+    tx: mpsc::Sender<Cmd>,
+}
+
+/// Task managing all timers, ordered by deadline
+#[derive(Debug, Default)]
+pub(crate) struct Timers {
+    /// Timers keyed by deadline, and sequence number for timers with same deadline
+    queue: BTreeMap<(Instant, u64), Timer>,
+    /// Key in queue of each cancellable timer
+    keys: HashMap<Ref, (Instant, u64)>,
+    next_seq: u64,
+}
+
+/// A timer in [Timers]
+#[derive(Debug)]
+enum Timer {
+    /// Wake a sleeping task
+    Wake(oneshot::Sender<()>),
+    /// Deliver message to process, repeating after interval if any
+    Deliver {
+        id: Ref,
+        dst: ProcessHandle,
+        msg: Message,
+        interval: Option<Duration>,
+    },
+}
+
+/// Commands between timer handle and async task
+#[derive(Debug)]
+enum Cmd {
+    Sleep(Duration, oneshot::Sender<()>),
+    Start {
+        id: Ref,
+        after: Duration,
+        interval: Option<Duration>,
+        dst: ProcessHandle,
+        msg: Message,
+    },
+    Cancel(Ref, oneshot::Sender<bool>),
+}
+
+impl TimerHandle {
+    /// Sleep calling task for given duration
+    pub(crate) async fn sleep(&self, duration: Duration) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(Cmd::Sleep(duration, tx))
+            .await
+            .map_err(|_| Error::NoTimers)?;
+        rx.await.map_err(|_| Error::NoTimers)
+    }
+
+    /// Deliver message to process after given duration. Returns reference for cancelling timer
+    pub(crate) async fn send_after(
+        &self,
+        after: Duration,
+        dst: ProcessHandle,
+        msg: Message,
+    ) -> Result<Ref> {
+        self.start(after, None, dst, msg).await
+    }
+
+    /// Deliver message to process periodically. Returns reference for cancelling timer
+    pub(crate) async fn interval(
+        &self,
+        period: Duration,
+        dst: ProcessHandle,
+        msg: Message,
+    ) -> Result<Ref> {
+        self.start(period, Some(period), dst, msg).await
+    }
+
+    /// Cancel timer with given reference. Returns false if timer already fired or was cancelled
+    pub(crate) async fn cancel(&self, id: Ref) -> Result<bool> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(Cmd::Cancel(id, tx))
+            .await
+            .map_err(|_| Error::NoTimers)?;
+        rx.await.map_err(|_| Error::NoTimers)
+    }
+
+    async fn start(
+        &self,
+        after: Duration,
+        interval: Option<Duration>,
+        dst: ProcessHandle,
+        msg: Message,
+    ) -> Result<Ref> {
+        let id = Ref::new();
+        self.tx
+            .send(Cmd::Start {
+                id: id.clone(),
+                after,
+                interval,
+                dst,
+                msg,
+            })
+            .await
+            .map_err(|_| Error::NoTimers)?;
+        Ok(id)
+    }
+}
+
+impl Timers {
+    /// Spawn a new timers task
+    pub(crate) fn spawn() -> TimerHandle {
+        let (tx, mut rx) = mpsc::channel(32);
+        tokio::spawn(async move {
+            let mut timers = Timers::default();
+            loop {
+                let next = timers.queue.keys().next().map(|(deadline, _)| *deadline);
+                tokio::select! {
+                    cmd = rx.recv() => match cmd {
+                        Some(cmd) => timers.handle_cmd(cmd),
+                        None => break,
+                    },
+                    _ = sleep_until(next) => timers.fire(Instant::now()).await,
+                }
+            }
+        });
+        TimerHandle { tx }
+    }
+
+    fn handle_cmd(&mut self, cmd: Cmd) {
+        debug!("timers: {:?}", cmd);
+        let now = Instant::now();
+        match cmd {
+            Cmd::Sleep(duration, tx) => self.insert(now + duration, Timer::Wake(tx)),
+            Cmd::Start {
+                id,
+                after,
+                interval,
+                dst,
+                msg,
+            } => self.insert(
+                now + after,
+                Timer::Deliver {
+                    id,
+                    dst,
+                    msg,
+                    interval,
+                },
+            ),
+            Cmd::Cancel(id, tx) => {
+                let cancelled = match self.keys.remove(&id) {
+                    Some(key) => self.queue.remove(&key).is_some(),
+                    None => false,
+                };
+                let _ = tx.send(cancelled);
+            }
+        }
+    }
+
+    fn insert(&mut self, deadline: Instant, timer: Timer) {
+        let key = (deadline, self.next_seq);
+        self.next_seq = self.next_seq.wrapping_add(1);
+        if let Timer::Deliver { id, .. } = &timer {
+            self.keys.insert(id.clone(), key);
+        }
+        self.queue.insert(key, timer);
+    }
+
+    /// Fire all timers with deadlines up to `now`
+    async fn fire(&mut self, now: Instant) {
+        while let Some(entry) = self.queue.first_entry() {
+            let (deadline, _) = *entry.key();
+            if deadline > now {
+                break;
+            }
+            match entry.remove() {
+                Timer::Wake(tx) => {
+                    let _ = tx.send(());
+                }
+                Timer::Deliver {
+                    id,
+                    dst,
+                    msg,
+                    interval,
+                } => {
+                    self.keys.remove(&id);
+                    if !dst.is_running() {
+                        continue;
+                    }
+                    dst.notify_message(msg.clone()).await;
+                    if let Some(period) = interval {
+                        // Skip missed intervals instead of delivering bursts of messages
+                        let next = (deadline + period).max(now);
+                        self.insert(
+                            next,
+                            Timer::Deliver {
+                                id,
+                                dst,
+                                msg,
+                                interval,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Sleep until deadline, or forever if there is no deadline
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+impl std::cmp::PartialEq for TimerHandle {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(&self.tx, &other.tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sleep_in_deadline_order() {
+        let timers = Timers::spawn();
+        let start = Instant::now();
+
+        let (tx, mut rx) = mpsc::channel(3);
+        for ms in [30, 10, 20] {
+            let timers = timers.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                timers.sleep(Duration::from_millis(ms)).await.unwrap();
+                tx.send(ms).await.unwrap();
+            });
+        }
+
+        let mut woken = vec![];
+        for _ in 0..3 {
+            woken.push(rx.recv().await.unwrap());
+        }
+        assert_eq!(woken, vec![10, 20, 30]);
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[tokio::test]
+    async fn cancel_unknown_timer() {
+        let timers = Timers::spawn();
+        assert!(!timers.cancel(Ref::new()).await.unwrap());
+    }
+}
@@ -0,0 +1,245 @@
+//! File System Watchers
+//! A single task owns file system watchers of all processes, delivering changes as
+//! `(:fs_event PATH KIND)` messages to owning process, where KIND is `:created`, `:modified`, or `:removed`.
+//! Watchers are removed when owning process exits.
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use lyric::Ref;
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+
+use super::mailbox::Message;
+use super::proc::ProcessHandle;
+use super::program::Val;
+use crate::rt::{Error, Result};
+
+/// Handle to [Watchers] task
+#[derive(Debug, Clone)]
+pub(crate) struct WatcherHandle {
+    tx: mpsc::Sender<Cmd>,
+}
+
+/// Task managing all file system watchers
+#[derive(Debug)]
+pub(crate) struct Watchers {
+    watches: HashMap<Ref, Watch>,
+    /// Sender for events from watchers, which run callbacks outside of tokio runtime
+    events_tx: mpsc::UnboundedSender<(Ref, notify::Result<Event>)>,
+    /// Sender for commands from task, which does not keep task alive
+    cmd_tx: mpsc::WeakSender<Cmd>,
+}
+
+/// A watcher owned by a process
+#[derive(Debug)]
+struct Watch {
+    owner: ProcessHandle,
+    /// Watcher stops watching when dropped
+    _watcher: RecommendedWatcher,
+}
+
+/// Commands between watcher handle and async task
+#[derive(Debug)]
+enum Cmd {
+    Watch {
+        path: PathBuf,
+        recursive: bool,
+        owner: ProcessHandle,
+        resp_tx: oneshot::Sender<Result<Ref>>,
+    },
+    Unwatch(Ref, Option<oneshot::Sender<bool>>),
+}
+
+impl WatcherHandle {
+    /// Watch path for changes, delivering events to owner. Returns reference for removing watcher
+    pub(crate) async fn watch(
+        &self,
+        path: PathBuf,
+        recursive: bool,
+        owner: ProcessHandle,
+    ) -> Result<Ref> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.tx
+            .send(Cmd::Watch {
+                path,
+                recursive,
+                owner,
+                resp_tx,
+            })
+            .await
+            .map_err(|_| Error::NoWatchers)?;
+        resp_rx.await.map_err(|_| Error::NoWatchers)?
+    }
+
+    /// Remove watcher with given reference. Returns false if there is no such watcher
+    pub(crate) async fn unwatch(&self, id: Ref) -> Result<bool> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(Cmd::Unwatch(id, Some(tx)))
+            .await
+            .map_err(|_| Error::NoWatchers)?;
+        rx.await.map_err(|_| Error::NoWatchers)
+    }
+}
+
+impl Watchers {
+    /// Spawn a new watchers task
+    pub(crate) fn spawn() -> WatcherHandle {
+        let (tx, mut rx) = mpsc::channel(32);
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+        let mut watchers = Watchers {
+            watches: HashMap::new(),
+            events_tx,
+            cmd_tx: tx.downgrade(),
+        };
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    cmd = rx.recv() => match cmd {
+                        Some(cmd) => watchers.handle_cmd(cmd),
+                        None => break,
+                    },
+                    Some((id, ev)) = events_rx.recv() => watchers.deliver(id, ev).await,
+                }
+            }
+        });
+        WatcherHandle { tx }
+    }
+
+    fn handle_cmd(&mut self, cmd: Cmd) {
+        debug!("watchers: {:?}", cmd);
+        match cmd {
+            Cmd::Watch {
+                path,
+                recursive,
+                owner,
+                resp_tx,
+            } => {
+                let _ = resp_tx.send(self.watch(path, recursive, owner));
+            }
+            Cmd::Unwatch(id, resp_tx) => {
+                let removed = self.watches.remove(&id).is_some();
+                if let Some(resp_tx) = resp_tx {
+                    let _ = resp_tx.send(removed);
+                }
+            }
+        }
+    }
+
+    fn watch(&mut self, path: PathBuf, recursive: bool, owner: ProcessHandle) -> Result<Ref> {
+        let id = Ref::new();
+        let events_tx = self.events_tx.clone();
+        let watch_id = id.clone();
+        let mut watcher = notify::recommended_watcher(move |ev| {
+            let _ = events_tx.send((watch_id.clone(), ev));
+        })
+        .map_err(|e| Error::IOError(format!("{e}")))?;
+
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher
+            .watch(&path, mode)
+            .map_err(|e| Error::IOError(format!("{} - {e}", path.display())))?;
+
+        // Remove watcher when owner exits
+        let cmd_tx = self.cmd_tx.clone();
+        let exit = owner.clone().join();
+        let exit_id = id.clone();
+        tokio::spawn(async move {
+            let _ = exit.await;
+            if let Some(cmd_tx) = cmd_tx.upgrade() {
+                let _ = cmd_tx.send(Cmd::Unwatch(exit_id, None)).await;
+            }
+        });
+
+        self.watches.insert(
+            id.clone(),
+            Watch {
+                owner,
+                _watcher: watcher,
+            },
+        );
+        Ok(id)
+    }
+
+    /// Deliver event from watcher to its owner
+    async fn deliver(&mut self, id: Ref, ev: notify::Result<Event>) {
+        let Some(watch) = self.watches.get(&id) else {
+            return;
+        };
+        let ev = match ev {
+            Ok(ev) => ev,
+            Err(e) => {
+                warn!("watcher error - {id:?} - {e}");
+                return;
+            }
+        };
+
+        for (path, kind) in changes(&ev) {
+            let msg = Val::List(vec![
+                Val::keyword("fs_event"),
+                Val::String(path.to_string_lossy().to_string()),
+                Val::keyword(kind),
+            ]);
+            let owner = &watch.owner;
+            owner.notify_message(Message::new(owner.id(), msg)).await;
+        }
+    }
+}
+
+impl std::cmp::PartialEq for WatcherHandle {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(&self.tx, &other.tx)
+    }
+}
+
+/// Paths changed by event and kind of each change. Access events are ignored.
+fn changes(ev: &Event) -> Vec<(&PathBuf, &'static str)> {
+    match ev.kind {
+        EventKind::Create(_) => ev.paths.iter().map(|p| (p, "created")).collect(),
+        EventKind::Remove(_) => ev.paths.iter().map(|p| (p, "removed")).collect(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            ev.paths.iter().map(|p| (p, "removed")).collect()
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            ev.paths.iter().map(|p| (p, "created")).collect()
+        }
+        // Renames within watched path have source then destination
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            ev.paths.iter().zip(["removed", "created"]).collect()
+        }
+        EventKind::Modify(_) | EventKind::Any => ev.paths.iter().map(|p| (p, "modified")).collect(),
+        EventKind::Access(_) | EventKind::Other => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, DataChange};
+
+    #[test]
+    fn change_kinds() {
+        let path = PathBuf::from("/tmp/file");
+        let ev = Event::new(EventKind::Create(CreateKind::File)).add_path(path.clone());
+        assert_eq!(changes(&ev), vec![(&path, "created")]);
+
+        let ev = Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+            .add_path(path.clone());
+        assert_eq!(changes(&ev), vec![(&path, "modified")]);
+
+        let to = PathBuf::from("/tmp/renamed");
+        let ev = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(path.clone())
+            .add_path(to.clone());
+        assert_eq!(changes(&ev), vec![(&path, "removed"), (&to, "created")]);
+
+        let ev = Event::new(EventKind::Access(notify::event::AccessKind::Any)).add_path(path);
+        assert_eq!(changes(&ev), vec![]);
+    }
+}
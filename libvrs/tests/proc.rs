@@ -2,7 +2,7 @@
 use assert_matches::assert_matches;
 use std::time::Duration;
 use tokio::time::timeout;
-use vrs::{Extern, ProcessResult, Program, Runtime, Val};
+use vrs::{Config, Extern, Isolation, ProcessResult, Program, Runtime, Val};
 
 #[tokio::test]
 async fn spawn_pid_is_different() {
@@ -70,7 +70,63 @@ async fn spawn_env_isolated() {
 }
 
 #[tokio::test]
-#[ignore] // TODO: Decide Isolation Policy / Fix
+async fn spawn_env_with_fiber_fails() {
+    let rt = Runtime::new();
+
+    let prog = r#"(begin
+        (def gen (fiber (lambda () (loop (yield :parent)))))
+        (def res (try (spawn (lambda () (resume gen)))))
+        (list (err? res) (resume gen))
+    )
+    "#;
+
+    let prog = Program::from_expr(prog).unwrap();
+    let hdl = rt.run(prog).await.unwrap();
+
+    let exit = timeout(Duration::from_secs(0), hdl.join())
+        .await
+        .expect("Should not timeout")
+        .unwrap();
+
+    assert_eq!(
+        exit.status.unwrap(),
+        ProcessResult::Done(Val::List(vec![Val::Bool(true), Val::keyword("parent")])),
+        "Spawning lambda capturing a fiber should fail instead of sharing fiber"
+    );
+}
+
+#[tokio::test]
+async fn spawn_env_with_unrelated_fiber() {
+    let rt = Runtime::new();
+
+    let prog = r#"(begin
+        (def s (range 3))
+        (def pid (self))
+        (spawn (fn () (send pid :spawned)))
+        (send (self) (fn () :sent))
+        (list (recv :spawned) ((recv)))
+    )
+    "#;
+
+    let prog = Program::from_expr(prog).unwrap();
+    let hdl = rt.run(prog).await.unwrap();
+
+    let exit = timeout(Duration::from_secs(1), hdl.join())
+        .await
+        .expect("Should not timeout")
+        .unwrap();
+
+    assert_eq!(
+        exit.status.unwrap(),
+        ProcessResult::Done(Val::List(vec![
+            Val::keyword("spawned"),
+            Val::keyword("sent")
+        ])),
+        "Fibers not captured by lambda should not prevent spawning or sending it"
+    );
+}
+
+#[tokio::test]
 async fn spawn_env_lambda_isolated() {
     let rt = Runtime::new();
 
@@ -99,8 +155,64 @@ async fn spawn_env_lambda_isolated() {
     );
 }
 
+#[tokio::test]
+async fn send_lambda_isolated() {
+    let rt = Runtime::new();
+
+    let prog = r#"(begin
+        (def parent_pid (self))
+
+        (def count 0)
+        (defn inc () (set count (+ count 1)))
+
+        (def child (spawn (fn ()
+            (def f (recv))
+            (f)
+            (send parent_pid (list :child_count (f))))))
+
+        (send child inc)
+        (def (:child_count child_count) (recv))
+        (list count child_count))"#;
+
+    let hdl = rt.run(Program::from_expr(prog).unwrap()).await.unwrap();
+    let exit = hdl.join().await.unwrap();
+
+    assert_eq!(
+        exit.status.unwrap(),
+        ProcessResult::Done(Val::List(vec![Val::Int(0), Val::Int(2)])),
+        "calling received lambda from child should not affect sender's variables"
+    );
+}
+
+#[tokio::test]
+async fn send_lambda_rejected() {
+    let rt = Runtime::with_config(Config {
+        isolation: Isolation::Reject,
+        ..Default::default()
+    });
+
+    let prog = r#"(begin
+        (def count 0)
+        (defn inc () (set count (+ count 1)))
+        (def child (spawn (fn () (recv))))
+        (list
+            (send child '(:data 1))
+            (err? (try (send child (list :inc inc))))))"#;
+
+    let hdl = rt.run(Program::from_expr(prog).unwrap()).await.unwrap();
+    let exit = hdl.join().await.unwrap();
+
+    assert_eq!(
+        exit.status.unwrap(),
+        ProcessResult::Done(Val::List(vec![
+            Val::List(vec![Val::keyword("data"), Val::Int(1)]),
+            Val::Bool(true),
+        ])),
+        "sending plain data should succeed, and sending closures should fail"
+    );
+}
+
 /// Test nested lambdas for pseudo-objects
-#[ignore] // TODO: Decide Isolation Policy / Fix
 #[tokio::test]
 async fn spawn_env_lambda_nested_isolated() {
     let rt = Runtime::new();
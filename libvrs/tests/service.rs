@@ -134,3 +134,105 @@ async fn spawn_echo_svc() {
 
 // TODO: Test srv w/o service name errors
 // TODO: Test srv w/o :interface errors
+
+#[tokio::test]
+async fn spawn_srv_macro() {
+    let rt = Runtime::new();
+
+    let prog = r#"(begin
+        (defn ping (msg) (list "pong" msg))
+        (spawn_srv :ping :interface '(ping))
+        (defn await_srv (name)
+            (begin
+                (def pid (try (find_srv name)))
+                (if (err? pid)
+                    (begin (sleep 0) (await_srv name))
+                    pid)))
+        (list
+            (call (await_srv :ping) '(:ping "hi"))
+            (macroexpand_1 '(spawn_srv :ping :interface '(ping)))))"#;
+    let hdl = rt.run(Program::from_expr(prog).unwrap()).await.unwrap();
+
+    let resp = hdl.join().await.unwrap();
+    assert_eq!(
+        resp.status.unwrap(),
+        ProcessResult::Done(Val::List(vec![
+            Val::List(vec![Val::string("pong"), Val::string("hi")]),
+            Val::from_expr(
+                "(spawn (lambda () (begin
+                    (try (kill (find_srv :ping)))
+                    (srv :ping :interface '(ping)))))"
+            )
+            .unwrap(),
+        ]))
+    );
+}
+
+#[tokio::test]
+async fn spawn_srv_without_interface() {
+    let rt = Runtime::new();
+
+    let prog = r#"(begin
+        (spawn_srv :quiet)
+        (defn await_srv (name)
+            (begin
+                (def pid (try (find_srv name)))
+                (if (err? pid)
+                    (begin (sleep 0) (await_srv name))
+                    pid)))
+        (await_srv :quiet)
+        (list
+            (info_srv :quiet :interface)
+            (call (find_srv :quiet) '(:ping))))"#;
+    let hdl = rt.run(Program::from_expr(prog).unwrap()).await.unwrap();
+
+    let resp = hdl.join().await.unwrap();
+    assert_eq!(
+        resp.status.unwrap(),
+        ProcessResult::Done(Val::List(vec![
+            Val::List(vec![]),
+            Val::from_expr("(:err \"Unrecognized message\")").unwrap(),
+        ]))
+    );
+}
+
+#[tokio::test]
+async fn srv_optional_rest_key() {
+    let rt = Runtime::new();
+
+    let prog = r#"(begin
+        (spawn (lambda () (begin
+            (defn greet (name &optional (greeting "hello") &key punct)
+                (list greeting name punct))
+            (defn tally (first &rest others)
+                (list first others))
+            (srv :greeter :interface '(greet tally)))))
+        (defn await_srv (name)
+            (begin
+                (def pid (try (find_srv name)))
+                (if (err? pid)
+                    (begin (sleep 0) (await_srv name))
+                    pid)))
+        (await_srv :greeter)
+        (bind_srv :greeter)
+        (list
+            (info_srv :greeter :interface)
+            (greet "alice")
+            (greet "bob" "hi" :punct "!")
+            (tally 1 2 3)))"#;
+    let hdl = rt.run(Program::from_expr(prog).unwrap()).await.unwrap();
+
+    let resp = hdl.join().await.unwrap();
+    assert_eq!(
+        resp.status.unwrap(),
+        ProcessResult::Done(
+            Val::from_expr(
+                r#"(((:greet name &optional greeting &key punct) (:tally first &rest others))
+                    ("hello" "alice" nil)
+                    ("hi" "bob" "!")
+                    (1 (2 3)))"#
+            )
+            .unwrap()
+        )
+    );
+}
@@ -0,0 +1,105 @@
+//! Benchmark for function bodies addressing local variables by slot, compared to binding local
+//! variables in new environment for each call. Both are compiled as function bodies, e.g. with tail calls.
+//!
+//! Run with `cargo bench -p lyric --bench locals`
+
+use lyric::bench::compile_dynamic;
+use lyric::{parse, Lambda, Signal, SymbolId};
+use std::time::{Duration, Instant};
+use void::Void;
+
+type Fiber = lyric::Fiber<Void, ()>;
+type Val = lyric::Val<Void, ()>;
+type Env = lyric::Env<Void, ()>;
+
+/// Number of times each case is run, reporting fastest run
+const RUNS: usize = 5;
+
+/// Functions used in benchmark, as (name, params, body, expression calling function)
+const CASES: &[(&str, &str, &str, &str)] = &[
+    (
+        "sum",
+        "n acc",
+        "(if (eq? n 0) acc (sum (- n 1) (+ acc n)))",
+        "(sum 200000 0)",
+    ),
+    (
+        "defs",
+        "n acc",
+        "(begin
+            (def m (- n 1))
+            (def a (+ acc n))
+            (if (eq? n 0) acc (defs m a)))",
+        "(defs 200000 0)",
+    ),
+    (
+        "fib",
+        "n",
+        "(if (< n 2) n (+ (fib (- n 1)) (fib (- n 2))))",
+        "(fib 22)",
+    ),
+];
+
+fn main() {
+    for (name, params, body, expr) in CASES {
+        let lexical = lexical_lambda(params, body);
+        let dynamic = dynamic_lambda(params, body);
+        assert!(lexical.scope.is_some());
+        assert!(dynamic.scope.is_none());
+
+        let (lexical_res, lexical_time) = bench(name, lexical, expr);
+        let (dynamic_res, dynamic_time) = bench(name, dynamic, expr);
+        assert_eq!(lexical_res, dynamic_res);
+
+        println!(
+            "{name:<8} locals: {lexical_time:>10.2?}  env: {dynamic_time:>10.2?}  speedup: {:.2}x",
+            dynamic_time.as_secs_f64() / lexical_time.as_secs_f64()
+        );
+    }
+}
+
+/// Lambda for function body compiled by `fn`, with locals addressed by slot
+fn lexical_lambda(params: &str, body: &str) -> Lambda<Void, ()> {
+    let f = Fiber::from_expr(&format!("(fn ({params}) {body})"), Env::standard(), ()).unwrap();
+    into_lambda(f)
+}
+
+/// Lambda for function body compiled by `fn` as dynamic, with locals bound in new environment per call
+fn dynamic_lambda(params: &str, body: &str) -> Lambda<Void, ()> {
+    let expr = parse(&format!("(fn ({params}) {body})")).unwrap().into();
+    let f = Fiber::from_bytecode(compile_dynamic(&expr).unwrap(), Env::standard(), ());
+    into_lambda(f)
+}
+
+/// Lambda created by running fiber
+fn into_lambda(mut f: Fiber) -> Lambda<Void, ()> {
+    match f.start().unwrap() {
+        // Run in global environment of benchmark
+        Signal::Done(Val::Lambda(l)) => Lambda { parent: None, ..l },
+        s => panic!("expected lambda - got {s:?}"),
+    }
+}
+
+/// Fastest time to evaluate expression, with lambda bound to name
+fn bench(name: &str, lambda: Lambda<Void, ()>, expr: &str) -> (Val, Duration) {
+    let mut res = None;
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let mut env = Env::standard();
+        env.bind_lambda(SymbolId::from(name), lambda.clone());
+
+        let start = Instant::now();
+        let val = eval(env, expr);
+        best = best.min(start.elapsed());
+        res = Some(val);
+    }
+    (res.unwrap(), best)
+}
+
+fn eval(env: Env, expr: &str) -> Val {
+    let mut f = Fiber::from_expr(expr, env, ()).unwrap();
+    match f.start().unwrap() {
+        Signal::Done(v) => v,
+        s => panic!("fiber is not complete - {s:?}"),
+    }
+}
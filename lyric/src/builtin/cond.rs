@@ -55,12 +55,11 @@ pub fn empty_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
         doc: "(empty? SEXP) - returns true if SEXP is empty".to_string(),
         func: |_, args| match args {
             [Val::List(l)] => Ok(NativeFnOp::Return(Val::Bool(l.is_empty()))),
+            [Val::Map(m)] => Ok(NativeFnOp::Return(Val::Bool(m.is_empty()))),
             [Val::String(s)] => Ok(NativeFnOp::Return(Val::Bool(s.is_empty()))),
-            _ => {
-                return Err(Error::UnexpectedArguments(
-                    "empty? expects one string or list argument".to_string(),
-                ))
-            }
+            _ => Err(Error::UnexpectedArguments(
+                "empty? expects one string or list argument".to_string(),
+            )),
         },
     }
 }
@@ -85,8 +84,10 @@ pub fn is_true<T: Extern, L: Locals>(v: &Val<T, L>) -> Result<bool> {
         Val::Nil => false,
         Val::Bool(b) => *b,
         Val::Int(i) => *i != 0,
+        Val::Float(n) => *n != 0.0,
         Val::String(s) => !s.is_empty(),
         Val::List(l) => !l.is_empty(),
+        Val::Map(m) => !m.is_empty(),
         v => {
             return Err(Error::UnexpectedArguments(format!(
                 "Value is not a valid condition - {v}"
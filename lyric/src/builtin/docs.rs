@@ -5,7 +5,7 @@ pub(crate) fn help_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
         doc: "(help SYMBOL) - Returns docstring for SYMBOL if any".to_string(),
         func: |_, args| {
             let docstring = match args {
-                [Val::Lambda(l)] => l
+                [Val::Lambda(l)] | [Val::Macro(l)] => l
                     .doc
                     .clone()
                     .unwrap_or("<missing documentation>".to_string()),
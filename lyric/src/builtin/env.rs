@@ -13,7 +13,8 @@ pub fn ls_env_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
             }
             let mut res = vec![];
             {
-                let env = f.cur_env().lock().unwrap();
+                let env = f.frame_env();
+                let env = env.lock().unwrap();
                 for (sym, _) in env.iter() {
                     res.push(Val::Symbol(sym.clone()));
                 }
@@ -0,0 +1,230 @@
+//! Builtins for fibers as values
+//! Fibers run as coroutines of the fiber resuming them - values yielded by a fiber are returned
+//! to the caller of `resume`, e.g. to implement generators within a single process.
+
+use crate::fiber::FiberHandle;
+use crate::run::run_until_yield;
+use crate::{
+    Error, Extern, Fiber, Inst, KeywordId, Locals, NativeAsyncFn, NativeFn, NativeFnOp, Result,
+    Signal, Status, Val,
+};
+
+/// Binding to create a fiber
+pub(crate) fn fiber_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(fiber LAMBDA) - Creates a new fiber that calls LAMBDA with no arguments when it is first resumed"
+            .to_string(),
+        func: |f, args| match args {
+            [l] if l.is_callable() => {
+                let child = f.nested(vec![Inst::PushConst(l.clone()), Inst::CallFunc(0)].into());
+                Ok(NativeFnOp::Return(Val::Fiber(FiberHandle::new(child))))
+            }
+            _ => Err(Error::UnexpectedArguments(
+                "fiber expects single function argument".to_string(),
+            )),
+        },
+    }
+}
+
+/// Binding to resume a fiber
+pub(crate) fn resume_fn<T: Extern, L: Locals>() -> NativeAsyncFn<T, L> {
+    NativeAsyncFn {
+        doc: "(resume FIBER) or (resume FIBER VAL) - Runs FIBER until it yields or completes, returning the yielded \
+              or final value. VAL, or nil, is returned by the yield FIBER is paused on, and is ignored when FIBER is new."
+            .to_string(),
+        func: |f, args| Box::new(resume_impl(f, args)),
+    }
+}
+
+/// Binding to get status of fiber
+pub(crate) fn fiber_status_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(fiber_status FIBER) - Returns status of FIBER as :new, :paused, :running, or :done"
+            .to_string(),
+        func: |_, args| match args {
+            [Val::Fiber(hdl)] => {
+                let status = match hdl.status() {
+                    Status::New => "new",
+                    Status::Paused => "paused",
+                    Status::Running | Status::Preempted => "running",
+                    Status::Done => "done",
+                };
+                Ok(NativeFnOp::Return(Val::Keyword(KeywordId::from(status))))
+            }
+            _ => Err(Error::UnexpectedArguments(
+                "fiber_status expects single fiber argument".to_string(),
+            )),
+        },
+    }
+}
+
+/// Binding to check if fiber is done
+pub(crate) fn fiber_done_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(fiber_done? FIBER) - Returns true if FIBER has completed, and cannot be resumed"
+            .to_string(),
+        func: |_, args| match args {
+            [Val::Fiber(hdl)] => Ok(NativeFnOp::Return(Val::Bool(hdl.status() == Status::Done))),
+            _ => Err(Error::UnexpectedArguments(
+                "fiber_done? expects single fiber argument".to_string(),
+            )),
+        },
+    }
+}
+
+/// Implementation for (resume FIBER [VAL])
+async fn resume_impl<T: Extern, L: Locals>(
+    _: &mut Fiber<T, L>,
+    args: Vec<Val<T, L>>,
+) -> Result<Val<T, L>> {
+    let (hdl, val) = match &args[..] {
+        [Val::Fiber(hdl)] => (hdl, Val::Nil),
+        [Val::Fiber(hdl), val] => (hdl, val.clone()),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "resume expects fiber, optionally followed by value".to_string(),
+            ))
+        }
+    };
+    match resume(hdl, val).await? {
+        Signal::Done(v) | Signal::Yield(v) => Ok(v),
+        s => Err(Error::UnexpectedResume(format!(
+            "fiber should be done or yielding - got {s:?}"
+        ))),
+    }
+}
+
+/// Run fiber in handle until it yields or completes, returning [Signal::Yield] or [Signal::Done].
+/// VAL is returned by the yield fiber is paused on, and is ignored when fiber is new.
+pub(crate) async fn resume<T: Extern, L: Locals>(
+    hdl: &FiberHandle<T, L>,
+    val: Val<T, L>,
+) -> Result<Signal<T, L>> {
+    let mut child = hdl.take().ok_or(Error::UnexpectedResume(
+        "resuming a fiber that is already running".to_string(),
+    ))?;
+
+    let res = match child.status() {
+        Status::New => child.start(),
+        _ => child.resume(Ok(val)),
+    };
+    let res = match res {
+        Ok(sig) => run_until_yield(&mut child, sig).await,
+        Err(e) => Err(e),
+    };
+    hdl.put(child);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{run, NativeAsyncFn, SymbolId};
+    use assert_matches::assert_matches;
+    use void::Void;
+
+    type Env = crate::Env<Void, ()>;
+    type Fiber = crate::Fiber<Void, ()>;
+    type Val = crate::Val<Void, ()>;
+
+    async fn eval(env: Env, expr: &str) -> Val {
+        let mut f = Fiber::from_expr(expr, env, ()).unwrap();
+        run(&mut f).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn fiber_generator() {
+        let prog = r#"(begin
+            (def gen (fiber (fn () (begin (yield 1) (yield 2) 3))))
+            (list
+                (fiber_status gen)
+                (resume gen)
+                (fiber_status gen)
+                (resume gen)
+                (resume gen)
+                (fiber_status gen)
+                (fiber_done? gen)
+                (err? (try (resume gen)))))"#;
+        assert_eq!(
+            eval(Env::standard(), prog).await,
+            Val::from_expr("(:new 1 :paused 2 3 :done true true)").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn fiber_resume_with_value() {
+        let prog = r#"(begin
+            (def total 0)
+            (def summer (fiber (fn () (loop (set total (+ total (yield total)))))))
+            (list (resume summer :ignored) (resume summer 5) (resume summer 10) (resume summer 0)))"#;
+        assert_eq!(
+            eval(Env::standard(), prog).await,
+            Val::from_expr("(0 5 15 15)").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn fiber_infinite_nested() {
+        let prog = r#"(begin
+            (defn counter (from)
+                (fiber (fn () (begin (def n from) (loop (begin (yield n) (set n (+ n 1))))))))
+            (defn evens ()
+                (fiber (fn () (begin
+                    (def nums (counter 0))
+                    (loop (begin
+                        (def n (resume nums))
+                        (if (eq? (mod n 2) 0) (yield n) nil)))))))
+            (def e (evens))
+            (list (resume e) (resume e) (resume e) (fiber_done? e)))"#;
+        assert_eq!(
+            eval(Env::standard(), prog).await,
+            Val::from_expr("(0 2 4 false)").unwrap(),
+            "yields of nested fibers go to fiber resuming them"
+        );
+    }
+
+    #[tokio::test]
+    async fn fiber_await() {
+        let mut env = Env::standard();
+        env.bind_native_async(
+            SymbolId::from("async_inc"),
+            NativeAsyncFn {
+                doc: "".to_string(),
+                func: |_, args| {
+                    let num = match args[..] {
+                        [Val::Int(n)] => n,
+                        _ => panic!(),
+                    };
+                    Box::new(async move {
+                        tokio::task::yield_now().await;
+                        Ok(Val::Int(num + 1))
+                    })
+                },
+            },
+        );
+        let prog = r#"(begin
+            (def f (fiber (fn () (begin (yield (async_inc 1)) (async_inc 41)))))
+            (list (resume f) (resume f)))"#;
+        assert_eq!(eval(env, prog).await, Val::from_expr("(2 42)").unwrap());
+    }
+
+    #[tokio::test]
+    async fn fiber_errors() {
+        let prog = r#"(begin
+            (def f (fiber (fn () (begin (yield 1) (undefined_function)))))
+            (resume f)
+            (list (try (resume f)) (fiber_status f)))"#;
+        assert_matches!(
+            eval(Env::standard(), prog).await,
+            Val::List(l) if matches!(&l[..], [Val::Error(_), Val::Keyword(k)] if k.as_str() == "done")
+        );
+
+        let prog = r#"(begin
+            (def f nil)
+            (set f (fiber (fn () (resume f))))
+            (list (try (resume f)) (try (fiber 1)) (try (resume 1))))"#;
+        assert_matches!(
+            eval(Env::standard(), prog).await,
+            Val::List(l) if l.iter().all(|v| matches!(v, Val::Error(_)))
+        );
+    }
+}
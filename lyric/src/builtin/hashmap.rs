@@ -0,0 +1,123 @@
+//! Map builtins
+use crate::{Error, Extern, Locals, Map, MapKey, NativeFn, NativeFnOp, Result, Val};
+
+/// Language binding for `hashmap`
+pub fn hashmap_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(hashmap KEY_1 VAL_1 .. KEY_N VAL_N) - Creates a new map containing each KEY associated with VAL.\
+              Arguments are optional.".to_string(),
+        func: |_, args| Ok(NativeFnOp::Return(Val::Map(assoc(Map::new(), args)?))),
+    }
+}
+
+/// Language binding for `assoc`
+pub fn assoc_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(assoc MAP KEY_1 VAL_1 .. KEY_N VAL_N) - Creates a new map containing entries of MAP, with each KEY associated with VAL".to_string(),
+        func: |_, args| match args {
+            [Val::Map(m), kvs @ ..] => Ok(NativeFnOp::Return(Val::Map(assoc(m.clone(), kvs)?))),
+            _ => Err(Error::UnexpectedArguments(
+                "assoc expects a map followed by keys and values".to_string(),
+            )),
+        },
+    }
+}
+
+/// Language binding for `dissoc`
+pub fn dissoc_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(dissoc MAP KEY_1 .. KEY_N) - Creates a new map containing entries of MAP without each KEY".to_string(),
+        func: |_, args| match args {
+            [Val::Map(m), keys @ ..] => {
+                let mut m = m.clone();
+                for k in keys {
+                    m.remove(&MapKey::try_from(k.clone())?);
+                }
+                Ok(NativeFnOp::Return(Val::Map(m)))
+            }
+            _ => Err(Error::UnexpectedArguments(
+                "dissoc expects a map followed by keys".to_string(),
+            )),
+        },
+    }
+}
+
+/// Language binding for `keys`
+pub fn keys_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(keys MAP) - Returns list of keys in MAP, in sorted order".to_string(),
+        func: |_, args| match args {
+            [Val::Map(m)] => Ok(NativeFnOp::Return(Val::List(
+                m.keys().cloned().map(Val::from).collect(),
+            ))),
+            _ => Err(Error::UnexpectedArguments(
+                "keys expects a map argument".to_string(),
+            )),
+        },
+    }
+}
+
+/// Language binding for `vals`
+pub fn vals_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(vals MAP) - Returns list of values in MAP, in sorted order of keys".to_string(),
+        func: |_, args| match args {
+            [Val::Map(m)] => Ok(NativeFnOp::Return(Val::List(m.values().cloned().collect()))),
+            _ => Err(Error::UnexpectedArguments(
+                "vals expects a map argument".to_string(),
+            )),
+        },
+    }
+}
+
+/// Language binding for `has_key?`
+pub fn has_key_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(has_key? MAP KEY) - Returns true if MAP contains an entry for KEY, otherwise false"
+            .to_string(),
+        func: |_, args| match args {
+            [Val::Map(m), k] => Ok(NativeFnOp::Return(Val::Bool(
+                MapKey::try_from(k.clone()).is_ok_and(|k| m.contains_key(&k)),
+            ))),
+            _ => Err(Error::UnexpectedArguments(
+                "has_key? expects a map and key argument".to_string(),
+            )),
+        },
+    }
+}
+
+/// Language binding for `merge`
+pub fn merge_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(merge MAP_1 MAP_2 .. MAP_N) - Creates a new map containing entries of each MAP. \
+              Entries in later maps replace entries for same key in earlier maps."
+            .to_string(),
+        func: |_, args| {
+            let mut res = Map::new();
+            for a in args {
+                match a {
+                    Val::Map(m) => res.extend(m.iter().map(|(k, v)| (k.clone(), v.clone()))),
+                    _ => {
+                        return Err(Error::UnexpectedArguments(format!(
+                            "merge expects map arguments - got {a}"
+                        )))
+                    }
+                }
+            }
+            Ok(NativeFnOp::Return(Val::Map(res)))
+        },
+    }
+}
+
+/// Associate each key and value pair in `kvs` within given map
+fn assoc<T: Extern, L: Locals>(mut m: Map<T, L>, kvs: &[Val<T, L>]) -> Result<Map<T, L>> {
+    if !kvs.len().is_multiple_of(2) {
+        return Err(Error::UnexpectedArguments(
+            "Expected an even number of keys and values".to_string(),
+        ));
+    }
+    for kv in kvs.chunks(2) {
+        m.insert(MapKey::try_from(kv[0].clone())?, kv[1].clone());
+    }
+    Ok(m)
+}
@@ -1,7 +1,8 @@
 //! List builtins
+use super::seq::collect_fn;
 use crate::{
-    compile, kwargs, parse, Error, Extern, Inst, Lambda, Locals, NativeFn, NativeFnOp, SymbolId,
-    Val,
+    compile, kwargs, parse, Error, Extern, Inst, Lambda, Locals, MapKey, NativeFn, NativeFnOp,
+    SymbolId, Val,
 };
 
 /// Language bindng for `list`
@@ -30,19 +31,43 @@ pub fn push_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
     }
 }
 
+/// Language binding for `concat`
+pub fn concat_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(concat LIST_1 LIST_2 .. LIST_N) - Creates a new list containing elements of each LIST in order".to_string(),
+        func: |_, args| {
+            let mut res = vec![];
+            for a in args {
+                match a {
+                    Val::List(l) => res.extend(l.iter().cloned()),
+                    _ => {
+                        return Err(Error::UnexpectedArguments(format!(
+                            "concat expects list arguments - got {a}"
+                        )))
+                    }
+                }
+            }
+            Ok(NativeFnOp::Return(Val::List(res)))
+        },
+    }
+}
+
 /// Language bindng for `get`
 pub fn get_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
     NativeFn {
-        doc: "(get LIST ATTR) - Returns element within LIST for given ATTR, which can be 0-indexed position in list, or keywords for association lists. Negative indexes return from end of list.".to_string(),
+        doc: "(get LIST ATTR) - Returns element within LIST for given ATTR, which can be 0-indexed position in list, or keywords for association lists. Negative indexes return from end of list. \
+              If LIST is a map, returns value for key ATTR.".to_string(),
         func: |_, x| match x {
             [Val::List(l), Val::Int(idx)] => {
                 let index = if *idx >= 0 {
-                    *idx as usize
+                    usize::try_from(*idx).ok()
                 } else {
-                    l.len() + *idx as usize
+                    isize::try_from(*idx)
+                        .ok()
+                        .and_then(|idx| l.len().checked_add_signed(idx))
                 };
 
-                let elem = match l.get(index) {
+                let elem = match index.and_then(|i| l.get(i)) {
                     Some(elem) => elem.clone(),
                     None => Val::Nil,
                 };
@@ -51,6 +76,12 @@ pub fn get_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
             [Val::List(l), Val::Keyword(target)] => Ok(NativeFnOp::Return(
                 kwargs::get(l, target).unwrap_or(Val::Nil),
             )),
+            [Val::Map(m), key] => Ok(NativeFnOp::Return(
+                MapKey::try_from(key.clone())
+                    .ok()
+                    .and_then(|k| m.get(&k).cloned())
+                    .unwrap_or(Val::Nil),
+            )),
             _ => Err(Error::UnexpectedArguments(
                 "get expects a list and indexing argument".to_string(),
             )),
@@ -61,9 +92,12 @@ pub fn get_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
 /// Language binding for `len`
 pub fn len_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
     NativeFn {
-        doc: "(len LIST) - Returns number of elements in LIST".to_string(),
+        doc:
+            "(len LIST) - Returns number of elements in LIST, or number of entries if LIST is a map"
+                .to_string(),
         func: |_, x| match x {
-            [Val::List(l)] => Ok(NativeFnOp::Return(Val::Int(l.len().try_into().unwrap()))),
+            [Val::List(l)] => Ok(NativeFnOp::Return(Val::Int(l.len() as i64))),
+            [Val::Map(m)] => Ok(NativeFnOp::Return(Val::Int(m.len() as i64))),
             _ => Err(Error::UnexpectedArguments(
                 "list expects a list argument".to_string(),
             )),
@@ -75,8 +109,18 @@ pub fn len_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
 /// Language binding for `map`
 pub(crate) fn map_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
     NativeFn {
-        doc: "(map LIST CALLABLE) - Creates a new list containing elements of LIST transformed by CALLABLE".to_string(),
+        doc: "(map LIST CALLABLE) - Creates a new list containing elements of LIST transformed by CALLABLE. \
+              LIST may be a lazy sequence, which is collected first.".to_string(),
         func: |_, args| match args {
+            [s @ Val::Fiber(_), val] if val.is_callable() => Ok(NativeFnOp::Exec(vec![
+                Inst::PushConst(Val::NativeFn(map_fn())),
+                Inst::PushConst(Val::NativeAsyncFn(collect_fn())),
+                Inst::PushConst(s.clone()),
+                Inst::CallFunc(1),
+                Inst::PushConst(val.clone()),
+                Inst::CallFunc(2),
+            ]
+            .into())),
             [Val::List(l), val] if val.is_callable() => {
                 let mut bc = vec![Inst::GetSym(SymbolId::from("list"))];
                 for elem in l {
@@ -87,7 +131,7 @@ pub(crate) fn map_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
                     ]);
                 }
                 bc.push(Inst::CallFunc(l.len()));
-                Ok(NativeFnOp::Exec(bc))
+                Ok(NativeFnOp::Exec(bc.into()))
             }
             _ => Err(Error::UnexpectedArguments(
                 "map expects a list and mapping operation".to_string(),
@@ -96,10 +140,30 @@ pub(crate) fn map_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
     }
 }
 
+/// Language binding for `apply`
+pub(crate) fn apply_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(apply CALLABLE ARGS) - Calls CALLABLE with elements of list ARGS as arguments"
+            .to_string(),
+        func: |_, args| match args {
+            [val, Val::List(l)] if val.is_callable() => {
+                let mut bc = vec![Inst::PushConst(val.clone())];
+                bc.extend(l.iter().cloned().map(Inst::PushConst));
+                bc.push(Inst::CallFunc(l.len()));
+                Ok(NativeFnOp::Exec(bc.into()))
+            }
+            _ => Err(Error::UnexpectedArguments(
+                "apply expects a callable and list of arguments".to_string(),
+            )),
+        },
+    }
+}
+
 /// Language binding for `filter`
 pub(crate) fn filter_fn<T: Extern, L: Locals>() -> Lambda<T, L> {
     Lambda {
-        doc: Some("(filter LIST CALLABLE) - Creates a new list containing elements of LIST filtered by CALLABLE".to_string()),
+        doc: Some("(filter LIST CALLABLE) - Creates a new list containing elements of LIST filtered by CALLABLE. \
+                   LIST may be a lazy sequence, which is collected first.".to_string()),
         params: vec![SymbolId::from("lst"), SymbolId::from("callable")],
         code: compile(
             &parse(
@@ -114,8 +178,10 @@ pub(crate) fn filter_fn<T: Extern, L: Locals>() -> Lambda<T, L> {
             .unwrap()
             .into(),
         )
-        .unwrap(),
+        .unwrap().into(),
         parent: None,
+        scope: None,
+        upvals: vec![],
     }
 }
 
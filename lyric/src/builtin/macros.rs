@@ -0,0 +1,84 @@
+//! Macro builtins
+use crate::{Error, Extern, Locals, NativeFn, NativeFnOp};
+
+/// Language binding for `macroexpand_1`
+pub(crate) fn macroexpand_1_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(macroexpand_1 FORM) - Returns FORM expanded once if it is a macro call, or FORM otherwise"
+            .to_string(),
+        func: |f, args| match args {
+            [form] => Ok(NativeFnOp::Return(
+                f.macroexpand_1(form)?.unwrap_or_else(|| form.clone()),
+            )),
+            _ => Err(Error::UnexpectedArguments(
+                "macroexpand_1 expects a single form".to_string(),
+            )),
+        },
+    }
+}
+
+/// Language binding for `macroexpand`
+pub(crate) fn macroexpand_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(macroexpand FORM) - Returns FORM expanded repeatedly until it is no longer a macro call"
+            .to_string(),
+        func: |f, args| match args {
+            [form] => {
+                let mut form = form.clone();
+                while let Some(expanded) = f.macroexpand_1(&form)? {
+                    form = expanded;
+                }
+                Ok(NativeFnOp::Return(form))
+            }
+            _ => Err(Error::UnexpectedArguments(
+                "macroexpand expects a single form".to_string(),
+            )),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Signal;
+    use void::Void;
+
+    type Env = crate::Env<Void, ()>;
+    type Fiber = crate::Fiber<Void, ()>;
+    type Val = crate::Val<Void, ()>;
+
+    fn eval(expr: &str) -> Val {
+        let mut f = Fiber::from_expr(expr, Env::standard(), ()).unwrap();
+        match f.start().unwrap() {
+            Signal::Done(v) => v,
+            s => panic!("unexpected signal - {s:?}"),
+        }
+    }
+
+    #[test]
+    fn macroexpand_1() {
+        assert_eq!(
+            eval(
+                "(begin
+                    (defmacro inc (x) `(+ ,x 1))
+                    (defmacro twice_inc (x) `(inc (inc ,x)))
+                    (list (macroexpand_1 '(twice_inc 1))
+                          (macroexpand_1 '(other 1))))"
+            ),
+            Val::from_expr("((inc (inc 1)) (other 1))").unwrap()
+        );
+    }
+
+    #[test]
+    fn macroexpand() {
+        assert_eq!(
+            eval(
+                "(begin
+                    (defmacro inc (x) `(+ ,x 1))
+                    (defmacro twice_inc (x) `(inc (inc ,x)))
+                    (macroexpand '(twice_inc 1)))"
+            ),
+            Val::from_expr("(+ (inc 1) 1)").unwrap(),
+            "macroexpand expands head of form only"
+        );
+    }
+}
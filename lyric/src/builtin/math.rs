@@ -1,35 +1,152 @@
 //! Math builtins
-use crate::{Extern, Locals, NativeFn, NativeFnOp, Result, Val};
+//! Operations on two ints return an int, and operations involving a float promote to float.
+use std::cmp::Ordering;
+
+use crate::{Error, Extern, Locals, NativeFn, NativeFnOp, Result, Val};
 
 /// Native binding for `+`
 pub fn plus_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
     NativeFn {
-        doc: "(+ LHS RHS) - If LHS and RHS are integers, returns sum of LHS and RHS.\
-              If LHS and RHS are lists, returns a new list containing elements of LHS followed by elements of RHS.".to_string(),
+        doc:
+            "(+ ARG1 ARG2 ... ARGN) - If arguments are numbers, returns sum of arguments.\
+              If arguments are lists, returns a new list containing elements of each list in order."
+                .to_string(),
         func: |_, args| match args {
-            [Val::Int(_), ..] => {
-                Ok(NativeFnOp::Return(
-                    plus_add_ints(args)?
-                ))
-            },
-            [Val::List(_), ..] => {
-                Ok(NativeFnOp::Return(
-                    plus_concat_list(args)?
-                ))
-            }
-            _ => panic!("only supports ints"),
+            [Val::List(_), ..] => Ok(NativeFnOp::Return(plus_concat_list(args)?)),
+            _ => Ok(NativeFnOp::Return(
+                fold("+", args, Num::Int(0), Num::add)?.into(),
+            )),
         },
     }
 }
 
-/// Native binding for `+` for adding integers
-fn plus_add_ints<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Val<T, L>> {
-    let mut res = 0;
-    for arg in args {
-        let num = arg.as_int()?;
-        res += num;
+/// Native binding for `-`
+pub fn minus_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(- ARG1 ARG2 ... ARGN) - Returns ARG1 minus remaining arguments, or negation of ARG1 if it is the only argument.".to_string(),
+        func: |_, args| {
+            let res = match args {
+                [] => return Err(Error::UnexpectedArguments("- expects at least one argument".to_string())),
+                [arg] => Num::Int(0).sub(Num::try_from(arg)?),
+                [first, rest @ ..] => fold("-", rest, Num::try_from(first)?, Num::sub),
+            };
+            Ok(NativeFnOp::Return(res?.into()))
+        },
+    }
+}
+
+/// Native binding for `*`
+pub fn mul_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(* ARG1 ARG2 ... ARGN) - Returns product of arguments.".to_string(),
+        func: |_, args| {
+            Ok(NativeFnOp::Return(
+                fold("*", args, Num::Int(1), Num::mul)?.into(),
+            ))
+        },
+    }
+}
+
+/// Native binding for `/`
+pub fn div_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(/ ARG1 ARG2 ... ARGN) - Returns ARG1 divided by remaining arguments, or reciprocal of ARG1 if it is the only argument. \
+              Dividing ints returns a truncated int.".to_string(),
+        func: |_, args| {
+            let res = match args {
+                [] => return Err(Error::UnexpectedArguments("/ expects at least one argument".to_string())),
+                [arg] => Num::Int(1).div(Num::try_from(arg)?),
+                [first, rest @ ..] => fold("/", rest, Num::try_from(first)?, Num::div),
+            };
+            Ok(NativeFnOp::Return(res?.into()))
+        },
+    }
+}
+
+/// Native binding for `mod`
+pub fn mod_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(mod NUM DIV) - Returns NUM modulo DIV, which has the same sign as DIV.".to_string(),
+        func: |_, args| match args {
+            [num, div] => Ok(NativeFnOp::Return(
+                Num::try_from(num)?.modulo(Num::try_from(div)?)?.into(),
+            )),
+            _ => Err(Error::UnexpectedArguments(
+                "mod expects two arguments".to_string(),
+            )),
+        },
+    }
+}
+
+/// Native binding for `<`
+pub fn lt_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(< ARG1 ARG2 ... ARGN) - Returns true if arguments are in strictly increasing order."
+            .to_string(),
+        func: |_, args| Ok(NativeFnOp::Return(compare(args, Ordering::is_lt)?)),
+    }
+}
+
+/// Native binding for `>`
+pub fn gt_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(> ARG1 ARG2 ... ARGN) - Returns true if arguments are in strictly decreasing order."
+            .to_string(),
+        func: |_, args| Ok(NativeFnOp::Return(compare(args, Ordering::is_gt)?)),
+    }
+}
+
+/// Native binding for `<=`
+pub fn lte_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(<= ARG1 ARG2 ... ARGN) - Returns true if arguments are in increasing order."
+            .to_string(),
+        func: |_, args| Ok(NativeFnOp::Return(compare(args, Ordering::is_le)?)),
+    }
+}
+
+/// Native binding for `>=`
+pub fn gte_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(>= ARG1 ARG2 ... ARGN) - Returns true if arguments are in decreasing order."
+            .to_string(),
+        func: |_, args| Ok(NativeFnOp::Return(compare(args, Ordering::is_ge)?)),
+    }
+}
+
+/// Native binding for `min`
+pub fn min_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(min ARG1 ARG2 ... ARGN) - Returns smallest argument.".to_string(),
+        func: |_, args| Ok(NativeFnOp::Return(select("min", args, Ordering::is_lt)?)),
+    }
+}
+
+/// Native binding for `max`
+pub fn max_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(max ARG1 ARG2 ... ARGN) - Returns largest argument.".to_string(),
+        func: |_, args| Ok(NativeFnOp::Return(select("max", args, Ordering::is_gt)?)),
+    }
+}
+
+/// Native binding for `abs`
+pub fn abs_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(abs NUM) - Returns absolute value of NUM.".to_string(),
+        func: |_, args| match args {
+            [arg] => {
+                let res = match Num::try_from(arg)? {
+                    Num::Int(i) => Num::Int(i.checked_abs().ok_or_else(overflow)?),
+                    Num::Float(n) => Num::Float(n.abs()),
+                };
+                Ok(NativeFnOp::Return(res.into()))
+            }
+            _ => Err(Error::UnexpectedArguments(
+                "abs expects one argument".to_string(),
+            )),
+        },
     }
-    Ok(Val::Int(res))
 }
 
 /// Native binding for `+` for concatenating lists
@@ -42,4 +159,182 @@ fn plus_concat_list<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Val<T, L
     Ok(Val::List(result))
 }
 
-// TODO: Write tests for +
+/// A number operand, which is either an int or a float
+#[derive(Debug, Clone, Copy)]
+enum Num {
+    Int(i64),
+    Float(f64),
+}
+
+impl Num {
+    /// Apply checked int operation if both operands are ints, otherwise float operation
+    fn apply(
+        self,
+        rhs: Num,
+        int_op: fn(i64, i64) -> Result<i64>,
+        float_op: fn(f64, f64) -> f64,
+    ) -> Result<Num> {
+        match (self, rhs) {
+            (Num::Int(l), Num::Int(r)) => Ok(Num::Int(int_op(l, r)?)),
+            (l, r) => Ok(Num::Float(float_op(l.as_float(), r.as_float()))),
+        }
+    }
+
+    fn add(self, rhs: Num) -> Result<Num> {
+        self.apply(
+            rhs,
+            |l, r| l.checked_add(r).ok_or_else(overflow),
+            |l, r| l + r,
+        )
+    }
+
+    fn sub(self, rhs: Num) -> Result<Num> {
+        self.apply(
+            rhs,
+            |l, r| l.checked_sub(r).ok_or_else(overflow),
+            |l, r| l - r,
+        )
+    }
+
+    fn mul(self, rhs: Num) -> Result<Num> {
+        self.apply(
+            rhs,
+            |l, r| l.checked_mul(r).ok_or_else(overflow),
+            |l, r| l * r,
+        )
+    }
+
+    fn div(self, rhs: Num) -> Result<Num> {
+        self.apply(
+            rhs,
+            |l, r| match r {
+                0 => Err(divide_by_zero()),
+                _ => l.checked_div(r).ok_or_else(overflow),
+            },
+            |l, r| l / r,
+        )
+    }
+
+    /// Modulo with sign of divisor
+    fn modulo(self, rhs: Num) -> Result<Num> {
+        self.apply(
+            rhs,
+            |l, r| match r {
+                0 => Err(divide_by_zero()),
+                _ => {
+                    let rem = l.checked_rem(r).ok_or_else(overflow)?;
+                    Ok(if rem != 0 && (rem < 0) != (r < 0) {
+                        rem + r
+                    } else {
+                        rem
+                    })
+                }
+            },
+            |l, r| {
+                let rem = l % r;
+                if rem != 0.0 && (rem < 0.0) != (r < 0.0) {
+                    rem + r
+                } else {
+                    rem
+                }
+            },
+        )
+    }
+
+    fn as_float(self) -> f64 {
+        match self {
+            Num::Int(i) => i as f64,
+            Num::Float(n) => n,
+        }
+    }
+
+    /// Compare two numbers, which is None if either is NaN
+    fn partial_cmp(self, rhs: Num) -> Option<Ordering> {
+        match (self, rhs) {
+            (Num::Int(l), Num::Int(r)) => Some(l.cmp(&r)),
+            (l, r) => l.as_float().partial_cmp(&r.as_float()),
+        }
+    }
+}
+
+impl<T: Extern, L: Locals> TryFrom<&Val<T, L>> for Num {
+    type Error = Error;
+
+    fn try_from(value: &Val<T, L>) -> Result<Self> {
+        match value {
+            Val::Int(i) => Ok(Num::Int(*i)),
+            Val::Float(n) => Ok(Num::Float(*n)),
+            _ => Err(Error::UnexpectedType(format!("expected number - {value}"))),
+        }
+    }
+}
+
+impl<T: Extern, L: Locals> From<Num> for Val<T, L> {
+    fn from(value: Num) -> Self {
+        match value {
+            Num::Int(i) => Val::Int(i),
+            Num::Float(n) => Val::Float(n),
+        }
+    }
+}
+
+fn overflow() -> Error {
+    Error::Runtime("Integer overflow".to_string())
+}
+
+fn divide_by_zero() -> Error {
+    Error::Runtime("Division by zero".to_string())
+}
+
+/// Fold numeric arguments with operation, starting from initial number
+fn fold<T: Extern, L: Locals>(
+    name: &str,
+    args: &[Val<T, L>],
+    init: Num,
+    op: fn(Num, Num) -> Result<Num>,
+) -> Result<Num> {
+    let mut res = init;
+    for arg in args {
+        let num = Num::try_from(arg)
+            .map_err(|e| Error::UnexpectedArguments(format!("{name} expects numbers - {e}")))?;
+        res = op(res, num)?;
+    }
+    Ok(res)
+}
+
+/// Whether or not ordering of each adjacent pair of arguments satisfies predicate
+fn compare<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    pred: fn(Ordering) -> bool,
+) -> Result<Val<T, L>> {
+    let nums = args.iter().map(Num::try_from).collect::<Result<Vec<_>>>()?;
+    if nums.is_empty() {
+        return Err(Error::UnexpectedArguments(
+            "Comparison expects at least one argument".to_string(),
+        ));
+    }
+    let res = nums
+        .windows(2)
+        .all(|w| w[0].partial_cmp(w[1]).is_some_and(pred));
+    Ok(Val::Bool(res))
+}
+
+/// Select argument which compares with predicate against all other arguments
+fn select<T: Extern, L: Locals>(
+    name: &str,
+    args: &[Val<T, L>],
+    pred: fn(Ordering) -> bool,
+) -> Result<Val<T, L>> {
+    let mut res: Option<(Num, &Val<T, L>)> = None;
+    for arg in args {
+        let num = Num::try_from(arg)?;
+        res = match res {
+            Some((best, _)) if !num.partial_cmp(best).is_some_and(pred) => res,
+            _ => Some((num, arg)),
+        };
+    }
+    res.map(|(_, v)| v.clone())
+        .ok_or(Error::UnexpectedArguments(format!(
+            "{name} expects at least one argument"
+        )))
+}
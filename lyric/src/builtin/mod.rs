@@ -2,10 +2,14 @@
 pub mod cond;
 pub mod docs;
 pub mod env;
+pub mod fiber;
+pub mod hashmap;
 pub mod list;
 pub mod log;
+pub mod macros;
 pub mod math;
 pub mod refs;
+pub mod seq;
 pub mod string;
 pub mod types;
 
@@ -18,6 +22,19 @@ pub(crate) use cond::is_keyword_fn;
 pub(crate) use cond::not_fn;
 pub(crate) use docs::help_fn;
 pub(crate) use env::ls_env_fn;
+pub(crate) use fiber::fiber_done_fn;
+pub(crate) use fiber::fiber_fn;
+pub(crate) use fiber::fiber_status_fn;
+pub(crate) use fiber::resume_fn;
+pub(crate) use hashmap::assoc_fn;
+pub(crate) use hashmap::dissoc_fn;
+pub(crate) use hashmap::has_key_fn;
+pub(crate) use hashmap::hashmap_fn;
+pub(crate) use hashmap::keys_fn;
+pub(crate) use hashmap::merge_fn;
+pub(crate) use hashmap::vals_fn;
+pub(crate) use list::apply_fn;
+pub(crate) use list::concat_fn;
 pub(crate) use list::filter_fn;
 pub(crate) use list::get_fn;
 pub(crate) use list::len_fn;
@@ -25,8 +42,30 @@ pub(crate) use list::list_fn;
 pub(crate) use list::map_fn;
 pub(crate) use list::push_fn;
 pub(crate) use log::dbg_fn;
+pub(crate) use macros::macroexpand_1_fn;
+pub(crate) use macros::macroexpand_fn;
+pub(crate) use math::abs_fn;
+pub(crate) use math::div_fn;
+pub(crate) use math::gt_fn;
+pub(crate) use math::gte_fn;
+pub(crate) use math::lt_fn;
+pub(crate) use math::lte_fn;
+pub(crate) use math::max_fn;
+pub(crate) use math::min_fn;
+pub(crate) use math::minus_fn;
+pub(crate) use math::mod_fn;
+pub(crate) use math::mul_fn;
 pub(crate) use math::plus_fn;
 pub(crate) use refs::ref_fn;
+pub(crate) use seq::collect_fn;
+pub(crate) use seq::drop_fn;
+pub(crate) use seq::iterate_fn;
+pub(crate) use seq::lazy_filter_fn;
+pub(crate) use seq::lazy_map_fn;
+pub(crate) use seq::range_fn;
+pub(crate) use seq::seq_fn;
+pub(crate) use seq::take_fn;
+pub(crate) use seq::take_while_fn;
 pub(crate) use string::display_fn;
 pub(crate) use string::format_fn;
 pub(crate) use string::join_fn;
@@ -34,6 +73,7 @@ pub(crate) use string::read_fn;
 pub(crate) use string::split_fn;
 pub(crate) use string::str_fn;
 pub(crate) use types::err_fn;
+pub(crate) use types::err_kind_fn;
 pub(crate) use types::ok_fn;
 
 pub use refs::Ref;
@@ -4,16 +4,26 @@ use crate::{Extern, Locals, NativeFn, NativeFnOp, Val};
 use nanoid::nanoid;
 
 /// Unique reference type
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Ref(pub(crate) String);
 
+impl Ref {
+    /// Create a new unique reference
+    pub fn new() -> Self {
+        Ref(nanoid!())
+    }
+}
+
+impl Default for Ref {
+    fn default() -> Self {
+        Ref::new()
+    }
+}
+
 /// Binding to create a new unique reference
 pub fn ref_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
     NativeFn {
         doc: "(ref) - Creates a new unique reference in runtime".to_string(),
-        func: |_, _| {
-            let r = Ref(nanoid!());
-            Ok(NativeFnOp::Return(Val::Ref(r)))
-        },
+        func: |_, _| Ok(NativeFnOp::Return(Val::Ref(Ref::new()))),
     }
 }
@@ -0,0 +1,354 @@
+//! Builtins for lazy sequences
+//! A lazy sequence is a fiber that yields each element of sequence, and completes when the sequence ends.
+//! Elements are computed as the sequence is consumed, so sequences may be infinite.
+
+use super::fiber::resume;
+use crate::fiber::FiberHandle;
+use crate::{
+    compile, parse, Env, Error, Extern, Fiber, Inst, Lambda, Locals, NativeAsyncFn, NativeFn,
+    NativeFnOp, Result, Signal, Status, SymbolId, Val,
+};
+use std::sync::{Arc, Mutex};
+
+/// Language binding for `seq`
+pub(crate) fn seq_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(seq LIST) - Creates a lazy sequence of elements in LIST. Lazy sequences are returned as is."
+            .to_string(),
+        func: |f, args| match args {
+            [Val::List(l)] => {
+                let mut bc = vec![];
+                for elem in l {
+                    bc.extend([Inst::PushConst(elem.clone()), Inst::YieldTop, Inst::PopTop]);
+                }
+                bc.push(Inst::PushConst(Val::Nil));
+                Ok(NativeFnOp::Return(Val::Fiber(FiberHandle::new(
+                    f.nested(bc.into()),
+                ))))
+            }
+            [s @ Val::Fiber(_)] => Ok(NativeFnOp::Return(s.clone())),
+            _ => Err(Error::UnexpectedArguments(
+                "seq expects a list or lazy sequence".to_string(),
+            )),
+        },
+    }
+}
+
+/// Language binding for `collect`
+pub(crate) fn collect_fn<T: Extern, L: Locals>() -> NativeAsyncFn<T, L> {
+    NativeAsyncFn {
+        doc: "(collect SEQ) - Creates a new list containing remaining elements of lazy sequence SEQ. Lists are returned as is."
+            .to_string(),
+        func: |f, args| Box::new(collect_impl(f, args)),
+    }
+}
+
+/// Language binding for `range`
+pub(crate) fn range_fn<T: Extern, L: Locals>() -> Lambda<T, L> {
+    lyric_fn(
+        "(range N) - Creates a lazy sequence of integers from 0 up to, but not including, N",
+        &["n"],
+        r#"(begin
+            (defn range_step (i)
+                (when (< i n)
+                    (yield i)
+                    (range_step (+ i 1))))
+            (fiber (fn () (range_step 0))))"#,
+    )
+}
+
+/// Language binding for `iterate`
+pub(crate) fn iterate_fn<T: Extern, L: Locals>() -> Lambda<T, L> {
+    lyric_fn(
+        "(iterate CALLABLE X) - Creates an infinite lazy sequence of X, (CALLABLE X), (CALLABLE (CALLABLE X)), etc",
+        &["callable", "x"],
+        r#"(fiber (fn () (begin
+            (def cur x)
+            (loop
+                (yield cur)
+                (set cur (callable cur))))))"#,
+    )
+}
+
+/// Language binding for `take`
+pub(crate) fn take_fn<T: Extern, L: Locals>() -> Lambda<T, L> {
+    lyric_fn(
+        "(take SEQ N) - Creates a lazy sequence of first N elements of SEQ",
+        &["s", "n"],
+        r#"(begin
+            (def s (seq s))
+            (defn take_step (i)
+                (when (< i n)
+                    (def x (resume s))
+                    (unless (fiber_done? s)
+                        (yield x)
+                        (take_step (+ i 1)))))
+            (fiber (fn () (take_step 0))))"#,
+    )
+}
+
+/// Language binding for `drop`
+pub(crate) fn drop_fn<T: Extern, L: Locals>() -> Lambda<T, L> {
+    lyric_fn(
+        "(drop SEQ N) - Creates a lazy sequence of elements of SEQ after first N elements",
+        &["s", "n"],
+        r#"(begin
+            (def s (seq s))
+            (defn drop_skip (i)
+                (when (< i n)
+                    (resume s)
+                    (unless (fiber_done? s)
+                        (drop_skip (+ i 1)))))
+            (defn drop_step ()
+                (def x (resume s))
+                (unless (fiber_done? s)
+                    (yield x)
+                    (drop_step)))
+            (fiber (fn () (begin
+                (drop_skip 0)
+                (unless (fiber_done? s)
+                    (drop_step))))))"#,
+    )
+}
+
+/// Language binding for `take_while`
+pub(crate) fn take_while_fn<T: Extern, L: Locals>() -> Lambda<T, L> {
+    lyric_fn(
+        "(take_while SEQ CALLABLE) - Creates a lazy sequence of elements of SEQ until CALLABLE returns false for an element",
+        &["s", "callable"],
+        r#"(begin
+            (def s (seq s))
+            (defn take_while_step ()
+                (def x (resume s))
+                (when (and (not? (fiber_done? s)) (callable x))
+                    (yield x)
+                    (take_while_step)))
+            (fiber take_while_step))"#,
+    )
+}
+
+/// Language binding for `lazy_map`
+pub(crate) fn lazy_map_fn<T: Extern, L: Locals>() -> Lambda<T, L> {
+    lyric_fn(
+        "(lazy_map SEQ CALLABLE) - Creates a lazy sequence of elements of SEQ transformed by CALLABLE",
+        &["s", "callable"],
+        r#"(begin
+            (def s (seq s))
+            (defn lazy_map_step ()
+                (def x (resume s))
+                (unless (fiber_done? s)
+                    (yield (callable x))
+                    (lazy_map_step)))
+            (fiber lazy_map_step))"#,
+    )
+}
+
+/// Language binding for `lazy_filter`
+pub(crate) fn lazy_filter_fn<T: Extern, L: Locals>() -> Lambda<T, L> {
+    lyric_fn(
+        "(lazy_filter SEQ CALLABLE) - Creates a lazy sequence of elements of SEQ filtered by CALLABLE",
+        &["s", "callable"],
+        r#"(begin
+            (def s (seq s))
+            (defn lazy_filter_step ()
+                (def x (resume s))
+                (unless (fiber_done? s)
+                    (when (callable x)
+                        (yield x))
+                    (lazy_filter_step)))
+            (fiber lazy_filter_step))"#,
+    )
+}
+
+/// Implementation for (collect SEQ)
+async fn collect_impl<T: Extern, L: Locals>(
+    _: &mut Fiber<T, L>,
+    args: Vec<Val<T, L>>,
+) -> Result<Val<T, L>> {
+    let hdl = match &args[..] {
+        [Val::Fiber(hdl)] => hdl,
+        [l @ Val::List(_)] => return Ok(l.clone()),
+        _ => {
+            return Err(Error::UnexpectedArguments(
+                "collect expects a lazy sequence or list".to_string(),
+            ))
+        }
+    };
+
+    let mut elems = vec![];
+    if hdl.status() == Status::Done {
+        return Ok(Val::List(elems));
+    }
+    while let Signal::Yield(elem) = resume(hdl, Val::Nil).await? {
+        elems.push(elem);
+    }
+    Ok(Val::List(elems))
+}
+
+/// Builtin lambda with body written in lyric.
+/// Body runs in a private environment, so it does not depend on bindings of caller, which may be rebound.
+fn lyric_fn<T: Extern, L: Locals>(doc: &str, params: &[&str], body: &str) -> Lambda<T, L> {
+    Lambda {
+        doc: Some(doc.to_string()),
+        params: params.iter().map(|p| SymbolId::from(*p)).collect(),
+        code: compile(&parse(body).unwrap().into()).unwrap().into(),
+        parent: Some(Arc::new(Mutex::new(lyric_fn_env()))),
+        scope: None,
+        upvals: vec![],
+    }
+}
+
+/// Environment with builtins used by bodies of [lyric_fn]
+fn lyric_fn_env<T: Extern, L: Locals>() -> Env<T, L> {
+    let mut e = Env::empty();
+    e.bind_native(SymbolId::from("+"), super::plus_fn())
+        .bind_native(SymbolId::from("<"), super::lt_fn())
+        .bind_native(SymbolId::from("not?"), super::not_fn())
+        .bind_native(SymbolId::from("fiber"), super::fiber_fn())
+        .bind_native_async(SymbolId::from("resume"), super::resume_fn())
+        .bind_native(SymbolId::from("fiber_done?"), super::fiber_done_fn())
+        .bind_native(SymbolId::from("seq"), seq_fn());
+    e
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run;
+    use void::Void;
+
+    type Env = crate::Env<Void, ()>;
+    type Fiber = crate::Fiber<Void, ()>;
+    type Val = crate::Val<Void, ()>;
+
+    #[test]
+    fn lyric_fn_bodies_compile() {
+        // Bodies are compiled when bindings are created, which panics for invalid bodies
+        let _ = [
+            super::range_fn::<Void, ()>(),
+            super::iterate_fn(),
+            super::take_fn(),
+            super::drop_fn(),
+            super::take_while_fn(),
+            super::lazy_map_fn(),
+            super::lazy_filter_fn(),
+        ];
+    }
+
+    async fn eval(expr: &str) -> Val {
+        let mut f = Fiber::from_expr(expr, Env::standard(), ()).unwrap();
+        run(&mut f).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn range_collect() {
+        assert_eq!(
+            eval("(collect (range 5))").await,
+            Val::from_expr("(0 1 2 3 4)").unwrap()
+        );
+        assert_eq!(eval("(collect (range 0))").await, Val::List(vec![]));
+        assert_eq!(
+            eval("(collect '(1 2 3))").await,
+            Val::from_expr("(1 2 3)").unwrap()
+        );
+        assert_eq!(
+            eval("(begin (def r (range 3)) (collect r) (collect r))").await,
+            Val::List(vec![]),
+            "consumed sequences are empty"
+        );
+    }
+
+    #[tokio::test]
+    async fn seq_list() {
+        assert_eq!(
+            eval("(collect (seq '(:a :b :c)))").await,
+            Val::from_expr("(:a :b :c)").unwrap()
+        );
+        assert_eq!(
+            eval("(collect (take '(1 2 3 4) 2))").await,
+            Val::from_expr("(1 2)").unwrap(),
+            "lists can be used as lazy sequences"
+        );
+    }
+
+    #[tokio::test]
+    async fn iterate_take_drop() {
+        assert_eq!(
+            eval("(collect (take (iterate (fn (x) (* x 2)) 1) 5))").await,
+            Val::from_expr("(1 2 4 8 16)").unwrap()
+        );
+        assert_eq!(
+            eval("(collect (take (drop (iterate (fn (x) (+ x 1)) 0) 3) 2))").await,
+            Val::from_expr("(3 4)").unwrap()
+        );
+        assert_eq!(
+            eval("(collect (drop (range 3) 5))").await,
+            Val::List(vec![])
+        );
+        assert_eq!(
+            eval("(collect (take (range 3) 5))").await,
+            Val::from_expr("(0 1 2)").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn take_while() {
+        assert_eq!(
+            eval("(collect (take_while (iterate (fn (x) (+ x 1)) 0) (fn (x) (< x 4))))").await,
+            Val::from_expr("(0 1 2 3)").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn lazy_map_filter() {
+        let prog = r#"(begin
+            (def calls 0)
+            (def squares (lazy_map (iterate (fn (x) (+ x 1)) 1)
+                                   (fn (x) (begin (set calls (+ calls 1)) (* x x)))))
+            (def odd_squares (lazy_filter squares (fn (x) (eq? (mod x 2) 1))))
+            (list (collect (take odd_squares 3)) calls))"#;
+        assert_eq!(
+            eval(prog).await,
+            Val::from_expr("((1 9 25) 5)").unwrap(),
+            "elements are only computed as sequence is consumed"
+        );
+    }
+
+    #[tokio::test]
+    async fn lyric_fns_ignore_rebound_builtins() {
+        let prog = r#"(begin
+            (def + (fn (a b) :shadowed))
+            (def < :shadowed)
+            (def resume :shadowed)
+            (def fiber_done? :shadowed)
+            (collect (take (lazy_map (drop (range 6) 2) (fn (x) (* x 10))) 3)))"#;
+        assert_eq!(
+            eval(prog).await,
+            Val::from_expr("(20 30 40)").unwrap(),
+            "lazy sequences should not depend on bindings of caller"
+        );
+    }
+
+    #[tokio::test]
+    async fn eager_map_filter() {
+        assert_eq!(
+            eval("(map (range 4) (fn (x) (* x 10)))").await,
+            Val::from_expr("(0 10 20 30)").unwrap()
+        );
+        assert_eq!(
+            eval(
+                "(begin
+                    (def lmap map)
+                    (def map :shadowed)
+                    (def collect :shadowed)
+                    (lmap (range 4) (fn (x) (* x 10))))"
+            )
+            .await,
+            Val::from_expr("(0 10 20 30)").unwrap(),
+            "mapping lazy sequences should not depend on bindings of map or collect"
+        );
+        assert_eq!(
+            eval("(filter (range 6) (fn (x) (eq? (mod x 2) 0)))").await,
+            Val::from_expr("(0 2 4)").unwrap()
+        );
+    }
+}
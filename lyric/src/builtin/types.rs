@@ -35,3 +35,16 @@ pub(crate) fn err_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
         },
     }
 }
+
+pub(crate) fn err_kind_fn<T: Extern, L: Locals>() -> NativeFn<T, L> {
+    NativeFn {
+        doc: "(err_kind ERR) - Returns keyword for kind of error value ERR, e.g. :not_found or :undefined_symbol"
+            .to_string(),
+        func: |_, args| match args {
+            [Val::Error(e)] => Ok(NativeFnOp::Return(Val::Keyword(e.kind()))),
+            _ => Err(Error::UnexpectedArguments(
+                "err_kind expects single error value".to_string(),
+            )),
+        },
+    }
+}
@@ -1,5 +1,11 @@
 //! Compiler for Lyric Form AST
-use crate::{Bytecode, Error, Extern, Locals, Result, SymbolId, Val};
+use crate::builtin::{concat_fn, eq_fn, hashmap_fn, list_fn};
+use crate::params::Params;
+use crate::{
+    Bytecode, Error, Extern, Fiber, Locals, Map, MapKey, Position, Result, Span, SymbolId, Val,
+};
+use std::collections::HashSet;
+use std::sync::Arc;
 
 // TODO: Compact bytecode repr
 /// Bytecode instructions
@@ -19,12 +25,30 @@ where
     DefBind,
     /// Set given symbol to value popped from TOS
     SetSym(SymbolId),
+    /// Push value of local variable in given slot of callframe onto stack
+    GetLocal(usize),
+    /// Set local variable in given slot of callframe to value at TOS
+    SetLocal(usize),
+    /// Push value of variable captured as given upvalue of function being run onto stack
+    GetUpval(usize),
+    /// Set variable captured as given upvalue of function being run to value at TOS
+    SetUpval(usize),
     /// Pop parameter list and function body from stack, and pushes a new function onto stack
     MakeFunc,
+    /// Pop parameter list and function body like [Inst::MakeFunc], and push a new function addressing
+    /// variables within given scope, capturing its upvalues from current callframe
+    MakeClosure(Arc<Scope>),
+    /// Pop function from stack, and push a macro that expands forms with function
+    MakeMacro,
     /// Call func by popping N forms and function object off stack, and pushing result
     CallFunc(usize),
+    /// Call func like [Inst::CallFunc] in tail position of function body, where callframe of
+    /// function body is reused by lambda being called
+    TailCallFunc(usize),
     /// Pop the top of the stack
     PopTop,
+    /// Push copy of the top of the stack
+    DupTop,
     /// Jump forward N inst
     JumpFwd(usize),
     /// Jump backward N inst
@@ -35,57 +59,307 @@ where
     YieldTop,
     /// Evaluate TOS and push value back onto stack. May be protected eval
     Eval(bool),
+    /// Evaluate TOS like unprotected eval, marking compiled code with source locations of form
+    EvalSpanned(Arc<SourceSpan>),
 }
 
+/// Spans of a form in source, for forms compiled at runtime
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceSpan {
+    /// Source file, if any
+    pub file: Option<Arc<str>>,
+    /// Span of form within source
+    pub span: Span,
+}
+
+/// Location in source for compiled instructions
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLoc {
+    /// Source file, if any
+    pub file: Option<Arc<str>>,
+    /// Position within source
+    pub pos: Position,
+}
+
+/// Local variables of function body whose symbols are lexically addressed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scope {
+    /// Symbols of local slots, starting with parameters in order they are bound
+    pub slots: Vec<SymbolId>,
+    /// Symbols of variables captured from enclosing function bodies, and where each is captured from
+    pub upvals: Vec<(SymbolId, Capture)>,
+}
+
+/// Variable of enclosing function body, captured as upvalue when creating a closure
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+    /// Local slot of enclosing function body
+    Local(usize),
+    /// Upvalue of enclosing function body
+    Upval(usize),
+}
+
+/// Function body being compiled
+#[derive(Debug)]
+struct FnScope {
+    /// Symbols of local slots, in order of slots
+    slots: Vec<SymbolId>,
+    /// Symbols defined anywhere in function body, which closures within body resolve to local slots
+    defs: HashSet<SymbolId>,
+    /// Variables captured from enclosing function bodies
+    upvals: Vec<(SymbolId, Capture)>,
+    /// Whether or not symbols are bound in a new environment for each call instead of being lexically
+    /// addressed, e.g. since function body evaluates forms at runtime
+    dynamic: bool,
+}
+
+/// State for compiling a top-level value
+struct Compiler<'a, T: Extern, L: Locals> {
+    /// Fiber whose current environment is used to expand macros, if any
+    fiber: Option<&'a Fiber<T, L>>,
+    /// Source file of spans for value being compiled, if any
+    file: Option<Arc<str>>,
+    /// Location marked for the code currently being compiled, if any
+    loc: Option<SourceLoc>,
+    /// Function bodies enclosing code currently being compiled, innermost last
+    scopes: Vec<FnScope>,
+    /// Whether or not all function bodies are compiled as dynamic
+    dynamic: bool,
+}
+
+/// Compilation context threaded through compilation of a value
+type Cx<'a, 'b, T, L> = &'a mut Compiler<'b, T, L>;
+
 /// Compile a value to bytecode representation
 pub fn compile<T: Extern, L: Locals>(v: &Val<T, L>) -> Result<Bytecode<T, L>> {
+    compile_toplevel(v, None, Compiler::new(None, None))
+}
+
+/// Compile a value to bytecode representation, with symbols of function bodies bound in a new environment
+/// for each call instead of being lexically addressed, e.g. to compare against lexical addressing
+pub fn compile_dynamic<T: Extern, L: Locals>(v: &Val<T, L>) -> Result<Bytecode<T, L>> {
+    let mut cx = Compiler::new(None, None);
+    cx.dynamic = true;
+    compile_toplevel(v, None, cx)
+}
+
+/// Compile a value to bytecode representation, expanding macros bound in current environment of fiber
+pub fn compile_in<T: Extern, L: Locals>(
+    v: &Val<T, L>,
+    fiber: &Fiber<T, L>,
+) -> Result<Bytecode<T, L>> {
+    compile_toplevel(v, None, Compiler::new(Some(fiber), None))
+}
+
+/// Compile a value to bytecode representation, marking instructions with source locations from
+/// span of parsed value. Macros are expanded within given fiber, if any.
+pub fn compile_spanned<T: Extern, L: Locals>(
+    v: &Val<T, L>,
+    span: &Span,
+    file: Option<&str>,
+    fiber: Option<&Fiber<T, L>>,
+) -> Result<Bytecode<T, L>> {
+    compile_toplevel(v, Some(span), Compiler::new(fiber, file.map(Arc::from)))
+}
+
+/// Compile a value outside of any function body, e.g. for forms evaluated at runtime while compiling
+/// macro expansions
+fn compile_toplevel<T: Extern, L: Locals>(
+    v: &Val<T, L>,
+    span: Option<&Span>,
+    mut cx: Compiler<T, L>,
+) -> Result<Bytecode<T, L>> {
+    compile_cx(v, span, &mut cx)
+}
+
+impl<'a, T: Extern, L: Locals> Compiler<'a, T, L> {
+    fn new(fiber: Option<&'a Fiber<T, L>>, file: Option<Arc<str>>) -> Self {
+        Self {
+            fiber,
+            file,
+            loc: None,
+            scopes: vec![],
+            dynamic: false,
+        }
+    }
+
+    /// Compile with given location marked as the current location, returning previous location
+    fn with_loc(
+        &mut self,
+        loc: Option<SourceLoc>,
+        f: impl FnOnce(&mut Self) -> Result<Bytecode<T, L>>,
+    ) -> (Option<SourceLoc>, Result<Bytecode<T, L>>) {
+        let prev = std::mem::replace(&mut self.loc, loc);
+        let res = f(self);
+        self.loc = prev.clone();
+        (prev, res)
+    }
+
+    /// Compile within function body with given scope, returning the scope after compiling
+    fn in_scope<R>(&mut self, scope: FnScope, f: impl FnOnce(&mut Self) -> R) -> (FnScope, R) {
+        self.scopes.push(scope);
+        let res = f(self);
+        let scope = self
+            .scopes
+            .pop()
+            .expect("Scope of function body should be on stack");
+        (scope, res)
+    }
+
+    /// Mark function bodies enclosing code being compiled as dynamic, since code binds symbols
+    /// in environment or evaluates forms at runtime that may refer to any enclosing variable
+    fn mark_dynamic(&mut self) {
+        self.scopes.iter_mut().for_each(|s| s.dynamic = true);
+    }
+
+    /// Instruction accessing symbol as local slot or upvalue if symbol resolves to a variable of
+    /// enclosing function bodies, or in current environment otherwise
+    fn sym_inst(
+        &mut self,
+        sym: &SymbolId,
+        local: fn(usize) -> Inst<T, L>,
+        upval: fn(usize) -> Inst<T, L>,
+        env: fn(SymbolId) -> Inst<T, L>,
+    ) -> Inst<T, L> {
+        match resolve(&mut self.scopes, sym) {
+            Some((0, slot)) => local(slot),
+            Some((depth, slot)) => upval(capture(&mut self.scopes, depth, slot, sym)),
+            None => env(sym.clone()),
+        }
+    }
+
+    /// Instruction defining symbol as value at TOS in innermost function body, or in current environment
+    fn def_inst(&mut self, sym: &SymbolId) -> Inst<T, L> {
+        match self.scopes.last_mut() {
+            Some(scope) if !scope.dynamic => Inst::SetLocal(scope.slot(sym)),
+            _ => Inst::DefSym(sym.clone()),
+        }
+    }
+}
+
+/// Compile a value with its span, if any, to bytecode representation within compilation context
+fn compile_cx<T: Extern, L: Locals>(
+    v: &Val<T, L>,
+    span: Option<&Span>,
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
+    // Mark location for list expressions starting on a new line
+    let loc = match (v, span) {
+        (Val::List(_), Some(span))
+            if cx.loc.as_ref().map(|l| l.pos.line) != Some(span.pos.line) =>
+        {
+            SourceLoc {
+                file: cx.file.clone(),
+                pos: span.pos,
+            }
+        }
+        _ => return compile_expr(v, span, cx),
+    };
+
+    let (prev, code) = cx.with_loc(Some(loc.clone()), |cx| compile_expr(v, span, cx));
+    let mut bc = Bytecode::new();
+    bc.mark(loc);
+    bc.extend(code?);
+    if let Some(prev) = prev {
+        bc.mark(prev);
+    }
+    Ok(bc)
+}
+
+/// Compile a single expression
+fn compile_expr<T: Extern, L: Locals>(
+    v: &Val<T, L>,
+    span: Option<&Span>,
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
     match v {
         Val::List(l) => {
             let (first, args) = l.split_first().ok_or(Error::InvalidExpression(
                 "Empty list expression".to_string(),
             ))?;
+            // spans of list items and arguments, which are empty if expression is not spanned
+            let spans = span.map(|s| &s.children[..]).unwrap_or_default();
+            let arg_spans = spans.get(1..).unwrap_or_default();
 
             // special forms
             if let Val::Symbol(s) = first {
                 match s.as_str() {
-                    "begin" => return compile_begin(args),
-                    "def" => return compile_def(args),
-                    "fn" => return compile_fn(args),
-                    "defn" => return compile_defn(args),
-                    "if" => return compile_if(args),
-                    "cond" => return compile_cond(args),
-                    "lambda" => return compile_lambda(args),
-                    "let" => return compile_let(args),
+                    "begin" => return compile_begin(args, arg_spans, cx),
+                    "def" => return compile_def(args, arg_spans, cx),
+                    "fn" => return compile_fn(args, arg_spans, cx),
+                    "defn" => return compile_defn(args, arg_spans, cx),
+                    "if" => return compile_if(args, arg_spans, cx),
+                    "cond" => return compile_cond(args, arg_spans, cx),
+                    "and" => return compile_and(args, arg_spans, cx),
+                    "or" => return compile_or(args, arg_spans, cx),
+                    "when" => return compile_when(args, arg_spans, cx, true),
+                    "unless" => return compile_when(args, arg_spans, cx, false),
+                    "lambda" => return compile_lambda(args, arg_spans, cx),
+                    "let" => return compile_let(args, arg_spans, cx),
                     "quote" => return compile_quote(args),
-                    "set" => return compile_set(args),
-                    "try" => return compile_try(args),
-                    "eval" => return compile_eval(args),
-                    "yield" => return compile_yield(args),
-                    "loop" => return compile_loop(args),
-                    "match" => return compile_match(args),
+                    "set" => return compile_set(args, arg_spans, cx),
+                    "try" => return compile_try(args, cx),
+                    "eval" => return compile_eval(args, arg_spans, cx),
+                    "yield" => return compile_yield(args, arg_spans, cx),
+                    "loop" => return compile_loop(args, arg_spans, cx),
+                    "match" => return compile_match(args, cx),
+                    "defmacro" => return compile_defmacro(args, arg_spans, cx),
+                    "quasiquote" => return compile_quasiquote(args, arg_spans, cx),
+                    "unquote" | "unquote_splice" => {
+                        return Err(Error::InvalidExpression(format!(
+                            "{s} is only valid within quasiquote"
+                        )))
+                    }
                     _ => (),
                 }
+
+                if let Some(fiber) = cx.fiber {
+                    if let Some(expanded) = fiber.macroexpand_1(v)? {
+                        return compile_cx(&expanded, None, cx);
+                    }
+                }
             }
-            compile_func_call(first, args)
+            compile_func_call(first, spans.first(), args, arg_spans, cx)
         }
-        Val::Symbol(s) => Ok(vec![Inst::GetSym(s.clone())]),
-        _ => Ok(vec![Inst::PushConst(v.clone())]),
+        Val::Symbol(s) => {
+            Ok(vec![cx.sym_inst(s, Inst::GetLocal, Inst::GetUpval, Inst::GetSym)].into())
+        }
+        Val::Map(m) => compile_map(m, cx),
+        _ => Ok(vec![Inst::PushConst(v.clone())].into()),
+    }
+}
+
+/// Compile map literal, where each value is evaluated
+fn compile_map<T: Extern, L: Locals>(m: &Map<T, L>, cx: Cx<T, L>) -> Result<Bytecode<T, L>> {
+    if m.values().all(is_const) {
+        return Ok(vec![Inst::PushConst(Val::Map(m.clone()))].into());
+    }
+    let entries = m
+        .iter()
+        .map(|(k, v)| Ok((k, compile_cx(v, None, cx)?)))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(map_code(entries))
+}
+
+/// Whether or not value evaluates to itself
+fn is_const<T: Extern, L: Locals>(v: &Val<T, L>) -> bool {
+    match v {
+        Val::Symbol(_) | Val::List(_) => false,
+        Val::Map(m) => m.values().all(is_const),
+        _ => true,
     }
 }
 
 /// Compile special form builtin def
-fn compile_def<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>> {
+fn compile_def<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
     match args {
-        [Val::Symbol(symbol), value] => {
-            let mut inst = compile(value)?;
-            inst.push(Inst::DefSym(symbol.clone()));
-            Ok(inst)
-        }
         [pat, value] => {
-            let mut inst = compile(value)?;
-            inst.push(Inst::PushConst(pat.clone()));
-            inst.push(Inst::DefBind);
-            Ok(inst)
+            let value = compile_cx(value, spans.get(1), cx)?;
+            Ok(def_code(pat, value, cx))
         }
         _ => Err(Error::InvalidExpression(
             "def accepts one symbol and one form as arguments".to_string(),
@@ -93,8 +367,29 @@ fn compile_def<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L
     }
 }
 
+/// Bytecode binding symbol or pattern to value produced by given code
+fn def_code<T: Extern, L: Locals>(
+    pat: &Val<T, L>,
+    mut inst: Bytecode<T, L>,
+    cx: Cx<T, L>,
+) -> Bytecode<T, L> {
+    match pat {
+        Val::Symbol(symbol) => inst.push(cx.def_inst(symbol)),
+        pat => {
+            cx.mark_dynamic();
+            inst.push(Inst::PushConst(pat.clone()));
+            inst.push(Inst::DefBind);
+        }
+    }
+    inst
+}
+
 /// Compile special form builtin set
-fn compile_set<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>> {
+fn compile_set<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
     let (symbol, value) = match args {
         [Val::Symbol(symbol), value] => (symbol, value),
         _ => {
@@ -104,17 +399,23 @@ fn compile_set<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L
         }
     };
 
-    let mut inst = compile(value)?;
-    inst.push(Inst::SetSym(symbol.clone()));
+    let mut inst = compile_cx(value, spans.get(1), cx)?;
+    inst.push(cx.sym_inst(symbol, Inst::SetLocal, Inst::SetUpval, Inst::SetSym));
     Ok(inst)
 }
 
 // TODO: Replace `fn` with a macro
 /// Compile fn
-fn compile_fn<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>> {
-    let (params, docs, body) = match args {
-        [params, Val::String(doc), body @ ..] if !body.is_empty() => (params, Some(doc), body),
-        [params, body @ ..] if !body.is_empty() => (params, None, body),
+fn compile_fn<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
+    let (params, docs, body, spans) = match args {
+        [params, Val::String(doc), body @ ..] if !body.is_empty() => {
+            (params, Some(doc), body, spans.get(2..))
+        }
+        [params, body @ ..] if !body.is_empty() => (params, None, body, spans.get(1..)),
         _ => {
             return Err(Error::InvalidExpression(
                 "defn expects at least three arguments with nonempty body".to_string(),
@@ -122,27 +423,20 @@ fn compile_fn<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>
         }
     };
 
-    let mut lambda = vec![Val::symbol("lambda"), params.clone()];
-    if let Some(docs) = docs {
-        lambda.push(Val::String(docs.clone()));
-    }
-    lambda.push(Val::List(
-        std::iter::once(Val::symbol("begin"))
-            .chain(body.iter().cloned())
-            .collect(),
-    ));
-    let inst = compile(&Val::List(lambda))?;
-
-    Ok(inst)
+    compile_func_obj(params, docs, body, spans.unwrap_or_default(), cx)
 }
 // TODO: Replace `defn` with a macro
 /// Compile defn
-fn compile_defn<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>> {
-    let (name, params, docs, body) = match args {
+fn compile_defn<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
+    let (name, params, docs, body, spans) = match args {
         [name, params, Val::String(doc), body @ ..] if !body.is_empty() => {
-            (name, params, Some(doc), body)
+            (name, params, Some(doc), body, spans.get(3..))
         }
-        [name, params, body @ ..] if !body.is_empty() => (name, params, None, body),
+        [name, params, body @ ..] if !body.is_empty() => (name, params, None, body, spans.get(2..)),
         _ => {
             return Err(Error::InvalidExpression(
                 "defn expects at least three arguments with nonempty body".to_string(),
@@ -150,30 +444,46 @@ fn compile_defn<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T,
         }
     };
 
-    let mut lambda = vec![Val::symbol("lambda"), params.clone()];
-    if let Some(docs) = docs {
-        lambda.push(Val::String(docs.clone()));
-    }
-    lambda.push(Val::List(
-        std::iter::once(Val::symbol("begin"))
-            .chain(body.iter().cloned())
-            .collect(),
-    ));
+    let func = compile_func_obj(params, docs, body, spans.unwrap_or_default(), cx)?;
+    Ok(def_code(name, func, cx))
+}
 
-    let inst = compile(&Val::List(vec![
-        Val::symbol("def"),
-        name.clone(),
-        Val::List(lambda),
-    ]))?;
+/// Compile defmacro
+fn compile_defmacro<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
+    let (name, params, docs, body, spans) = match args {
+        [Val::Symbol(name), params, Val::String(doc), body @ ..] if !body.is_empty() => {
+            (name, params, Some(doc), body, spans.get(3..))
+        }
+        [Val::Symbol(name), params, body @ ..] if !body.is_empty() => {
+            (name, params, None, body, spans.get(2..))
+        }
+        _ => {
+            return Err(Error::InvalidExpression(
+                "defmacro expects a symbol, parameter list, and nonempty body".to_string(),
+            ))
+        }
+    };
 
+    cx.mark_dynamic();
+    let mut inst = compile_func_obj(params, docs, body, spans.unwrap_or_default(), cx)?;
+    inst.push(Inst::MakeMacro);
+    inst.push(Inst::DefSym(name.clone()));
     Ok(inst)
 }
 
 /// Compile special form lambda
-fn compile_lambda<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>> {
-    let (param, docs, body) = match args {
-        [param, Val::String(docs), body] => (param, Some(docs), body),
-        [param, body] => (param, None, body),
+fn compile_lambda<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
+    let (param, docs, body, spans) = match args {
+        [param, Val::String(docs), body] => (param, Some(docs), body, spans.get(2..)),
+        [param, body] => (param, None, body, spans.get(1..)),
         _ => {
             return Err(Error::InvalidExpression(
                 "lambda expects a parameter list and body expression as arguments".to_string(),
@@ -181,18 +491,269 @@ fn compile_lambda<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T
         }
     };
 
-    let bytecode = compile(body)?;
+    let body = std::slice::from_ref(body);
+    compile_func_obj(param, docs, body, spans.unwrap_or_default(), cx)
+}
+
+/// Compile function object with body evaluating each expression in order, given spans of body
+fn compile_func_obj<T: Extern, L: Locals>(
+    params: &Val<T, L>,
+    docs: Option<&String>,
+    body: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
+    let (params, defaults) = lambda_list(params)?;
+    let forms = || body.iter().chain(defaults.iter().map(|(_, d)| *d));
+    let mut defs = HashSet::new();
+    forms().for_each(|f| collect_defs(f, &mut defs));
+
+    let force_dynamic = cx.dynamic;
+    let mut compile_body = |dynamic| {
+        let scope = FnScope {
+            slots: Params::parse(&params)?.bound().cloned().collect(),
+            defs: defs.clone(),
+            upvals: vec![],
+            dynamic,
+        };
+        let (scope, bytecode) = cx.in_scope(scope, |cx| {
+            // function body runs in a new callframe, without a location
+            let (_, bytecode) = cx.with_loc(None, |cx| {
+                let mut bc = Bytecode::new();
+                for (sym, default) in &defaults {
+                    bc.extend(default_code(sym, default, cx)?);
+                }
+                bc.extend(compile_begin(body, spans, cx)?);
+                mark_tail_calls(&mut bc);
+                Ok(bc)
+            });
+            bytecode
+        });
+        Ok::<_, Error>((scope, bytecode?))
+    };
+
+    let dynamic = force_dynamic || forms().any(is_dynamic);
+    let (mut scope, mut bytecode) = compile_body(dynamic)?;
+    if scope.dynamic && !dynamic {
+        // forms evaluated at runtime were found after expanding macros
+        (scope, bytecode) = compile_body(true)?;
+    }
 
     Ok(vec![
-        Inst::PushConst(param.clone()),
-        Inst::PushConst(if docs.is_some() {
-            Val::String(docs.unwrap().clone())
-        } else {
-            Val::Nil
+        Inst::PushConst(Val::List(params.into_iter().map(Val::Symbol).collect())),
+        Inst::PushConst(match docs {
+            Some(docs) => Val::String(docs.clone()),
+            None => Val::Nil,
         }),
         Inst::PushConst(Val::Bytecode(bytecode)),
-        Inst::MakeFunc,
-    ])
+        match scope.dynamic {
+            true => Inst::MakeFunc,
+            false => Inst::MakeClosure(Arc::new(Scope {
+                slots: scope.slots,
+                upvals: scope.upvals,
+            })),
+        },
+    ]
+    .into())
+}
+
+/// Resolve symbol to (depth, slot) of local variable in function bodies, where depth of innermost
+/// function body is 0. Symbols are not resolved beyond dynamic function bodies.
+fn resolve(scopes: &mut [FnScope], sym: &SymbolId) -> Option<(usize, usize)> {
+    for (depth, scope) in scopes.iter_mut().rev().enumerate() {
+        if scope.dynamic {
+            return None;
+        }
+        if let Some(slot) = scope.slots.iter().position(|s| s == sym) {
+            return Some((depth, slot));
+        }
+        // variables may be referred to before they are defined in function body, e.g. by closures or
+        // loops. Reading the slot before it is set falls back to the environment.
+        if scope.defs.contains(sym) {
+            return Some((depth, scope.slot(sym)));
+        }
+    }
+    None
+}
+
+/// Upvalue of innermost function body capturing local slot of function body at given depth.
+/// Function bodies in between capture the variable as their upvalues.
+fn capture(scopes: &mut [FnScope], depth: usize, slot: usize, sym: &SymbolId) -> usize {
+    let (inner, outer) = scopes
+        .split_last_mut()
+        .expect("Captured variable should be within function body");
+    let from = match depth {
+        1 => Capture::Local(slot),
+        _ => Capture::Upval(capture(outer, depth - 1, slot, sym)),
+    };
+    match inner.upvals.iter().position(|(_, c)| *c == from) {
+        Some(idx) => idx,
+        None => {
+            inner.upvals.push((sym.clone(), from));
+            inner.upvals.len() - 1
+        }
+    }
+}
+
+impl FnScope {
+    /// Local slot for symbol, allocating a new slot if needed
+    fn slot(&mut self, sym: &SymbolId) -> usize {
+        match self.slots.iter().position(|s| s == sym) {
+            Some(slot) => slot,
+            None => {
+                self.slots.push(sym.clone());
+                self.slots.len() - 1
+            }
+        }
+    }
+}
+
+/// Collect symbols defined by form within function body, excluding those in nested function bodies
+fn collect_defs<T: Extern, L: Locals>(v: &Val<T, L>, defs: &mut HashSet<SymbolId>) {
+    let l = match v {
+        Val::List(l) => l,
+        _ => return,
+    };
+    let head = match l.first() {
+        Some(Val::Symbol(s)) => s.as_str(),
+        _ => "",
+    };
+    match (head, &l[..]) {
+        ("def", [_, Val::Symbol(sym), value]) => {
+            defs.insert(sym.clone());
+            collect_defs(value, defs);
+        }
+        ("defn", [_, Val::Symbol(sym), ..]) => {
+            defs.insert(sym.clone());
+        }
+        ("let", [_, Val::List(bindings), ..]) => {
+            // binding values are evaluated in enclosing function body
+            for b in bindings {
+                if let Val::List(pair) = b {
+                    pair.iter().skip(1).for_each(|v| collect_defs(v, defs));
+                }
+            }
+        }
+        ("fn" | "lambda" | "defmacro" | "quote" | "quasiquote", _) => (),
+        _ => l.iter().for_each(|v| collect_defs(v, defs)),
+    }
+}
+
+/// Whether or not form binds symbols in environment or evaluates forms at runtime, which
+/// requires binding symbols in an environment for enclosing function bodies
+fn is_dynamic<T: Extern, L: Locals>(v: &Val<T, L>) -> bool {
+    match v {
+        Val::List(l) => match (l.first(), l.get(1)) {
+            (Some(Val::Symbol(s)), _) if s.as_str() == "quote" => false,
+            (Some(Val::Symbol(s)), _)
+                if matches!(s.as_str(), "try" | "eval" | "match" | "defmacro") =>
+            {
+                true
+            }
+            (Some(Val::Symbol(s)), pat) if s.as_str() == "def" => {
+                !matches!(pat, Some(Val::Symbol(_))) || l.iter().any(is_dynamic)
+            }
+            _ => l.iter().any(is_dynamic),
+        },
+        Val::Map(m) => m.values().any(is_dynamic),
+        _ => false,
+    }
+}
+
+/// Replace calls in tail position of function body with [Inst::TailCallFunc].
+/// A call is in tail position when only jumps follow it before end of body,
+/// e.g. last form of `begin`, or branches of `if`, `cond`, and `match` in tail position.
+fn mark_tail_calls<T: Extern, L: Locals>(bc: &mut Bytecode<T, L>) {
+    let bc = &mut bc.inst;
+    let returns_after = |mut ip: usize| loop {
+        match bc.get(ip) {
+            None => return true,
+            Some(Inst::JumpFwd(fwd)) => ip += fwd + 1,
+            Some(_) => return false,
+        }
+    };
+    let tail_calls = (0..bc.len())
+        .filter(|ip| matches!(bc[*ip], Inst::CallFunc(_)) && returns_after(ip + 1))
+        .collect::<Vec<_>>();
+    for ip in tail_calls {
+        if let Inst::CallFunc(nargs) = bc[ip] {
+            bc[ip] = Inst::TailCallFunc(nargs);
+        }
+    }
+}
+
+/// Parameter symbols, with default value forms of optional and keyword parameters
+type LambdaList<'a, T, L> = (Vec<SymbolId>, Vec<(&'a SymbolId, &'a Val<T, L>)>);
+
+/// Split lambda list into parameter symbols, and default values of optional and keyword parameters
+fn lambda_list<T: Extern, L: Locals>(params: &Val<T, L>) -> Result<LambdaList<'_, T, L>> {
+    let items = match params {
+        Val::List(items) => items,
+        _ => {
+            return Err(Error::InvalidExpression(format!(
+                "Parameters should be a list - found {params}"
+            )))
+        }
+    };
+
+    let mut symbols = vec![];
+    let mut defaults = vec![];
+    for p in items {
+        match p {
+            Val::Symbol(s) => symbols.push(s.clone()),
+            Val::List(l) => match &l[..] {
+                [Val::Symbol(s), default] => {
+                    symbols.push(s.clone());
+                    defaults.push((s, default));
+                }
+                _ => {
+                    return Err(Error::InvalidExpression(format!(
+                    "Parameter with default value should be a list of symbol and value - found {p}"
+                )))
+                }
+            },
+            _ => {
+                return Err(Error::InvalidExpression(format!(
+                    "Parameter should be a symbol - found {p}"
+                )))
+            }
+        }
+    }
+
+    let parsed = Params::parse(&symbols)?;
+    for (s, _) in &defaults {
+        if !parsed.optional.contains(s) && !parsed.key.contains(s) {
+            return Err(Error::InvalidExpression(format!(
+                "Only &optional and &key parameters can have default values - found {s}"
+            )));
+        }
+    }
+
+    Ok((symbols, defaults))
+}
+
+/// Bytecode setting parameter to default value when argument is nil
+fn default_code<T: Extern, L: Locals>(
+    sym: &SymbolId,
+    default: &Val<T, L>,
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
+    let is_nil = vec![
+        Inst::PushConst(Val::NativeFn(eq_fn())),
+        cx.sym_inst(sym, Inst::GetLocal, Inst::GetUpval, Inst::GetSym),
+        Inst::PushConst(Val::Nil),
+        Inst::CallFunc(2),
+    ];
+    let mut set_default = compile_cx(default, None, cx)?;
+    set_default.push(cx.def_inst(sym));
+
+    let mut bc = if_code(
+        is_nil.into(),
+        set_default,
+        vec![Inst::PushConst(Val::Nil)].into(),
+    );
+    bc.push(Inst::PopTop);
+    Ok(bc)
 }
 
 /// Compile quote special forms
@@ -205,10 +766,14 @@ fn compile_quote<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T,
             ))
         }
     };
-    Ok(vec![Inst::PushConst(v.clone())])
+    Ok(vec![Inst::PushConst(v.clone())].into())
 }
 
-fn compile_eval<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>> {
+fn compile_eval<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
     let v = match args {
         [v] => v,
         _ => {
@@ -218,25 +783,29 @@ fn compile_eval<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T,
         }
     };
 
-    let mut bc = compile(v)?;
+    cx.mark_dynamic();
+    let mut bc = compile_cx(v, spans.first(), cx)?;
     bc.push(Inst::Eval(false));
     Ok(bc)
 }
 
-/// Compile function calls
+/// Compile function calls, given spans of function and arguments
 fn compile_func_call<T: Extern, L: Locals>(
     func: &Val<T, L>,
+    func_span: Option<&Span>,
     args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
 ) -> Result<Bytecode<T, L>> {
-    let mut bytecode = vec![];
+    let mut bytecode = Bytecode::new();
     let nargs = args.len();
 
-    let func_code = compile(func)?;
+    let func_code = compile_cx(func, func_span, cx)?;
     let arg_code = args
         .iter()
-        .map(compile)
-        .collect::<Result<Vec<_>>>()?
-        .concat();
+        .enumerate()
+        .map(|(i, a)| compile_cx(a, spans.get(i), cx))
+        .collect::<Result<Bytecode<_, _>>>()?;
 
     bytecode.extend(func_code);
     bytecode.extend(arg_code);
@@ -246,7 +815,11 @@ fn compile_func_call<T: Extern, L: Locals>(
 }
 
 /// Compile builtin let
-fn compile_let<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>> {
+fn compile_let<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
     let (bindings, body) = match args.split_first() {
         Some((Val::List(bindings), body)) => (bindings, body),
         _ => {
@@ -256,9 +829,12 @@ fn compile_let<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L
         }
     };
 
+    // spans of binding pairs
+    let binding_spans = spans.first().map(|s| &s.children[..]).unwrap_or_default();
+
     let mut params: Vec<Val<_, _>> = vec![]; /* get first symbol in each binding pair */
-    let mut args: Vec<Val<_, _>> = vec![]; /* get second symbol in each thing */
-    for b in bindings {
+    let mut args: Vec<(&Val<_, _>, Option<&Span>)> = vec![]; /* get second symbol in each thing */
+    for (i, b) in bindings.iter().enumerate() {
         let pair = match b {
             Val::List(pair) => pair,
             _ => {
@@ -270,7 +846,8 @@ fn compile_let<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L
         match &pair[..] {
             [sym, val] => {
                 params.push(sym.clone());
-                args.push(val.clone());
+                let span = binding_spans.get(i).and_then(|s| s.children.get(1));
+                args.push((val, span));
             }
 
             _ => {
@@ -281,30 +858,45 @@ fn compile_let<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L
         }
     }
 
-    let mut body_block = vec![Val::symbol("begin")];
-    body_block.extend(body.iter().cloned());
-
-    let mut lambda = vec![Val::List(vec![
-        Val::symbol("lambda"),
-        Val::List(params),
-        Val::List(body_block),
-    ])];
-    lambda.extend(args);
-
-    compile(&Val::List(lambda))
+    // ((lambda PARAMS (begin BODY...)) ARGS...)
+    let body_spans = spans.get(1..).unwrap_or_default();
+    let mut inst = compile_func_obj(&Val::List(params), None, body, body_spans, cx)?;
+    for (a, span) in &args {
+        inst.extend(compile_cx(a, *span, cx)?);
+    }
+    inst.push(Inst::CallFunc(args.len()));
+    Ok(inst)
 }
 
 /// Compile builtin begin
-fn compile_begin<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>> {
-    let mut inst = vec![];
+fn compile_begin<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
+    let mut inst = Bytecode::new();
     let mut is_first = true;
-    for a in args {
+    let mut defer = false;
+    for (i, a) in args.iter().enumerate() {
         if is_first {
             is_first = false;
         } else {
             inst.push(Inst::PopTop); // discard result from previous call
         }
-        inst.extend(compile(a)?);
+        if defer {
+            // compile at runtime, when macros defined by earlier forms are bound
+            inst.push(Inst::PushConst(a.clone()));
+            inst.push(match spans.get(i) {
+                Some(span) => Inst::EvalSpanned(Arc::new(SourceSpan {
+                    file: cx.file.clone(),
+                    span: span.clone(),
+                })),
+                None => Inst::Eval(false),
+            });
+        } else {
+            inst.extend(compile_cx(a, spans.get(i), cx)?);
+        }
+        defer = defer || is_defmacro(a);
     }
 
     if inst.is_empty() {
@@ -314,8 +906,146 @@ fn compile_begin<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T,
     Ok(inst)
 }
 
+/// Whether or not form defines a macro
+fn is_defmacro<T: Extern, L: Locals>(v: &Val<T, L>) -> bool {
+    matches!(v, Val::List(l) if l.first() == Some(&Val::symbol("defmacro")))
+}
+
+/// Compile quasiquote
+fn compile_quasiquote<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
+    match args {
+        [v] => quasi(v, spans.first(), 0, cx),
+        _ => Err(Error::InvalidExpression(
+            "quasiquote expects a single argument".to_string(),
+        )),
+    }
+}
+
+/// Compile quasiquoted form with its span, nested within given depth of inner quasiquotes
+fn quasi<T: Extern, L: Locals>(
+    v: &Val<T, L>,
+    span: Option<&Span>,
+    depth: usize,
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
+    let l = match v {
+        Val::List(l) if has_unquote(v) => l,
+        Val::Map(m) if has_unquote(v) => {
+            let entries = m
+                .iter()
+                .map(|(k, v)| Ok((k, quasi(v, None, depth, cx)?)))
+                .collect::<Result<Vec<_>>>()?;
+            return Ok(map_code(entries));
+        }
+        _ => return Ok(vec![Inst::PushConst(v.clone())].into()),
+    };
+    let spans = span.map(|s| &s.children[..]).unwrap_or_default();
+
+    if let [Val::Symbol(s), e] = &l[..] {
+        match (s.as_str(), depth) {
+            ("unquote", 0) => return compile_cx(e, spans.get(1), cx),
+            ("unquote_splice", 0) => {
+                return Err(Error::InvalidExpression(
+                    "unquote_splice is only valid within a list".to_string(),
+                ))
+            }
+            ("unquote" | "unquote_splice", _) => {
+                return Ok(list_code(vec![
+                    vec![Inst::PushConst(Val::Symbol(s.clone()))].into(),
+                    quasi(e, spans.get(1), depth - 1, cx)?,
+                ]))
+            }
+            ("quasiquote", _) => {
+                return Ok(list_code(vec![
+                    vec![Inst::PushConst(Val::Symbol(s.clone()))].into(),
+                    quasi(e, spans.get(1), depth + 1, cx)?,
+                ]))
+            }
+            _ => (),
+        }
+    }
+
+    // Build list from segments of adjacent elements and spliced lists
+    let mut segments = vec![];
+    let mut elems = vec![];
+    for (i, item) in l.iter().enumerate() {
+        let span = spans.get(i);
+        match item {
+            Val::List(splice)
+                if depth == 0 && splice.first() == Some(&Val::symbol("unquote_splice")) =>
+            {
+                let e = match &splice[..] {
+                    [_, e] => e,
+                    _ => {
+                        return Err(Error::InvalidExpression(
+                            "unquote_splice expects a single argument".to_string(),
+                        ))
+                    }
+                };
+                if !elems.is_empty() {
+                    segments.push(list_code(std::mem::take(&mut elems)));
+                }
+                let e_span = span.and_then(|s| s.children.get(1));
+                segments.push(compile_cx(e, e_span, cx)?);
+            }
+            _ => elems.push(quasi(item, span, depth, cx)?),
+        }
+    }
+    if !elems.is_empty() || segments.is_empty() {
+        segments.push(list_code(elems));
+    }
+
+    if segments.len() == 1 {
+        return Ok(segments.remove(0));
+    }
+    let nsegments = segments.len();
+    let mut inst = Bytecode::from(vec![Inst::PushConst(Val::NativeFn(concat_fn()))]);
+    inst.extend(segments.into_iter().collect::<Bytecode<_, _>>());
+    inst.push(Inst::CallFunc(nsegments));
+    Ok(inst)
+}
+
+/// Whether or not form contains unquoted forms
+fn has_unquote<T: Extern, L: Locals>(v: &Val<T, L>) -> bool {
+    match v {
+        Val::List(l) => match l.first() {
+            Some(Val::Symbol(s)) if s.as_str() == "unquote" || s.as_str() == "unquote_splice" => {
+                true
+            }
+            _ => l.iter().any(has_unquote),
+        },
+        Val::Map(m) => m.values().any(has_unquote),
+        _ => false,
+    }
+}
+
+/// Bytecode creating a map from keys, and values produced by code for each key
+fn map_code<T: Extern, L: Locals>(entries: Vec<(&MapKey, Bytecode<T, L>)>) -> Bytecode<T, L> {
+    let nargs = 2 * entries.len();
+    let mut inst = Bytecode::from(vec![Inst::PushConst(Val::NativeFn(hashmap_fn()))]);
+    for (k, code) in entries {
+        inst.push(Inst::PushConst(k.clone().into()));
+        inst.extend(code);
+    }
+    inst.push(Inst::CallFunc(nargs));
+    inst
+}
+
+/// Bytecode creating a list from values produced by code for each element
+fn list_code<T: Extern, L: Locals>(elems: Vec<Bytecode<T, L>>) -> Bytecode<T, L> {
+    let nelems = elems.len();
+    let mut inst = Bytecode::from(vec![Inst::PushConst(Val::NativeFn(list_fn()))]);
+    inst.extend(elems.into_iter().collect::<Bytecode<_, _>>());
+    inst.push(Inst::CallFunc(nelems));
+    inst
+}
+
 // TODO: try can be macro w/ quote
-fn compile_try<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>> {
+fn compile_try<T: Extern, L: Locals>(args: &[Val<T, L>], cx: Cx<T, L>) -> Result<Bytecode<T, L>> {
     let v = match args {
         [v] => v,
         _ => {
@@ -326,11 +1056,16 @@ fn compile_try<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L
     };
 
     // `try` is quoting
-    Ok(vec![Inst::PushConst(v.clone()), Inst::Eval(true)])
+    cx.mark_dynamic();
+    Ok(vec![Inst::PushConst(v.clone()), Inst::Eval(true)].into())
 }
 
 /// Compile if
-fn compile_if<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>> {
+fn compile_if<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
     let (cond, t, f) = match args {
         [c, t, f] => (c, t, f),
         [c, t] => (c, t, &Val::Nil),
@@ -341,24 +1076,35 @@ fn compile_if<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>
         }
     };
 
-    let mut bc = compile(cond)?;
-    let t_code = compile(t)?;
-    let f_code = compile(f)?;
+    Ok(if_code(
+        compile_cx(cond, spans.first(), cx)?,
+        compile_cx(t, spans.get(1), cx)?,
+        compile_cx(f, spans.get(2), cx)?,
+    ))
+}
 
+/// Bytecode for branching on value of cond code
+fn if_code<T: Extern, L: Locals>(
+    mut bc: Bytecode<T, L>,
+    t_code: Bytecode<T, L>,
+    f_code: Bytecode<T, L>,
+) -> Bytecode<T, L> {
     bc.push(Inst::PopJumpFwdIfTrue(f_code.len() + 1));
     bc.extend(f_code);
     bc.push(Inst::JumpFwd(t_code.len()));
     bc.extend(t_code);
-
-    Ok(bc)
+    bc
 }
 
 // TODO: Replace `cond` with a macro
 /// Compile cond
-fn compile_cond<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>> {
-    let mut res = Val::Nil;
-
-    for f in args.iter().rev() {
+fn compile_cond<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
+    let mut pairs = vec![];
+    for (i, f) in args.iter().enumerate() {
         let pair = match f {
             Val::List(pair) => pair,
             _ => {
@@ -368,7 +1114,7 @@ fn compile_cond<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T,
             }
         };
         let (cond, expr) = match &pair[..] {
-            [cond, expr] => (cond.clone(), expr.clone()),
+            [cond, expr] => (cond, expr),
             _ => {
                 return Err(Error::UnexpectedArguments(
                     "cond expects a list of pairs".to_string(),
@@ -376,15 +1122,99 @@ fn compile_cond<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T,
             }
         };
 
-        // transform to nested `if`
-        res = Val::List(vec![Val::symbol("if"), cond, expr, res]);
+        let pair_spans = spans.get(i).map(|s| &s.children[..]).unwrap_or_default();
+        pairs.push((cond, expr, pair_spans));
     }
 
-    compile(&res)
+    // compile as nested `if`
+    let mut res = compile_cx(&Val::Nil, None, cx)?;
+    for (cond, expr, spans) in pairs.into_iter().rev() {
+        let cond = compile_cx(cond, spans.first(), cx)?;
+        res = if_code(cond, compile_cx(expr, spans.get(1), cx)?, res);
+    }
+    Ok(res)
+}
+
+/// Compile short-circuiting `and`, which evaluates to first false value or last value
+fn compile_and<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
+    let (last, rest) = match args.split_last() {
+        Some(split) => split,
+        None => return Ok(vec![Inst::PushConst(Val::Bool(true))].into()),
+    };
+
+    let mut res = compile_cx(last, spans.get(rest.len()), cx)?;
+    for (i, v) in rest.iter().enumerate().rev() {
+        let mut bc = compile_cx(v, spans.get(i), cx)?;
+        bc.push(Inst::DupTop);
+        bc.push(Inst::PopJumpFwdIfTrue(1));
+        bc.push(Inst::JumpFwd(res.len() + 1));
+        bc.push(Inst::PopTop);
+        bc.extend(res);
+        res = bc;
+    }
+    Ok(res)
+}
+
+/// Compile short-circuiting `or`, which evaluates to first true value or last value
+fn compile_or<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
+    let (last, rest) = match args.split_last() {
+        Some(split) => split,
+        None => return Ok(vec![Inst::PushConst(Val::Nil)].into()),
+    };
+
+    let mut res = compile_cx(last, spans.get(rest.len()), cx)?;
+    for (i, v) in rest.iter().enumerate().rev() {
+        let mut bc = compile_cx(v, spans.get(i), cx)?;
+        bc.push(Inst::DupTop);
+        bc.push(Inst::PopJumpFwdIfTrue(res.len() + 1));
+        bc.push(Inst::PopTop);
+        bc.extend(res);
+        res = bc;
+    }
+    Ok(res)
+}
+
+/// Compile `when` and `unless`, which evaluate body forms in implicit `begin` if condition is `expected`
+fn compile_when<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+    expected: bool,
+) -> Result<Bytecode<T, L>> {
+    let (cond, body) = match args.split_first() {
+        Some((cond, body)) if !body.is_empty() => (cond, body),
+        _ => {
+            return Err(Error::InvalidExpression(format!(
+                "{} expects a condition and body forms",
+                if expected { "when" } else { "unless" }
+            )))
+        }
+    };
+
+    let cond = compile_cx(cond, spans.first(), cx)?;
+    let body = compile_begin(body, spans.get(1..).unwrap_or_default(), cx)?;
+    let nil = compile_cx(&Val::Nil, None, cx)?;
+    Ok(if expected {
+        if_code(cond, body, nil)
+    } else {
+        if_code(cond, nil, body)
+    })
 }
 
 /// Compile yield statement
-fn compile_yield<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>> {
+fn compile_yield<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
     let v = match args {
         [] => &Val::Nil,
         [v] => v,
@@ -394,14 +1224,18 @@ fn compile_yield<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T,
             ))
         }
     };
-    let mut inst = compile(v)?;
+    let mut inst = compile_cx(v, spans.first(), cx)?;
     inst.push(Inst::YieldTop);
     Ok(inst)
 }
 
 /// Compile loop expr
-fn compile_loop<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>> {
-    let mut inst = compile_begin(args)?;
+fn compile_loop<T: Extern, L: Locals>(
+    args: &[Val<T, L>],
+    spans: &[Span],
+    cx: Cx<T, L>,
+) -> Result<Bytecode<T, L>> {
+    let mut inst = compile_begin(args, spans, cx)?;
     inst.push(Inst::PopTop);
     inst.push(Inst::JumpBck(inst.len() + 1));
     Ok(inst)
@@ -410,7 +1244,7 @@ fn compile_loop<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T,
 // TODO: Implement `gensym`?
 // TODO: Replace `match` with macro
 /// Compile `match` expr
-fn compile_match<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T, L>> {
+fn compile_match<T: Extern, L: Locals>(args: &[Val<T, L>], cx: Cx<T, L>) -> Result<Bytecode<T, L>> {
     // convert to:
     // (let ((_expr EXPR))
     //   (cond
@@ -462,7 +1296,7 @@ fn compile_match<T: Extern, L: Locals>(args: &[Val<T, L>]) -> Result<Bytecode<T,
         ),
     ]);
 
-    compile(&ast)
+    compile_cx(&ast, None, cx)
 }
 
 impl<T: Extern, L: Locals> std::fmt::Display for Inst<T, L> {
@@ -473,9 +1307,17 @@ impl<T: Extern, L: Locals> std::fmt::Display for Inst<T, L> {
             Inst::DefSym(s) => write!(f, "defsym {s}"),
             Inst::DefBind => write!(f, "defbind"),
             Inst::SetSym(s) => write!(f, "setsym {s}"),
+            Inst::GetLocal(slot) => write!(f, "getlocal {slot}"),
+            Inst::SetLocal(slot) => write!(f, "setlocal {slot}"),
+            Inst::GetUpval(idx) => write!(f, "getupval {idx}"),
+            Inst::SetUpval(idx) => write!(f, "setupval {idx}"),
             Inst::MakeFunc => write!(f, "makefn"),
+            Inst::MakeClosure(_) => write!(f, "makeclosure"),
+            Inst::MakeMacro => write!(f, "makemacro"),
             Inst::CallFunc(nargs) => write!(f, "callfn {nargs}"),
+            Inst::TailCallFunc(nargs) => write!(f, "tailcallfn {nargs}"),
             Inst::PopTop => write!(f, "poptop"),
+            Inst::DupTop => write!(f, "duptop"),
             Inst::JumpFwd(o) => write!(f, "jmpfwd {o}"),
             Inst::JumpBck(o) => write!(f, "jmpbck {o}"),
             Inst::PopJumpFwdIfTrue(o) => write!(f, "jmpift {o}"),
@@ -487,6 +1329,16 @@ impl<T: Extern, L: Locals> std::fmt::Display for Inst<T, L> {
                     write!(f, "peval")
                 }
             }
+            Inst::EvalSpanned(_) => write!(f, "evalspanned"),
+        }
+    }
+}
+
+impl std::fmt::Display for SourceLoc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{file}:{}", self.pos.line),
+            None => write!(f, "line {}", self.pos.line),
         }
     }
 }
@@ -495,7 +1347,7 @@ impl<T: Extern, L: Locals> std::fmt::Display for Inst<T, L> {
 mod tests {
     use super::Inst::*;
     use super::*;
-    use crate::parse;
+    use crate::{parse, parse_all};
     use void::Void;
 
     type Val = super::Val<Void, Void>;
@@ -551,8 +1403,8 @@ mod tests {
             Ok(vec![
                 PushConst(Val::List(vec![Val::symbol("x")])),
                 PushConst(Val::Nil),
-                PushConst(Val::Bytecode(vec![GetSym(SymbolId::from("x"))])),
-                MakeFunc
+                PushConst(Val::Bytecode(vec![GetLocal(0)].into())),
+                closure("x", &[])
             ])
         );
 
@@ -561,10 +1413,10 @@ mod tests {
             Ok(vec![
                 PushConst(Val::List(vec![Val::symbol("x")])),
                 PushConst(Val::Nil),
-                PushConst(Val::Bytecode(vec![PushConst(Val::String(
-                    "not_a_docstring".to_string()
-                ))])),
-                MakeFunc
+                PushConst(Val::Bytecode(
+                    vec![PushConst(Val::String("not_a_docstring".to_string()))].into()
+                )),
+                closure("x", &[])
             ])
         );
 
@@ -573,8 +1425,8 @@ mod tests {
             Ok(vec![
                 PushConst(Val::List(vec![Val::symbol("x")])),
                 PushConst(Val::String("docstring".to_string())),
-                PushConst(Val::Bytecode(vec![GetSym(SymbolId::from("x"))])),
-                MakeFunc
+                PushConst(Val::Bytecode(vec![GetLocal(0)].into())),
+                closure("x", &[])
             ])
         );
 
@@ -583,17 +1435,74 @@ mod tests {
             Ok(vec![
                 PushConst(Val::List(vec![Val::symbol("x")])),
                 PushConst(Val::Nil),
-                PushConst(Val::Bytecode(vec![
-                    PushConst(Val::List(vec![])),
-                    PushConst(Val::Nil),
-                    PushConst(Val::Bytecode(vec![GetSym(SymbolId::from("x"))])),
-                    MakeFunc,
-                ])),
-                MakeFunc
+                PushConst(Val::Bytecode(
+                    vec![
+                        PushConst(Val::List(vec![])),
+                        PushConst(Val::Nil),
+                        PushConst(Val::Bytecode(vec![GetUpval(0)].into())),
+                        closure("", &[("x", Capture::Local(0))]),
+                    ]
+                    .into()
+                )),
+                closure("x", &[])
             ])
         );
     }
 
+    #[test]
+    fn compile_tail_calls() {
+        let body = |expr: &str| match compile(&f(expr)).unwrap().remove(2) {
+            PushConst(Val::Bytecode(bc)) => bc.inst,
+            inst => panic!("expected function body - got {inst}"),
+        };
+
+        assert_eq!(
+            body("(fn () (one) (two))"),
+            vec![
+                GetSym(SymbolId::from("one")),
+                CallFunc(0),
+                PopTop,
+                GetSym(SymbolId::from("two")),
+                TailCallFunc(0),
+            ],
+            "last form of body is in tail position"
+        );
+
+        assert_eq!(
+            body("(fn (x) (if (x) (one) (two (three))))"),
+            vec![
+                GetLocal(0),
+                CallFunc(0),
+                PopJumpFwdIfTrue(5),
+                GetSym(SymbolId::from("two")),
+                GetSym(SymbolId::from("three")),
+                CallFunc(0),
+                TailCallFunc(1),
+                JumpFwd(2),
+                GetSym(SymbolId::from("one")),
+                TailCallFunc(0),
+            ],
+            "branches of if in tail position are in tail position, but not condition or arguments"
+        );
+
+        assert_eq!(
+            body("(fn () (loop (one)))"),
+            vec![
+                GetSym(SymbolId::from("one")),
+                CallFunc(0),
+                PopTop,
+                JumpBck(4),
+            ],
+            "calls followed by other instructions are not in tail position"
+        );
+
+        assert_eq!(
+            compile(&f("(one)")),
+            Ok(vec![GetSym(SymbolId::from("one")), CallFunc(0)]),
+            "calls outside of function bodies are not tail calls"
+        );
+    }
+
     #[test]
     fn compile_func_call() {
         assert_eq!(
@@ -639,8 +1548,8 @@ mod tests {
             Ok(vec![
                 PushConst(Val::List(vec![])),
                 PushConst(Val::Nil),
-                PushConst(Val::Bytecode(vec![PushConst(Val::string("hello")),])),
-                MakeFunc,
+                PushConst(Val::Bytecode(vec![PushConst(Val::string("hello")),].into())),
+                closure("", &[]),
                 CallFunc(0),
             ])
         );
@@ -649,8 +1558,8 @@ mod tests {
             Ok(vec![
                 PushConst(Val::List(vec![Val::symbol("x")])),
                 PushConst(Val::Nil),
-                PushConst(Val::Bytecode(vec![GetSym(SymbolId::from("x")),])),
-                MakeFunc,
+                PushConst(Val::Bytecode(vec![GetLocal(0),].into())),
+                closure("x", &[]),
                 PushConst(Val::Int(10)),
                 CallFunc(1),
             ])
@@ -664,13 +1573,16 @@ mod tests {
             Ok(vec![
                 PushConst(Val::List(vec![Val::symbol("x")])),
                 PushConst(Val::Nil),
-                PushConst(Val::Bytecode(vec![
-                    PushConst(Val::List(vec![])),
-                    PushConst(Val::Nil),
-                    PushConst(Val::Bytecode(vec![GetSym(SymbolId::from("x"))])),
-                    MakeFunc
-                ])),
-                MakeFunc,
+                PushConst(Val::Bytecode(
+                    vec![
+                        PushConst(Val::List(vec![])),
+                        PushConst(Val::Nil),
+                        PushConst(Val::Bytecode(vec![GetUpval(0)].into())),
+                        closure("", &[("x", Capture::Local(0))])
+                    ]
+                    .into()
+                )),
+                closure("x", &[]),
                 PushConst(Val::string("hello")),
                 CallFunc(1),
                 CallFunc(0),
@@ -681,13 +1593,16 @@ mod tests {
             Ok(vec![
                 PushConst(Val::List(vec![])),
                 PushConst(Val::Nil),
-                PushConst(Val::Bytecode(vec![
-                    PushConst(Val::List(vec![Val::symbol("x")])),
-                    PushConst(Val::Nil),
-                    PushConst(Val::Bytecode(vec![GetSym(SymbolId::from("x"))])),
-                    MakeFunc
-                ])),
-                MakeFunc,
+                PushConst(Val::Bytecode(
+                    vec![
+                        PushConst(Val::List(vec![Val::symbol("x")])),
+                        PushConst(Val::Nil),
+                        PushConst(Val::Bytecode(vec![GetLocal(0)].into())),
+                        closure("x", &[])
+                    ]
+                    .into()
+                )),
+                closure("", &[]),
                 CallFunc(0),
                 PushConst(Val::string("hello")),
                 CallFunc(1),
@@ -695,6 +1610,103 @@ mod tests {
         );
     }
 
+    #[test]
+    fn compile_locals() {
+        assert_eq!(
+            compile(&f("(lambda (x) (begin (def y x) (set y 1)))")),
+            Ok(vec![
+                PushConst(Val::List(vec![Val::symbol("x")])),
+                PushConst(Val::Nil),
+                PushConst(Val::Bytecode(
+                    vec![
+                        GetLocal(0),
+                        SetLocal(1),
+                        PopTop,
+                        PushConst(Val::Int(1)),
+                        SetLocal(1),
+                    ]
+                    .into()
+                )),
+                closure("x y", &[]),
+            ])
+        );
+        assert_eq!(
+            compile(&f("(lambda (x) (lambda () (lambda () (set x 1))))")),
+            Ok(vec![
+                PushConst(Val::List(vec![Val::symbol("x")])),
+                PushConst(Val::Nil),
+                PushConst(Val::Bytecode(
+                    vec![
+                        PushConst(Val::List(vec![])),
+                        PushConst(Val::Nil),
+                        PushConst(Val::Bytecode(
+                            vec![
+                                PushConst(Val::List(vec![])),
+                                PushConst(Val::Nil),
+                                PushConst(Val::Bytecode(
+                                    vec![PushConst(Val::Int(1)), SetUpval(0)].into()
+                                )),
+                                closure("", &[("x", Capture::Upval(0))]),
+                            ]
+                            .into()
+                        )),
+                        closure("", &[("x", Capture::Local(0))]),
+                    ]
+                    .into()
+                )),
+                closure("x", &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn compile_locals_defined_later() {
+        // closures capture locals defined after closure
+        assert_eq!(
+            compile(&f("(lambda () (begin (def f (lambda () y)) (def y 1)))")),
+            Ok(vec![
+                PushConst(Val::List(vec![])),
+                PushConst(Val::Nil),
+                PushConst(Val::Bytecode(
+                    vec![
+                        PushConst(Val::List(vec![])),
+                        PushConst(Val::Nil),
+                        PushConst(Val::Bytecode(vec![GetUpval(0)].into())),
+                        closure("", &[("y", Capture::Local(0))]),
+                        SetLocal(1),
+                        PopTop,
+                        PushConst(Val::Int(1)),
+                        SetLocal(0),
+                    ]
+                    .into()
+                )),
+                closure("y f", &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn compile_locals_dynamic() {
+        // function bodies that eval or catch errors bind symbols in environment
+        assert_eq!(
+            compile(&f("(lambda (x) (begin (try 1) x))")),
+            Ok(vec![
+                PushConst(Val::List(vec![Val::symbol("x")])),
+                PushConst(Val::Nil),
+                PushConst(Val::Bytecode(
+                    vec![
+                        PushConst(Val::Int(1)),
+                        Eval(true),
+                        PopTop,
+                        GetSym(SymbolId::from("x")),
+                    ]
+                    .into()
+                )),
+                MakeFunc,
+            ])
+        );
+    }
+
     #[test]
     fn compile_begin() {
         assert_eq!(
@@ -758,6 +1770,31 @@ mod tests {
         )
     }
 
+    #[test]
+    fn compile_and_or() {
+        assert_eq!(
+            compile(&f("(and a b)")),
+            Ok(vec![
+                GetSym(SymbolId::from("a")),
+                DupTop,
+                PopJumpFwdIfTrue(1),
+                JumpFwd(2),
+                PopTop,
+                GetSym(SymbolId::from("b")),
+            ])
+        );
+        assert_eq!(
+            compile(&f("(or a b)")),
+            Ok(vec![
+                GetSym(SymbolId::from("a")),
+                DupTop,
+                PopJumpFwdIfTrue(2),
+                PopTop,
+                GetSym(SymbolId::from("b")),
+            ])
+        );
+    }
+
     #[test]
     fn compile_yield() {
         assert_eq!(
@@ -775,8 +1812,8 @@ mod tests {
             Ok(vec![
                 PushConst(Val::List(vec![])),
                 PushConst(Val::Nil),
-                PushConst(Val::Bytecode(vec![PushConst(Val::Int(10))])),
-                MakeFunc,
+                PushConst(Val::Bytecode(vec![PushConst(Val::Int(10))].into())),
+                closure("", &[]),
                 CallFunc(0),
                 YieldTop,
             ])
@@ -790,8 +1827,8 @@ mod tests {
             Ok(vec![
                 PushConst(Val::List(vec![])),
                 PushConst(Val::Nil),
-                PushConst(Val::Bytecode(vec![PushConst(Val::Int(10))])),
-                MakeFunc,
+                PushConst(Val::Bytecode(vec![PushConst(Val::Int(10))].into())),
+                closure("", &[]),
                 CallFunc(0)
             ])
         );
@@ -807,15 +1844,18 @@ mod tests {
             Ok(vec![
                 PushConst(Val::List(vec![Val::symbol("a"), Val::symbol("b")])),
                 PushConst(Val::Nil),
-                PushConst(Val::Bytecode(vec![
-                    GetSym(SymbolId::from("+")),
-                    GetSym(SymbolId::from("a")),
-                    GetSym(SymbolId::from("b")),
-                    CallFunc(2),
-                    PopTop,
-                    PushConst(Val::keyword("ok"))
-                ])),
-                MakeFunc,
+                PushConst(Val::Bytecode(
+                    vec![
+                        GetSym(SymbolId::from("+")),
+                        GetLocal(0),
+                        GetLocal(1),
+                        CallFunc(2),
+                        PopTop,
+                        PushConst(Val::keyword("ok"))
+                    ]
+                    .into()
+                )),
+                closure("a b", &[]),
                 PushConst(Val::Int(10)),
                 GetSym(SymbolId::from("+")),
                 PushConst(Val::Int(1)),
@@ -866,8 +1906,142 @@ mod tests {
         );
     }
 
+    #[test]
+    fn compile_quasiquote() {
+        assert_eq!(
+            compile(&f("`(a b)")),
+            Ok(vec![PushConst(Val::List(vec![
+                Val::symbol("a"),
+                Val::symbol("b")
+            ]))])
+        );
+        assert_eq!(
+            compile(&f("`(a ,b ,@c)")),
+            Ok(vec![
+                PushConst(Val::NativeFn(concat_fn())),
+                PushConst(Val::NativeFn(list_fn())),
+                PushConst(Val::symbol("a")),
+                GetSym(SymbolId::from("b")),
+                CallFunc(2),
+                GetSym(SymbolId::from("c")),
+                CallFunc(2),
+            ])
+        );
+    }
+
+    #[test]
+    fn compile_defmacro() {
+        assert_eq!(
+            compile(&f("(defmacro m (x) x)")),
+            Ok(vec![
+                PushConst(Val::List(vec![Val::symbol("x")])),
+                PushConst(Val::Nil),
+                PushConst(Val::Bytecode(vec![GetLocal(0)].into())),
+                closure("x", &[]),
+                MakeMacro,
+                DefSym(SymbolId::from("m")),
+            ])
+        );
+        assert_eq!(
+            compile(&f("(begin (defmacro m (x) x) (m 1))")),
+            Ok(vec![
+                PushConst(Val::List(vec![Val::symbol("x")])),
+                PushConst(Val::Nil),
+                PushConst(Val::Bytecode(vec![GetLocal(0)].into())),
+                closure("x", &[]),
+                MakeMacro,
+                DefSym(SymbolId::from("m")),
+                PopTop,
+                PushConst(f("(m 1)")),
+                Eval(false),
+            ]),
+            "forms after defmacro should be compiled when evaluated"
+        );
+    }
+
+    #[test]
+    fn compile_spanned_locs() {
+        let (form, span) = parse_all("(begin\n  (one)\n  (two (three)\n       (four)))")
+            .unwrap()
+            .remove(0);
+        let loc = |line, col| SourceLoc {
+            file: Some(Arc::from("a.ll")),
+            pos: Position { line, col },
+        };
+        let bc = super::compile_spanned(&Val::from(form), &span, Some("a.ll"), None).unwrap();
+        assert_eq!(
+            bc.inst,
+            vec![
+                GetSym(SymbolId::from("one")),
+                CallFunc(0),
+                PopTop,
+                GetSym(SymbolId::from("two")),
+                GetSym(SymbolId::from("three")),
+                CallFunc(0),
+                GetSym(SymbolId::from("four")),
+                CallFunc(0),
+                CallFunc(2),
+            ]
+        );
+        assert_eq!(
+            bc.locs,
+            vec![
+                (0, loc(1, 1)),
+                (0, loc(2, 3)),
+                (2, loc(1, 1)),
+                (3, loc(3, 3)),
+                (6, loc(4, 8)),
+                (8, loc(3, 3)),
+                (9, loc(1, 1)),
+            ],
+            "expressions starting on new lines should be marked, and restore enclosing location"
+        );
+        assert_eq!(bc.loc(1), Some(&loc(2, 3)));
+        assert_eq!(bc.loc(5), Some(&loc(3, 3)));
+        assert_eq!(bc.loc(7), Some(&loc(4, 8)));
+        assert_eq!(bc.loc(8), Some(&loc(3, 3)));
+
+        let (form, span) = parse_all("(lambda () (one))").unwrap().remove(0);
+        let loc = |col| SourceLoc {
+            file: None,
+            pos: Position { line: 1, col },
+        };
+        assert_eq!(
+            super::compile_spanned(&Val::from(form), &span, None, None),
+            Ok(Bytecode {
+                inst: vec![
+                    PushConst(Val::List(vec![])),
+                    PushConst(Val::Nil),
+                    PushConst(Val::Bytecode(Bytecode {
+                        inst: vec![GetSym(SymbolId::from("one")), TailCallFunc(0)],
+                        locs: vec![(0, loc(12))],
+                    })),
+                    closure("", &[]),
+                ],
+                locs: vec![(0, loc(1))],
+            }),
+            "function bodies should be marked with their own locations"
+        );
+    }
+
+    /// Compile value, returning instructions of bytecode
+    fn compile(v: &Val) -> Result<Vec<Inst<Void, Void>>> {
+        super::compile(v).map(|bc| bc.inst)
+    }
+
     /// Convenience for creating Val from expressions
     fn f(expr: &str) -> Val {
         parse(expr).expect("expr should be valid form").into()
     }
+
+    /// Convenience for creating closures with given local slots and upvalues
+    fn closure(slots: &str, upvals: &[(&str, Capture)]) -> Inst<Void, Void> {
+        MakeClosure(Arc::new(Scope {
+            slots: slots.split_whitespace().map(SymbolId::from).collect(),
+            upvals: upvals
+                .iter()
+                .map(|(s, c)| (SymbolId::from(*s), *c))
+                .collect(),
+        }))
+    }
 }
@@ -1,6 +1,9 @@
-use crate::{builtin, Error, Extern, Lambda, Locals, NativeAsyncFn, NativeFn, SymbolId, Val};
+use crate::{
+    builtin, Bytecode, Error, Extern, Inst, Lambda, Locals, NativeAsyncFn, NativeFn, Result,
+    SymbolId, Upval, Val,
+};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{Arc, Mutex},
 };
 
@@ -16,20 +19,45 @@ pub struct Env<T: Extern, L: Locals> {
 pub type EnvRef<T, L> = Arc<Mutex<Env<T, L>>>;
 
 impl<T: Extern, L: Locals> Env<T, L> {
-    /// Create standard base env
-    pub fn standard() -> Self {
-        let mut e = Env {
+    /// Create env without any bindings
+    pub(crate) fn empty() -> Self {
+        Env {
             bindings: HashMap::default(),
             parent: None,
-        };
+        }
+    }
+
+    /// Create standard base env
+    pub fn standard() -> Self {
+        let mut e = Self::empty();
         e.bind_native(SymbolId::from("contains?"), builtin::contains_fn())
             .bind_native(SymbolId::from("eq?"), builtin::eq_fn())
             .bind_native(SymbolId::from("+"), builtin::plus_fn())
+            .bind_native(SymbolId::from("-"), builtin::minus_fn())
+            .bind_native(SymbolId::from("*"), builtin::mul_fn())
+            .bind_native(SymbolId::from("/"), builtin::div_fn())
+            .bind_native(SymbolId::from("mod"), builtin::mod_fn())
+            .bind_native(SymbolId::from("<"), builtin::lt_fn())
+            .bind_native(SymbolId::from(">"), builtin::gt_fn())
+            .bind_native(SymbolId::from("<="), builtin::lte_fn())
+            .bind_native(SymbolId::from(">="), builtin::gte_fn())
+            .bind_native(SymbolId::from("min"), builtin::min_fn())
+            .bind_native(SymbolId::from("max"), builtin::max_fn())
+            .bind_native(SymbolId::from("abs"), builtin::abs_fn())
             .bind_native(SymbolId::from("ref"), builtin::ref_fn())
             .bind_native(SymbolId::from("list"), builtin::list_fn())
             .bind_native(SymbolId::from("push"), builtin::push_fn())
+            .bind_native(SymbolId::from("concat"), builtin::concat_fn())
             .bind_native(SymbolId::from("get"), builtin::get_fn())
+            .bind_native(SymbolId::from("hashmap"), builtin::hashmap_fn())
+            .bind_native(SymbolId::from("assoc"), builtin::assoc_fn())
+            .bind_native(SymbolId::from("dissoc"), builtin::dissoc_fn())
+            .bind_native(SymbolId::from("keys"), builtin::keys_fn())
+            .bind_native(SymbolId::from("vals"), builtin::vals_fn())
+            .bind_native(SymbolId::from("has_key?"), builtin::has_key_fn())
+            .bind_native(SymbolId::from("merge"), builtin::merge_fn())
             .bind_native(SymbolId::from("map"), builtin::map_fn())
+            .bind_native(SymbolId::from("apply"), builtin::apply_fn())
             .bind_native(SymbolId::from("len"), builtin::len_fn())
             .bind_lambda(SymbolId::from("filter"), builtin::filter_fn())
             .bind_native(SymbolId::from("not?"), builtin::not_fn())
@@ -37,6 +65,7 @@ impl<T: Extern, L: Locals> Env<T, L> {
             .bind_native(SymbolId::from("empty?"), builtin::empty_fn())
             .bind_native(SymbolId::from("keyword?"), builtin::is_keyword_fn())
             .bind_native(SymbolId::from("err?"), builtin::err_fn())
+            .bind_native(SymbolId::from("err_kind"), builtin::err_kind_fn())
             .bind_native(SymbolId::from("str"), builtin::str_fn())
             .bind_native(SymbolId::from("join"), builtin::join_fn())
             .bind_native(SymbolId::from("split"), builtin::split_fn())
@@ -45,7 +74,22 @@ impl<T: Extern, L: Locals> Env<T, L> {
             .bind_native(SymbolId::from("dbg"), builtin::dbg_fn())
             .bind_native(SymbolId::from("read"), builtin::read_fn())
             .bind_native(SymbolId::from("help"), builtin::help_fn())
-            .bind_native(SymbolId::from("ls_env"), builtin::ls_env_fn());
+            .bind_native(SymbolId::from("macroexpand"), builtin::macroexpand_fn())
+            .bind_native(SymbolId::from("macroexpand_1"), builtin::macroexpand_1_fn())
+            .bind_native(SymbolId::from("ls_env"), builtin::ls_env_fn())
+            .bind_native(SymbolId::from("fiber"), builtin::fiber_fn())
+            .bind_native_async(SymbolId::from("resume"), builtin::resume_fn())
+            .bind_native(SymbolId::from("fiber_status"), builtin::fiber_status_fn())
+            .bind_native(SymbolId::from("fiber_done?"), builtin::fiber_done_fn())
+            .bind_native(SymbolId::from("seq"), builtin::seq_fn())
+            .bind_native_async(SymbolId::from("collect"), builtin::collect_fn())
+            .bind_lambda(SymbolId::from("range"), builtin::range_fn())
+            .bind_lambda(SymbolId::from("iterate"), builtin::iterate_fn())
+            .bind_lambda(SymbolId::from("take"), builtin::take_fn())
+            .bind_lambda(SymbolId::from("drop"), builtin::drop_fn())
+            .bind_lambda(SymbolId::from("take_while"), builtin::take_while_fn())
+            .bind_lambda(SymbolId::from("lazy_map"), builtin::lazy_map_fn())
+            .bind_lambda(SymbolId::from("lazy_filter"), builtin::lazy_filter_fn());
 
         e
     }
@@ -58,16 +102,11 @@ impl<T: Extern, L: Locals> Env<T, L> {
         }
     }
 
-    /// Fork this environment in to a *deep copy*
-    pub fn fork(&self) -> Self {
-        let parent = self
-            .parent
-            .as_ref()
-            .map(|parent| Arc::new(Mutex::new(parent.as_ref().lock().unwrap().clone())));
-        Self {
-            bindings: self.bindings.clone(),
-            parent,
-        }
+    /// Fork environment in to a *deep copy*, including environments captured by lambdas bound within it.
+    /// Lambdas in the copy close over copied environments, so the copy shares no state with the original.
+    /// Fails if environment holds a fiber, which can not be copied.
+    pub fn fork(env: &EnvRef<T, L>) -> Result<EnvRef<T, L>> {
+        Forker::new().env(env)
     }
 
     /// Define a new symbol with given value in current environment
@@ -86,8 +125,13 @@ impl<T: Extern, L: Locals> Env<T, L> {
         }
     }
 
+    /// Get value for symbol defined in this environment, excluding parent environments
+    pub(crate) fn get_defined(&self, symbol: &SymbolId) -> Option<Val<T, L>> {
+        self.bindings.get(symbol).cloned()
+    }
+
     /// Set value of symbol in lexical scope
-    pub fn set(&mut self, symbol: &SymbolId, value: Val<T, L>) -> Result<(), Error> {
+    pub fn set(&mut self, symbol: &SymbolId, value: Val<T, L>) -> Result<()> {
         if let Some(b) = self.bindings.get_mut(symbol) {
             *b = value;
             return Ok(());
@@ -123,6 +167,12 @@ impl<T: Extern, L: Locals> Env<T, L> {
         self
     }
 
+    /// Convenience to bind macros
+    pub fn bind_macro(&mut self, symbol: SymbolId, lambda: Lambda<T, L>) -> &mut Self {
+        self.define(symbol, Val::Macro(lambda));
+        self
+    }
+
     /// Iterate over all symbols and bindings
     pub fn iter(&self) -> EnvIter<'_, T, L> {
         EnvIter(self.bindings.iter())
@@ -143,6 +193,236 @@ impl<T: Extern, L: Locals> std::clone::Clone for Env<T, L> {
     }
 }
 
+/// Deep copies environments and values, remembering copied environments and upvalues so that
+/// those shared in the original are also shared in the copy.
+/// Environments captured by lambdas are copied with only bindings the lambdas may refer to.
+pub(crate) struct Forker<T: Extern, L: Locals> {
+    envs: HashMap<EnvPtr<T, L>, EnvRef<T, L>>,
+    /// Bindings already copied, as environment and symbol bound in it
+    bound: HashSet<(EnvPtr<T, L>, SymbolId)>,
+    upvals: HashMap<UpvalPtr<T, L>, Upval<T, L>>,
+    /// Parent of copies of environments without a parent, if any
+    root: Option<EnvRef<T, L>>,
+}
+
+/// Address identifying shared environment
+type EnvPtr<T, L> = *const Mutex<Env<T, L>>;
+
+/// Address identifying shared upvalue
+type UpvalPtr<T, L> = *const Mutex<Option<Val<T, L>>>;
+
+impl<T: Extern, L: Locals> Forker<T, L> {
+    pub(crate) fn new() -> Self {
+        Self {
+            envs: HashMap::new(),
+            bound: HashSet::new(),
+            upvals: HashMap::new(),
+            root: None,
+        }
+    }
+
+    /// Forker with copies of top-level environments extending given root environment
+    pub(crate) fn with_root(root: Env<T, L>) -> Self {
+        Self {
+            root: Some(Arc::new(Mutex::new(root))),
+            ..Self::new()
+        }
+    }
+
+    /// Copy of environment reference, with all bindings in environment and its parents
+    pub(crate) fn env(&mut self, env: &EnvRef<T, L>) -> Result<EnvRef<T, L>> {
+        let copy = self.env_copy(env);
+        let mut cur = Some(Arc::clone(env));
+        while let Some(env) = cur {
+            let (syms, parent) = {
+                let env = env.lock().unwrap();
+                (
+                    env.bindings.keys().cloned().collect::<Vec<_>>(),
+                    env.parent.clone(),
+                )
+            };
+            for sym in syms {
+                self.bind(&env, &sym)?;
+            }
+            cur = parent;
+        }
+        Ok(copy)
+    }
+
+    /// Copy of environment reference and its parents, without copying any bindings
+    fn env_copy(&mut self, env: &EnvRef<T, L>) -> EnvRef<T, L> {
+        if let Some(copy) = self.envs.get(&Arc::as_ptr(env)) {
+            return Arc::clone(copy);
+        }
+        let copy = Arc::new(Mutex::new(Env {
+            bindings: HashMap::new(),
+            parent: None,
+        }));
+        self.envs.insert(Arc::as_ptr(env), Arc::clone(&copy));
+
+        let parent = env.lock().unwrap().parent.clone();
+        let parent = match parent {
+            Some(p) => Some(self.env_copy(&p)),
+            None => self.root.clone(),
+        };
+        copy.lock().unwrap().parent = parent;
+        copy
+    }
+
+    /// Copy binding for symbol as seen from environment, i.e. from nearest environment defining it
+    fn capture(&mut self, env: &EnvRef<T, L>, sym: &SymbolId) -> Result<()> {
+        let mut cur = Some(Arc::clone(env));
+        while let Some(env) = cur {
+            let parent = {
+                let env = env.lock().unwrap();
+                if env.bindings.contains_key(sym) {
+                    None
+                } else {
+                    Some(env.parent.clone())
+                }
+            };
+            match parent {
+                None => return self.bind(&env, sym),
+                Some(parent) => cur = parent,
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy binding for symbol defined in given environment into its copy
+    fn bind(&mut self, env: &EnvRef<T, L>, sym: &SymbolId) -> Result<()> {
+        if !self.bound.insert((Arc::as_ptr(env), sym.clone())) {
+            return Ok(());
+        }
+        // Lock is released before copying value, since lambdas may refer back to this environment
+        let val = env.lock().unwrap().get_defined(sym);
+        if let Some(val) = val {
+            let val = self.val(&val)?;
+            self.env_copy(env).lock().unwrap().define(sym.clone(), val);
+        }
+        Ok(())
+    }
+
+    /// Copy of value. Fibers are rejected, since a fiber runs with locals of process that created it
+    pub(crate) fn val(&mut self, val: &Val<T, L>) -> Result<Val<T, L>> {
+        let copy = match val {
+            Val::List(l) => Val::List(l.iter().map(|v| self.val(v)).collect::<Result<_>>()?),
+            Val::Map(m) => Val::Map(
+                m.iter()
+                    .map(|(k, v)| Ok((k.clone(), self.val(v)?)))
+                    .collect::<Result<_>>()?,
+            ),
+            Val::Lambda(l) => Val::Lambda(self.lambda(l)?),
+            Val::Macro(l) => Val::Macro(self.lambda(l)?),
+            Val::Bytecode(bc) => Val::Bytecode(self.bytecode(bc)?),
+            Val::Fiber(_) => {
+                return Err(Error::UnexpectedType(format!(
+                    "{val} can not be copied - fibers are not shared between processes"
+                )))
+            }
+            v => v.clone(),
+        };
+        Ok(copy)
+    }
+
+    fn lambda(&mut self, l: &Lambda<T, L>) -> Result<Lambda<T, L>> {
+        let parent = match &l.parent {
+            Some(p) => {
+                let copy = self.env_copy(p);
+                for sym in lambda_symbols(l) {
+                    self.capture(p, &sym)?;
+                }
+                Some(copy)
+            }
+            None => None,
+        };
+        Ok(Lambda {
+            doc: l.doc.clone(),
+            params: l.params.clone(),
+            code: Arc::new(self.bytecode(&l.code)?),
+            parent,
+            scope: l.scope.clone(),
+            upvals: l
+                .upvals
+                .iter()
+                .map(|u| self.upval(u))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Copy of upvalue
+    fn upval(&mut self, upval: &Upval<T, L>) -> Result<Upval<T, L>> {
+        if let Some(copy) = self.upvals.get(&Arc::as_ptr(upval)) {
+            return Ok(Arc::clone(copy));
+        }
+        let copy = Arc::new(Mutex::new(None));
+        self.upvals.insert(Arc::as_ptr(upval), Arc::clone(&copy));
+
+        // Lock is released before copying value, since lambdas may capture this upvalue
+        let val = upval.lock().unwrap().clone();
+        *copy.lock().unwrap() = val.map(|v| self.val(&v)).transpose()?;
+        Ok(copy)
+    }
+
+    fn bytecode(&mut self, bc: &Bytecode<T, L>) -> Result<Bytecode<T, L>> {
+        let inst = bc
+            .inst
+            .iter()
+            .map(|inst| match inst {
+                Inst::PushConst(v) => Ok(Inst::PushConst(self.val(v)?)),
+                inst => Ok(inst.clone()),
+            })
+            .collect::<Result<_>>()?;
+        Ok(Bytecode {
+            inst,
+            locs: bc.locs.clone(),
+        })
+    }
+}
+
+/// Symbols lambda may look up in its parent environment, including symbols within quoted forms
+/// that may be evaluated at runtime
+fn lambda_symbols<T: Extern, L: Locals>(l: &Lambda<T, L>) -> HashSet<SymbolId> {
+    let mut syms = HashSet::new();
+    code_symbols(&l.code, &mut syms);
+    if let Some(scope) = &l.scope {
+        // variables that are not defined yet fall back to environment
+        syms.extend(scope.slots.iter().cloned());
+        syms.extend(scope.upvals.iter().map(|(sym, _)| sym.clone()));
+    }
+    syms
+}
+
+/// Collect symbols referred to by bytecode
+fn code_symbols<T: Extern, L: Locals>(bc: &Bytecode<T, L>, syms: &mut HashSet<SymbolId>) {
+    for inst in &bc.inst {
+        match inst {
+            Inst::GetSym(s) | Inst::SetSym(s) | Inst::DefSym(s) => {
+                syms.insert(s.clone());
+            }
+            Inst::MakeClosure(scope) => {
+                syms.extend(scope.slots.iter().cloned());
+                syms.extend(scope.upvals.iter().map(|(sym, _)| sym.clone()));
+            }
+            Inst::PushConst(v) => val_symbols(v, syms),
+            _ => (),
+        }
+    }
+}
+
+/// Collect symbols within value, excluding those in lambdas which capture their own environment
+fn val_symbols<T: Extern, L: Locals>(v: &Val<T, L>, syms: &mut HashSet<SymbolId>) {
+    match v {
+        Val::Symbol(s) => {
+            syms.insert(s.clone());
+        }
+        Val::List(l) => l.iter().for_each(|v| val_symbols(v, syms)),
+        Val::Map(m) => m.values().for_each(|v| val_symbols(v, syms)),
+        Val::Bytecode(bc) => code_symbols(bc, syms),
+        _ => (),
+    }
+}
+
 pub struct EnvIter<'a, T: Extern, L: Locals>(
     std::collections::hash_map::Iter<'a, SymbolId, Val<T, L>>,
 );
@@ -240,5 +520,82 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fork_detaches_closures() {
+        let sym = SymbolId::from("x");
+        let env = Arc::new(Mutex::new(Env::standard()));
+        let lambda = Val::Lambda(Lambda {
+            doc: None,
+            params: vec![],
+            code: Arc::new(Bytecode::new()),
+            parent: Some(Arc::clone(&env)),
+            scope: None,
+            upvals: vec![],
+        });
+        {
+            let mut env = env.lock().unwrap();
+            env.define(sym.clone(), Val::keyword("original"));
+            env.define(SymbolId::from("f"), lambda.clone());
+        }
+
+        let forked = Env::fork(&env).unwrap();
+        forked
+            .lock()
+            .unwrap()
+            .set(&sym, Val::keyword("forked"))
+            .unwrap();
+        assert_eq!(
+            env.lock().unwrap().get(&sym),
+            Some(Val::keyword("original")),
+            "updating fork should not update original"
+        );
+
+        let forked_f = forked.lock().unwrap().get(&SymbolId::from("f"));
+        match forked_f {
+            Some(Val::Lambda(l)) => assert!(
+                Arc::ptr_eq(l.parent.as_ref().unwrap(), &forked),
+                "lambdas in fork should close over forked environment"
+            ),
+            _ => panic!("fork should contain lambda"),
+        }
+
+        match lambda.fork().unwrap() {
+            Val::Lambda(l) => assert!(!Arc::ptr_eq(l.parent.as_ref().unwrap(), &env)),
+            _ => panic!("forked lambda should be a lambda"),
+        }
+        assert!(lambda.has_closure());
+        assert!(!Val::List(vec![Val::Int(1)]).has_closure());
+    }
+
+    #[test]
+    fn fork_lambda_copies_captured_bindings() {
+        let env = Arc::new(Mutex::new(Env::standard()));
+        {
+            let mut env = env.lock().unwrap();
+            env.define(SymbolId::from("x"), Val::Int(1));
+            env.define(SymbolId::from("y"), Val::Int(2));
+        }
+        let lambda = Val::Lambda(Lambda {
+            doc: None,
+            params: vec![],
+            code: Arc::new(Bytecode::from(vec![Inst::GetSym(SymbolId::from("x"))])),
+            parent: Some(Arc::clone(&env)),
+            scope: None,
+            upvals: vec![],
+        });
+
+        let parent = match lambda.fork().unwrap() {
+            Val::Lambda(l) => l.parent.unwrap(),
+            _ => panic!("forked lambda should be a lambda"),
+        };
+        let parent = parent.lock().unwrap();
+        assert_eq!(parent.get(&SymbolId::from("x")), Some(Val::Int(1)));
+        assert_eq!(
+            parent.get(&SymbolId::from("y")),
+            None,
+            "bindings not referred to by lambda should not be copied"
+        );
+    }
+
     // TODO: Test Clone Isolation
 }
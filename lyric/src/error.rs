@@ -1,4 +1,4 @@
-use crate::SymbolId;
+use crate::{KeywordId, SymbolId};
 use serde::{Deserialize, Serialize};
 
 #[derive(thiserror::Error, Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -33,4 +33,45 @@ pub enum Error {
 
     #[error("Runtime error - {0}")]
     Runtime(String),
+
+    /// Operation did not complete in time, e.g. waiting for a message
+    #[error("Timed out - {0}")]
+    Timeout(String),
+
+    /// Error from IO on path, with kind of error like `:not_found`
+    #[error("IO error {kind} - {path} - {message}")]
+    IO {
+        kind: KeywordId,
+        path: String,
+        message: String,
+    },
+
+    /// Error raised with source locations of callframes, innermost first
+    #[error("{error}{}", .trace.iter().map(|l| format!("\n    at {l}")).collect::<String>())]
+    Traceback {
+        error: Box<Error>,
+        trace: Vec<String>,
+    },
+}
+
+impl Error {
+    /// Keyword identifying kind of error, e.g. `:undefined_symbol` or `:not_found` for IO errors
+    pub fn kind(&self) -> KeywordId {
+        let kind = match self {
+            Error::IncompleteExpression(_) => "incomplete_expression",
+            Error::InvalidExpression(_) => "invalid_expression",
+            Error::UndefinedSymbol(_) => "undefined_symbol",
+            Error::UnexpectedArguments(_) => "unexpected_arguments",
+            Error::UnexpectedType(_) => "unexpected_type",
+            Error::UnexpectedStack(_) => "unexpected_stack",
+            Error::UnexpectedResume(_) => "unexpected_resume",
+            Error::InvalidPatternMatch => "invalid_pattern_match",
+            Error::UnexpectedTopLevelYield => "unexpected_top_level_yield",
+            Error::Runtime(_) => "runtime",
+            Error::Timeout(_) => "timeout",
+            Error::IO { kind, .. } => return kind.clone(),
+            Error::Traceback { error, .. } => return error.kind(),
+        };
+        KeywordId::from(kind)
+    }
 }
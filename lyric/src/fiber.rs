@@ -1,11 +1,14 @@
 //! A fiber of execution that can be driven by caller as a coroutine.
 
-use super::{Env, Inst};
+use super::{Env, Inst, SourceLoc};
+use crate::env::EnvRef;
+use crate::params::Params;
 use crate::types::NativeAsyncCall;
 use crate::{
-    builtin::cond::is_true, compile, parse, Bytecode, Error, Extern, Lambda, Locals, NativeFnOp,
-    Pattern, Result, Val,
+    builtin::cond::is_true, compile_in, compile_spanned, parse, Bytecode, Capture, Error, Extern,
+    Lambda, Locals, NativeFnOp, Pattern, Result, Scope, SymbolId, Upval, Val,
 };
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use tracing::warn;
 
@@ -22,15 +25,26 @@ pub struct Fiber<T: Extern, L: Locals> {
     stack: Vec<Val<T, L>>,
     global: Arc<Mutex<Env<T, L>>>,
     locals: L,
+    /// Number of instructions run, shared with nested fibers
+    reductions: Arc<AtomicU64>,
+    /// Maximum number of instructions to run before fiber is preempted, if any
+    budget: Option<u64>,
 }
 
+/// A fiber as a value, shared between copies of the value.
+/// The fiber is taken out of the handle while it is running.
+#[derive(Debug, Clone)]
+pub struct FiberHandle<T: Extern, L: Locals>(Arc<Mutex<Option<Fiber<T, L>>>>);
+
 /// The status of fiber
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Status {
     /// Fiber was created, and can be started.
     New,
     /// Fiber is paused, and can be resumed
     Paused,
+    /// Fiber ran out of instruction budget, and can be resumed with [Fiber::resume_preempted]
+    Preempted,
     /// Fiber is currently running
     Running,
     /// Fiber has completeed execution, and cannot be resumed
@@ -46,6 +60,8 @@ pub enum Signal<T: Extern, L: Locals> {
     Yield(Val<T, L>),
     /// Fiber must be resumed after awaiting future
     Await(NativeAsyncCall<T, L>),
+    /// Fiber ran out of instruction budget, and must be resumed via [Fiber::resume_preempted]
+    Preempted,
 }
 
 impl<T: Extern, L: Locals> std::cmp::PartialEq for Signal<T, L> {
@@ -54,6 +70,7 @@ impl<T: Extern, L: Locals> std::cmp::PartialEq for Signal<T, L> {
             (Signal::Done(lhs), Signal::Done(rhs)) => lhs == rhs,
             (Signal::Yield(lhs), Signal::Yield(rhs)) => lhs == rhs,
             (Signal::Await(lhs), Signal::Await(rhs)) => lhs == rhs,
+            (Signal::Preempted, Signal::Preempted) => true,
             _ => false,
         }
     }
@@ -65,15 +82,27 @@ struct CallFrame<T: Extern, L: Locals> {
     /// instruction pointer in code
     ip: usize,
     /// Code in callframe
-    code: Bytecode<T, L>,
+    code: Arc<Bytecode<T, L>>,
     /// Environment this callframe is operating in
     env: Arc<Mutex<Env<T, L>>>,
     /// Length of stack when callframe was created
     stack_len: usize,
     /// Length of callframe of fiber to unwind to on error, if any
     unwind_cf_len: Option<usize>,
+    /// Scope of lexically addressed function body being run, if any
+    scope: Option<Arc<Scope>>,
+    /// Local variables of function body, in order of slots in scope
+    slots: Vec<Upval<T, L>>,
+    /// Variables captured by function being run, in order of upvalues in scope
+    upvals: Vec<Upval<T, L>>,
+    /// Variables of function body copied into environment of callframe, which are written back when
+    /// callframe returns if code defined or set them in environment
+    frame_vars: Vec<FrameVar<T, L>>,
 }
 
+/// Variable of function body copied into an environment, with its symbol and copied value
+type FrameVar<T, L> = (SymbolId, Upval<T, L>, Option<Val<T, L>>);
+
 impl<T: Extern, L: Locals> Fiber<T, L> {
     /// Create a new fiber from given bytecode
     pub fn from_bytecode(bytecode: Bytecode<T, L>, env: Env<T, L>, locals: L) -> Self {
@@ -83,19 +112,22 @@ impl<T: Extern, L: Locals> Fiber<T, L> {
             stack: vec![],
             cframes: vec![CallFrame::from_bytecode(
                 Arc::clone(&global),
-                bytecode,
+                Arc::new(bytecode),
                 0,
                 None,
             )],
             global,
             locals,
+            reductions: Arc::new(AtomicU64::new(0)),
+            budget: None,
         }
     }
 
     /// Create a new fiber from value
     pub fn from_val(val: &Val<T, L>, env: Env<T, L>, locals: L) -> Result<Self> {
-        let bytecode = compile(val)?;
-        Ok(Fiber::from_bytecode(bytecode, env, locals))
+        let mut f = Fiber::from_bytecode(Bytecode::new(), env, locals);
+        f.cf_mut().code = Arc::new(compile_in(val, &f)?);
+        Ok(f)
     }
 
     /// Create a new fiber from given expressino
@@ -104,6 +136,25 @@ impl<T: Extern, L: Locals> Fiber<T, L> {
         Fiber::from_val(&val, env, locals)
     }
 
+    /// Create a new fiber that evaluates bytecode within the current environment of this fiber,
+    /// with a copy of its locals. Instructions run by nested fiber count towards this fiber's reductions.
+    pub fn nested(&self, bytecode: Bytecode<T, L>) -> Self {
+        Fiber {
+            status: Status::New,
+            stack: vec![],
+            cframes: vec![CallFrame::from_bytecode(
+                Arc::clone(self.cur_env()),
+                Arc::new(bytecode),
+                0,
+                None,
+            )],
+            global: Arc::clone(&self.global),
+            locals: self.locals.clone(),
+            reductions: Arc::clone(&self.reductions),
+            budget: self.budget,
+        }
+    }
+
     // TODO: Safeguard start / resume via typestate pat?
 
     /// Start a fiber execution
@@ -133,11 +184,52 @@ impl<T: Extern, L: Locals> Fiber<T, L> {
         self.run()
     }
 
+    /// Resume a fiber that was preempted after running out of instruction budget
+    pub fn resume_preempted(&mut self) -> Result<Signal<T, L>> {
+        if self.status != Status::Preempted {
+            return Err(Error::UnexpectedResume(
+                "resuming a fiber that is not preempted".to_string(),
+            ));
+        }
+        self.run()
+    }
+
+    /// Number of callframes in fiber, i.e. depth of nested calls being run
+    pub fn depth(&self) -> usize {
+        self.cframes.len()
+    }
+
+    /// Number of values on stack of fiber
+    pub fn stack_len(&self) -> usize {
+        self.stack.len()
+    }
+
     /// Whether or not fiber is done running
     pub fn is_done(&self) -> bool {
         self.status == Status::Done
     }
 
+    /// Current status of fiber
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// Set maximum number of instructions to run on each start or resume, before fiber is
+    /// preempted with [Signal::Preempted]. Fiber runs until it is paused or done if budget is None.
+    pub fn set_budget(&mut self, budget: Option<u64>) {
+        self.budget = budget;
+    }
+
+    /// Number of instructions run by fiber and its nested fibers
+    pub fn reductions(&self) -> u64 {
+        self.reductions.load(Ordering::Relaxed)
+    }
+
+    /// Shared counter of instructions run by fiber, for observing fiber while it runs
+    pub fn reduction_counter(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.reductions)
+    }
+
     /// Get current environment
     pub fn cur_env(&self) -> &Arc<Mutex<Env<T, L>>> {
         &self.cf().env
@@ -148,6 +240,75 @@ impl<T: Extern, L: Locals> Fiber<T, L> {
         &self.global
     }
 
+    /// Get environment of current callframe, with local variables of function body bound by name.
+    /// Local variables are copied, so definitions in returned environment are not visible to function body.
+    pub fn frame_env(&self) -> Arc<Mutex<Env<T, L>>> {
+        self.frame_env_vars().0
+    }
+
+    /// Environment of current callframe, with variables of function body copied into it
+    fn frame_env_vars(&self) -> (EnvRef<T, L>, Vec<FrameVar<T, L>>) {
+        let cf = self.cf();
+        let scope = match &cf.scope {
+            Some(scope) => scope,
+            None => return (Arc::clone(&cf.env), vec![]),
+        };
+        // slots shadow upvalues with same symbol
+        let slots = scope.slots.iter().zip(&cf.slots);
+        let upvals = scope
+            .upvals
+            .iter()
+            .map(|(sym, _)| sym)
+            .zip(&cf.upvals)
+            .filter(|(sym, _)| !scope.slots.contains(sym));
+
+        let mut env = Env::extend(&cf.env);
+        let mut vars = vec![];
+        for (sym, var) in slots.chain(upvals) {
+            let val = var.lock().unwrap().clone();
+            if let Some(val) = &val {
+                env.define(sym.clone(), val.clone());
+            }
+            vars.push((sym.clone(), Arc::clone(var), val));
+        }
+        (Arc::new(Mutex::new(env)), vars)
+    }
+
+    /// Expand form once if it is a call to macro bound in current environment.
+    /// Returns None if form is not a macro call.
+    pub fn macroexpand_1(&self, form: &Val<T, L>) -> Result<Option<Val<T, L>>> {
+        let (mac, args) = match form {
+            Val::List(l) => match l.split_first() {
+                Some((Val::Symbol(s), args)) => {
+                    let val = self.cur_env().lock().unwrap().get(s);
+                    match val {
+                        Some(Val::Macro(mac)) => (mac, args),
+                        _ => return Ok(None),
+                    }
+                }
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+
+        // Run expansion to completion in a fiber within defining environment of macro
+        let mut f = Fiber {
+            status: Status::New,
+            stack: vec![],
+            cframes: vec![self.call_frame(mac, args.to_vec(), 0, None)?],
+            global: Arc::clone(&self.global),
+            locals: self.locals.clone(),
+            reductions: Arc::clone(&self.reductions),
+            budget: None,
+        };
+        match f.start()? {
+            Signal::Done(v) => Ok(Some(v)),
+            _ => Err(Error::Runtime(format!(
+                "Macro expansion should not yield - {form}"
+            ))),
+        }
+    }
+
     /// Local storage
     pub fn locals(&self) -> &L {
         &self.locals
@@ -164,17 +325,32 @@ impl<T: Extern, L: Locals> Fiber<T, L> {
     /// - completes with a value
     /// - completes with an error
     /// - becomes paused
+    /// - runs out of instruction budget
     fn run(&mut self) -> Result<Signal<T, L>> {
         self.status = Status::Running;
-        while self.status == Status::Running {
+        let mut ran = 0;
+        let res = loop {
+            if self.status != Status::Running {
+                break Ok(());
+            }
+            if self.budget.is_some_and(|budget| ran >= budget) {
+                self.status = Status::Preempted;
+                break Ok(());
+            }
+            ran += 1;
+
             // TODO(dev): Bytecode debugging utilities
             // tracing::debug!("{self:?}");
 
             if let Err(e) = self.step() {
-                let err_val = self.maybe_catch_err(e)?;
-                self.stack.push(err_val);
+                match self.maybe_catch_err(e) {
+                    Ok(err_val) => self.stack.push(err_val),
+                    Err(e) => break Err(e),
+                }
             }
-        }
+        };
+        self.reductions.fetch_add(ran, Ordering::Relaxed);
+        res?;
 
         match &self.status {
             Status::Paused => {
@@ -206,6 +382,7 @@ impl<T: Extern, L: Locals> Fiber<T, L> {
                 }
                 Ok(Signal::Done(res))
             }
+            Status::Preempted => Ok(Signal::Preempted),
             s => panic!("Fiber::run exiting in unexpected state - {s:?}"),
         }
     }
@@ -217,7 +394,7 @@ impl<T: Extern, L: Locals> Fiber<T, L> {
         let unwind_len = match self.cf().unwind_cf_len {
             None => {
                 self.status = Status::Done;
-                return Err(e); // no catching - propagate
+                return Err(self.traceback(e)); // no catching - propagate
             }
             Some(l) => l,
         };
@@ -227,6 +404,32 @@ impl<T: Extern, L: Locals> Fiber<T, L> {
         Ok(Val::Error(e)) // return as Val::Error
     }
 
+    /// Attach source locations of callframes to error propagating out of fiber, innermost first
+    fn traceback(&self, e: Error) -> Error {
+        let locs = self
+            .cframes
+            .iter()
+            .rev()
+            .filter_map(|cf| cf.loc().map(|l| l.to_string()));
+        match e {
+            Error::Traceback { error, mut trace } => {
+                trace.extend(locs);
+                Error::Traceback { error, trace }
+            }
+            e => {
+                let trace = locs.collect::<Vec<_>>();
+                if trace.is_empty() {
+                    e
+                } else {
+                    Error::Traceback {
+                        error: Box::new(e),
+                        trace,
+                    }
+                }
+            }
+        }
+    }
+
     /// Run a single fetch-decode-execute cycle
     fn step(&mut self) -> Result<()> {
         while self.cframes.len() > 1 && self.cf().at_return() {
@@ -235,7 +438,9 @@ impl<T: Extern, L: Locals> Fiber<T, L> {
                 // tracing::debug!("panic {:?}", self);
                 panic!("Unexpected state during execution - all function are expected to have stack effect of 1. Was {}", cf.stack_len + 1);
             }
-            let _ = self.cframes.pop();
+            if let Some(cf) = self.cframes.pop() {
+                cf.write_back();
+            }
         }
 
         let inst = match self.inst() {
@@ -301,43 +506,61 @@ impl<T: Extern, L: Locals> Fiber<T, L> {
                     .ok_or(Error::UndefinedSymbol(s))?;
                 self.stack.push(value.clone());
             }
+            Inst::GetLocal(slot) => {
+                let value = self.cf().slot(slot)?.lock().unwrap().clone();
+                let value = match value {
+                    Some(v) => v,
+                    None => self.get_undefined(&self.cf().slot_sym(slot))?,
+                };
+                self.stack.push(value);
+            }
+            Inst::SetLocal(slot) => {
+                let value = self.stack.last().ok_or(Error::UnexpectedStack(
+                    "Stack should contain value to bind".to_string(),
+                ))?;
+                *self.cf().slot(slot)?.lock().unwrap() = Some(value.clone());
+            }
+            Inst::GetUpval(idx) => {
+                let value = self.cf().upval(idx)?.lock().unwrap().clone();
+                let value = match value {
+                    Some(v) => v,
+                    None => self.get_undefined(&self.cf().upval_sym(idx))?,
+                };
+                self.stack.push(value);
+            }
+            Inst::SetUpval(idx) => {
+                let value = self.stack.last().ok_or(Error::UnexpectedStack(
+                    "Stack should contain value to bind".to_string(),
+                ))?;
+                *self.cf().upval(idx)?.lock().unwrap() = Some(value.clone());
+            }
             Inst::MakeFunc => {
-                let code = match self.stack.pop() {
-                    Some(Val::Bytecode(b)) => Ok(b),
-                    _ => Err(Error::UnexpectedStack(
-                        "Missing function bytecode".to_string(),
-                    )),
-                }?;
-                let doc = match self.stack.pop() {
-                    Some(Val::String(doc)) => Ok(Some(doc)),
-                    Some(Val::Nil) => Ok(None),
+                let l = self.pop_func()?;
+                self.stack.push(Val::Lambda(l));
+            }
+            Inst::MakeClosure(scope) => {
+                let mut l = self.pop_func()?;
+                l.upvals = scope
+                    .upvals
+                    .iter()
+                    .map(|(_, from)| match from {
+                        Capture::Local(slot) => self.cf().slot(*slot).cloned(),
+                        Capture::Upval(idx) => self.cf().upval(*idx).cloned(),
+                    })
+                    .collect::<Result<_>>()?;
+                l.scope = Some(scope);
+                self.stack.push(Val::Lambda(l));
+            }
+            Inst::MakeMacro => {
+                let mac = match self.stack.pop() {
+                    Some(Val::Lambda(l)) => Ok(l),
                     _ => Err(Error::UnexpectedStack(
-                        "Expected doc string in stack".to_string(),
+                        "Missing function for macro".to_string(),
                     )),
                 }?;
-                let params = match self.stack.pop() {
-                    Some(Val::List(p)) => Ok(p),
-                    _ => Err(Error::UnexpectedStack("Missing parameter list".to_string())),
-                }?;
-
-                let params = params
-                    .into_iter()
-                    .map(|f| match f {
-                        Val::Symbol(s) => Ok(s),
-                        _ => Err(Error::UnexpectedStack(
-                            "Unexpected parameter list".to_string(),
-                        )),
-                    })
-                    .collect::<Result<Vec<_>>>()?;
-
-                self.stack.push(Val::Lambda(Lambda {
-                    doc,
-                    params,
-                    code,
-                    parent: Some(Arc::clone(&self.cf().env)),
-                }));
+                self.stack.push(Val::Macro(mac));
             }
-            Inst::CallFunc(nargs) => {
+            Inst::CallFunc(nargs) | Inst::TailCallFunc(nargs) => {
                 let mut args = vec![];
                 for _ in 0..nargs {
                     let v = self.stack.pop().ok_or(Error::UnexpectedStack(
@@ -345,41 +568,46 @@ impl<T: Extern, L: Locals> Fiber<T, L> {
                     ))?;
                     args.push(v);
                 }
-                let args = args.into_iter().rev();
+                args.reverse();
 
                 match self.stack.pop() {
                     Some(Val::Lambda(l)) => {
-                        let parent_env = l.parent.unwrap_or_else(|| Arc::clone(&self.global));
-                        let mut fn_env = Env::extend(&parent_env);
-                        for (s, arg) in l.params.into_iter().zip(args) {
-                            fn_env.define(s, arg);
+                        let cf =
+                            self.call_frame(l, args, self.stack.len(), self.cf().unwind_cf_len)?;
+                        // Tail calls replace callframe of function body, which has nothing left on stack
+                        if matches!(inst, Inst::TailCallFunc(_))
+                            && self.stack.len() == self.cf().stack_len
+                        {
+                            *self.cf_mut() = cf;
+                        } else {
+                            self.cframes.push(cf);
                         }
-                        self.cframes.push(CallFrame::from_bytecode(
-                            Arc::new(Mutex::new(fn_env)),
-                            l.code,
-                            self.stack.len(),
-                            self.cf().unwind_cf_len,
-                        ))
                     }
                     Some(Val::NativeFn(n)) => {
-                        let v = (n.func)(self, &args.collect::<Vec<_>>())?;
+                        let v = (n.func)(self, &args)?;
                         match v {
                             NativeFnOp::Return(v) => self.stack.push(v),
                             NativeFnOp::Yield(v) => {
                                 self.stack.push(v);
                                 self.status = Status::Paused;
                             }
-                            NativeFnOp::Exec(code) => self.cframes.push(CallFrame::from_bytecode(
-                                Arc::clone(self.cur_env()),
-                                code,
-                                self.stack.len(),
-                                self.cf().unwind_cf_len,
-                            )),
+                            NativeFnOp::Exec(code) => {
+                                let (env, vars) = self.frame_env_vars();
+                                self.cframes.push(CallFrame {
+                                    frame_vars: vars,
+                                    ..CallFrame::from_bytecode(
+                                        env,
+                                        Arc::new(code),
+                                        self.stack.len(),
+                                        self.cf().unwind_cf_len,
+                                    )
+                                })
+                            }
                         }
                     }
                     Some(Val::NativeAsyncFn(fun)) => {
                         // TODO: Hack - pass to parent scope via stack
-                        self.stack.push(Val::List(args.collect::<Vec<_>>()));
+                        self.stack.push(Val::List(args));
                         self.stack.push(Val::NativeAsyncFn(fun));
                         self.status = Status::Paused;
                     }
@@ -394,19 +622,24 @@ impl<T: Extern, L: Locals> Fiber<T, L> {
                     }
                 };
             }
-            Inst::Eval(protected) => {
+            Inst::Eval(_) | Inst::EvalSpanned(_) => {
                 // set new protected frame or inherit
-                let unwind_cf_len = match protected {
-                    true => Some(self.cframes.len()),
-                    false => self.cf().unwind_cf_len,
+                let unwind_cf_len = match inst {
+                    Inst::Eval(true) => Some(self.cframes.len()),
+                    _ => self.cf().unwind_cf_len,
                 };
                 let val = self.stack.pop().ok_or(Error::UnexpectedStack(
                     "Did not find form to eval on stack".to_string(),
                 ))?;
-                let bc = compile(&val)?;
+                let bc = match inst {
+                    Inst::EvalSpanned(src) => {
+                        compile_spanned(&val, &src.span, src.file.as_deref(), Some(self))?
+                    }
+                    _ => compile_in(&val, self)?,
+                };
                 self.cframes.push(CallFrame::from_bytecode(
                     Arc::clone(self.cur_env()),
-                    bc,
+                    Arc::new(bc),
                     self.stack.len(),
                     unwind_cf_len,
                 ));
@@ -418,6 +651,12 @@ impl<T: Extern, L: Locals> Fiber<T, L> {
                     ));
                 }
             }
+            Inst::DupTop => {
+                let top = self.stack.last().cloned().ok_or(Error::UnexpectedStack(
+                    "Attempting to duplicate empty stack".to_string(),
+                ))?;
+                self.stack.push(top);
+            }
             Inst::JumpFwd(fwd) => self.cf_mut().ip += fwd,
             Inst::JumpBck(back) => self.cf_mut().ip -= back,
             Inst::PopJumpFwdIfTrue(offset) => {
@@ -434,10 +673,107 @@ impl<T: Extern, L: Locals> Fiber<T, L> {
         Ok(())
     }
 
+    /// Pop parameter list, doc, and function body off stack for new function in current callframe
+    fn pop_func(&mut self) -> Result<Lambda<T, L>> {
+        let code = match self.stack.pop() {
+            Some(Val::Bytecode(b)) => Ok(Arc::new(b)),
+            _ => Err(Error::UnexpectedStack(
+                "Missing function bytecode".to_string(),
+            )),
+        }?;
+        let doc = match self.stack.pop() {
+            Some(Val::String(doc)) => Ok(Some(doc)),
+            Some(Val::Nil) => Ok(None),
+            _ => Err(Error::UnexpectedStack(
+                "Expected doc string in stack".to_string(),
+            )),
+        }?;
+        let params = match self.stack.pop() {
+            Some(Val::List(p)) => Ok(p),
+            _ => Err(Error::UnexpectedStack("Missing parameter list".to_string())),
+        }?;
+
+        let params = params
+            .into_iter()
+            .map(|f| match f {
+                Val::Symbol(s) => Ok(s),
+                _ => Err(Error::UnexpectedStack(
+                    "Unexpected parameter list".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Lambda {
+            doc,
+            params,
+            code,
+            parent: Some(Arc::clone(&self.cf().env)),
+            scope: None,
+            upvals: vec![],
+        })
+    }
+
+    /// Callframe for calling function with given arguments. Arguments are bound to local slots if
+    /// function body is lexically addressed, or in a new environment otherwise.
+    fn call_frame(
+        &self,
+        l: Lambda<T, L>,
+        args: Vec<Val<T, L>>,
+        stack_len: usize,
+        unwind_cf_len: Option<usize>,
+    ) -> Result<CallFrame<T, L>> {
+        let bindings = Params::parse(&l.params)?.bind(args).map_err(|e| match e {
+            Error::UnexpectedArguments(msg) => {
+                Error::UnexpectedArguments(format!("{msg} - calling {}", Val::Lambda(l.clone())))
+            }
+            e => e,
+        })?;
+        let parent_env = l.parent.unwrap_or_else(|| Arc::clone(&self.global));
+
+        let scope = match l.scope {
+            Some(scope) => scope,
+            None => {
+                let mut fn_env = Env::extend(&parent_env);
+                for (s, arg) in bindings {
+                    fn_env.define(s, arg);
+                }
+                return Ok(CallFrame::from_bytecode(
+                    Arc::new(Mutex::new(fn_env)),
+                    l.code,
+                    stack_len,
+                    unwind_cf_len,
+                ));
+            }
+        };
+
+        // parameters are the first slots, in order they are bound
+        let mut args = bindings.into_iter().map(|(_, arg)| arg);
+        let slots = scope
+            .slots
+            .iter()
+            .map(|_| Arc::new(Mutex::new(args.next())))
+            .collect();
+        Ok(CallFrame {
+            slots,
+            upvals: l.upvals,
+            scope: Some(scope),
+            ..CallFrame::from_bytecode(parent_env, l.code, stack_len, unwind_cf_len)
+        })
+    }
+
+    /// Value of variable that is not defined yet in function body, which falls back to binding in environment
+    fn get_undefined(&self, sym: &SymbolId) -> Result<Val<T, L>> {
+        self.cur_env()
+            .lock()
+            .unwrap()
+            .get(sym)
+            .ok_or(Error::UndefinedSymbol(sym.clone()))
+    }
+
     /// Next instruction in fiber, or None if fiber is complete
     fn inst(&self) -> Option<&Inst<T, L>> {
         let cf = self.cf();
-        cf.code.get(cf.ip)
+        cf.code.inst.get(cf.ip)
     }
 
     /// Top callframe
@@ -451,11 +787,44 @@ impl<T: Extern, L: Locals> Fiber<T, L> {
     }
 }
 
+impl<T: Extern, L: Locals> FiberHandle<T, L> {
+    /// Create a new handle for fiber
+    pub fn new(f: Fiber<T, L>) -> Self {
+        Self(Arc::new(Mutex::new(Some(f))))
+    }
+
+    /// Take fiber out of handle to run it, or None if it is already running
+    pub fn take(&self) -> Option<Fiber<T, L>> {
+        self.0.lock().unwrap().take()
+    }
+
+    /// Return fiber taken via [FiberHandle::take] to handle
+    pub fn put(&self, f: Fiber<T, L>) {
+        *self.0.lock().unwrap() = Some(f);
+    }
+
+    /// Current status of fiber, which is [Status::Running] while it is taken out of handle
+    pub fn status(&self) -> Status {
+        self.0
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(Fiber::status)
+            .unwrap_or(Status::Running)
+    }
+}
+
+impl<T: Extern, L: Locals> std::cmp::PartialEq for FiberHandle<T, L> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
 impl<T: Extern, L: Locals> CallFrame<T, L> {
     /// Create a new callframe for executing given bytecode from start
     fn from_bytecode(
         env: Arc<Mutex<Env<T, L>>>,
-        code: Bytecode<T, L>,
+        code: Arc<Bytecode<T, L>>,
         stack_len: usize,
         unwind_cf_len: Option<usize>,
     ) -> Self {
@@ -465,13 +834,68 @@ impl<T: Extern, L: Locals> CallFrame<T, L> {
             code,
             stack_len,
             unwind_cf_len,
+            scope: None,
+            slots: vec![],
+            upvals: vec![],
+            frame_vars: vec![],
+        }
+    }
+
+    /// Write variables defined or set in environment of callframe back to function body they were copied from
+    fn write_back(self) {
+        if self.frame_vars.is_empty() {
+            return;
+        }
+        let env = self.env.lock().unwrap();
+        for (sym, var, copied) in self.frame_vars {
+            let val = env.get_defined(&sym);
+            if val.as_ref() != copied.as_ref() {
+                *var.lock().unwrap() = val;
+            }
         }
     }
 
+    /// Local variable in given slot
+    fn slot(&self, slot: usize) -> Result<&Upval<T, L>> {
+        self.slots.get(slot).ok_or(Error::UnexpectedStack(format!(
+            "Callframe should have local slot {slot}"
+        )))
+    }
+
+    /// Variable captured as given upvalue
+    fn upval(&self, idx: usize) -> Result<&Upval<T, L>> {
+        self.upvals.get(idx).ok_or(Error::UnexpectedStack(format!(
+            "Callframe should have upvalue {idx}"
+        )))
+    }
+
+    /// Symbol of local variable in given slot
+    fn slot_sym(&self, slot: usize) -> SymbolId {
+        self.scope
+            .as_ref()
+            .and_then(|s| s.slots.get(slot))
+            .cloned()
+            .unwrap_or_else(|| SymbolId::from(format!("<local {slot}>")))
+    }
+
+    /// Symbol of variable captured as given upvalue
+    fn upval_sym(&self, idx: usize) -> SymbolId {
+        self.scope
+            .as_ref()
+            .and_then(|s| s.upvals.get(idx))
+            .map(|(sym, _)| sym.clone())
+            .unwrap_or_else(|| SymbolId::from(format!("<upval {idx}>")))
+    }
+
     /// Whether or not call frame is at implicit return
     fn at_return(&self) -> bool {
         self.ip == self.code.len()
     }
+
+    /// Source location of instruction last executed in callframe, if known
+    fn loc(&self) -> Option<&SourceLoc> {
+        self.ip.checked_sub(1).and_then(|ip| self.code.loc(ip))
+    }
 }
 
 #[cfg(test)]
@@ -481,7 +905,7 @@ mod tests {
     use void::Void;
 
     use super::Inst::*;
-    use crate::SymbolId;
+    use crate::{compile_spanned, parse_all, SymbolId};
 
     type Fiber = super::Fiber<Void, ()>;
     type Val = super::Val<Void, ()>;
@@ -489,7 +913,7 @@ mod tests {
 
     #[test]
     fn empty() {
-        let mut f = Fiber::from_bytecode(vec![], Env::standard(), ());
+        let mut f = Fiber::from_bytecode(vec![].into(), Env::standard(), ());
 
         assert!(!f.is_done());
         assert_matches!(f.start(), Err(Error::UnexpectedStack(_)));
@@ -498,7 +922,7 @@ mod tests {
 
     #[test]
     fn done_with_result() {
-        let mut f = Fiber::from_bytecode(vec![PushConst(Val::Int(5))], Env::standard(), ());
+        let mut f = Fiber::from_bytecode(vec![PushConst(Val::Int(5))].into(), Env::standard(), ());
 
         assert!(!f.is_done());
         assert!(f.start().is_ok());
@@ -525,7 +949,8 @@ mod tests {
                 PopTop,
                 PopTop,
                 PopTop,
-            ],
+            ]
+            .into(),
             Env::standard(),
             (),
         );
@@ -548,15 +973,19 @@ mod tests {
     #[test]
     fn push_const() {
         {
-            let mut f = Fiber::from_bytecode(vec![PushConst(Val::Int(5))], Env::standard(), ());
+            let mut f =
+                Fiber::from_bytecode(vec![PushConst(Val::Int(5))].into(), Env::standard(), ());
             assert!(!f.is_done());
             assert_eq!(f.start().unwrap(), Signal::Done(Val::Int(5)));
             assert!(f.is_done());
         }
 
         {
-            let mut f =
-                Fiber::from_bytecode(vec![PushConst(Val::string("Hi"))], Env::standard(), ());
+            let mut f = Fiber::from_bytecode(
+                vec![PushConst(Val::string("Hi"))].into(),
+                Env::standard(),
+                (),
+            );
             assert_eq!(f.start().unwrap(), Signal::Done(Val::string("Hi")));
         }
     }
@@ -564,7 +993,7 @@ mod tests {
     #[test]
     fn def_symbol() {
         let mut f = Fiber::from_bytecode(
-            vec![PushConst(Val::Int(5)), DefSym(SymbolId::from("x"))],
+            vec![PushConst(Val::Int(5)), DefSym(SymbolId::from("x"))].into(),
             Env::standard(),
             (),
         );
@@ -579,7 +1008,11 @@ mod tests {
 
     #[test]
     fn get_symbol() {
-        let mut f = Fiber::from_bytecode(vec![GetSym(SymbolId::from("x"))], Env::standard(), ());
+        let mut f = Fiber::from_bytecode(
+            vec![GetSym(SymbolId::from("x"))].into(),
+            Env::standard(),
+            (),
+        );
         f.cf()
             .env
             .lock()
@@ -591,7 +1024,11 @@ mod tests {
 
     #[test]
     fn get_symbol_undefined() {
-        let mut f = Fiber::from_bytecode(vec![GetSym(SymbolId::from("x"))], Env::standard(), ());
+        let mut f = Fiber::from_bytecode(
+            vec![GetSym(SymbolId::from("x"))].into(),
+            Env::standard(),
+            (),
+        );
 
         assert_matches!(f.start(), Err(Error::UndefinedSymbol(_)));
         assert!(f.is_done(), "Should be done after error");
@@ -603,7 +1040,8 @@ mod tests {
             vec![
                 PushConst(Val::string("updated")),
                 SetSym(SymbolId::from("x")),
-            ],
+            ]
+            .into(),
             Env::standard(),
             (),
         );
@@ -620,7 +1058,7 @@ mod tests {
     #[test]
     fn set_symbol_undefined() {
         let mut f = Fiber::from_bytecode(
-            vec![PushConst(Val::string("value")), SetSym(SymbolId::from("x"))],
+            vec![PushConst(Val::string("value")), SetSym(SymbolId::from("x"))].into(),
             Env::standard(),
             (),
         );
@@ -635,16 +1073,17 @@ mod tests {
             vec![
                 PushConst(Val::List(vec![Val::symbol("x")])),
                 PushConst(Val::Nil),
-                PushConst(Val::Bytecode(vec![GetSym(SymbolId::from("x"))])),
+                PushConst(Val::Bytecode(vec![GetSym(SymbolId::from("x"))].into())),
                 MakeFunc,
-            ],
+            ]
+            .into(),
             Env::standard(),
             (),
         );
 
         assert_matches!(
             f.start().unwrap(),
-            Signal::Done(Val::Lambda(l)) if l.params == vec![SymbolId::from("x")] && l.code == vec![GetSym(SymbolId::from("x"))],
+            Signal::Done(Val::Lambda(l)) if l.params == vec![SymbolId::from("x")] && *l.code == vec![GetSym(SymbolId::from("x"))].into(),
             "A function object was created"
         );
         assert!(f.is_done());
@@ -657,12 +1096,15 @@ mod tests {
                 PushConst(Val::Lambda(Lambda {
                     doc: None,
                     params: vec![SymbolId::from("x")],
-                    code: vec![GetSym(SymbolId::from("x"))],
+                    code: Arc::new(vec![GetSym(SymbolId::from("x"))].into()),
                     parent: None,
+                    scope: None,
+                    upvals: vec![],
                 })),
                 PushConst(Val::string("hello")),
                 CallFunc(1),
-            ],
+            ]
+            .into(),
             Env::standard(),
             (),
         );
@@ -678,11 +1120,12 @@ mod tests {
             vec![
                 PushConst(Val::List(vec![Val::symbol("x")])),
                 PushConst(Val::Nil),
-                PushConst(Val::Bytecode(vec![GetSym(SymbolId::from("x"))])),
+                PushConst(Val::Bytecode(vec![GetSym(SymbolId::from("x"))].into())),
                 MakeFunc,
                 PushConst(Val::string("hello")),
                 CallFunc(1),
-            ],
+            ]
+            .into(),
             Env::standard(),
             (),
         );
@@ -699,17 +1142,21 @@ mod tests {
             vec![
                 PushConst(Val::List(vec![])),
                 PushConst(Val::Nil),
-                PushConst(Val::Bytecode(vec![
-                    PushConst(Val::List(vec![Val::symbol("x")])),
-                    PushConst(Val::Nil),
-                    PushConst(Val::Bytecode(vec![GetSym(SymbolId::from("x"))])),
-                    MakeFunc,
-                ])),
+                PushConst(Val::Bytecode(
+                    vec![
+                        PushConst(Val::List(vec![Val::symbol("x")])),
+                        PushConst(Val::Nil),
+                        PushConst(Val::Bytecode(vec![GetSym(SymbolId::from("x"))].into())),
+                        MakeFunc,
+                    ]
+                    .into(),
+                )),
                 MakeFunc,
                 CallFunc(0),
                 PushConst(Val::string("hello")),
                 CallFunc(1),
-            ],
+            ]
+            .into(),
             Env::standard(),
             (),
         );
@@ -722,17 +1169,21 @@ mod tests {
             vec![
                 PushConst(Val::List(vec![Val::symbol("x")])),
                 PushConst(Val::Nil),
-                PushConst(Val::Bytecode(vec![
-                    PushConst(Val::List(vec![])),
-                    PushConst(Val::Nil),
-                    PushConst(Val::Bytecode(vec![GetSym(SymbolId::from("x"))])),
-                    MakeFunc,
-                ])),
+                PushConst(Val::Bytecode(
+                    vec![
+                        PushConst(Val::List(vec![])),
+                        PushConst(Val::Nil),
+                        PushConst(Val::Bytecode(vec![GetSym(SymbolId::from("x"))].into())),
+                        MakeFunc,
+                    ]
+                    .into(),
+                )),
                 MakeFunc,
                 PushConst(Val::string("hello")),
                 CallFunc(1),
                 CallFunc(0),
-            ],
+            ]
+            .into(),
             Env::standard(),
             (),
         );
@@ -747,7 +1198,8 @@ mod tests {
                 PushConst(Val::string("this")),
                 PushConst(Val::string("not this")),
                 PopTop,
-            ],
+            ]
+            .into(),
             Env::standard(),
             (),
         );
@@ -757,7 +1209,7 @@ mod tests {
 
     #[test]
     fn pop_top_empty() {
-        let mut f = Fiber::from_bytecode(vec![PopTop], Env::standard(), ());
+        let mut f = Fiber::from_bytecode(vec![PopTop].into(), Env::standard(), ());
         assert_matches!(f.start(), Err(Error::UnexpectedStack(_)));
     }
 
@@ -772,7 +1224,8 @@ mod tests {
                 PushConst(Val::string("notthis")),
                 PushConst(Val::string("notthis")),
                 PushConst(Val::string("notthis")),
-            ],
+            ]
+            .into(),
             Env::standard(),
             (),
         );
@@ -790,7 +1243,8 @@ mod tests {
                 PushConst(Val::string("notthis")),
                 JumpFwd(1),
                 PushConst(Val::string("this")),
-            ],
+            ]
+            .into(),
             Env::standard(),
             (),
         );
@@ -808,7 +1262,8 @@ mod tests {
                 PushConst(Val::string("this")),
                 JumpFwd(1),
                 PushConst(Val::string("notthis")),
-            ],
+            ]
+            .into(),
             Env::standard(),
             (),
         );
@@ -831,7 +1286,8 @@ mod tests {
                 PopTop,
                 JumpBck(6), // loop back to getsym
                 GetSym(SymbolId::from("x")),
-            ],
+            ]
+            .into(),
             Env::standard(),
             (),
         );
@@ -843,7 +1299,7 @@ mod tests {
     #[test]
     fn yield_once() {
         let mut f = Fiber::from_bytecode(
-            vec![PushConst(Val::string("before")), YieldTop],
+            vec![PushConst(Val::string("before")), YieldTop].into(),
             Env::standard(),
             (),
         );
@@ -871,7 +1327,8 @@ mod tests {
                 YieldTop,
                 PopTop,
                 JumpBck(8),
-            ],
+            ]
+            .into(),
             Env::standard(),
             (),
         );
@@ -888,7 +1345,7 @@ mod tests {
     #[test]
     fn eval() {
         let mut f = Fiber::from_bytecode(
-            vec![PushConst(Val::Int(42)), Eval(false)],
+            vec![PushConst(Val::Int(42)), Eval(false)].into(),
             Env::standard(),
             (),
         );
@@ -906,7 +1363,8 @@ mod tests {
                     ]),
                 ])),
                 Eval(false),
-            ],
+            ]
+            .into(),
             Env::standard(),
             (),
         );
@@ -925,7 +1383,7 @@ mod tests {
     #[test]
     fn eval_error() {
         let mut f = Fiber::from_bytecode(
-            vec![PushConst(Val::symbol("jibberish")), Eval(false)],
+            vec![PushConst(Val::symbol("jibberish")), Eval(false)].into(),
             Env::standard(),
             (),
         );
@@ -940,7 +1398,8 @@ mod tests {
                     Val::keyword("c"),
                 ])),
                 Eval(false),
-            ],
+            ]
+            .into(),
             Env::standard(),
             (),
         );
@@ -950,7 +1409,7 @@ mod tests {
     #[test]
     fn yield_loop() {
         let mut f = Fiber::from_bytecode(
-            vec![PushConst(Val::string("hi")), YieldTop, PopTop, JumpBck(4)],
+            vec![PushConst(Val::string("hi")), YieldTop, PopTop, JumpBck(4)].into(),
             Env::standard(),
             (),
         );
@@ -976,5 +1435,25 @@ mod tests {
     }
 
     // TODO: Add Test case for NativeFnOp::Call
+    #[test]
+    fn error_traceback() {
+        let (form, span) = parse_all(
+            "(begin\n  (def f (lambda ()\n    (undefined)))\n  (list (try (f))\n    (f)))",
+        )
+        .unwrap()
+        .remove(0);
+        let code = compile_spanned(&Val::from(form), &span, Some("a.ll"), None).unwrap();
+        let mut f = Fiber::from_bytecode(code, Env::standard(), ());
+
+        assert_eq!(
+            f.start(),
+            Err(Error::Traceback {
+                error: Box::new(Error::UndefinedSymbol(SymbolId::from("undefined"))),
+                trace: vec!["a.ll:3".to_string(), "a.ll:5".to_string()],
+            }),
+            "uncaught errors should carry locations of callframes, innermost first"
+        );
+    }
+
     // TODO: Test that Fiber::resume w/ Err resume value (i.e. from nativeasyncfn err) is catch-able - (try (exec "jibberish"))
 }
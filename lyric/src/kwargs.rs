@@ -13,6 +13,7 @@ pub fn get<T: Extern, L: Locals>(lst: &[Val<T, L>], target: &KeywordId) -> Optio
 /// Supports both implicit and explicit values:
 /// - (my_func :my_flag)
 /// - (my_func :my_flag true) / (my_func :my_flag false)
+///
 /// If value after `:my_flag` is not a boolean, it is not considered a value for flag argument
 pub fn flag<T: Extern, L: Locals>(lst: &[Val<T, L>], target: &KeywordId) -> Option<Val<T, L>> {
     let mut iter = lst
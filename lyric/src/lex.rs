@@ -9,13 +9,19 @@ use crate::{Error, Result};
 pub enum Token {
     Nil,
     Bool(bool),
-    Int(i32),
+    Int(i64),
+    Float(f64),
     String(String),
     Symbol(String),
     Keyword(String),
     ParenLeft,
     ParenRight,
+    BraceLeft,
+    BraceRight,
     Quote,
+    Quasiquote,
+    Unquote,
+    UnquoteSplice,
 }
 
 impl std::fmt::Display for Token {
@@ -24,24 +30,53 @@ impl std::fmt::Display for Token {
             Token::Nil => write!(f, "nil"),
             Token::Bool(b) => write!(f, "{}", if *b { "true" } else { "false" }),
             Token::Int(i) => write!(f, "{}", i),
+            Token::Float(n) => write!(f, "{:?}", n),
             Token::String(s) => write!(f, "\"{}\"", s),
             Token::Symbol(s) => write!(f, "{}", s),
             Token::Keyword(s) => write!(f, ":{}", s),
             Token::ParenLeft => write!(f, "("),
             Token::ParenRight => write!(f, ")"),
+            Token::BraceLeft => write!(f, "{{"),
+            Token::BraceRight => write!(f, "}}"),
             Token::Quote => write!(f, "'"),
+            Token::Quasiquote => write!(f, "`"),
+            Token::Unquote => write!(f, ","),
+            Token::UnquoteSplice => write!(f, ",@"),
         }
     }
 }
 
+/// Position within source expression
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    /// Line number, starting from 1
+    pub line: usize,
+    /// Column number in characters, starting from 1
+    pub col: usize,
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
 /// Tokenize entire expression as vector
+#[cfg(test)]
 pub(crate) fn lex(expr: &str) -> Result<Vec<Token>> {
+    Ok(lex_with_pos(expr)?.into_iter().map(|(t, _)| t).collect())
+}
+
+/// Tokenize entire expression as vector of tokens and their starting positions
+pub(crate) fn lex_with_pos(expr: &str) -> Result<Vec<(Token, Position)>> {
     let mut tokens = vec![];
-    for token in Tokens::new(expr) {
+    let mut tokenizer = Tokens::new(expr);
+    while let Some(token) = tokenizer.next() {
         match token {
-            Ok(token) => tokens.push(token),
+            Ok(token) => tokens.push((token, tokenizer.start)),
             Err(err) => {
                 error!("lexing failed - {}, tokens={:?}", err, tokens);
+                return Err(err);
             }
         }
     }
@@ -50,14 +85,27 @@ pub(crate) fn lex(expr: &str) -> Result<Vec<Token>> {
 
 /// An iterator over Tokens
 struct Tokens<'a> {
-    inner: Peekable<std::str::Chars<'a>>,
+    inner: Cursor<'a>,
+    /// Starting position of last token
+    start: Position,
+}
+
+/// Peekable iterator over characters that tracks position of next character
+struct Cursor<'a> {
+    chars: Peekable<std::str::Chars<'a>>,
+    pos: Position,
 }
 
 impl Tokens<'_> {
     /// Create Tokens iterator from &str
     fn new(expr: &str) -> Tokens<'_> {
+        let inner = Cursor {
+            chars: expr.chars().peekable(),
+            pos: Position { line: 1, col: 1 },
+        };
         Tokens {
-            inner: expr.chars().peekable(),
+            start: inner.pos,
+            inner,
         }
     }
 
@@ -69,20 +117,11 @@ impl Tokens<'_> {
             "true" => Ok(Token::Bool(true)),
             "false" => Ok(Token::Bool(false)),
             "nil" => Ok(Token::Nil),
+            _ if is_number(&expr) => parse_number(&expr),
             _ => Ok(Token::Symbol(expr)),
         }
     }
 
-    /// Pares the next int
-    fn next_int(&mut self) -> Result<Token> {
-        let expr: String =
-            std::iter::from_fn(|| self.inner.next_if(|ch| !is_symbol_delimiter(ch))).collect();
-        let num = expr.parse::<i32>().map_err(|_| {
-            Error::IncompleteExpression(format!("Unable to parse integer - {expr}"))
-        })?;
-        Ok(Token::Int(num))
-    }
-
     /// Parse next punctuation
     fn next_punct(&mut self) -> Result<Token> {
         let ch = self.inner.next().ok_or(Error::IncompleteExpression(
@@ -91,7 +130,12 @@ impl Tokens<'_> {
         match ch {
             '(' => Ok(Token::ParenLeft),
             ')' => Ok(Token::ParenRight),
+            '{' => Ok(Token::BraceLeft),
+            '}' => Ok(Token::BraceRight),
             '\'' => Ok(Token::Quote),
+            '`' => Ok(Token::Quasiquote),
+            ',' if self.inner.next_if(|ch| *ch == '@').is_some() => Ok(Token::UnquoteSplice),
+            ',' => Ok(Token::Unquote),
             _ => Err(Error::IncompleteExpression(format!(
                 "Unexpected punctuation - {ch}"
             ))),
@@ -160,28 +204,59 @@ impl Tokens<'_> {
     }
 }
 
+impl Cursor<'_> {
+    fn peek(&mut self) -> Option<&char> {
+        self.chars.peek()
+    }
+
+    fn next_if(&mut self, func: impl FnOnce(&char) -> bool) -> Option<char> {
+        let ch = self.chars.next_if(func)?;
+        self.advance(ch);
+        Some(ch)
+    }
+
+    fn advance(&mut self, ch: char) {
+        if ch == '\n' {
+            self.pos.line += 1;
+            self.pos.col = 1;
+        } else {
+            self.pos.col += 1;
+        }
+    }
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ch = self.chars.next()?;
+        self.advance(ch);
+        Some(ch)
+    }
+}
+
 impl<'a> Iterator for Tokens<'a> {
     type Item = Result<Token>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let mut is_comment = false;
 
-        while let Some(ch) = self.inner.peek() {
-            if *ch == '\n' && is_comment {
+        while let Some(&ch) = self.inner.peek() {
+            if ch == '\n' && is_comment {
                 is_comment = false;
             }
-            if *ch == '#' {
+            if ch == '#' {
                 is_comment = true;
             }
             if ch.is_whitespace() || is_comment {
                 let _ = self.inner.next();
                 continue;
             }
+            self.start = self.inner.pos;
             let token = match ch {
                 '\"' => self.next_string(),
                 ':' => self.next_keyword(),
-                _ if is_punct(ch) => self.next_punct(),
-                _ if ch.is_numeric() || ch == &'-' => self.next_int(),
+                _ if is_punct(&ch) => self.next_punct(),
                 _ => self.next_symbol(),
             };
             return Some(token);
@@ -190,6 +265,29 @@ impl<'a> Iterator for Tokens<'a> {
     }
 }
 
+/// Whether or not expression should be parsed as number, i.e. starts with digit or negative sign followed by digit
+fn is_number(expr: &str) -> bool {
+    let mut chars = expr.chars();
+    match chars.next() {
+        Some('-') => chars.next().is_some_and(|ch| ch.is_ascii_digit()),
+        Some(ch) => ch.is_ascii_digit(),
+        None => false,
+    }
+}
+
+/// Parse number as int, or float if it has a fractional part or exponent
+fn parse_number(expr: &str) -> Result<Token> {
+    let is_float = expr.contains(['.', 'e', 'E']);
+    let token = if is_float {
+        expr.parse::<f64>().ok().map(Token::Float)
+    } else {
+        expr.parse::<i64>().ok().map(Token::Int)
+    };
+    token.ok_or(Error::IncompleteExpression(format!(
+        "Unable to parse number - {expr}"
+    )))
+}
+
 /// Return whether or not a given character is a symbol delimiter
 fn is_symbol_delimiter(ch: &char) -> bool {
     ch.is_whitespace() || is_punct(ch)
@@ -197,7 +295,7 @@ fn is_symbol_delimiter(ch: &char) -> bool {
 
 /// Return whether or not token is an interesting punctuation
 fn is_punct(ch: &char) -> bool {
-    *ch == '(' || *ch == ')' || *ch == '\''
+    matches!(ch, '(' | ')' | '{' | '}' | '\'' | '`' | ',')
 }
 
 #[cfg(test)]
@@ -220,6 +318,32 @@ mod tests {
         assert_eq!(lex("1"), Ok(vec![Token::Int(1)]));
         assert_eq!(lex("     1     "), Ok(vec![Token::Int(1)]));
         assert_eq!(lex("-99"), Ok(vec![Token::Int(-99)]));
+        assert_eq!(lex("9223372036854775807"), Ok(vec![Token::Int(i64::MAX)]));
+        assert!(lex("9223372036854775808").is_err());
+        assert!(lex("12ab").is_err());
+    }
+
+    #[test]
+    fn lex_float() {
+        assert_eq!(lex("1.5"), Ok(vec![Token::Float(1.5)]));
+        assert_eq!(lex("-0.25"), Ok(vec![Token::Float(-0.25)]));
+        assert_eq!(lex("1e3"), Ok(vec![Token::Float(1000.0)]));
+        assert!(lex("1.2.3").is_err());
+    }
+
+    #[test]
+    fn lex_minus_symbol() {
+        assert_eq!(
+            lex("(- 1 -2 -x)"),
+            Ok(vec![
+                Token::ParenLeft,
+                Token::Symbol("-".to_string()),
+                Token::Int(1),
+                Token::Int(-2),
+                Token::Symbol("-x".to_string()),
+                Token::ParenRight,
+            ])
+        );
     }
 
     #[test]
@@ -415,6 +539,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lex_quasiquoted() {
+        assert_eq!(
+            lex("`(a ,b ,@c)"),
+            Ok(vec![
+                Token::Quasiquote,
+                Token::ParenLeft,
+                Token::Symbol("a".to_string()),
+                Token::Unquote,
+                Token::Symbol("b".to_string()),
+                Token::UnquoteSplice,
+                Token::Symbol("c".to_string()),
+                Token::ParenRight,
+            ])
+        );
+    }
+
+    #[test]
+    fn lex_map() {
+        assert_eq!(
+            lex("{:a 1 :b{}}"),
+            Ok(vec![
+                Token::BraceLeft,
+                Token::Keyword("a".to_string()),
+                Token::Int(1),
+                Token::Keyword("b".to_string()),
+                Token::BraceLeft,
+                Token::BraceRight,
+                Token::BraceRight,
+            ])
+        );
+    }
+
     #[test]
     fn lex_empty() {
         assert_eq!(lex(""), Ok(vec![]));
@@ -450,8 +607,27 @@ mod tests {
             ]),
         );
     }
-}
 
-// TODO(bug): Cannot parse non-number "-" prefix:
-//     vrs> (- (read (get (exec "date" "+%s") 1)) 10)
-//     Incomplete expression - Unable to parse integer - -
+    #[test]
+    fn lex_positions() {
+        assert_eq!(
+            lex_with_pos("(a \"b\nc\")\n  # comment\n  :d"),
+            Ok(vec![
+                (Token::ParenLeft, Position { line: 1, col: 1 }),
+                (
+                    Token::Symbol(String::from("a")),
+                    Position { line: 1, col: 2 }
+                ),
+                (
+                    Token::String(String::from("b\nc")),
+                    Position { line: 1, col: 4 }
+                ),
+                (Token::ParenRight, Position { line: 2, col: 3 }),
+                (
+                    Token::Keyword(String::from("d")),
+                    Position { line: 4, col: 3 }
+                ),
+            ])
+        );
+    }
+}
@@ -8,18 +8,35 @@ pub mod builtin;
 pub mod env;
 pub mod fiber;
 pub mod kwargs;
+pub mod params;
 pub mod pmatch;
 pub mod types;
 
+/// Internals used by benchmarks, which are not part of stable API
+#[doc(hidden)]
+pub mod bench {
+    pub use crate::codegen::compile_dynamic;
+}
+
 pub use builtin::Ref;
 pub use codegen::compile;
+pub use codegen::compile_in;
+pub use codegen::compile_spanned;
+pub use codegen::Capture;
 pub use codegen::Inst;
+pub use codegen::Scope;
+pub use codegen::SourceLoc;
+pub use codegen::SourceSpan;
 pub use env::Env;
 pub use error::Error;
 pub use fiber::Fiber;
+pub use fiber::FiberHandle;
 pub use fiber::Signal;
 pub use fiber::Status;
+pub use lex::Position;
 pub use parse::parse;
+pub use parse::parse_all;
+pub use parse::Span;
 pub use pmatch::Pattern;
 pub use run::run;
 pub use types::Bytecode;
@@ -28,10 +45,13 @@ pub use types::Form;
 pub use types::KeywordId;
 pub use types::Lambda;
 pub use types::Locals;
+pub use types::Map;
+pub use types::MapKey;
 pub use types::NativeAsyncFn;
 pub use types::NativeFn;
 pub use types::NativeFnOp;
 pub use types::SymbolId;
+pub use types::Upval;
 pub use types::Val;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,251 @@
+//! Lambda lists of parameters
+//! Parameters are a list of symbols, where `&optional`, `&rest` and `&key` markers separate
+//! required, optional, rest, and keyword parameters, in that order.
+use crate::{Error, Extern, Locals, Result, SymbolId, Val};
+
+/// Parameters of a lambda, grouped by kind
+#[derive(Debug, Default, PartialEq)]
+pub struct Params<'a> {
+    /// Required positional parameters
+    pub required: &'a [SymbolId],
+    /// Optional positional parameters, which are nil if missing
+    pub optional: &'a [SymbolId],
+    /// Parameter bound to list of remaining arguments
+    pub rest: Option<&'a SymbolId>,
+    /// Keyword parameters, which are nil if missing
+    pub key: &'a [SymbolId],
+}
+
+/// Kinds of parameters, in the order they appear in lambda list
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Kind {
+    Required,
+    Optional,
+    Rest,
+    Key,
+}
+
+impl<'a> Params<'a> {
+    /// Parse parameter list with `&optional`, `&rest` and `&key` markers
+    pub fn parse(params: &'a [SymbolId]) -> Result<Self> {
+        let mut res = Params::default();
+        let mut kind = Kind::Required;
+        let mut start = 0;
+        for (idx, sym) in params.iter().enumerate() {
+            let next = match sym.as_str() {
+                "&optional" => Kind::Optional,
+                "&rest" => Kind::Rest,
+                "&key" => Kind::Key,
+                _ => continue,
+            };
+            if next <= kind {
+                return Err(Error::InvalidExpression(format!(
+                    "Unexpected {sym} in parameter list - markers should be in order of &optional, &rest, &key"
+                )));
+            }
+            res.assign(kind, &params[start..idx])?;
+            kind = next;
+            start = idx + 1;
+        }
+        res.assign(kind, &params[start..])?;
+        Ok(res)
+    }
+
+    fn assign(&mut self, kind: Kind, params: &'a [SymbolId]) -> Result<()> {
+        match kind {
+            Kind::Required => self.required = params,
+            Kind::Optional => self.optional = params,
+            Kind::Key => self.key = params,
+            Kind::Rest => match params {
+                [rest] => self.rest = Some(rest),
+                _ => {
+                    return Err(Error::InvalidExpression(
+                        "&rest should be followed by exactly one parameter".to_string(),
+                    ))
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Bind arguments to each parameter
+    pub fn bind<T: Extern, L: Locals>(
+        &self,
+        args: Vec<Val<T, L>>,
+    ) -> Result<Vec<(SymbolId, Val<T, L>)>> {
+        let nreq = self.required.len();
+        let npos = nreq + self.optional.len();
+        let variadic = self.rest.is_some() || !self.key.is_empty();
+        if args.len() < nreq || (!variadic && args.len() > npos) {
+            return Err(Error::UnexpectedArguments(format!(
+                "Expected {} - got {}",
+                self.arity(),
+                args.len()
+            )));
+        }
+
+        let mut args = args.into_iter();
+        let mut bindings = vec![];
+        for sym in self.required {
+            bindings.push((sym.clone(), args.next().unwrap_or(Val::Nil)));
+        }
+        for sym in self.optional {
+            bindings.push((sym.clone(), args.next().unwrap_or(Val::Nil)));
+        }
+
+        let rest = args.collect::<Vec<_>>();
+        if !self.key.is_empty() {
+            bindings.extend(self.bind_keys(&rest)?);
+        }
+        if let Some(sym) = self.rest {
+            bindings.push((sym.clone(), Val::List(rest)));
+        }
+        Ok(bindings)
+    }
+
+    /// Bind keyword parameters from list of keyword and value pairs
+    fn bind_keys<T: Extern, L: Locals>(
+        &self,
+        args: &[Val<T, L>],
+    ) -> Result<Vec<(SymbolId, Val<T, L>)>> {
+        let mut bindings = self
+            .key
+            .iter()
+            .map(|sym| (sym.clone(), Val::Nil))
+            .collect::<Vec<_>>();
+        for kv in args.chunks(2) {
+            let pos = match kv {
+                [Val::Keyword(k), _] => bindings
+                    .iter()
+                    .position(|(sym, _)| sym.clone().to_keyword() == *k),
+                _ => None,
+            };
+            match pos {
+                Some(pos) => bindings[pos].1 = kv[1].clone(),
+                None if self.rest.is_some() => (),
+                None => {
+                    return Err(Error::UnexpectedArguments(format!(
+                        "Unexpected keyword arguments - expected one of {}",
+                        self.key
+                            .iter()
+                            .map(|k| format!(":{k}"))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    )))
+                }
+            }
+        }
+        Ok(bindings)
+    }
+
+    /// Description of number of accepted arguments
+    fn arity(&self) -> String {
+        let nreq = self.required.len();
+        let npos = nreq + self.optional.len();
+        if self.rest.is_some() || !self.key.is_empty() {
+            format!("at least {nreq} arguments")
+        } else if nreq == npos {
+            format!("{nreq} arguments")
+        } else {
+            format!("{nreq} to {npos} arguments")
+        }
+    }
+
+    /// Parameter symbols in order they are bound by [Params::bind]
+    pub fn bound(&self) -> impl Iterator<Item = &'a SymbolId> {
+        self.required
+            .iter()
+            .chain(self.optional)
+            .chain(self.key)
+            .chain(self.rest)
+    }
+
+    /// All parameter symbols, without markers
+    pub fn symbols(&self) -> impl Iterator<Item = &'a SymbolId> {
+        self.required
+            .iter()
+            .chain(self.optional)
+            .chain(self.rest)
+            .chain(self.key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use void::Void;
+
+    type Val = crate::Val<Void, ()>;
+
+    #[test]
+    fn parse_markers() {
+        let syms = symbols("a &optional b c &rest d &key e");
+        let params = Params::parse(&syms).unwrap();
+        assert_eq!(params.required, &symbols("a")[..]);
+        assert_eq!(params.optional, &symbols("b c")[..]);
+        assert_eq!(params.rest, Some(&SymbolId::from("d")));
+        assert_eq!(params.key, &symbols("e")[..]);
+
+        assert_matches!(
+            Params::parse(&symbols("&rest a b")),
+            Err(Error::InvalidExpression(_))
+        );
+        assert_matches!(
+            Params::parse(&symbols("&key a &optional b")),
+            Err(Error::InvalidExpression(_))
+        );
+        assert_matches!(
+            Params::parse(&symbols("&optional a &optional b")),
+            Err(Error::InvalidExpression(_))
+        );
+    }
+
+    #[test]
+    fn bind_arity() {
+        let syms = symbols("a &optional b");
+        let params = Params::parse(&syms).unwrap();
+        assert_eq!(
+            params.bind(vec![Val::Int(1)]).unwrap(),
+            vec![
+                (SymbolId::from("a"), Val::Int(1)),
+                (SymbolId::from("b"), Val::Nil)
+            ]
+        );
+        assert_matches!(params.bind::<Void, ()>(vec![]), Err(Error::UnexpectedArguments(msg)) if msg == "Expected 1 to 2 arguments - got 0");
+        assert_matches!(
+            params.bind(vec![Val::Int(1), Val::Int(2), Val::Int(3)]),
+            Err(Error::UnexpectedArguments(_))
+        );
+    }
+
+    #[test]
+    fn bind_rest_and_keys() {
+        let syms = symbols("a &rest r &key k");
+        let params = Params::parse(&syms).unwrap();
+        assert_eq!(
+            params
+                .bind(vec![Val::Int(1), Val::keyword("k"), Val::Int(2)])
+                .unwrap(),
+            vec![
+                (SymbolId::from("a"), Val::Int(1)),
+                (SymbolId::from("k"), Val::Int(2)),
+                (
+                    SymbolId::from("r"),
+                    Val::List(vec![Val::keyword("k"), Val::Int(2)])
+                ),
+            ]
+        );
+
+        let syms = symbols("&key k");
+        let params = Params::parse(&syms).unwrap();
+        assert_matches!(
+            params.bind(vec![Val::keyword("other"), Val::Int(2)]),
+            Err(Error::UnexpectedArguments(_))
+        );
+    }
+
+    fn symbols(s: &str) -> Vec<SymbolId> {
+        s.split_whitespace().map(SymbolId::from).collect()
+    }
+}
@@ -1,15 +1,25 @@
 //! Parser for Lyric
-use crate::lex::{lex, Token};
-use crate::types::KeywordId;
+use crate::lex::{lex_with_pos, Position, Token};
+use crate::types::{KeywordId, MapKey};
 use crate::{Error, Result};
 use crate::{Form, SymbolId};
 
+use std::collections::BTreeMap;
 use std::iter::Peekable;
 
+/// Source positions of a parsed form and its subforms, as a side table to [Form]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    /// Starting position of form
+    pub pos: Position,
+    /// Spans of subforms, if form is a list
+    pub children: Vec<Span>,
+}
+
 /// Parse a given expression as form
 pub fn parse(expr: &str) -> Result<Form> {
-    let mut tokens = lex(expr)?.into_iter().peekable();
-    let form = parse_form(&mut tokens)?;
+    let mut tokens = lex_with_pos(expr)?.into_iter().peekable();
+    let (form, _) = parse_form(&mut tokens)?;
     if tokens.peek().is_some() {
         return Err(Error::IncompleteExpression(
             "Unable to parse full expression - unbalanced trailing expressions".to_string(),
@@ -18,30 +28,52 @@ pub fn parse(expr: &str) -> Result<Form> {
     Ok(form)
 }
 
-/// Parse single expression into a form. Returns result of tuple of parsed form and remaining tokens
-fn parse_form<I>(tokens: &mut Peekable<I>) -> Result<Form>
+/// Parse all top-level expressions in order, with the span of each form.
+/// Errors for incomplete expressions are reported with starting position of malformed form.
+pub fn parse_all(expr: &str) -> Result<Vec<(Form, Span)>> {
+    let mut tokens = lex_with_pos(expr)?.into_iter().peekable();
+    let mut forms = vec![];
+    while let Some((_, pos)) = tokens.peek() {
+        let pos = *pos;
+        let form = parse_form(&mut tokens).map_err(|e| match e {
+            Error::IncompleteExpression(msg) => {
+                Error::IncompleteExpression(format!("{msg} - at {pos}"))
+            }
+            e => e,
+        })?;
+        forms.push(form);
+    }
+    Ok(forms)
+}
+
+/// Parse single expression into a form and its span
+fn parse_form<I>(tokens: &mut Peekable<I>) -> Result<(Form, Span)>
 where
-    I: Iterator<Item = Token>,
+    I: Iterator<Item = (Token, Position)>,
 {
-    let next = tokens
+    let (next, pos) = tokens
         .next()
         .ok_or(Error::IncompleteExpression("Expected a form".to_string()))?;
+    let mut span = Span::new(pos);
     let form = match next {
         Token::Nil => Form::Nil,
         Token::Bool(b) => Form::Bool(b),
         Token::Int(i) => Form::Int(i),
+        Token::Float(n) => Form::Float(n),
         Token::Symbol(s) => Form::Symbol(SymbolId::from(s)),
         Token::String(s) => Form::String(s),
         Token::Keyword(k) => Form::Keyword(KeywordId::from(k)),
         Token::ParenLeft => {
             let mut items = vec![];
-            while let Some(next) = tokens.peek() {
+            while let Some((next, _)) = tokens.peek() {
                 if next == &Token::ParenRight {
                     break;
                 }
-                items.push(parse_form(tokens)?);
+                let (item, item_span) = parse_form(tokens)?;
+                items.push(item);
+                span.children.push(item_span);
             }
-            if tokens.peek() != Some(&Token::ParenRight) {
+            if !matches!(tokens.peek(), Some((Token::ParenRight, _))) {
                 return Err(Error::IncompleteExpression(
                     "Expected closing parenthesis".to_string(),
                 ));
@@ -54,17 +86,67 @@ where
                 "Unexpected closing parenthesis while parsing expression".to_string(),
             ))
         }
-        Token::Quote => {
-            let quoted = parse_form(tokens)?;
-            Form::List(vec![Form::symbol("quote"), quoted])
+        Token::BraceLeft => {
+            let mut items = vec![];
+            while let Some((next, _)) = tokens.peek() {
+                if next == &Token::BraceRight {
+                    break;
+                }
+                let (item, _) = parse_form(tokens)?;
+                items.push(item);
+            }
+            if !matches!(tokens.peek(), Some((Token::BraceRight, _))) {
+                return Err(Error::IncompleteExpression(
+                    "Expected closing brace".to_string(),
+                ));
+            }
+            tokens.next(); // discard BraceRight
+            if !items.len().is_multiple_of(2) {
+                return Err(Error::InvalidExpression(
+                    "Map literal expects an even number of keys and values".to_string(),
+                ));
+            }
+            // entries are ordered by key, with later entries replacing earlier entries
+            let mut entries = BTreeMap::new();
+            let mut items = items.into_iter();
+            while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                entries.insert(MapKey::try_from(k)?, v);
+            }
+            Form::Map(entries.into_iter().collect())
+        }
+        Token::BraceRight => {
+            return Err(Error::IncompleteExpression(
+                "Unexpected closing brace while parsing expression".to_string(),
+            ))
+        }
+        Token::Quote | Token::Quasiquote | Token::Unquote | Token::UnquoteSplice => {
+            let sym = match next {
+                Token::Quote => "quote",
+                Token::Quasiquote => "quasiquote",
+                Token::Unquote => "unquote",
+                _ => "unquote_splice",
+            };
+            let (quoted, quoted_span) = parse_form(tokens)?;
+            span.children = vec![Span::new(pos), quoted_span];
+            Form::List(vec![Form::symbol(sym), quoted])
         }
     };
-    Ok(form)
+    Ok((form, span))
+}
+
+impl Span {
+    fn new(pos: Position) -> Self {
+        Self {
+            pos,
+            children: vec![],
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lex::lex;
 
     #[test]
     fn parse_empty() {
@@ -250,6 +332,46 @@ mod tests {
         )
     }
 
+    #[test]
+    fn parse_quasiquoted() {
+        assert_eq!(
+            parse("`(a ,b ,@(c))"),
+            Ok(Form::List(vec![
+                Form::symbol("quasiquote"),
+                Form::List(vec![
+                    Form::symbol("a"),
+                    Form::List(vec![Form::symbol("unquote"), Form::symbol("b")]),
+                    Form::List(vec![
+                        Form::symbol("unquote_splice"),
+                        Form::List(vec![Form::symbol("c")]),
+                    ]),
+                ]),
+            ]))
+        );
+    }
+
+    #[test]
+    fn parse_map() {
+        assert_eq!(parse("{}"), Ok(Form::Map(vec![])));
+        assert_eq!(
+            parse("{:a 1 \"b\" (c) 2 {} :a 3}"),
+            Ok(Form::Map(vec![
+                (MapKey::Int(2), Form::Map(vec![])),
+                (
+                    MapKey::String("b".to_string()),
+                    Form::List(vec![Form::symbol("c")])
+                ),
+                (MapKey::Keyword(KeywordId::from("a")), Form::Int(3)),
+            ]))
+        );
+        assert!(matches!(parse("{:a}"), Err(Error::InvalidExpression(_))));
+        assert!(matches!(parse("{(a) 1}"), Err(Error::UnexpectedType(_))));
+        assert!(matches!(
+            parse("{:a 1"),
+            Err(Error::IncompleteExpression(_))
+        ));
+    }
+
     #[test]
     fn parse_partial_form() {
         assert!(
@@ -265,4 +387,68 @@ mod tests {
             Err(Error::IncompleteExpression(_))
         ));
     }
+
+    #[test]
+    fn parse_all_forms() {
+        assert_eq!(parse_all(""), Ok(vec![]));
+        assert_eq!(parse_all("  # only comments\n"), Ok(vec![]));
+
+        let forms = parse_all("(def x 1)\n# comment\n  x\n'(a\n b)").unwrap();
+        assert_eq!(
+            forms.iter().map(|(f, _)| f.clone()).collect::<Vec<_>>(),
+            vec![
+                Form::List(vec![Form::symbol("def"), Form::symbol("x"), Form::Int(1)]),
+                Form::symbol("x"),
+                Form::List(vec![
+                    Form::symbol("quote"),
+                    Form::List(vec![Form::symbol("a"), Form::symbol("b")])
+                ]),
+            ]
+        );
+        assert_eq!(
+            forms.iter().map(|(_, s)| s.pos).collect::<Vec<_>>(),
+            vec![
+                Position { line: 1, col: 1 },
+                Position { line: 3, col: 3 },
+                Position { line: 4, col: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_all_spans() {
+        let forms = parse_all("(a\n  (b c)\n  'd)").unwrap();
+        let pos = |line, col| Position { line, col };
+        let leaf = |line, col| Span {
+            pos: pos(line, col),
+            children: vec![],
+        };
+        assert_eq!(
+            forms[0].1,
+            Span {
+                pos: pos(1, 1),
+                children: vec![
+                    leaf(1, 2),
+                    Span {
+                        pos: pos(2, 3),
+                        children: vec![leaf(2, 4), leaf(2, 6)],
+                    },
+                    Span {
+                        pos: pos(3, 3),
+                        children: vec![leaf(3, 3), leaf(3, 4)],
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn parse_all_incomplete() {
+        assert_eq!(
+            parse_all("(def x 1)\n(def y"),
+            Err(Error::IncompleteExpression(
+                "Expected closing parenthesis - at 2:1".to_string()
+            ))
+        );
+    }
 }
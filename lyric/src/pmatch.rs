@@ -103,8 +103,16 @@ where
                     .all(|(lhs, rhs)| Self::matches_inner(lhs, rhs, matches)),
                 _ => false,
             },
-            Nil | Bool(_) | Int(_) | String(_) | Keyword(_) | Lambda(_) | NativeFn(_)
-            | NativeAsyncFn(_) | Bytecode(_) | Error(_) | Ref(_) | Extern(_) => pat == val,
+            Map(pat) => match val {
+                Map(val) => pat.iter().all(|(k, p)| match val.get(k) {
+                    Some(v) => Self::matches_inner(p, v, matches),
+                    None => false,
+                }),
+                _ => false,
+            },
+            Nil | Bool(_) | Int(_) | Float(_) | String(_) | Keyword(_) | Lambda(_) | Macro(_)
+            | NativeFn(_) | NativeAsyncFn(_) | Bytecode(_) | Fiber(_) | Error(_) | Ref(_)
+            | Extern(_) => pat == val,
         }
     }
 }
@@ -349,6 +357,40 @@ mod tests {
         assert!(!pat.is_match(&v("()")));
     }
 
+    #[test]
+    fn map_keys() {
+        let pat = Pattern::from_val(v("{:name n :kind :file}"));
+
+        {
+            let m = pat
+                .matches(&v("{:name \"a.txt\" :kind :file :size 10}"))
+                .expect("should match with extra keys");
+            assert_eq!(m.bindings.len(), 1);
+            assert_eq!(
+                m.bindings.get(&SymbolId::from("n")),
+                Some(&Val::string("a.txt"))
+            );
+        }
+
+        assert!(!pat.is_match(&v("{:name \"a.txt\" :kind :dir}")));
+        assert!(!pat.is_match(&v("{:name \"a.txt\"}")));
+        assert!(!pat.is_match(&v("(:name \"a.txt\" :kind :file)")));
+        assert!(Pattern::from_val(v("{}")).is_match(&v("{:a 1}")));
+    }
+
+    #[test]
+    fn map_nested() {
+        let pat = Pattern::from_val(v("(:ok {:user {:id id} :tags (first _)})"));
+        let m = pat
+            .matches(&v("(:ok {:user {:id 5 :name \"x\"} :tags (:a :b)})"))
+            .expect("should match");
+        assert_eq!(m.bindings.get(&SymbolId::from("id")), Some(&Val::Int(5)));
+        assert_eq!(
+            m.bindings.get(&SymbolId::from("first")),
+            Some(&Val::keyword("a"))
+        );
+    }
+
     #[test]
     fn repeated_symbols() {
         let pat = Pattern::from_val(v("(a b a)"));
@@ -9,19 +9,39 @@
 //! ergonomic for hosting application, e.g. via spawning thread-per-fiber, or
 //! via future to take advantage of async IO if it is available.
 
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
 use crate::{Error, Extern, Fiber, Locals, Result, Signal, Val};
 
-/// Run the fiber to completion as a Future
+/// Run the fiber to completion as a Future.
+/// Preempted fibers yield to executor before resuming, so other futures can make progress.
 pub async fn run<T, L>(f: &mut Fiber<T, L>) -> Result<Val<T, L>>
 where
     T: Extern,
     L: Locals,
 {
-    let mut res = f.start()?;
+    let res = f.start()?;
+    match run_until_yield(f, res).await? {
+        Signal::Done(v) => Ok(v),
+        _ => Err(Error::UnexpectedTopLevelYield),
+    }
+}
+
+/// Continue running the fiber after given signal, until it completes or yields a value.
+/// Returns [Signal::Done] or [Signal::Yield].
+pub(crate) async fn run_until_yield<T, L>(
+    f: &mut Fiber<T, L>,
+    mut res: Signal<T, L>,
+) -> Result<Signal<T, L>>
+where
+    T: Extern,
+    L: Locals,
+{
     loop {
         match res {
-            Signal::Done(v) => return Ok(v),
-            Signal::Yield(_) => return Err(Error::UnexpectedTopLevelYield),
+            Signal::Done(_) | Signal::Yield(_) => return Ok(res),
             Signal::Await(call) => {
                 // TODO: Should errors in fut properly update `Fiber::state`?
                 // TODO: Jiggle code between fiber::run and run::run
@@ -29,7 +49,27 @@ where
                 let poll_res = call.apply(f).await;
                 res = f.resume(poll_res)?;
             }
+            Signal::Preempted => {
+                YieldNow(false).await;
+                res = f.resume_preempted()?;
+            }
+        }
+    }
+}
+
+/// Future that is pending once, waking itself so executor can run other tasks first
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
         }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
     }
 }
 
@@ -184,6 +224,18 @@ mod tests {
         assert_matches!(run(&mut f).await, Err(Error::UnexpectedArguments(s)) if s == "Cannot be called with argument 3");
     }
 
+    #[tokio::test]
+    async fn run_preempted() {
+        let prog = r#"(begin
+            (defn count (n)
+                (if (< n 100) (count (+ n 1)) n))
+            (count 0))"#;
+        let mut f = Fiber::from_expr(prog, Env::standard(), ()).unwrap();
+        f.set_budget(Some(10));
+        assert_eq!(run(&mut f).await.unwrap(), Val::Int(100));
+        assert!(f.reductions() > 100);
+    }
+
     #[tokio::test]
     async fn run_is_send() {
         fn require_send<T: Send>(_t: &T) {}
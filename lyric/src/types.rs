@@ -1,7 +1,10 @@
 //! Types in Lisp virtual machine
-use crate::codegen::Inst;
+use crate::codegen::{Inst, Scope, SourceLoc};
+use crate::env::Forker;
+use crate::fiber::FiberHandle;
 use crate::{parse, Env, Error, Fiber, Ref, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
@@ -14,7 +17,9 @@ pub enum Val<T: Extern, L: Locals> {
     /// True or false
     Bool(bool),
     /// Integers
-    Int(i32),
+    Int(i64),
+    /// Floating point numbers
+    Float(f64),
     /// Strings
     String(String),
     /// Named slots for values
@@ -23,14 +28,20 @@ pub enum Val<T: Extern, L: Locals> {
     Keyword(KeywordId),
     /// Lists
     List(Vec<Val<T, L>>),
+    /// Maps from keys to values
+    Map(Map<T, L>),
     /// A callable function object
     Lambda(Lambda<T, L>),
     /// A callable native function object
     NativeFn(NativeFn<T, L>),
     /// A callable async native function object
     NativeAsyncFn(NativeAsyncFn<T, L>),
+    /// A macro, with function object that transforms unevaluated arguments into a form
+    Macro(Lambda<T, L>),
     /// Compiled bytecode sequence
     Bytecode(Bytecode<T, L>),
+    /// A fiber that can be resumed as a coroutine
+    Fiber(FiberHandle<T, L>),
     /// Error as a value
     Error(Error),
     /// References as a value
@@ -45,28 +56,58 @@ pub enum Val<T: Extern, L: Locals> {
 pub enum Form {
     Nil,
     Bool(bool),
-    Int(i32),
+    Int(i64),
+    Float(f64),
     String(String),
     RawString(String), // TODO: Remove this w/ new client API
     Symbol(SymbolId),
     Keyword(KeywordId),
     List(Vec<Form>),
+    Map(Vec<(MapKey, Form)>),
 }
 
-/// Bytecode sequence
-pub type Bytecode<T, L> = Vec<Inst<T, L>>;
+/// Map from keys to values, ordered by key
+pub type Map<T, L> = BTreeMap<MapKey, Val<T, L>>;
+
+/// Keys of [Val::Map], which are the subset of values that can be ordered and hashed
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum MapKey {
+    Bool(bool),
+    Int(i64),
+    String(String),
+    Symbol(SymbolId),
+    Keyword(KeywordId),
+}
+
+/// Bytecode sequence, with source locations of instructions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bytecode<T: Extern, L: Locals> {
+    /// Instructions in order
+    pub inst: Vec<Inst<T, L>>,
+    /// Source locations marked at instruction offsets, in order of offsets.
+    /// A location applies to instructions up to the next marked location.
+    pub locs: Vec<(usize, SourceLoc)>,
+}
 
 /// A function object that closes over environment it was created in
 #[derive(Clone)]
 pub struct Lambda<T: Extern, L: Locals> {
     pub doc: Option<String>,
     pub params: Vec<SymbolId>,
-    pub code: Bytecode<T, L>,
+    pub code: Arc<Bytecode<T, L>>,
     pub parent: Option<Arc<Mutex<Env<T, L>>>>,
+    /// Local slots and upvalues addressed by code, or None if code binds symbols in a new environment for each call
+    pub scope: Option<Arc<Scope>>,
+    /// Local variables of enclosing functions captured by lambda, in order of upvalues in scope
+    pub upvals: Vec<Upval<T, L>>,
 }
 
+/// A local variable shared between function body and closures capturing it, which is None until it is defined
+pub type Upval<T, L> = Arc<Mutex<Option<Val<T, L>>>>;
+
 /// A native founction bound to given symbol
 #[derive(Debug, Clone, PartialEq)]
+#[allow(unpredictable_function_pointer_comparisons)]
 pub struct NativeFn<T: Extern, L: Locals> {
     pub doc: String,
     pub func: NativeFnSig<T, L>,
@@ -82,12 +123,15 @@ pub enum NativeFnOp<T: Extern, L: Locals> {
     Return(Val<T, L>),
     /// Yield a value
     Yield(Val<T, L>),
-    /// Execute bytecode-level instructions
+    /// Execute bytecode-level instructions in [Fiber::frame_env] of caller.
+    /// Variables of caller's function body that the code defines or sets are written back when the code
+    /// returns, but not if it exits with an error. Other symbols it defines are not visible to caller.
     Exec(Bytecode<T, L>),
 }
 
 /// A native async function
 #[derive(Debug, Clone, PartialEq)]
+#[allow(unpredictable_function_pointer_comparisons)]
 pub struct NativeAsyncFn<T: Extern, L: Locals> {
     pub doc: String,
     pub func: NativeAsyncFnSig<T, L>,
@@ -95,6 +139,7 @@ pub struct NativeAsyncFn<T: Extern, L: Locals> {
 
 /// A deferred call to native async fn
 #[derive(Debug, Clone, PartialEq)]
+#[allow(unpredictable_function_pointer_comparisons)]
 pub struct NativeAsyncCall<T: Extern, L: Locals> {
     pub args: Vec<Val<T, L>>,
     pub func: NativeAsyncFnSig<T, L>,
@@ -108,30 +153,31 @@ type NativeAsyncFnSig<T, L> =
 type ValFuture<'a, T, L> = Box<dyn Future<Output = Result<Val<T, L>>> + 'a + Send>;
 
 /// Identifier for Symbol
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct SymbolId(String);
 
 /// Identifier for Keywords
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct KeywordId(String);
 
 /// Trait alias for host defined type in Val (until unstable trait_alias)
 pub trait Extern:
-    std::fmt::Display + std::fmt::Debug + std::cmp::PartialEq + std::clone::Clone
+    std::fmt::Display + std::fmt::Debug + std::cmp::PartialEq + std::clone::Clone + Send + Sync
 {
 }
 
 /// Trait alias impl for [Extern]
 impl<T> Extern for T where
-    T: std::fmt::Display + std::fmt::Debug + std::cmp::PartialEq + std::clone::Clone
+    T: std::fmt::Display + std::fmt::Debug + std::cmp::PartialEq + std::clone::Clone + Send + Sync
 {
 }
 
 /// Trait alias for fiber local storage
-pub trait Locals: std::fmt::Debug + std::cmp::PartialEq + std::clone::Clone {}
+pub trait Locals: std::fmt::Debug + std::cmp::PartialEq + std::clone::Clone + Send + Sync {}
 
 /// Trait alias impl for [Locals]
-impl<T> Locals for T where T: std::fmt::Debug + std::cmp::PartialEq + std::clone::Clone {}
+impl<T> Locals for T where T: std::fmt::Debug + std::cmp::PartialEq + std::clone::Clone + Send + Sync
+{}
 
 impl<T, L> Val<T, L>
 where
@@ -158,7 +204,7 @@ where
         }
     }
 
-    pub fn as_int(&self) -> Result<&i32> {
+    pub fn as_int(&self) -> Result<&i64> {
         if let Val::Int(inner) = &self {
             Ok(inner)
         } else {
@@ -188,6 +234,7 @@ where
             Val::Bool(true) => Ok("true".to_string()),
             Val::Bool(false) => Ok("false".to_string()),
             Val::Int(i) => Ok(format!("{}", i)),
+            Val::Float(n) => Ok(format!("{:?}", n)),
             Val::String(s) => Ok(s.clone()),
             _ => Err(Error::UnexpectedType(format!(
                 "{} is not convertible to str",
@@ -196,6 +243,14 @@ where
         }
     }
 
+    pub fn as_map(&self) -> Result<&Map<T, L>> {
+        if let Val::Map(inner) = &self {
+            Ok(inner)
+        } else {
+            Err(Error::UnexpectedType("expected map".to_string()))
+        }
+    }
+
     pub fn to_list(self) -> Result<Vec<Val<T, L>>> {
         if let Val::List(inner) = self {
             Ok(inner)
@@ -243,12 +298,109 @@ impl<T: Extern, L: Locals> Val<T, L> {
         Self::Keyword(KeywordId::from(id))
     }
 
+    /// Fork value in to a *deep copy*, including environments captured by lambdas within it.
+    /// Only bindings that lambdas refer to are copied from captured environments, so symbols that are
+    /// only known at runtime, e.g. from expanding macros, are not bound in copy.
+    /// Fails if value refers to a fiber. See [Env::fork]
+    pub fn fork(&self) -> Result<Self> {
+        Forker::new().val(self)
+    }
+
+    /// Fork value like [Val::fork], where copies of top-level environments captured by lambdas
+    /// extend given root environment
+    pub fn fork_with_root(&self, root: Env<T, L>) -> Result<Self> {
+        Forker::with_root(root).val(self)
+    }
+
+    /// Whether or not val contains lambdas, macros, or fibers that capture an environment
+    pub fn has_closure(&self) -> bool {
+        let const_has_closure =
+            |inst: &Inst<T, L>| matches!(inst, Inst::PushConst(v) if v.has_closure());
+        match self {
+            Val::List(l) => l.iter().any(Val::has_closure),
+            Val::Map(m) => m.values().any(Val::has_closure),
+            Val::Lambda(l) | Val::Macro(l) => {
+                l.parent.is_some()
+                    || !l.upvals.is_empty()
+                    || l.code.inst.iter().any(const_has_closure)
+            }
+            Val::Bytecode(bc) => bc.inst.iter().any(const_has_closure),
+            Val::Fiber(_) => true,
+            _ => false,
+        }
+    }
+
     /// Whether or not val is a callable function
     pub fn is_callable(&self) -> bool {
         matches!(self, Val::Lambda(_) | Val::NativeFn(_))
     }
 }
 
+impl<T: Extern, L: Locals> Bytecode<T, L> {
+    /// Create empty bytecode
+    pub fn new() -> Self {
+        Self {
+            inst: vec![],
+            locs: vec![],
+        }
+    }
+
+    /// Number of instructions
+    pub fn len(&self) -> usize {
+        self.inst.len()
+    }
+
+    /// Whether or not there are no instructions
+    pub fn is_empty(&self) -> bool {
+        self.inst.is_empty()
+    }
+
+    /// Append instruction
+    pub fn push(&mut self, inst: Inst<T, L>) {
+        self.inst.push(inst);
+    }
+
+    /// Append instructions of given code, along with its source locations
+    pub fn extend(&mut self, code: impl Into<Bytecode<T, L>>) {
+        let code = code.into();
+        let offset = self.inst.len();
+        self.inst.extend(code.inst);
+        self.locs
+            .extend(code.locs.into_iter().map(|(ip, loc)| (ip + offset, loc)));
+    }
+
+    /// Mark source location of instructions appended after this point
+    pub fn mark(&mut self, loc: SourceLoc) {
+        self.locs.push((self.inst.len(), loc));
+    }
+
+    /// Source location of instruction at given offset, if known
+    pub fn loc(&self, ip: usize) -> Option<&SourceLoc> {
+        let idx = self.locs.partition_point(|(start, _)| *start <= ip);
+        idx.checked_sub(1).map(|idx| &self.locs[idx].1)
+    }
+}
+
+impl<T: Extern, L: Locals> Default for Bytecode<T, L> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Extern, L: Locals> From<Vec<Inst<T, L>>> for Bytecode<T, L> {
+    fn from(inst: Vec<Inst<T, L>>) -> Self {
+        Self { inst, locs: vec![] }
+    }
+}
+
+impl<T: Extern, L: Locals> FromIterator<Bytecode<T, L>> for Bytecode<T, L> {
+    fn from_iter<I: IntoIterator<Item = Bytecode<T, L>>>(iter: I) -> Self {
+        let mut bc = Self::new();
+        iter.into_iter().for_each(|code| bc.extend(code));
+        bc
+    }
+}
+
 impl SymbolId {
     /// Returns inner ID as string slice
     pub fn as_str(&self) -> &str {
@@ -292,6 +444,13 @@ impl<T: Extern, L: Locals> PartialEq for Lambda<T, L> {
     fn eq(&self, other: &Self) -> bool {
         self.params == other.params
             && self.code == other.code
+            && self.scope == other.scope
+            && self.upvals.len() == other.upvals.len()
+            && self
+                .upvals
+                .iter()
+                .zip(&other.upvals)
+                .all(|(a, b)| Arc::ptr_eq(a, b))
             && ((self.parent.is_none() && other.parent.is_none())
                 || Arc::ptr_eq(
                     self.parent.as_ref().unwrap(),
@@ -309,6 +468,7 @@ where
             Val::Nil => write!(f, "nil"),
             Val::Bool(b) => write!(f, "{}", if *b { "true" } else { "false" }),
             Val::Int(i) => write!(f, "{}", i),
+            Val::Float(n) => write!(f, "{:?}", n),
             Val::String(s) => write!(f, "\"{}\"", s),
             Val::Keyword(k) => write!(f, "{}", k),
             Val::Symbol(s) => write!(f, "{}", s),
@@ -325,6 +485,14 @@ where
                         .join(" ")
                 ),
             },
+            Val::Map(m) => write!(
+                f,
+                "{{{}}}",
+                m.iter()
+                    .map(|(k, v)| format!("{k} {v}"))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
             Val::Lambda(l) => write!(
                 f,
                 "<lambda ({})>",
@@ -334,9 +502,19 @@ where
                     .collect::<Vec<_>>()
                     .join(" ")
             ),
+            Val::Macro(l) => write!(
+                f,
+                "<macro ({})>",
+                l.params
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
             Val::NativeFn(_) => write!(f, "<nativefn>"),
             Val::NativeAsyncFn(_) => write!(f, "<nativeasyncfn>"),
             Val::Bytecode(_) => write!(f, "<bytecode>"),
+            Val::Fiber(_) => write!(f, "<fiber>"),
             Val::Error(e) => write!(f, "<error {e}>"),
             Val::Ref(r) => write!(f, "<ref {}>", r.0),
             Val::Extern(e) => write!(f, "{e}"),
@@ -350,6 +528,7 @@ impl std::fmt::Display for Form {
             Form::Nil => write!(f, "nil"),
             Form::Bool(b) => write!(f, "{}", if *b { "true" } else { "false" }),
             Form::Int(i) => write!(f, "{}", i),
+            Form::Float(n) => write!(f, "{:?}", n),
             Form::String(s) => write!(f, "\"{}\"", s),
             Form::Keyword(k) => write!(f, "{}", k),
             Form::Symbol(s) => write!(f, "{}", s),
@@ -366,11 +545,31 @@ impl std::fmt::Display for Form {
                         .join(" ")
                 ),
             },
+            Form::Map(m) => write!(
+                f,
+                "{{{}}}",
+                m.iter()
+                    .map(|(k, v)| format!("{k} {v}"))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
             Form::RawString(s) => write!(f, "{}", s),
         }
     }
 }
 
+impl std::fmt::Display for MapKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapKey::Bool(b) => write!(f, "{}", if *b { "true" } else { "false" }),
+            MapKey::Int(i) => write!(f, "{}", i),
+            MapKey::String(s) => write!(f, "\"{}\"", s),
+            MapKey::Symbol(s) => write!(f, "{}", s),
+            MapKey::Keyword(k) => write!(f, "{}", k),
+        }
+    }
+}
+
 impl std::fmt::Display for SymbolId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
@@ -410,10 +609,12 @@ impl<T: Extern, L: Locals> From<Form> for Val<T, L> {
             Form::Nil => Val::Nil,
             Form::Bool(b) => Val::Bool(b),
             Form::Int(i) => Val::Int(i),
+            Form::Float(n) => Val::Float(n),
             Form::String(s) => Val::String(s),
             Form::Symbol(s) => Val::Symbol(s),
             Form::Keyword(k) => Val::Keyword(k),
             Form::List(l) => Val::List(l.into_iter().map(|e| e.into()).collect()),
+            Form::Map(m) => Val::Map(m.into_iter().map(|(k, v)| (k, v.into())).collect()),
             Form::RawString(s) => Val::String(s),
         }
     }
@@ -427,6 +628,7 @@ impl<T: Extern, L: Locals> TryFrom<Val<T, L>> for Form {
             Val::Nil => Ok(Form::Nil),
             Val::Bool(b) => Ok(Form::Bool(b)),
             Val::Int(i) => Ok(Form::Int(i)),
+            Val::Float(n) => Ok(Form::Float(n)),
             Val::String(s) => Ok(Form::String(s)),
             Val::Symbol(s) => Ok(Form::Symbol(s)),
             Val::Keyword(k) => Ok(Form::Keyword(k)),
@@ -435,10 +637,17 @@ impl<T: Extern, L: Locals> TryFrom<Val<T, L>> for Form {
                     .map(|e| e.try_into())
                     .collect::<Result<Vec<_>>>()?,
             )),
+            Val::Map(m) => Ok(Form::Map(
+                m.into_iter()
+                    .map(|(k, v)| Ok((k, v.try_into()?)))
+                    .collect::<Result<Vec<_>>>()?,
+            )),
             Val::Ref(_)
             | Val::Error(_)
             | Val::Bytecode(_)
+            | Val::Fiber(_)
             | Val::Lambda(_)
+            | Val::Macro(_)
             | Val::NativeFn(_)
             | Val::NativeAsyncFn(_)
             | Val::Extern(_) => Ok(Form::RawString(value.to_string())),
@@ -446,6 +655,52 @@ impl<T: Extern, L: Locals> TryFrom<Val<T, L>> for Form {
     }
 }
 
+impl<T: Extern, L: Locals> From<MapKey> for Val<T, L> {
+    fn from(value: MapKey) -> Self {
+        match value {
+            MapKey::Bool(b) => Val::Bool(b),
+            MapKey::Int(i) => Val::Int(i),
+            MapKey::String(s) => Val::String(s),
+            MapKey::Symbol(s) => Val::Symbol(s),
+            MapKey::Keyword(k) => Val::Keyword(k),
+        }
+    }
+}
+
+impl<T: Extern, L: Locals> TryFrom<Val<T, L>> for MapKey {
+    type Error = Error;
+
+    fn try_from(value: Val<T, L>) -> Result<Self> {
+        match value {
+            Val::Bool(b) => Ok(MapKey::Bool(b)),
+            Val::Int(i) => Ok(MapKey::Int(i)),
+            Val::String(s) => Ok(MapKey::String(s)),
+            Val::Symbol(s) => Ok(MapKey::Symbol(s)),
+            Val::Keyword(k) => Ok(MapKey::Keyword(k)),
+            _ => Err(Error::UnexpectedType(format!(
+                "Map keys should be a bool, int, string, symbol, or keyword - found {value}"
+            ))),
+        }
+    }
+}
+
+impl TryFrom<Form> for MapKey {
+    type Error = Error;
+
+    fn try_from(value: Form) -> Result<Self> {
+        match value {
+            Form::Bool(b) => Ok(MapKey::Bool(b)),
+            Form::Int(i) => Ok(MapKey::Int(i)),
+            Form::String(s) => Ok(MapKey::String(s)),
+            Form::Symbol(s) => Ok(MapKey::Symbol(s)),
+            Form::Keyword(k) => Ok(MapKey::Keyword(k)),
+            _ => Err(Error::UnexpectedType(format!(
+                "Map keys should be a bool, int, string, symbol, or keyword - found {value}"
+            ))),
+        }
+    }
+}
+
 impl From<String> for SymbolId {
     fn from(value: String) -> Self {
         Self(value)
@@ -494,6 +749,22 @@ mod tests {
         assert_eq!(Val::Int(-99).to_string(), "-99");
     }
 
+    #[test]
+    fn map_to_string() {
+        assert_eq!(Val::from_expr("{}").unwrap().to_string(), "{}");
+        assert_eq!(
+            Val::from_expr("{:b (1 2) :a \"x\"}").unwrap().to_string(),
+            "{:a \"x\" :b (1 2)}"
+        );
+    }
+
+    #[test]
+    fn float_to_string() {
+        assert_eq!(Val::Float(1.5).to_string(), "1.5");
+        assert_eq!(Val::Float(2.0).to_string(), "2.0");
+        assert_eq!(Val::Float(-0.25).to_string(), "-0.25");
+    }
+
     #[test]
     fn string_to_string() {
         assert_eq!(Val::string("hello").to_string(), "\"hello\"");
@@ -16,7 +16,7 @@ enum Ext {
 
 #[derive(Debug, Clone, PartialEq)]
 struct Locals {
-    val: i32,
+    val: i64,
 }
 
 fn env() -> Env {
@@ -49,7 +49,7 @@ fn fiber_invalid_expr() {
 
 #[test]
 fn fiber_empty_bytecode() {
-    let mut f = Fiber::from_bytecode(vec![], env(), locals());
+    let mut f = Fiber::from_bytecode(vec![].into(), env(), locals());
     assert_matches!(
         f.start(),
         Err(Error::UnexpectedStack(_)),
@@ -60,7 +60,7 @@ fn fiber_empty_bytecode() {
 #[test]
 fn fiber_invalid_bytecode() {
     let mut f = Fiber::from_bytecode(
-        vec![Inst::PopTop, Inst::PopTop, Inst::PopTop],
+        vec![Inst::PopTop, Inst::PopTop, Inst::PopTop].into(),
         env(),
         locals(),
     );
@@ -83,13 +83,62 @@ fn fiber_yielding() {
     let mut f = Fiber::from_expr(prog, env(), locals()).unwrap();
 
     assert_eq!(f.start().unwrap(), Signal::Yield(Val::Int(0)));
+    let depth = f.depth();
     assert_eq!(f.resume(Ok(Val::Nil)).unwrap(), Signal::Yield(Val::Int(1)));
     assert_eq!(f.resume(Ok(Val::Nil)).unwrap(), Signal::Yield(Val::Int(2)));
     assert_eq!(f.resume(Ok(Val::Nil)).unwrap(), Signal::Yield(Val::Int(3)));
     assert_eq!(f.resume(Ok(Val::Nil)).unwrap(), Signal::Yield(Val::Int(4)));
     assert_eq!(f.resume(Ok(Val::Nil)).unwrap(), Signal::Yield(Val::Int(5)));
+    assert_eq!(
+        f.depth(),
+        depth,
+        "tail calls to yielding_add should not grow callframes"
+    );
+}
+
+#[test]
+fn fiber_deep_tail_recursion() {
+    let prog = r#"
+        (begin
+            (defn count (n)
+                (cond ((eq? n 0) (begin (yield :bottom) :done))
+                      (true (count (- n 1)))))
+            (count 1000000))
+    "#;
+    let mut f = Fiber::from_expr(prog, env(), locals()).unwrap();
 
-    // TODO: Use fiber_yielding as test case for tail-call optimization (shouldn't grow number of CallFrames)
+    assert_eq!(f.start().unwrap(), Signal::Yield(Val::keyword("bottom")));
+    assert_eq!(f.depth(), 2, "recursion should run in a single callframe");
+    assert_eq!(
+        f.stack_len(),
+        0,
+        "recursion should not leave values on stack"
+    );
+    assert_eq!(
+        f.resume(Ok(Val::Nil)).unwrap(),
+        Signal::Done(Val::keyword("done"))
+    );
+}
+
+#[test]
+fn fiber_preempted() {
+    let mut f = Fiber::from_expr("(loop 5)", env(), locals()).unwrap();
+    f.set_budget(Some(100));
+
+    assert_eq!(f.start().unwrap(), Signal::Preempted);
+    assert_eq!(f.reductions(), 100);
+    assert_eq!(f.resume_preempted().unwrap(), Signal::Preempted);
+    assert_eq!(f.reductions(), 200);
+    assert_matches!(f.resume(Ok(Val::Nil)), Err(Error::UnexpectedResume(_)));
+
+    // Fiber runs to completion without budget
+    let mut f = Fiber::from_expr("(+ 1 2)", env(), locals()).unwrap();
+    f.set_budget(Some(1));
+    let mut res = f.start().unwrap();
+    while res == Signal::Preempted {
+        res = f.resume_preempted().unwrap();
+    }
+    assert_eq!(res, Signal::Done(Val::Int(3)));
 }
 
 #[test]
@@ -1,7 +1,7 @@
 //! Tests for implementation of language
 
 use assert_matches::assert_matches;
-use lyric::{Error, NativeFn, NativeFnOp, Result, Signal, SymbolId};
+use lyric::{Error, Inst, NativeFn, NativeFnOp, Result, Signal, SymbolId};
 use void::Void;
 
 type Fiber = lyric::Fiber<Void, ()>;
@@ -24,7 +24,7 @@ fn eval_expr(e: &str) -> Result<Val> {
     // TODO: Think about ergonomics here
     let res = match f.start()? {
         Signal::Done(res) => res,
-        Signal::Yield(_) | Signal::Await(_) => panic!("fiber is not complete"),
+        Signal::Yield(_) | Signal::Await(_) | Signal::Preempted => panic!("fiber is not complete"),
     };
 
     Ok(res)
@@ -41,6 +41,77 @@ fn int() {
     assert_eq!(eval_expr("5").unwrap(), Val::Int(5));
 }
 
+#[test]
+fn float() {
+    assert_eq!(eval_expr("1.5").unwrap(), Val::Float(1.5));
+    assert_eq!(eval_expr("-2.0").unwrap(), Val::Float(-2.0));
+}
+
+#[test]
+fn eval_arithmetic() {
+    assert_eq!(eval_expr("(+)").unwrap(), Val::Int(0));
+    assert_eq!(eval_expr("(+ 1 2 3)").unwrap(), Val::Int(6));
+    assert_eq!(eval_expr("(- 10 1 2)").unwrap(), Val::Int(7));
+    assert_eq!(eval_expr("(- 5)").unwrap(), Val::Int(-5));
+    assert_eq!(eval_expr("(* 2 3 4)").unwrap(), Val::Int(24));
+    assert_eq!(eval_expr("(/ 7 2)").unwrap(), Val::Int(3));
+    assert_eq!(eval_expr("(/ 4.0)").unwrap(), Val::Float(0.25));
+    assert_eq!(eval_expr("(mod 7 3)").unwrap(), Val::Int(1));
+    assert_eq!(eval_expr("(mod -7 3)").unwrap(), Val::Int(2));
+    assert_eq!(eval_expr("(mod 7 -3)").unwrap(), Val::Int(-2));
+    assert_eq!(eval_expr("(abs -3)").unwrap(), Val::Int(3));
+    assert_eq!(eval_expr("(abs -1.5)").unwrap(), Val::Float(1.5));
+    assert_eq!(
+        eval_expr("(+ '(1 2) '(3))").unwrap(),
+        Val::List(vec![Val::Int(1), Val::Int(2), Val::Int(3)])
+    );
+}
+
+#[test]
+fn eval_arithmetic_float_promotion() {
+    assert_eq!(eval_expr("(+ 1 0.5)").unwrap(), Val::Float(1.5));
+    assert_eq!(eval_expr("(* 2 1.5)").unwrap(), Val::Float(3.0));
+    assert_eq!(eval_expr("(/ 7 2.0)").unwrap(), Val::Float(3.5));
+    assert_eq!(eval_expr("(- 1.0 1)").unwrap(), Val::Float(0.0));
+}
+
+#[test]
+fn eval_arithmetic_errors() {
+    assert_matches!(
+        eval_expr("(+ 9223372036854775807 1)"),
+        Err(Error::Runtime(_))
+    );
+    assert_matches!(
+        eval_expr("(* 9223372036854775807 2)"),
+        Err(Error::Runtime(_))
+    );
+    assert_matches!(
+        eval_expr("(abs (- -9223372036854775807 1))"),
+        Err(Error::Runtime(_))
+    );
+    assert_matches!(eval_expr("(/ 1 0)"), Err(Error::Runtime(_)));
+    assert_matches!(eval_expr("(mod 1 0)"), Err(Error::Runtime(_)));
+    assert_matches!(
+        eval_expr("(+ 1 \"two\")"),
+        Err(Error::UnexpectedArguments(_))
+    );
+    assert_matches!(eval_expr("(-)"), Err(Error::UnexpectedArguments(_)));
+}
+
+#[test]
+fn eval_comparisons() {
+    assert_eq!(eval_expr("(< 1 2 3)").unwrap(), Val::Bool(true));
+    assert_eq!(eval_expr("(< 1 3 2)").unwrap(), Val::Bool(false));
+    assert_eq!(eval_expr("(> 3 2.5 1)").unwrap(), Val::Bool(true));
+    assert_eq!(eval_expr("(<= 1 1 2)").unwrap(), Val::Bool(true));
+    assert_eq!(eval_expr("(>= 2 2 3)").unwrap(), Val::Bool(false));
+    assert_eq!(eval_expr("(< 1)").unwrap(), Val::Bool(true));
+    assert_eq!(eval_expr("(min 3 1.5 2)").unwrap(), Val::Float(1.5));
+    assert_eq!(eval_expr("(max 3 1.5 2)").unwrap(), Val::Int(3));
+    assert_matches!(eval_expr("(< 1 :two)"), Err(Error::UnexpectedType(_)));
+    assert_matches!(eval_expr("(max)"), Err(Error::UnexpectedArguments(_)));
+}
+
 #[test]
 fn string() {
     assert_eq!(eval_expr("\"hello\"").unwrap(), Val::string("hello"));
@@ -201,6 +272,119 @@ fn adder() {
     assert_eq!(eval_expr(prog).unwrap(), Val::Int(42));
 }
 
+#[test]
+fn closure_counter() {
+    let prog = r#"
+        (begin
+            (defn make_counter ()
+                (begin
+                    (def count 0)
+                    (list (lambda () (set count (+ count 1)))
+                          (lambda () count))))
+            (def (incr get) (make_counter))
+            (def (other_incr other_get) (make_counter))
+            (incr)
+            (incr)
+            (other_incr)
+            (list (get) (other_get)))
+    "#;
+    assert_eq!(
+        eval_expr(prog).unwrap(),
+        Val::List(vec![Val::Int(2), Val::Int(1)])
+    );
+}
+
+#[test]
+fn closure_later_def() {
+    let prog = r#"
+        (begin
+            (defn parity (n)
+                (begin
+                    (defn is_even (n) (if (eq? n 0) :even (is_odd (- n 1))))
+                    (defn is_odd (n) (if (eq? n 0) :odd (is_even (- n 1))))
+                    (is_even n)))
+            (list (parity 10) (parity 7)))
+    "#;
+    assert_eq!(
+        eval_expr(prog).unwrap(),
+        Val::List(vec![Val::keyword("even"), Val::keyword("odd")])
+    );
+}
+
+#[test]
+fn closure_upval_chain() {
+    let prog = r#"
+        (begin
+            (defn outer (x)
+                (lambda () (lambda () (begin (set x (+ x 1)) x))))
+            (def inner ((outer 40)))
+            (inner)
+            (inner))
+    "#;
+    assert_eq!(eval_expr(prog).unwrap(), Val::Int(42));
+}
+
+#[test]
+fn local_read_before_def() {
+    let prog = r#"
+        (begin
+            (def x :global)
+            (defn f () (loop (yield x) (def x :local)))
+            (f))
+    "#;
+    let mut f = Fiber::from_expr(prog, Env::standard(), ()).unwrap();
+    assert_eq!(f.start().unwrap(), Signal::Yield(Val::keyword("global")));
+    assert_eq!(
+        f.resume(Ok(Val::Nil)).unwrap(),
+        Signal::Yield(Val::keyword("local")),
+        "symbol read before its def in function body should refer to local once defined"
+    );
+    assert_eq!(
+        f.resume(Ok(Val::Nil)).unwrap(),
+        Signal::Yield(Val::keyword("local"))
+    );
+}
+
+#[test]
+fn local_def_by_native_exec() {
+    let mut env = Env::standard();
+    env.bind_native(
+        SymbolId::from("def_x_two"),
+        NativeFn {
+            doc: "".to_string(),
+            func: |_, _| {
+                Ok(NativeFnOp::Exec(
+                    vec![
+                        Inst::PushConst(Val::Int(2)),
+                        Inst::DefSym(SymbolId::from("x")),
+                    ]
+                    .into(),
+                ))
+            },
+        },
+    );
+    let prog = r#"
+        (begin
+            (defn f ()
+                (begin
+                    (def x 1)
+                    (def_x_two)
+                    x))
+            (defn g ()
+                (begin
+                    (def n 0)
+                    (map '(1 2 3) (fn (x) (set n (+ n x))))
+                    n))
+            (list (f) (g)))
+    "#;
+    let mut f = Fiber::from_expr(prog, env, ()).unwrap();
+    assert_eq!(
+        f.start().unwrap(),
+        Signal::Done(Val::from_expr("(2 6)").unwrap()),
+        "variables defined by native code should be visible to function body, without overwriting variables set by closures"
+    );
+}
+
 #[test]
 fn eval_quote() {
     {
@@ -250,6 +434,89 @@ fn eval_defn() {
     assert_eq!(eval_expr(prog).unwrap(), Val::Int(15),);
 }
 
+#[test]
+fn eval_lambda_arity() {
+    assert_matches!(
+        eval_expr("((lambda (x) x))"),
+        Err(Error::UnexpectedArguments(msg)) if msg.starts_with("Expected 1 arguments - got 0")
+    );
+    assert_matches!(
+        eval_expr("((lambda (x) x) 1 2)"),
+        Err(Error::UnexpectedArguments(_))
+    );
+    assert_matches!(
+        eval_expr("((lambda (x &optional y) x) 1 2 3)"),
+        Err(Error::UnexpectedArguments(msg)) if msg.starts_with("Expected 1 to 2 arguments - got 3")
+    );
+}
+
+#[test]
+fn eval_lambda_optional() {
+    let prog = r#"(begin
+        (defn greet (name &optional (greeting "hello") suffix)
+            (list greeting name suffix))
+        (list (greet "a") (greet "b" "hi") (greet "c" "hey" "!")))"#;
+    assert_eq!(
+        eval_expr(prog).unwrap(),
+        Val::from_expr(r#"(("hello" "a" nil) ("hi" "b" nil) ("hey" "c" "!"))"#).unwrap()
+    );
+
+    assert_eq!(
+        eval_expr("(begin (def y 10) ((lambda (x &optional (z (+ x y))) z) 1))").unwrap(),
+        Val::Int(11),
+        "defaults are evaluated with earlier parameters in scope"
+    );
+    assert_matches!(
+        eval_expr("(lambda ((x 1)) x)"),
+        Err(Error::InvalidExpression(_)),
+        "defaults are only allowed for optional and keyword parameters"
+    );
+}
+
+#[test]
+fn eval_lambda_rest() {
+    let prog = r#"(begin
+        (defn tally (first &rest others) (list first others))
+        (list (tally 1) (tally 1 2 3)))"#;
+    assert_eq!(
+        eval_expr(prog).unwrap(),
+        Val::from_expr("((1 ()) (1 (2 3)))").unwrap()
+    );
+    assert_eq!(eval_expr("(apply + (list 1 2 3))").unwrap(), Val::Int(6));
+    assert_matches!(
+        eval_expr("(lambda (&rest a b) a)"),
+        Err(Error::InvalidExpression(_))
+    );
+}
+
+#[test]
+fn eval_lambda_key() {
+    let prog = r#"(begin
+        (defn item (title &key cmd (icon :default)) (list title cmd icon))
+        (list (item "a") (item "b" :cmd "ls") (item "c" :icon :star :cmd "pwd")))"#;
+    assert_eq!(
+        eval_expr(prog).unwrap(),
+        Val::from_expr(r#"(("a" nil :default) ("b" "ls" :default) ("c" "pwd" :star))"#).unwrap()
+    );
+    assert_matches!(
+        eval_expr("((lambda (&key a) a) :b 1)"),
+        Err(Error::UnexpectedArguments(_))
+    );
+    assert_eq!(
+        eval_expr("((lambda (&rest r &key a) (list a r)) :b 1 :a 2)").unwrap(),
+        Val::from_expr("(2 (:b 1 :a 2))").unwrap(),
+        "unknown keywords are allowed with rest parameter"
+    );
+}
+
+#[test]
+fn eval_defmacro_rest() {
+    let prog = r#"(begin
+        (defmacro my_begin (&rest body) `(begin ,@body))
+        (my_begin 1 2 3))"#;
+    assert_eq!(eval_expr(prog).unwrap(), Val::Int(3));
+}
+
 // TODO(test): Test using def referencing var in parent scope, e.g. (def count (+ count 1)) w/ sequence in eval_defn
 
 #[test]
@@ -728,6 +995,212 @@ fn eval_not() {
     );
 }
 
+#[test]
+fn eval_map_literal() {
+    assert_eq!(eval_expr("{}").unwrap(), Val::from_expr("{}").unwrap());
+    assert_eq!(
+        eval_expr("{:a 1 :b \"two\"}").unwrap(),
+        Val::from_expr("{:b \"two\" :a 1}").unwrap()
+    );
+    assert_eq!(
+        eval_expr(
+            r#"(begin
+            (def x 10)
+            {:x x :sum (+ x 1) :nested {:y x} :quoted '(a b)})"#
+        )
+        .unwrap(),
+        Val::from_expr("{:x 10 :sum 11 :nested {:y 10} :quoted (a b)}").unwrap()
+    );
+    assert_eq!(
+        eval_expr("'{:a x}").unwrap(),
+        Val::from_expr("{:a x}").unwrap(),
+        "quoted map values should not be evaluated"
+    );
+    assert_eq!(
+        eval_expr("(begin (def x 1) `{:a ,x :b y})").unwrap(),
+        Val::from_expr("{:a 1 :b y}").unwrap(),
+    );
+}
+
+#[test]
+fn eval_map_builtins() {
+    assert_eq!(
+        eval_expr("(hashmap :a 1 :b 2)").unwrap(),
+        Val::from_expr("{:a 1 :b 2}").unwrap()
+    );
+    assert_eq!(
+        eval_expr("(assoc {:a 1} :a 10 \"b\" 2)").unwrap(),
+        Val::from_expr("{:a 10 \"b\" 2}").unwrap()
+    );
+    assert_eq!(
+        eval_expr("(dissoc {:a 1 :b 2 :c 3} :a :c :d)").unwrap(),
+        Val::from_expr("{:b 2}").unwrap()
+    );
+    assert_eq!(
+        eval_expr("(keys {:b 2 :a 1})").unwrap(),
+        Val::from_expr("(:a :b)").unwrap()
+    );
+    assert_eq!(
+        eval_expr("(vals {:b 2 :a 1})").unwrap(),
+        Val::from_expr("(1 2)").unwrap()
+    );
+    assert_eq!(
+        eval_expr("(has_key? {:a nil} :a)").unwrap(),
+        Val::Bool(true)
+    );
+    assert_eq!(eval_expr("(has_key? {:a 1} :b)").unwrap(), Val::Bool(false));
+    assert_eq!(
+        eval_expr("(merge {:a 1 :b 2} {:b 20 :c 30} {})").unwrap(),
+        Val::from_expr("{:a 1 :b 20 :c 30}").unwrap()
+    );
+    assert_eq!(eval_expr("(get {:a 1 2 :two} :a)").unwrap(), Val::Int(1));
+    assert_eq!(
+        eval_expr("(get {:a 1 2 :two} 2)").unwrap(),
+        Val::keyword("two")
+    );
+    assert_eq!(eval_expr("(get {:a 1} :b)").unwrap(), Val::Nil);
+    assert_eq!(eval_expr("(len {:a 1 :b 2})").unwrap(), Val::Int(2));
+    assert_eq!(eval_expr("(empty? {})").unwrap(), Val::Bool(true));
+    assert_matches!(
+        eval_expr("(assoc {} :a)"),
+        Err(Error::UnexpectedArguments(_))
+    );
+    assert_matches!(eval_expr("(hashmap '(1) 2)"), Err(Error::UnexpectedType(_)));
+}
+
+#[test]
+fn eval_map_destructuring() {
+    assert_eq!(
+        eval_expr(
+            r#"(begin
+            (def {:name name :size size} {:name "a.txt" :size 10 :kind :file})
+            (list name size))"#
+        )
+        .unwrap(),
+        Val::from_expr("(\"a.txt\" 10)").unwrap()
+    );
+    assert_eq!(
+        eval_expr(
+            r#"(match {:status :err :reason "timeout"}
+                ({:status :ok :value v} v)
+                ({:status :err :reason r} (list :failed r)))"#
+        )
+        .unwrap(),
+        Val::from_expr("(:failed \"timeout\")").unwrap()
+    );
+}
+
+#[test]
+fn eval_and() {
+    assert_eq!(eval_expr("(and)").unwrap(), Val::Bool(true));
+    assert_eq!(eval_expr("(and true 1 \"a\")").unwrap(), Val::string("a"));
+    assert_eq!(eval_expr("(and 1 nil 2)").unwrap(), Val::Nil);
+    assert_eq!(eval_expr("(and true false)").unwrap(), Val::Bool(false));
+    assert_eq!(
+        eval_expr("(and false (undefined_fn))").unwrap(),
+        Val::Bool(false),
+        "and should not evaluate arguments after first false value"
+    );
+    assert_eq!(
+        eval_expr(
+            r#"(begin
+            (def count 0)
+            (and true (set count (+ count 1)) false (set count (+ count 10)))
+            count)"#
+        )
+        .unwrap(),
+        Val::Int(1)
+    );
+}
+
+#[test]
+fn eval_or() {
+    assert_eq!(eval_expr("(or)").unwrap(), Val::Nil);
+    assert_eq!(
+        eval_expr("(or nil \"default\")").unwrap(),
+        Val::string("default")
+    );
+    assert_eq!(eval_expr("(or 0 false nil)").unwrap(), Val::Nil);
+    assert_eq!(
+        eval_expr("(or '(1) 2)").unwrap(),
+        Val::from_expr("(1)").unwrap()
+    );
+    assert_eq!(
+        eval_expr("(or true (undefined_fn))").unwrap(),
+        Val::Bool(true),
+        "or should not evaluate arguments after first true value"
+    );
+    assert_eq!(
+        eval_expr(
+            r#"(begin
+            (def items '(:title "Title"))
+            (or (get items :subtitle) (get items :title)))"#
+        )
+        .unwrap(),
+        Val::string("Title")
+    );
+}
+
+#[test]
+fn eval_and_or_try() {
+    assert_eq!(
+        eval_expr("(or (ok? (try (undefined_fn))) :fallback)").unwrap(),
+        Val::keyword("fallback")
+    );
+    assert_eq!(
+        eval_expr("(and (err? (try (undefined_fn))) \"caught\")").unwrap(),
+        Val::string("caught")
+    );
+    assert_matches!(
+        eval_expr("(try (and true (undefined_fn)))").unwrap(),
+        Val::Error(Error::UndefinedSymbol(_))
+    );
+    assert_matches!(
+        eval_expr("(and true (undefined_fn))"),
+        Err(Error::UndefinedSymbol(_))
+    );
+}
+
+#[test]
+fn eval_err_kind() {
+    assert_eq!(
+        eval_expr("(err_kind (try (undefined_fn)))").unwrap(),
+        Val::keyword("undefined_symbol")
+    );
+    assert_eq!(
+        eval_expr("(err_kind (try (+ 1 :a)))").unwrap(),
+        Val::keyword("unexpected_arguments")
+    );
+    assert_matches!(
+        eval_expr("(err_kind :ok)"),
+        Err(Error::UnexpectedArguments(_))
+    );
+}
+
+#[test]
+fn eval_when_unless() {
+    assert_eq!(eval_expr("(when true 1 2)").unwrap(), Val::Int(2));
+    assert_eq!(eval_expr("(when false (undefined_fn))").unwrap(), Val::Nil);
+    assert_eq!(eval_expr("(unless false 1 2)").unwrap(), Val::Int(2));
+    assert_eq!(eval_expr("(unless true (undefined_fn))").unwrap(), Val::Nil);
+    assert_eq!(
+        eval_expr(
+            r#"(begin
+            (def count 0)
+            (when (> 2 1)
+                (set count (+ count 1))
+                (set count (+ count 1)))
+            (unless (> 2 1)
+                (set count (+ count 10)))
+            count)"#
+        )
+        .unwrap(),
+        Val::Int(2)
+    );
+    assert_matches!(eval_expr("(when true)"), Err(Error::InvalidExpression(_)));
+    assert_matches!(eval_expr("(unless)"), Err(Error::InvalidExpression(_)));
+}
+
 #[test]
 #[tracing_test::traced_test]
 fn eval_match() {
@@ -847,6 +1320,73 @@ fn eval_match() {
     }
 }
 
+#[test]
+fn eval_quasiquote() {
+    assert_eq!(
+        eval_expr("(begin (def x 2) (def xs '(3 4)) `(1 ,x ,@xs (5 ,@xs) ,(+ x 4)))").unwrap(),
+        Val::from_expr("(1 2 3 4 (5 3 4) 6)").unwrap()
+    );
+    assert_eq!(
+        eval_expr("`(a b)").unwrap(),
+        Val::from_expr("(a b)").unwrap(),
+        "forms without unquote should be quoted"
+    );
+    assert_eq!(
+        eval_expr("(begin (def x 1) `(a `(b ,(c ,x))))").unwrap(),
+        Val::from_expr("(a (quasiquote (b (unquote (c 1)))))").unwrap(),
+        "nested quasiquotes should only unquote innermost level"
+    );
+    assert_matches!(eval_expr(",x"), Err(Error::InvalidExpression(_)));
+    assert_matches!(eval_expr("`,@x"), Err(Error::InvalidExpression(_)));
+}
+
+#[test]
+fn eval_defmacro() {
+    let prog = r#"(begin
+        (defmacro if_not (c body)
+            "Evaluate BODY if C is false"
+            `(if ,c nil ,body))
+        (def count 0)
+        (if_not false (set count (+ count 1)))
+        (if_not true (set count (+ count 10)))
+        count)"#;
+    assert_eq!(eval_expr(prog).unwrap(), Val::Int(1));
+
+    let prog = r#"(begin
+        (defmacro swap (a b)
+            `(begin (def tmp ,a) (set ,a ,b) (set ,b tmp)))
+        (def x 1)
+        (def y 2)
+        (swap x y)
+        (list x y))"#;
+    assert_eq!(eval_expr(prog).unwrap(), Val::from_expr("(2 1)").unwrap());
+}
+
+#[test]
+fn eval_defmacro_defining_env() {
+    let prog = r#"(begin
+        (def suffix :from_definition)
+        (defmacro tag (x) `(list ,x ,(list 'quote suffix)))
+        (defn with_local (suffix) (tag suffix))
+        (with_local :from_call))"#;
+    assert_eq!(
+        eval_expr(prog).unwrap(),
+        Val::from_expr("(:from_call :from_definition)").unwrap(),
+        "macro body should be evaluated in defining environment, with expansion evaluated at call site"
+    );
+}
+
+#[test]
+fn eval_macro_in_eval() {
+    let prog = r#"(begin
+        (defmacro double (x) `(+ ,x ,x))
+        (list (eval '(double 2)) (help double)))"#;
+    assert_eq!(
+        eval_expr(prog).unwrap(),
+        Val::List(vec![Val::Int(4), Val::string("<missing documentation>")])
+    );
+}
+
 // TODO: Test - if with blocks
 
 //     #[test]
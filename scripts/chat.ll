@@ -27,7 +27,7 @@
            (defn send_message (message)
              "(send_message MESSAGE) - Send message to chat session then return new assistant message"
              (set msgs (push msgs (list :user message)))
-             (def (:ok assistant_msg) (run_llm msgs))
+             (def assistant_msg (get (run_llm msgs) :stdout))
              (set msgs (push msgs (list :assistant assistant_msg)))
              assistant_msg)
 
@@ -3,8 +3,6 @@
 #
 
 # TODO: New builtin - throw / error
-# TODO: New builtin - "hashmap" type? Set value for key, instead of (list :name ... :cmds ...) rebuilding
-# TODO: New builtin - "or" to gracefully fallback nil values?
 
 (def macros '())
 (def record_pid nil)
@@ -19,7 +17,7 @@
 
 (defn start_macro_record (name)
   "(start_macro_record NAME) - Starts recording the :cmd ran by user in macro called NAME"
-  (if (macro_is_recording)
+  (when (macro_is_recording)
     (kill_record_proc))
   (start_record_proc name)
   :ok)
@@ -30,11 +28,10 @@
 
 (defn end_macro_record ()
   "(end_macro_record) - Ends current macro recording."
-  (if (not? (macro_is_recording)) nil
-      (begin
-       (save_macro)
-       (kill_record_proc)
-       :ok)))
+  (when (macro_is_recording)
+    (save_macro)
+    (kill_record_proc)
+    :ok))
 
 (defn save_macro ()
   "(save_macro) - Save current macro stored in RECORDING"
@@ -47,14 +44,13 @@
   "(start_record_proc NAME) - Start a process that is recording commands"
   (set record_pid
        (spawn (fn ()
-                (def recording (list :name name :cmds '(begin)))
+                (def recording {:name name :cmds '(begin)})
                 (subscribe :cmd)
                 (loop (begin
                        (match (recv)
                          ((:topic_updated :cmd ('end_macro_record)) nil)
                          ((:topic_updated :cmd cmd) 
-                            (set recording (list :name (get recording :name)
-                                                :cmds (push (get recording :cmds) cmd))))
+                            (set recording (assoc recording :cmds (push (get recording :cmds) cmd))))
                          ((r src (:get_recording)) (send src (list r recording))))))))))
 
 (defn kill_record_proc ()
@@ -12,7 +12,7 @@
 
 (defn refresh_pull_requests ()
   "(refresh_pull_requests) - Fetch open pull requests"
-  (def (:ok res) (exec "./scripts/gh_pr_list.sh"))
+  (def res (get (exec "./scripts/gh_pr_list.sh") :stdout))
   (set pull_requests (read res))
   :ok)
 
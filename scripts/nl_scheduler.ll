@@ -14,7 +14,7 @@
   (if (eq? day_schedule "")
     (set day_schedule "NO EVENTS"))
 
-  (def date (get (exec "date") -1))
+  (def date (get (exec "date") :stdout))
 
   (format "Today is {}
 
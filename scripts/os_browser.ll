@@ -9,14 +9,14 @@
   (if (err? (try (exec "pgrep" "-ax" "Safari")))
     nil
     (begin
-     (def (:ok url) (exec "osascript" "-e" "tell application \"Safari\" to return URL of front document"))
-     (def (:ok title) (exec "osascript" "-e" "tell application \"Safari\" to return name of front document"))
+     (def url (get (exec "osascript" "-e" "tell application \"Safari\" to return URL of front document") :stdout))
+     (def title (get (exec "osascript" "-e" "tell application \"Safari\" to return name of front document") :stdout))
      (list :title title :url url))))
 
 (defn active_tab_chrome ()
   "Retrieve the active tab info for Chrome"
-  (def (:ok url) (exec "osascript" "-e" "tell application \"Google Chrome\" to return URL of active tab of front window"))
-  (def (:ok title) (exec "osascript" "-e" "tell application \"Google Chrome\" to return title of active tab of front window"))
+  (def url (get (exec "osascript" "-e" "tell application \"Google Chrome\" to return URL of active tab of front window") :stdout))
+  (def title (get (exec "osascript" "-e" "tell application \"Google Chrome\" to return title of active tab of front window") :stdout))
   (list :title title :url url))
 
 (defn active_tab ()
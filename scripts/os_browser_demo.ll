@@ -4,8 +4,8 @@
 
 (defn active_tab ()
   "(active_tab) Retrieve the current URL of active browser window"
-  (def (:ok url) (exec "osascript" "-e" "tell application \"Safari\" to return URL of front document"))
-  (def (:ok title) (exec "osascript" "-e" "tell application \"Safari\" to return name of front document"))
+  (def url (get (exec "osascript" "-e" "tell application \"Safari\" to return URL of front document") :stdout))
+  (def title (get (exec "osascript" "-e" "tell application \"Safari\" to return name of front document") :stdout))
   (list :title title :url url))
 
 (spawn_srv :os_browser :interface '(active_tab))
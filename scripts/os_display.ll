@@ -4,7 +4,7 @@
 
 (defn list_alternative_resolutions ()
   "(list_other_resolutions) - Lists available resolution except current)"
-  (def (:ok res) (exec "hs" "-q" "-c" "display.list_resolutions()"))
+  (def res (get (exec "hs" "-q" "-c" "display.list_resolutions()") :stdout))
   (split "\n" res))
 
 (defn select_resolution (desc)
@@ -4,7 +4,7 @@
 
 (defn get_windows ()
   "(get_windows) - Get all windows"
-  (def (:ok res) (exec "./scripts/yabai_window_shim.sh"))
+  (def res (get (exec "./scripts/yabai_window_shim.sh") :stdout))
   (read res))
 
 # TODO: Consider dynamic type check - e.g. `islist?` / `isstring?` to accept flexible window selector
@@ -6,44 +6,31 @@
 
 # TODO: Nice-to-have is defining CRUD resource service via macro
 
-(def rlist_path "~/Dropbox/rlist.ll")
-
-(def id 0)
-(def rlist '())
-
-(defn load_rlist ()
-  (def res (try (def (:id _ :rlist _) (fread rlist_path))))
-  (if (ok? res) (begin
-                 (set id (get res :id))
-                 (set rlist (get res :rlist)))))
-
-(defn save_rlist ()
-  "(save_rlist) - Save current rlist to filesystem"
-  (spawn (fn () (fdump rlist_path (list :id id :rlist rlist)))))
+(defn next_id ()
+  "(next_id) - Return the next ID to assign"
+  (def id (or (store_get :counters :rlist) 0))
+  (store_put :counters :rlist (+ id 1))
+  id)
 
 (defn get_rlist ()
   "(get_rlist) - Get all items in reading list"
-  (load_rlist)
-  rlist)
+  (store_query :rlist))
 
 (defn add_rlist (title url)
   "(add_rlist TITLE URL) - Add item with TITLE and URL to reading list"
-  (set rlist (push rlist (list :id id :rlist :title title :url url)))
-  (set id (+ id 1))
-  (save_rlist)
+  (def id (next_id))
+  (store_put :rlist id (list :id id :title title :url url))
   (publish :rlist_event :updated_rlist)
   :ok)
 
 (defn remove_rlist (id)
   "(remove_rlist ID) - Remove item with ID from reading list"
-  (set rlist (filter rlist (fn (it) (not? (contains? it id)))))
-  (save_rlist)
+  (store_delete :rlist id)
   (publish :rlist_event :updated_rlist))
 
 (defn clear_rlist ()
   "(clear_rlist) - Clear all reading list items"
-  (set rlist '())
-  (save_rlist)
+  (store_tx (map (store_query :rlist) (fn (it) (list :delete :rlist (get it :id)))))
   (publish :rlist_event :updated_rlist)
   :ok)
 
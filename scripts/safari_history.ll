@@ -14,7 +14,7 @@
 
 (defn refresh_safari_history ()
   "(refresh_safari_history) - Refresh in-memory Safari History"
-  (def (:ok res) (exec "./scripts/safari_history_shim.tcl"))
+  (def res (get (exec "./scripts/safari_history_shim.tcl") :stdout))
   (set safari_history (read res)))
 
 (spawn_srv :safari_history :interface '(get_safari_history refresh_safari_history))
@@ -3,12 +3,12 @@
 #
 
 (defn is_darkmode ()
-  (def (:ok result) (exec "osascript"
+  (def result (get (exec "osascript"
                           "-e" "tell application \"System Events\""
                           "-e" "tell appearance preferences"
                           "-e" "return dark mode"
                           "-e" "end tell"
-                          "-e" "end tell"))
+                          "-e" "end tell") :stdout))
   (eq? result "true"))
 
 (defn set_darkmode (dark)
@@ -30,7 +30,7 @@
   (exec "shortcuts" "run" "color-filters-toggle"))
 
 (defn toggle_quick_shade ()
-  (def (:ok result) (exec "osascript"
+  (def result (get (exec "osascript"
                           "-e" "tell application \"System Events\""
                           "-e" "set isRunning to (exists (processes where name is \"QuickShade\"))"
                           "-e" "end tell"
@@ -38,7 +38,7 @@
                           "-e" "tell application \"QuickShade\" to quit"
                           "-e" "else"
                           "-e" "tell application \"QuickShade\" to activate"
-                          "-e" "end if"))
+                          "-e" "end if") :stdout))
   :ok)
 
 (spawn_srv :system_appearance :interface '(toggle_darkmode toggle_color_filters toggle_quick_shade))
@@ -3,7 +3,7 @@
 #
 
 (defn is_personal? ()
-  (eq? (exec "uname" "-n") "shinjuku.local"))
+  (eq? (get (exec "uname" "-n") :stdout) "shinjuku.local"))
 
 # TODO: Move to init.ll w/ supervision tree
 (bind_srv :system_appearance)
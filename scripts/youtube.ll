@@ -34,7 +34,7 @@
 
 (defn list_videos ()
   "(list_videos) - List available youtube videos"
-   (read (get (exec ytdlp_shim_script) -1)))
+   (read (get (exec ytdlp_shim_script) :stdout)))
 
 # TODO: Write about iterative dev experience? Took ~15m?
 # - Inspiration - flying to japan
@@ -61,10 +61,10 @@ async fn main() -> Result<()> {
             }
         }
 
-        let file = open_file(
-            args.get_one::<String>("file")
-                .expect("file has a default value"),
-        )?;
+        let file_name = args
+            .get_one::<String>("file")
+            .expect("file has a default value");
+        let file = open_file(file_name)?;
 
         let format = args
             .get_one::<Format>("format")
@@ -73,7 +73,11 @@ async fn main() -> Result<()> {
         if let Some(cmd) = args.get_one::<String>("command") {
             run_cmd(&client, cmd).await
         } else if let Some(file) = file {
-            run_file(&client, format, file).await
+            let name = match file_name.as_str() {
+                "-" => "<stdin>",
+                name => name,
+            };
+            run_file(&client, format, file, name).await
         } else if let Some(topic) = args.get_one::<String>("subscribe") {
             let follow = args.get_flag("follow");
             let follow_clear = args.get_flag("follow_clear");
@@ -159,11 +163,12 @@ async fn run_cmd(client: &Client, cmd: &str) -> Result<()> {
     Ok(())
 }
 
-/// Run a script file
-async fn run_file(client: &Client, format: &Format, file: Box<dyn Read>) -> Result<()> {
+/// Run a script file, where errors are located by file name and line
+async fn run_file(client: &Client, format: &Format, file: Box<dyn Read>, name: &str) -> Result<()> {
     let mut f = BufReader::new(file);
     let mut line = String::new();
     let mut lineno = 0;
+    let mut start = 1; // line where buffered lines start
     loop {
         match f.read_line(&mut line) {
             Ok(0) => break,
@@ -176,8 +181,8 @@ async fn run_file(client: &Client, format: &Format, file: Box<dyn Read>) -> Resu
 
         lineno += 1;
 
-        let f = match lyric::parse(&line) {
-            Ok(f) => f,
+        match lyric::parse(&line) {
+            Ok(_) => (),
             Err(lyric::Error::IncompleteExpression(_)) => {
                 continue;
             }
@@ -185,15 +190,24 @@ async fn run_file(client: &Client, format: &Format, file: Box<dyn Read>) -> Resu
                 eprintln!("{}: {} - {}", lineno, e, line);
                 break;
             }
-        };
+        }
 
         if *format == Format::Editor {
             print!("{}", line);
         }
 
+        // (load_str LINES NAME START), so runtime locates errors in file
+        let req = Form::List(vec![
+            Form::symbol("load_str"),
+            Form::String(line.clone()),
+            Form::String(name.to_string()),
+            Form::Int(start),
+        ]);
+
         line.clear();
+        start = lineno + 1;
 
-        match client.request(f).await {
+        match client.request(req).await {
             Ok(resp) if *format == Format::Editor => match resp.contents {
                 Ok(c) => println!("# => {}", c),
                 Err(e) => eprintln!("# => {}", e),
@@ -1,12 +1,15 @@
 use anyhow::{Context, Result};
+use clap::{arg, command};
+use lyric::Form;
+use std::path::{Path, PathBuf};
 use tokio::net::UnixListener;
-use tracing::{error, info};
-use vrs::Connection;
-use vrs::Runtime;
+use tracing::{error, info, warn};
+use vrs::{Config, Connection, ProcessResult, Program, Runtime, Val};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
+    let args = cli().get_matches();
 
     let path = vrs::runtime_socket();
     if path.exists() {
@@ -14,7 +17,25 @@ async fn main() -> Result<()> {
             .with_context(|| format!("Failed to remove existing socket {}", path.display()))?;
     }
 
-    let runtime = Runtime::new();
+    let runtime = Runtime::with_config(Config {
+        jobs: vrs::jobs_file(),
+        store: vrs::store_file(),
+        ..Default::default()
+    });
+
+    if let Some(init) = args.get_one::<PathBuf>("init") {
+        // explicitly requested init file must exist, unlike default init file
+        if !init.exists() {
+            anyhow::bail!("Init file not found at {}", init.display());
+        }
+        run_init(&runtime, init).await?;
+    } else if !args.get_flag("no_init") {
+        match vrs::init_file() {
+            Some(init) if init.exists() => run_init(&runtime, &init).await?,
+            Some(init) => info!("No init file at {}", init.display()),
+            None => info!("No config directory for init file"),
+        }
+    }
 
     let listener = UnixListener::bind(&path)
         .with_context(|| format!("Failed to start listener at {}", path.display()))?;
@@ -32,3 +53,58 @@ async fn main() -> Result<()> {
         }
     }
 }
+
+/// The clap CLI interface
+fn cli() -> clap::Command {
+    command!()
+        .arg(
+            arg!(init: -i --init <FILE> "Path to init file evaluated before accepting connections. Defaults to init.ll in config directory")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(no_init: --noinit "If present, skips evaluating init file").conflicts_with("init"),
+        )
+}
+
+/// Evaluate each form in init file in order, reporting forms that failed
+async fn run_init(runtime: &Runtime, path: &Path) -> Result<()> {
+    info!("Loading init file {}", path.display());
+
+    // (load PATH :keep_going true)
+    let prog = Form::List(vec![
+        Form::symbol("load"),
+        Form::String(path.display().to_string()),
+        Form::keyword("keep_going"),
+        Form::Bool(true),
+    ]);
+
+    let hdl = runtime.run(Program::from_val(Val::from(prog))?).await?;
+    let results = match hdl.join().await?.status {
+        Ok(ProcessResult::Done(Val::List(results))) => results,
+        status => {
+            error!("Init file did not complete - {status:?}");
+            return Ok(());
+        }
+    };
+
+    // Errors are located by file and line of failing form
+    let failed: Vec<_> = results
+        .iter()
+        .filter_map(|res| match res {
+            Val::Error(e) => Some(e),
+            _ => None,
+        })
+        .collect();
+
+    for e in &failed {
+        warn!("Init form failed - {e}");
+    }
+    info!(
+        "Loaded init file {} - {} of {} forms failed",
+        path.display(),
+        failed.len(),
+        results.len()
+    );
+
+    Ok(())
+}
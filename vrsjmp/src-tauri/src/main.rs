@@ -107,8 +107,9 @@ fn set_query(query: &str, state: tauri::State<State>) -> Vec<serde_json::Value>
     let mut matcher = state.matcher.lock().unwrap();
 
     // TODO: Contents of `query` should be escaped
+    // Timeout so an unresponsive service does not hang the UI
     let request = match Form::from_expr(&format!(
-        "(begin (bind_srv :vrsjmp) (get_items \"{}\"))",
+        "(call (find_srv :vrsjmp) (list :get_items \"{}\") :timeout 1000)",
         query
     )) {
         Ok(f) => f,